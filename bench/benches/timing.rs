@@ -35,6 +35,41 @@ fn criterion_locate(c: &mut Criterion) {
     locate(&mut group, &keys, &keys);
 }
 
+fn criterion_locate_many(c: &mut Criterion) {
+    let mut group = c.benchmark_group("locate_many");
+    group.sample_size(SAMPLE_SIZE);
+    group.warm_up_time(WARM_UP_TIME);
+    group.measurement_time(MEASURE_TIME);
+    group.sampling_mode(SamplingMode::Flat);
+
+    let keys = load_keyset("data/words-100000");
+    locate_many(&mut group, &keys, &keys);
+}
+
+fn criterion_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.sample_size(SAMPLE_SIZE);
+    group.warm_up_time(WARM_UP_TIME);
+    group.measurement_time(MEASURE_TIME);
+    group.sampling_mode(SamplingMode::Flat);
+
+    let keys = load_keyset("data/wiki-urls-100000");
+    // Visits the ids in a scattered order to simulate random accesses.
+    let ids: Vec<_> = (0..keys.len()).map(|i| i * 7919 % keys.len()).collect();
+    decode(&mut group, &keys, &ids);
+}
+
+fn criterion_deserialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deserialize");
+    group.sample_size(SAMPLE_SIZE);
+    group.warm_up_time(WARM_UP_TIME);
+    group.measurement_time(MEASURE_TIME);
+    group.sampling_mode(SamplingMode::Flat);
+
+    let keys = load_keyset("data/wiki-urls-100000");
+    deserialize(&mut group, &keys);
+}
+
 fn build(group: &mut BenchmarkGroup<WallTime>, keys: &[String]) {
     for &bs in &BUCKET_SIZES {
         group.bench_function(format!("fcsd<{}>", bs), |b| {
@@ -83,6 +118,69 @@ fn locate(group: &mut BenchmarkGroup<WallTime>, keys: &[String], queries: &[Stri
     });
 }
 
+fn locate_many(group: &mut BenchmarkGroup<WallTime>, keys: &[String], queries: &[String]) {
+    for &bs in &BUCKET_SIZES {
+        group.bench_function(format!("fcsd<{}>", bs), |b| {
+            let dict = fcsd::Set::with_bucket_size(keys, bs).unwrap();
+            let mut locator = dict.locator();
+            b.iter(|| {
+                let mut sum = 0;
+                for id in locator.run_many(queries) {
+                    sum += id.unwrap();
+                }
+                if sum == 0 {
+                    panic!();
+                }
+            });
+        });
+    }
+}
+
+fn decode(group: &mut BenchmarkGroup<WallTime>, keys: &[String], ids: &[usize]) {
+    for &bs in &BUCKET_SIZES {
+        for aligned in [false, true] {
+            let title = if aligned {
+                format!("fcsd<{}>/aligned", bs)
+            } else {
+                format!("fcsd<{}>", bs)
+            };
+            group.bench_function(title, |b| {
+                let mut builder = fcsd::builder::Builder::new(bs)
+                    .unwrap()
+                    .align_buckets(aligned);
+                builder.extend(keys).unwrap();
+                let dict = builder.finish();
+                let mut decoder = dict.decoder();
+                b.iter(|| {
+                    let mut sum = 0;
+                    for &id in ids {
+                        sum += decoder.run(id).len();
+                    }
+                    if sum == 0 {
+                        panic!();
+                    }
+                });
+            });
+        }
+    }
+}
+
+fn deserialize(group: &mut BenchmarkGroup<WallTime>, keys: &[String]) {
+    for &bs in &BUCKET_SIZES {
+        group.bench_function(format!("fcsd<{}>", bs), |b| {
+            let dict = fcsd::Set::with_bucket_size(keys, bs).unwrap();
+            let mut data = Vec::with_capacity(dict.size_in_bytes());
+            dict.serialize_into(&mut data).unwrap();
+            b.iter(|| {
+                let other = fcsd::Set::deserialize_from(&data[..]).unwrap();
+                if other.len() != dict.len() {
+                    panic!();
+                }
+            });
+        });
+    }
+}
+
 fn load_keyset<P>(path: P) -> Vec<String>
 where
     P: AsRef<Path>,
@@ -95,6 +193,13 @@ where
     keys
 }
 
-criterion_group!(benches, criterion_build, criterion_locate);
+criterion_group!(
+    benches,
+    criterion_build,
+    criterion_locate,
+    criterion_locate_many,
+    criterion_decode,
+    criterion_deserialize
+);
 
 criterion_main!(benches);
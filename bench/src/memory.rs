@@ -21,6 +21,16 @@ fn memory(filename: &str) {
         let dict = fcsd::Set::with_bucket_size(&keys, bs).unwrap();
         print(&format!("fcsd<{}>", bs), dict.size_in_bytes(), orig_size);
     }
+    for &bs in &BUCKET_SIZES {
+        let mut builder = fcsd::builder::Builder::new(bs).unwrap().align_buckets(true);
+        builder.extend(&keys).unwrap();
+        let dict = builder.finish();
+        print(
+            &format!("fcsd<{}>/aligned", bs),
+            dict.size_in_bytes(),
+            orig_size,
+        );
+    }
 
     // Fst
     {
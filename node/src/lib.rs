@@ -0,0 +1,117 @@
+//! Node.js bindings of fcsd.
+//!
+//! The serialized form is shared with the Rust crate, so dictionaries built by
+//! Rust pipelines can be loaded with `FcsdSet.deserialize` and vice versa.
+use napi::bindgen_prelude::{Buffer, Either};
+use napi::{Error, Result};
+use napi_derive::napi;
+
+type Key = Either<String, Buffer>;
+
+fn key_bytes(key: &Key) -> &[u8] {
+    match key {
+        Either::A(s) => s.as_bytes(),
+        Either::B(b) => b.as_ref(),
+    }
+}
+
+fn to_napi_error<E: std::fmt::Display>(e: E) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+fn to_id(id: i64, len: usize) -> Result<usize> {
+    usize::try_from(id)
+        .ok()
+        .filter(|&id| id < len)
+        .ok_or_else(|| {
+            Error::from_reason(format!("The id {} is out of range for {} keys.", id, len))
+        })
+}
+
+/// Key and its id reported by prefix queries.
+#[napi(object)]
+pub struct Entry {
+    pub id: i64,
+    pub key: Buffer,
+}
+
+/// Front-coding string dictionary.
+#[napi]
+pub struct FcsdSet {
+    set: fcsd::Set,
+}
+
+#[napi]
+impl FcsdSet {
+    /// Builds a dictionary from keys sorted in the lexicographical order of their bytes.
+    #[napi(factory)]
+    pub fn build(keys: Vec<Key>, bucket_size: Option<u32>) -> Result<Self> {
+        let bucket_size = bucket_size.map_or(fcsd::DEFAULT_BUCKET_SIZE, |b| b as usize);
+        let set = fcsd::Set::with_bucket_size(keys.iter().map(key_bytes), bucket_size)
+            .map_err(to_napi_error)?;
+        Ok(Self { set })
+    }
+
+    /// Deserializes a dictionary written by `serialize` or the Rust crate.
+    ///
+    /// Broken data, such as a truncated or corrupted buffer, throws an error
+    /// without allocating more memory than the buffer has.
+    #[napi(factory)]
+    pub fn deserialize(data: Buffer) -> Result<Self> {
+        let set = fcsd::Set::deserialize_from(data.as_ref()).map_err(to_napi_error)?;
+        Ok(Self { set })
+    }
+
+    /// Serializes the dictionary into a buffer.
+    #[napi]
+    pub fn serialize(&self) -> Result<Buffer> {
+        let mut data = Vec::with_capacity(self.set.size_in_bytes());
+        self.set.serialize_into(&mut data).map_err(to_napi_error)?;
+        Ok(data.into())
+    }
+
+    /// Gets the number of stored keys.
+    #[napi(getter)]
+    pub fn len(&self) -> i64 {
+        self.set.len() as i64
+    }
+
+    /// Checks if the dictionary has no keys.
+    #[napi(getter)]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    /// Gets the number of bytes of the serialized form.
+    #[napi(getter)]
+    pub fn size_in_bytes(&self) -> i64 {
+        self.set.size_in_bytes() as i64
+    }
+
+    /// Returns the id of a key, or `null` if it is not stored.
+    #[napi]
+    pub fn locate(&self, key: Key) -> Option<i64> {
+        self.set.locate(key_bytes(&key)).map(|id| id as i64)
+    }
+
+    /// Returns the key of an id.
+    #[napi]
+    pub fn decode(&self, id: i64) -> Result<Buffer> {
+        let id = to_id(id, self.set.len())?;
+        Ok(self.set.decode(id).into())
+    }
+
+    /// Returns the keys starting with a prefix and their ids, up to `limit` entries if given.
+    #[napi]
+    pub fn predict(&self, prefix: Key, limit: Option<u32>) -> Vec<Entry> {
+        let limit = limit.map_or(usize::MAX, |l| l as usize);
+        self.set
+            .predictive_iter(key_bytes(&prefix))
+            .take(limit)
+            .map(|(id, key)| Entry {
+                id: id as i64,
+                key: key.into(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,220 @@
+//! Alphabet remapping for keys over small alphabets.
+//!
+//! When the keys consist of a few distinct bytes, such as `ACGT` of DNA sequences,
+//! each byte is replaced with a dense code of `⌈log2 σ⌉` bits for the alphabet size `σ`,
+//! and the suffixes of front coding are packed with the codes.
+//! The keys are restored on decoding, so queries take and return the original keys.
+use std::io;
+
+use anyhow::{anyhow, Result};
+use byteorder::{ReadBytesExt, WriteBytesExt};
+
+use crate::utils;
+use crate::END_MARKER;
+
+/// The maximum number of symbols, whose codes fit in 7 bits.
+pub const MAX_SYMBOLS: usize = 128;
+
+/// Code of bytes out of the alphabet.
+const NO_CODE: u8 = u8::MAX;
+
+/// Set of bytes composing keys, each of which is packed in a fixed number of bits.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::alphabet::Alphabet;
+///
+/// let alphabet = Alphabet::new(b"TGCA").unwrap();
+/// assert_eq!(alphabet.symbols(), b"ACGT");
+/// assert_eq!(alphabet.bits_per_symbol(), 2);
+/// assert!(alphabet.contains(b'G'));
+/// assert!(!alphabet.contains(b'N'));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alphabet {
+    symbols: Vec<u8>,
+    codes: [u8; 256],
+    bits: u32,
+}
+
+impl Alphabet {
+    /// Creates an [`Alphabet`] of the given bytes, which are sorted and deduplicated.
+    ///
+    /// # Arguments
+    ///
+    ///  - `symbols`: Bytes composing keys.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `symbols` is empty,
+    ///  - `symbols` contains [`END_MARKER`], or
+    ///  - `symbols` has more than [`MAX_SYMBOLS`] distinct bytes.
+    pub fn new(symbols: &[u8]) -> Result<Self> {
+        let mut symbols = symbols.to_vec();
+        symbols.sort_unstable();
+        symbols.dedup();
+        if symbols.is_empty() {
+            return Err(anyhow!("The alphabet must not be empty."));
+        }
+        if symbols[0] == END_MARKER {
+            return Err(anyhow!(
+                "The alphabet must not contain END_MARKER (={}).",
+                END_MARKER
+            ));
+        }
+        if MAX_SYMBOLS < symbols.len() {
+            return Err(anyhow!(
+                "The alphabet must not have more than {} symbols, but it has {}.",
+                MAX_SYMBOLS,
+                symbols.len()
+            ));
+        }
+        let mut codes = [NO_CODE; 256];
+        for (code, &c) in symbols.iter().enumerate() {
+            codes[c as usize] = code as u8;
+        }
+        let bits = utils::needed_bits(symbols.len() as u64 - 1) as u32;
+        Ok(Self {
+            symbols,
+            codes,
+            bits,
+        })
+    }
+
+    /// Creates an [`Alphabet`] of DNA sequences, `ACGT`, packed in 2 bits.
+    pub fn dna() -> Self {
+        Self::new(b"ACGT").unwrap()
+    }
+
+    /// Creates an [`Alphabet`] of the bytes occurring in the keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: String keys.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the keys have no bytes,
+    /// contain [`END_MARKER`], or have more than [`MAX_SYMBOLS`] distinct bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::alphabet::Alphabet;
+    ///
+    /// let alphabet = Alphabet::from_keys(["GATTACA", "TAG"]).unwrap();
+    /// assert_eq!(alphabet.symbols(), b"ACGT");
+    /// ```
+    pub fn from_keys<I, P>(keys: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let mut used = [false; 256];
+        for key in keys {
+            for &c in key.as_ref() {
+                used[c as usize] = true;
+            }
+        }
+        let symbols: Vec<_> = (0..=u8::MAX).filter(|&c| used[c as usize]).collect();
+        Self::new(&symbols)
+    }
+
+    /// Gets the symbols in ascending order.
+    pub fn symbols(&self) -> &[u8] {
+        &self.symbols
+    }
+
+    /// Gets the number of bits of each packed symbol.
+    pub const fn bits_per_symbol(&self) -> u32 {
+        self.bits
+    }
+
+    /// Checks if the byte is in the alphabet.
+    pub const fn contains(&self, c: u8) -> bool {
+        self.codes[c as usize] != NO_CODE
+    }
+
+    /// Returns the first byte of the key out of the alphabet, if any.
+    pub(crate) fn find_missing(&self, key: &[u8]) -> Option<u8> {
+        key.iter().copied().find(|&c| !self.contains(c))
+    }
+
+    /// Appends the suffix in the form of its length in vbyte followed by the packed codes.
+    pub(crate) fn pack(&self, suffix: &[u8], out: &mut Vec<u8>) {
+        debug_assert!(self.find_missing(suffix).is_none());
+        utils::vbyte::append(out, suffix.len());
+        let (mut acc, mut n) = (0u32, 0);
+        for &c in suffix {
+            acc |= u32::from(self.codes[c as usize]) << n;
+            n += self.bits;
+            if 8 <= n {
+                out.push(acc as u8);
+                acc >>= 8;
+                n -= 8;
+            }
+        }
+        if n != 0 {
+            out.push(acc as u8);
+        }
+    }
+
+    /// Appends the suffix packed at the beginning of `data` to `dec`,
+    /// returning the number of consumed bytes, or `None` if `data` is broken.
+    pub(crate) fn unpack(&self, data: &[u8], dec: &mut Vec<u8>) -> Option<usize> {
+        let (len, num) = utils::vbyte::try_decode(data)?;
+        let num_bytes = len.checked_mul(self.bits as usize)?.div_ceil(8);
+        let packed = data.get(num..num.checked_add(num_bytes)?)?;
+        let mask = (1 << self.bits) - 1;
+        let (mut acc, mut n) = (0u32, 0);
+        let mut bytes = packed.iter();
+        dec.reserve(len);
+        for _ in 0..len {
+            if n < self.bits {
+                acc |= u32::from(*bytes.next()?) << n;
+                n += 8;
+            }
+            dec.push(*self.symbols.get((acc & mask) as usize)?);
+            acc >>= self.bits;
+            n -= self.bits;
+        }
+        Some(num + num_bytes)
+    }
+
+    /// Returns the length of the suffix packed at the beginning of `data`
+    /// and the number of bytes it occupies without unpacking it, or `None` if `data` is broken.
+    pub(crate) fn packed_len(&self, data: &[u8]) -> Option<(usize, usize)> {
+        let (len, num) = utils::vbyte::try_decode(data)?;
+        let num_bytes = len.checked_mul(self.bits as usize)?.div_ceil(8);
+        Some((len, num.checked_add(num_bytes)?))
+    }
+
+    pub(crate) const fn size_in_bytes(&self) -> usize {
+        1 + self.symbols.len()
+    }
+
+    pub(crate) fn serialize_into<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_u8(self.symbols.len() as u8)?;
+        writer.write_all(&self.symbols)?;
+        Ok(())
+    }
+
+    pub(crate) fn deserialize_from<R>(mut reader: R) -> Result<Self>
+    where
+        R: io::Read,
+    {
+        let mut symbols = vec![0; reader.read_u8()? as usize];
+        reader.read_exact(&mut symbols)?;
+        let alphabet = Self::new(&symbols)?;
+        if alphabet.symbols != symbols {
+            return Err(anyhow!("broken alphabet"));
+        }
+        Ok(alphabet)
+    }
+}
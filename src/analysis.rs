@@ -0,0 +1,256 @@
+//! Compressibility analysis of keysets.
+//!
+//! [`analyze`] scans a sorted keyset once and reports statistics that help choose
+//! a bucket size and a codec before committing to a long build.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::analysis::{analyze, Codec};
+//! use fcsd::Set;
+//!
+//! let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+//! let analysis = analyze(keys);
+//!
+//! assert_eq!(analysis.num_keys, 5);
+//! assert_eq!(analysis.lcp_histogram, vec![2, 0, 1, 2]);
+//!
+//! // The projection of plain front coding is exact.
+//! let set = Set::with_bucket_size(keys, 4).unwrap();
+//! assert_eq!(analysis.projected_bytes(Codec::PlainFc, 4), Some(set.size_in_bytes()));
+//! ```
+use std::collections::HashMap;
+
+use crate::utils;
+
+/// Bucket sizes for which sizes are projected.
+pub const BUCKET_SIZES: [usize; 5] = [4, 8, 16, 32, 64];
+
+/// Maximum number of bytes of the sample compressed to estimate RePair.
+const REPAIR_SAMPLE_BYTES: usize = 1 << 14;
+
+/// Maximum number of RePair rules created in the estimation.
+const REPAIR_MAX_RULES: usize = 512;
+
+/// Codec variants of front coding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// Plain front coding, which is implemented by [`Set`](crate::Set).
+    PlainFc,
+    /// Front coding whose strings are compressed with Hu–Tucker codes.
+    Htfc,
+    /// Front coding whose strings are compressed with RePair.
+    Rpfc,
+}
+
+/// Projected size of a dictionary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Projection {
+    /// Codec of strings.
+    pub codec: Codec,
+    /// Bucket size.
+    pub bucket_size: usize,
+    /// Projected number of bytes of the serialized dictionary.
+    pub bytes: usize,
+}
+
+/// Result of [`analyze`].
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// Number of keys.
+    pub num_keys: usize,
+    /// Total number of bytes of keys.
+    pub total_bytes: usize,
+    /// `lcp_histogram[l]` is the number of keys whose LCP with the previous key is `l`.
+    /// The first key is counted as zero.
+    pub lcp_histogram: Vec<usize>,
+    /// `position_entropy[i]` is the empirical entropy in bits of the bytes at position `i`
+    /// among the keys longer than `i`.
+    pub position_entropy: Vec<f64>,
+    /// Projected sizes for the codecs and bucket sizes in [`BUCKET_SIZES`].
+    ///
+    /// The sizes of [`Codec::PlainFc`] are exact, and the others are estimated from the
+    /// order-0 entropy of the strings and RePair over a sample of them, respectively.
+    pub projections: Vec<Projection>,
+}
+
+impl Analysis {
+    /// Returns the projected number of bytes for a codec and a bucket size in [`BUCKET_SIZES`].
+    pub fn projected_bytes(&self, codec: Codec, bucket_size: usize) -> Option<usize> {
+        self.projections
+            .iter()
+            .find(|p| p.codec == codec && p.bucket_size == bucket_size)
+            .map(|p| p.bytes)
+    }
+}
+
+/// Sizes of the parts of a front-coded dictionary for a bucket size.
+#[derive(Default)]
+struct Parts {
+    num_buckets: usize,
+    // Bytes of headers and suffixes, including terminators.
+    strings: usize,
+    // Bytes of vbyte-coded LCPs.
+    lcps: usize,
+    // Offset of the last bucket.
+    last_pointer: usize,
+}
+
+/// Analyzes a keyset given in the lexicographical order.
+///
+/// # Arguments
+///
+///  - `keys`: String keys sorted in the lexicographical order.
+pub fn analyze<I, P>(keys: I) -> Analysis
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<[u8]>,
+{
+    let mut analysis = Analysis::default();
+    let mut parts: Vec<Parts> = BUCKET_SIZES.iter().map(|_| Parts::default()).collect();
+    let mut position_freqs: Vec<[usize; 256]> = Vec::new();
+    let mut string_freqs = [0usize; 256];
+    let mut sample = Vec::new();
+    let mut last = Vec::new();
+
+    for key in keys {
+        let key = key.as_ref();
+        let lcp = if analysis.num_keys == 0 {
+            0
+        } else {
+            utils::get_lcp(&last, key).0
+        };
+        if analysis.lcp_histogram.len() <= lcp {
+            analysis.lcp_histogram.resize(lcp + 1, 0);
+        }
+        analysis.lcp_histogram[lcp] += 1;
+
+        if position_freqs.len() < key.len() {
+            position_freqs.resize(key.len(), [0; 256]);
+        }
+        for (freqs, &c) in position_freqs.iter_mut().zip(key) {
+            freqs[c as usize] += 1;
+        }
+
+        for (parts, &bucket_size) in parts.iter_mut().zip(BUCKET_SIZES.iter()) {
+            if analysis.num_keys % bucket_size == 0 {
+                parts.last_pointer = parts.strings + parts.lcps;
+                parts.num_buckets += 1;
+                parts.strings += key.len() + 1;
+            } else {
+                let mut lcp_bytes = Vec::new();
+                utils::vbyte::append(&mut lcp_bytes, lcp);
+                parts.lcps += lcp_bytes.len();
+                parts.strings += key.len() - lcp + 1;
+            }
+        }
+
+        // Statistics of strings are taken under the default bucket size.
+        let stored = if analysis.num_keys % crate::DEFAULT_BUCKET_SIZE == 0 {
+            key
+        } else {
+            &key[lcp..]
+        };
+        for &c in stored {
+            string_freqs[c as usize] += 1;
+        }
+        string_freqs[crate::END_MARKER as usize] += 1;
+        if sample.len() < REPAIR_SAMPLE_BYTES {
+            sample.extend_from_slice(stored);
+            sample.push(crate::END_MARKER);
+        }
+
+        analysis.num_keys += 1;
+        analysis.total_bytes += key.len();
+        last.clear();
+        last.extend_from_slice(key);
+    }
+
+    analysis.position_entropy = position_freqs.iter().map(|f| entropy(f)).collect();
+
+    let entropy_ratio = entropy(&string_freqs) / 8.0;
+    let repair_ratio = repair_ratio(&sample);
+    for (parts, &bucket_size) in parts.iter().zip(BUCKET_SIZES.iter()) {
+        let overhead = overhead_bytes(parts);
+        let strings = parts.strings as f64;
+        for &(codec, ratio) in &[
+            (Codec::PlainFc, 1.0),
+            (Codec::Htfc, entropy_ratio),
+            (Codec::Rpfc, repair_ratio),
+        ] {
+            analysis.projections.push(Projection {
+                codec,
+                bucket_size,
+                bytes: overhead + parts.lcps + (strings * ratio).ceil() as usize,
+            });
+        }
+    }
+    analysis
+}
+
+/// Returns the number of bytes of the serialized dictionary other than strings and LCPs.
+const fn overhead_bytes(parts: &Parts) -> usize {
+    let bits = utils::needed_bits(parts.last_pointer as u64);
+    let pointer_words = (parts.num_buckets * bits).div_ceil(64);
+    let mut bytes = 4 + 1; // cookie, flags
+    bytes += 8 + pointer_words * 8 + 8 * 3; // pointers
+    bytes += 8; // length of the serialized area
+    bytes += 8 * 4; // len, bucket_bits, bucket_mask, max_length
+    bytes += 3; // flags of external_ids, payloads, and values
+    bytes
+}
+
+/// Returns the empirical entropy in bits of a frequency table.
+fn entropy(freqs: &[usize]) -> f64 {
+    let total: usize = freqs.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    freqs
+        .iter()
+        .filter(|&&f| f != 0)
+        .map(|&f| {
+            let p = f as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Estimates the compression ratio of RePair by replacing the most frequent pairs
+/// in a sample until no pair occurs twice or the number of rules reaches the limit.
+fn repair_ratio(sample: &[u8]) -> f64 {
+    if sample.is_empty() {
+        return 1.0;
+    }
+    let mut seq: Vec<u32> = sample.iter().map(|&c| u32::from(c)).collect();
+    let mut num_rules = 0;
+    while num_rules < REPAIR_MAX_RULES {
+        let mut counts: HashMap<(u32, u32), usize> = HashMap::new();
+        for w in seq.windows(2) {
+            *counts.entry((w[0], w[1])).or_insert(0) += 1;
+        }
+        let Some((&pair, &freq)) = counts.iter().max_by_key(|&(&pair, &freq)| (freq, pair)) else {
+            break;
+        };
+        if freq < 2 {
+            break;
+        }
+        let symbol = 256 + num_rules as u32;
+        let mut replaced = Vec::with_capacity(seq.len());
+        let mut i = 0;
+        while i < seq.len() {
+            if i + 1 < seq.len() && (seq[i], seq[i + 1]) == pair {
+                replaced.push(symbol);
+                i += 2;
+            } else {
+                replaced.push(seq[i]);
+                i += 1;
+            }
+        }
+        seq = replaced;
+        num_rules += 1;
+    }
+    let bits = utils::needed_bits(255 + num_rules as u64) as f64;
+    let compressed = (seq.len() + 2 * num_rules) as f64 * bits;
+    (compressed / (sample.len() as f64 * 8.0)).min(1.0)
+}
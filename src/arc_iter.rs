@@ -0,0 +1,172 @@
+//! Owned iterators over dictionaries shared with [`Arc`].
+//!
+//! Different from [`Iter`](crate::iter::Iter) and [`PredictiveIter`](crate::predictive_iter::PredictiveIter),
+//! which borrow the dictionary, the iterators in this module hold a reference count of it,
+//! so they are `'static` and can be returned from functions, stored in structs,
+//! and moved to other threads.
+use std::iter::FusedIterator;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::iter::IterState;
+use crate::predictive_iter::PredictiveState;
+use crate::Set;
+
+/// Owned version of [`Iter`](crate::iter::Iter) to enumerate keys stored in the dictionary.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+/// use fcsd::Set;
+/// use fcsd::arc_iter::ArcIter;
+///
+/// let set = Arc::new(Set::new(["ICDM", "ICML", "SIGIR"]).unwrap());
+/// let iter = ArcIter::new(set.clone());
+///
+/// let keys = std::thread::spawn(move || iter.map(|(_, key)| key).collect::<Vec<_>>());
+/// assert_eq!(keys.join().unwrap(), vec![b"ICDM".to_vec(), b"ICML".to_vec(), b"SIGIR".to_vec()]);
+/// ```
+#[derive(Clone)]
+pub struct ArcIter {
+    set: Arc<Set>,
+    state: IterState,
+}
+
+impl ArcIter {
+    /// Makes an iterator [`ArcIter`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Shared front-coding dictionay.
+    pub fn new(set: Arc<Set>) -> Self {
+        let state = IterState::new(&set);
+        Self { set, state }
+    }
+
+    /// Moves the iterator so that the next key is the one associated with `id`,
+    /// like [`Iter::seek_id`](crate::iter::Iter::seek_id).
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Id of the next key.
+    pub fn seek_id(&mut self, id: usize) {
+        self.state.seek_id(&self.set, id);
+    }
+
+    /// Returns the next id and key, reporting broken data as an error
+    /// like [`Iter::try_next`](crate::iter::Iter::try_next).
+    pub fn try_next(&mut self) -> Result<Option<(usize, Vec<u8>)>> {
+        self.state.try_next(&self.set)
+    }
+
+    /// Gets the shared dictionary.
+    pub const fn set(&self) -> &Arc<Set> {
+        &self.set
+    }
+}
+
+impl Iterator for ArcIter {
+    type Item = (usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.try_next().ok().flatten()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.state.nth(&self.set, n)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.state.num_remaining(&self.set);
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for ArcIter {}
+
+impl FusedIterator for ArcIter {}
+
+/// Owned version of [`PredictiveIter`](crate::predictive_iter::PredictiveIter)
+/// to enumerate keys starting from a given string.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+/// use fcsd::Set;
+/// use fcsd::arc_iter::ArcPredictiveIter;
+///
+/// fn complete(set: &Arc<Set>, prefix: &str) -> impl Iterator<Item = (usize, Vec<u8>)> {
+///     ArcPredictiveIter::new(set.clone(), prefix)
+/// }
+///
+/// let set = Arc::new(Set::new(["ICDM", "ICML", "SIGIR"]).unwrap());
+/// let mut iter = complete(&set, "IC");
+/// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
+/// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[derive(Clone)]
+pub struct ArcPredictiveIter {
+    set: Arc<Set>,
+    state: PredictiveState,
+}
+
+impl ArcPredictiveIter {
+    /// Makes an iterator [`ArcPredictiveIter`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Shared front-coding dictionay.
+    ///  - `key`: Prefix key.
+    pub fn new<P>(set: Arc<Set>, key: P) -> Self
+    where
+        P: AsRef<[u8]>,
+    {
+        let state = PredictiveState::new(&set, key.as_ref());
+        Self { set, state }
+    }
+
+    /// Resets the prefix key.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: Prefix key.
+    pub fn reset<P>(&mut self, key: P)
+    where
+        P: AsRef<[u8]>,
+    {
+        self.state.reset(&self.set, key.as_ref());
+    }
+
+    /// Returns the next id and key, reporting broken data as an error
+    /// like [`PredictiveIter::try_next`](crate::predictive_iter::PredictiveIter::try_next).
+    pub fn try_next(&mut self) -> Result<Option<(usize, Vec<u8>)>> {
+        self.state.try_next(&self.set)
+    }
+
+    /// Gets the shared dictionary.
+    pub const fn set(&self) -> &Arc<Set> {
+        &self.set
+    }
+}
+
+impl Iterator for ArcPredictiveIter {
+    type Item = (usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Broken data ends the iteration.
+        self.try_next().ok().flatten()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.state.num_remaining(&self.set);
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for ArcPredictiveIter {}
+
+impl FusedIterator for ArcPredictiveIter {}
@@ -0,0 +1,122 @@
+//! Automata to enumerate the keys accepted by them.
+//!
+//! An [`Automaton`] is run over each key byte by byte. [`AutomatonIter`] shares the
+//! states of common prefixes between consecutive keys and skips all the keys
+//! under a prefix once the automaton reports that the prefix can never match.
+use crate::iter::Iter;
+use crate::order::KeyOrder;
+use crate::utils;
+use crate::Set;
+
+/// Deterministic automaton over bytes.
+pub trait Automaton {
+    /// State of the automaton.
+    type State: Clone;
+
+    /// Returns the start state.
+    fn start(&self) -> Self::State;
+
+    /// Returns the state transitioned from `state` by `byte`.
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State;
+
+    /// Checks if `state` is a match state.
+    fn is_match(&self, state: &Self::State) -> bool;
+
+    /// Checks if a match state can be reached from `state`.
+    ///
+    /// Returning `true` is always correct, but returning `false` as early as
+    /// possible allows keys to be skipped.
+    fn can_match(&self, state: &Self::State) -> bool;
+}
+
+/// Iterator to enumerate keys accepted by an [`Automaton`].
+pub struct AutomatonIter<'a, A>
+where
+    A: Automaton,
+{
+    set: &'a Set,
+    aut: A,
+    iter: Iter<'a>,
+    // Prefix of the last key whose states are stored.
+    key: Vec<u8>,
+    // states[i] is the state after reading key[..i].
+    states: Vec<A::State>,
+}
+
+impl<'a, A> AutomatonIter<'a, A>
+where
+    A: Automaton,
+{
+    /// Makes an iterator [`AutomatonIter`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///  - `aut`: Automaton to be run.
+    pub fn new(set: &'a Set, aut: A) -> Self {
+        let start = aut.start();
+        let mut iter = set.iter();
+        if !aut.can_match(&start) {
+            iter.seek_id(set.len());
+        }
+        Self {
+            set,
+            aut,
+            iter,
+            key: Vec::with_capacity(set.max_length()),
+            states: vec![start],
+        }
+    }
+
+    /// Moves the iterator so that the keys before `id` are skipped.
+    pub(crate) fn seek(&mut self, id: usize) {
+        if self.aut.can_match(&self.states[0]) {
+            self.iter.seek_id(id);
+        }
+    }
+}
+
+impl<'a, A> Iterator for AutomatonIter<'a, A>
+where
+    A: Automaton,
+{
+    type Item = (usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let id = self.iter.advance()?;
+            let key = self.iter.key();
+
+            let (lcp, _) = utils::get_lcp(&self.key, key);
+            self.states.truncate(lcp + 1);
+            self.key.truncate(lcp);
+
+            let mut dead = false;
+            for &c in &key[lcp..] {
+                let state = self.aut.accept(self.states.last().unwrap(), c);
+                self.key.push(c);
+                if !self.aut.can_match(&state) {
+                    dead = true;
+                    break;
+                }
+                self.states.push(state);
+            }
+
+            if dead {
+                // Skips all the keys starting with the dead prefix, if contiguous.
+                let prefix = &self.key[..];
+                if self.set.key_order() == KeyOrder::Lexicographic {
+                    let next_id = self
+                        .set
+                        .partition_point(|key| key < prefix || key.starts_with(prefix));
+                    self.iter.seek_id(next_id);
+                }
+                self.key.pop();
+                continue;
+            }
+            if self.aut.is_match(self.states.last().unwrap()) {
+                return Some((id, key.to_vec()));
+            }
+        }
+    }
+}
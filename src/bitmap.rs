@@ -0,0 +1,89 @@
+//! Fixed-length bitmap answering batched membership queries.
+
+/// Bitmap of a fixed number of bits, such as returned by [`Set::prefix_membership`](crate::Set::prefix_membership).
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let set = Set::new(["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"]).unwrap();
+/// let bits = set.prefix_membership(&["IC", "KDD", "SIGM"]);
+/// assert_eq!(bits.len(), 3);
+/// assert!(bits.get(0));
+/// assert!(!bits.get(1));
+/// assert_eq!(bits.count_ones(), 2);
+/// assert_eq!(bits.ones().collect::<Vec<_>>(), vec![0, 2]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    /// Makes an empty [`Bitmap`].
+    pub const fn new() -> Self {
+        Self {
+            words: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Clears the bitmap to `len` unset bits, reusing the allocated memory.
+    pub(crate) fn reset(&mut self, len: usize) {
+        self.words.clear();
+        self.words.resize(len.div_ceil(64), 0);
+        self.len = len;
+    }
+
+    pub(crate) fn set(&mut self, i: usize) {
+        debug_assert!(i < self.len);
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    /// Gets the number of bits.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the bitmap has no bits.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Gets the `i`-th bit.
+    ///
+    /// # Panics
+    ///
+    /// If `i` is no less than the number of bits, `panic!` will occur.
+    pub fn get(&self, i: usize) -> bool {
+        assert!(i < self.len);
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    /// Gets the number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Makes an iterator to enumerate the positions of set bits in ascending order.
+    pub fn ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &w)| {
+            let mut w = w;
+            std::iter::from_fn(move || {
+                if w == 0 {
+                    return None;
+                }
+                let j = w.trailing_zeros() as usize;
+                w &= w - 1;
+                Some(i * 64 + j)
+            })
+        })
+    }
+
+    /// Gets the bits packed in words, where the `i`-th bit is the `i % 64`-th bit of word `i / 64`.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+}
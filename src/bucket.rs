@@ -0,0 +1,221 @@
+//! Decoded and raw views of buckets.
+use std::iter::FusedIterator;
+
+use crate::Set;
+
+/// View of the keys in a bucket, decoded at once.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+/// let set = Set::with_bucket_size(keys, 4).unwrap();
+///
+/// let mut headers = vec![];
+/// set.for_each_bucket(|bi, bucket| {
+///     headers.push((bi, bucket.first_id(), bucket.len(), bucket.key(0).to_vec()));
+/// });
+/// assert_eq!(headers, vec![(0, 0, 4, b"ICDM".to_vec()), (1, 4, 1, b"SIGMOD".to_vec())]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct BucketView<'a> {
+    first_id: usize,
+    keys: &'a [u8],
+    ends: &'a [usize],
+}
+
+impl<'a> BucketView<'a> {
+    pub(crate) const fn new(first_id: usize, keys: &'a [u8], ends: &'a [usize]) -> Self {
+        Self {
+            first_id,
+            keys,
+            ends,
+        }
+    }
+
+    /// Gets the id of the first key in the bucket.
+    pub const fn first_id(&self) -> usize {
+        self.first_id
+    }
+
+    /// Gets the number of keys in the bucket.
+    pub const fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Checks if the bucket has no keys.
+    pub const fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Gets the `i`-th key in the bucket.
+    ///
+    /// # Panics
+    ///
+    /// If `i` is no less than the number of keys in the bucket, `panic!` will occur.
+    pub fn key(&self, i: usize) -> &'a [u8] {
+        let beg = if i == 0 { 0 } else { self.ends[i - 1] };
+        &self.keys[beg..self.ends[i]]
+    }
+
+    /// Makes an iterator to enumerate ids and keys in the bucket.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &'a [u8])> + 'a {
+        let view = *self;
+        (0..view.len()).map(move |i| (view.first_id + i, view.key(i)))
+    }
+}
+
+/// Read-only view of the encoded content of a bucket, made by [`Set::raw_bucket`].
+///
+/// A bucket consists of the header, which is the first key, followed by the pairs of
+/// the LCP with the previous key and the remaining suffix of the others.
+/// The suffixes are the bytes stored in the dictionary, which are packed with their
+/// vbyte-encoded lengths if the dictionary has an [`Alphabet`](crate::alphabet::Alphabet).
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+/// let set = Set::with_bucket_size(keys, 4).unwrap();
+///
+/// let bucket = set.raw_bucket(0);
+/// assert_eq!(bucket.header(), b"ICDM");
+/// let pairs: Vec<_> = bucket.iter().collect();
+/// assert_eq!(
+///     pairs,
+///     vec![(0, &b"ICDM"[..]), (2, b"ML"), (0, b"SIGIR"), (3, b"KDD")]
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct RawBucket<'a> {
+    set: &'a Set,
+    bi: usize,
+}
+
+impl<'a> RawBucket<'a> {
+    pub(crate) const fn new(set: &'a Set, bi: usize) -> Self {
+        Self { set, bi }
+    }
+
+    /// Gets the bucket id.
+    pub const fn bucket_id(&self) -> usize {
+        self.bi
+    }
+
+    /// Gets the id of the first key in the bucket.
+    pub const fn first_id(&self) -> usize {
+        self.bi * self.set.bucket_size()
+    }
+
+    /// Gets the number of keys in the bucket.
+    pub fn len(&self) -> usize {
+        std::cmp::min(self.set.bucket_size(), self.set.len() - self.first_id())
+    }
+
+    /// Checks if the bucket has no keys, which never holds for a bucket of a dictionary.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the encoded header.
+    pub fn header(&self) -> &'a [u8] {
+        self.suffix_at(self.start()).0
+    }
+
+    /// Gets the encoded bytes of the bucket, including the padding to the next bucket
+    /// if the dictionary was built with [`Builder::align_buckets`](crate::builder::Builder::align_buckets).
+    pub fn as_bytes(&self) -> &'a [u8] {
+        let end = if self.bi + 1 < self.set.num_buckets() {
+            self.set.pointers.get(self.bi + 1) as usize
+        } else {
+            self.set.serialized.len()
+        };
+        &self.set.serialized[self.start()..end]
+    }
+
+    /// Makes an iterator to enumerate the pairs of the LCP and the encoded suffix of the keys,
+    /// where the header is yielded with the LCP of zero.
+    pub fn iter(&self) -> RawBucketIter<'a> {
+        RawBucketIter {
+            bucket: *self,
+            pos: self.start(),
+            remaining: self.len(),
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.set.pointers.get(self.bi) as usize
+    }
+
+    /// Returns the encoded suffix at `pos` and the position following it.
+    fn suffix_at(&self, pos: usize) -> (&'a [u8], usize) {
+        let set = self.set;
+        let (len, next_pos) = set.skip_next(pos);
+        if set.alphabet.is_some() {
+            (&set.serialized[pos..next_pos], next_pos)
+        } else {
+            (&set.serialized[pos..pos + len], next_pos)
+        }
+    }
+}
+
+/// Iterator enumerating the pairs of the LCP and the encoded suffix in a bucket,
+/// made by [`RawBucket::iter`].
+#[derive(Clone)]
+pub struct RawBucketIter<'a> {
+    bucket: RawBucket<'a>,
+    pos: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for RawBucketIter<'a> {
+    type Item = (usize, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let lcp = if self.remaining == self.bucket.len() {
+            0
+        } else {
+            let (lcp, pos) = self.bucket.set.decode_lcp(self.pos);
+            self.pos = pos;
+            lcp
+        };
+        let (suffix, pos) = self.bucket.suffix_at(self.pos);
+        self.pos = pos;
+        self.remaining -= 1;
+        Some((lcp, suffix))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for RawBucketIter<'_> {}
+
+impl FusedIterator for RawBucketIter<'_> {}
+
+/// Decodes the keys in the bucket into the concatenation `keys` and their end positions `ends`.
+pub(crate) fn decode_bucket(set: &Set, bi: usize, keys: &mut Vec<u8>, ends: &mut Vec<usize>) {
+    keys.clear();
+    ends.clear();
+    let mut pos = set.decode_header(bi, keys);
+    ends.push(keys.len());
+
+    let num_keys = std::cmp::min(set.bucket_size(), set.len() - bi * set.bucket_size());
+    for _ in 1..num_keys {
+        let (lcp, next_pos) = set.decode_lcp(pos);
+        let prev = ends.len().checked_sub(2).map_or(0, |i| ends[i]);
+        // Broken LCPs of an unverified dictionary are clamped to the previous key.
+        let lcp = std::cmp::min(lcp, keys.len() - prev);
+        keys.extend_from_within(prev..prev + lcp);
+        pos = set.decode_next(next_pos, keys);
+        ends.push(keys.len());
+    }
+}
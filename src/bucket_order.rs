@@ -0,0 +1,82 @@
+use std::iter::FusedIterator;
+
+use crate::Set;
+
+/// Iterator to enumerate requested ids and their keys in the order of storage.
+///
+/// The requests are sorted by id, so each bucket is decoded at most once
+/// and the serialized area is scanned forward.
+/// Each item reports the position of the id in the request,
+/// which is the permutation back to the request order.
+#[derive(Clone)]
+pub struct BucketOrderIter<'a> {
+    set: &'a Set,
+    // Pairs of ids and their positions in the request, sorted by id.
+    requests: Vec<(usize, usize)>,
+    cursor: usize,
+    dec: Vec<u8>,
+    // The id of the key in dec and the position of the next key in the serialized area.
+    dec_id: usize,
+    pos: usize,
+}
+
+impl<'a> BucketOrderIter<'a> {
+    /// Makes an iterator [`BucketOrderIter`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///  - `ids`: Ids to be decoded.
+    ///
+    /// # Panics
+    ///
+    /// If an id is no less than the number of keys, `panic!` will occur.
+    pub fn new(set: &'a Set, ids: &[usize]) -> Self {
+        let mut requests: Vec<_> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        requests.sort_unstable();
+        if let Some(&(id, _)) = requests.last() {
+            assert!(id < set.len(), "The id {} is out of bounds.", id);
+        }
+        Self {
+            set,
+            requests,
+            cursor: 0,
+            dec: Vec::with_capacity(set.max_length()),
+            dec_id: usize::MAX,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for BucketOrderIter<'a> {
+    /// Triple of the position in the request, the id, and the key.
+    type Item = (usize, usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &(id, index) = self.requests.get(self.cursor)?;
+        self.cursor += 1;
+
+        let set = self.set;
+        let bi = set.bucket_id(id);
+        if self.dec_id == usize::MAX || set.bucket_id(self.dec_id) != bi {
+            self.pos = set.decode_header(bi, &mut self.dec);
+            self.dec_id = bi * set.bucket_size();
+        }
+        while self.dec_id < id {
+            let (lcp, next_pos) = set.decode_lcp(self.pos);
+            self.dec.truncate(lcp);
+            self.pos = set.decode_next(next_pos, &mut self.dec);
+            self.dec_id += 1;
+        }
+        Some((index, id, self.dec.clone()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.requests.len() - self.cursor;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for BucketOrderIter<'_> {}
+
+impl FusedIterator for BucketOrderIter<'_> {}
@@ -0,0 +1,142 @@
+//! Budgets bounding the work of enumerating queries.
+//!
+//! A query such as a 1-byte prefix over a huge dictionary can enumerate most of the keys.
+//! [`Budgeted`] wraps an iterator of keys and stops it with [`BudgetExceeded`]
+//! once a [`Budget`] is used up, which protects services shared by many clients.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::budget::Budget;
+//! use fcsd::Set;
+//!
+//! let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+//! let set = Set::new(keys).unwrap();
+//!
+//! let budget = Budget::new().max_keys(2);
+//! let mut iter = set.predictive_iter_with_budget(b"SIG", budget);
+//! assert_eq!(iter.next(), Some(Ok((2, b"SIGIR".to_vec()))));
+//! assert_eq!(iter.next(), Some(Ok((3, b"SIGKDD".to_vec()))));
+//! assert!(iter.next().unwrap().is_err());
+//! assert_eq!(iter.next(), None);
+//! ```
+use std::fmt;
+use std::iter::FusedIterator;
+
+/// Limits of the keys and bytes enumerated by a query, which are unlimited by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    max_keys: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl Budget {
+    /// Makes an unlimited [`Budget`].
+    pub const fn new() -> Self {
+        Self {
+            max_keys: None,
+            max_bytes: None,
+        }
+    }
+
+    /// Limits the number of keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `max_keys`: Maximum number of keys.
+    pub const fn max_keys(mut self, max_keys: usize) -> Self {
+        self.max_keys = Some(max_keys);
+        self
+    }
+
+    /// Limits the total length of the keys in bytes.
+    ///
+    /// # Arguments
+    ///
+    ///  - `max_bytes`: Maximum number of decoded bytes.
+    pub const fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+}
+
+/// Error returned when a query uses up its [`Budget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetExceeded {
+    /// The number of keys enumerated before the error.
+    pub keys: usize,
+    /// The total length of the keys enumerated before the error.
+    pub bytes: usize,
+}
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The query exceeded its budget after {} keys of {} bytes.",
+            self.keys, self.bytes
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Iterator adaptor enforcing a [`Budget`] on an iterator of ids and keys,
+/// such as [`Iter`](crate::iter::Iter) and [`PredictiveIter`](crate::predictive_iter::PredictiveIter).
+///
+/// It yields [`BudgetExceeded`] once instead of the key exceeding the budget and then ends.
+#[derive(Clone)]
+pub struct Budgeted<I> {
+    iter: I,
+    budget: Budget,
+    keys: usize,
+    bytes: usize,
+    exceeded: bool,
+}
+
+impl<I> Budgeted<I> {
+    /// Makes an iterator [`Budgeted`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `iter`: Iterator of ids and keys.
+    ///  - `budget`: Budget of the enumeration.
+    pub const fn new(iter: I, budget: Budget) -> Self {
+        Self {
+            iter,
+            budget,
+            keys: 0,
+            bytes: 0,
+            exceeded: false,
+        }
+    }
+}
+
+impl<I> Iterator for Budgeted<I>
+where
+    I: Iterator<Item = (usize, Vec<u8>)>,
+{
+    type Item = Result<(usize, Vec<u8>), BudgetExceeded>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exceeded {
+            return None;
+        }
+        let (id, key) = self.iter.next()?;
+        let (keys, bytes) = (self.keys + 1, self.bytes + key.len());
+        if self.budget.max_keys.is_some_and(|max| max < keys)
+            || self.budget.max_bytes.is_some_and(|max| max < bytes)
+        {
+            self.exceeded = true;
+            return Some(Err(BudgetExceeded {
+                keys: self.keys,
+                bytes: self.bytes,
+            }));
+        }
+        self.keys = keys;
+        self.bytes = bytes;
+        Some(Ok((id, key)))
+    }
+}
+
+impl<I> FusedIterator for Budgeted<I> where I: FusedIterator<Item = (usize, Vec<u8>)> {}
@@ -1,20 +1,254 @@
+use std::fmt;
+#[cfg(feature = "spill")]
+use std::fs::File;
+use std::io::{self, Write};
+#[cfg(feature = "spill")]
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(feature = "spill")]
+use std::sync::PoisonError;
+use std::sync::{Arc, Mutex};
+
 use anyhow::{anyhow, Result};
 
+use crate::alphabet::Alphabet;
+use crate::external_ids::ExternalIds;
 use crate::intvec::IntVector;
+use crate::order::KeyOrder;
+use crate::padding::PaddedWriter;
 use crate::utils;
+use crate::values::{ValueCodec, Values};
 use crate::Set;
-use crate::END_MARKER;
+use crate::{CACHE_LINE, DEFAULT_BUCKET_SIZE, END_MARKER};
+
+/// Reason why a key cannot be added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddErrorKind {
+    /// The key is no more than the last one in the order set with [`Builder::key_order`].
+    Unsorted,
+    /// The key contains [`END_MARKER`].
+    EndMarker,
+    /// The key is not valid UTF-8 while [`Builder::require_utf8`] is enabled.
+    InvalidUtf8,
+    /// The key is longer than the limit set with [`Builder::max_key_len`].
+    TooLong {
+        /// Length of the key in bytes.
+        len: usize,
+        /// The limit of the key length in bytes.
+        max_key_len: usize,
+    },
+    /// The key contains a byte out of the alphabet set with [`Builder::alphabet`].
+    OutOfAlphabet {
+        /// The first byte out of the alphabet.
+        byte: u8,
+    },
+}
+
+impl fmt::Display for AddErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsorted => write!(f, "The input key must be more than the last one."),
+            Self::EndMarker => write!(
+                f,
+                "The input key must not contain END_MARKER (={}).",
+                END_MARKER
+            ),
+            Self::InvalidUtf8 => write!(f, "The input key must be valid UTF-8."),
+            Self::TooLong { len, max_key_len } => write!(
+                f,
+                "The input key must not be longer than max_key_len (={}), but it has {} bytes.",
+                max_key_len, len
+            ),
+            Self::OutOfAlphabet { byte } => write!(
+                f,
+                "The input key must consist of the alphabet, but it contains {:#04x}.",
+                byte
+            ),
+        }
+    }
+}
+
+/// Error of [`Builder::extend`] locating the offending key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddError {
+    /// Position of the key in the input.
+    pub index: usize,
+    /// The key that cannot be added.
+    pub key: Vec<u8>,
+    /// Reason why the key cannot be added.
+    pub kind: AddErrorKind,
+}
+
+impl fmt::Display for AddError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (index: {}, key: \"{}\")",
+            self.kind,
+            self.index,
+            self.key.escape_ascii()
+        )
+    }
+}
+
+impl std::error::Error for AddError {}
+
+/// The number of keys between two progress reports.
+pub(crate) const PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// Progress of a long-running construction, reported by [`Builder::on_progress`] and similar hooks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of keys added so far.
+    pub keys: usize,
+    /// The number of bytes of the serialized area written so far.
+    pub bytes: usize,
+}
+
+/// Policy for keys longer than the limit set with [`Builder::max_key_len`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyLenPolicy {
+    /// Rejects the key with [`AddErrorKind::TooLong`].
+    #[default]
+    Reject,
+    /// Truncates the key to the limit, which is shortened to a character boundary
+    /// while [`Builder::require_utf8`] is enabled.
+    /// The truncated key must still be more than the last one.
+    Truncate,
+}
+
+/// Configuration of building a dictionary, collecting the options of [`Builder`].
+///
+/// The options except [`BuildConfig::key_len_policy`] are recorded in the dictionary,
+/// so [`Set::build_config`] restores them and a dictionary can be rebuilt with the same choices.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::builder::{BuildConfig, Builder};
+/// use fcsd::order::KeyOrder;
+///
+/// let config = BuildConfig {
+///     bucket_size: 4,
+///     require_utf8: true,
+///     key_order: KeyOrder::Natural,
+///     ..Default::default()
+/// };
+/// let mut builder = Builder::with_config(config.clone()).unwrap();
+/// builder.extend(["file1", "file2", "file10"]).unwrap();
+///
+/// let set = builder.finish();
+/// assert_eq!(set.build_config(), config);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildConfig {
+    /// The number of keys in each bucket, which must be a power of two.
+    pub bucket_size: usize,
+    /// Whether keys must be valid UTF-8, set with [`Builder::require_utf8`].
+    pub require_utf8: bool,
+    /// Order of keys, set with [`Builder::key_order`].
+    pub key_order: KeyOrder,
+    /// Limit of the key length in bytes, set with [`Builder::max_key_len`].
+    pub max_key_len: Option<usize>,
+    /// Policy for over-long keys, set with [`Builder::key_len_policy`].
+    pub key_len_policy: KeyLenPolicy,
+    /// Alphabet with which suffixes are packed, set with [`Builder::alphabet`].
+    pub alphabet: Option<Alphabet>,
+    /// Whether buckets are aligned to cache lines, set with [`Builder::align_buckets`].
+    pub align_buckets: bool,
+    /// Codec of the value area, set with [`Builder::with_value_codec`].
+    pub value_codec: ValueCodec,
+}
+
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            bucket_size: DEFAULT_BUCKET_SIZE,
+            require_utf8: false,
+            key_order: KeyOrder::default(),
+            max_key_len: None,
+            key_len_policy: KeyLenPolicy::default(),
+            alphabet: None,
+            align_buckets: false,
+            value_codec: ValueCodec::default(),
+        }
+    }
+}
+
+type ProgressHook = Arc<Mutex<dyn FnMut(Progress) + Send>>;
+
+/// Temporary file receiving the buckets spilled from the serialized area.
+///
+/// Clones of a builder share the file and append their own segments to it,
+/// so that cloning performs no I/O.
+#[cfg(feature = "spill")]
+#[derive(Clone)]
+struct Spill {
+    threshold: usize,
+    // Created on the first spill.
+    file: Option<Arc<Mutex<File>>>,
+    // Offsets and lengths of the segments spilled by this builder, in order.
+    segments: Vec<(u64, u64)>,
+}
+
+#[cfg(feature = "spill")]
+impl Spill {
+    /// Appends `bytes` to the file.
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self
+                .file
+                .insert(Arc::new(Mutex::new(tempfile::tempfile()?))),
+        };
+        let offset = {
+            let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            let offset = file.seek(SeekFrom::End(0))?;
+            file.write_all(bytes)?;
+            offset
+        };
+        self.segments.push((offset, bytes.len() as u64));
+        Ok(())
+    }
+
+    /// Copies the spilled bytes into `writer`.
+    fn copy_into<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            for &(offset, len) in &self.segments {
+                file.seek(SeekFrom::Start(offset))?;
+                if io::copy(&mut (&mut *file).take(len), writer)? != len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
 
 /// Builder class for [`Set`].
 #[derive(Clone)]
 pub struct Builder {
-    pointers: Vec<u64>,
+    pointers: IntVector,
     serialized: Vec<u8>,
     last_key: Vec<u8>,
+    external_ids: Vec<u64>,
+    payloads: IntVector,
+    values: Values,
     len: usize,
     bucket_bits: usize,
     bucket_mask: usize,
     max_length: usize,
+    utf8: bool,
+    max_key_len: Option<usize>,
+    key_len_policy: KeyLenPolicy,
+    order: KeyOrder,
+    alphabet: Option<Alphabet>,
+    bucket_aligned: bool,
+    #[cfg(feature = "spill")]
+    spill: Option<Spill>,
+    // The number of bytes spilled from the head of the serialized area.
+    spilled: usize,
+    on_progress: Option<ProgressHook>,
 }
 
 impl Builder {
@@ -37,17 +271,99 @@ impl Builder {
             Err(anyhow!("bucket_size must be a power of two."))
         } else {
             Ok(Self {
-                pointers: Vec::new(),
+                pointers: IntVector::new(),
                 serialized: Vec::new(),
                 last_key: Vec::new(),
+                external_ids: Vec::new(),
+                payloads: IntVector::new(),
+                values: Values::new(ValueCodec::default()),
                 len: 0,
-                bucket_bits: utils::needed_bits((bucket_size - 1) as u64),
+                bucket_bits: bucket_size.trailing_zeros() as usize,
                 bucket_mask: bucket_size - 1,
                 max_length: 0,
+                utf8: false,
+                max_key_len: None,
+                key_len_policy: KeyLenPolicy::Reject,
+                order: KeyOrder::Lexicographic,
+                alphabet: None,
+                bucket_aligned: false,
+                #[cfg(feature = "spill")]
+                spill: None,
+                spilled: 0,
+                on_progress: None,
             })
         }
     }
 
+    /// Creates a [`Builder`] with the given configuration.
+    ///
+    /// # Arguments
+    ///
+    ///  - `config`: Configuration of building.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `config.bucket_size` is zero, or
+    ///  - `config.bucket_size` is not a power of two.
+    pub fn with_config(config: BuildConfig) -> Result<Self> {
+        let mut builder = Self::new(config.bucket_size)?
+            .require_utf8(config.require_utf8)
+            .key_order(config.key_order)
+            .key_len_policy(config.key_len_policy)
+            .align_buckets(config.align_buckets)
+            .with_value_codec(config.value_codec);
+        builder.max_key_len = config.max_key_len;
+        builder.alphabet = config.alphabet;
+        Ok(builder)
+    }
+
+    /// Gets the configuration of building.
+    pub fn config(&self) -> BuildConfig {
+        BuildConfig {
+            bucket_size: self.bucket_mask + 1,
+            require_utf8: self.utf8,
+            key_order: self.order,
+            max_key_len: self.max_key_len,
+            key_len_policy: self.key_len_policy,
+            alphabet: self.alphabet.clone(),
+            align_buckets: self.bucket_aligned,
+            value_codec: self.values.codec(),
+        }
+    }
+
+    /// Creates a [`Builder`] continuing from the keys of an existing dictionary.
+    pub(crate) fn resume(set: Set) -> Self {
+        let last_key = if set.is_empty() {
+            Vec::new()
+        } else {
+            set.decoder().run(set.len() - 1)
+        };
+        Self {
+            pointers: set.pointers,
+            serialized: set.serialized.into_vec(),
+            last_key,
+            external_ids: Vec::new(),
+            payloads: IntVector::new(),
+            values: Values::new(ValueCodec::default()),
+            len: set.len,
+            bucket_bits: set.bucket_bits,
+            bucket_mask: set.bucket_mask,
+            max_length: set.max_length,
+            utf8: set.utf8,
+            max_key_len: set.max_key_len,
+            key_len_policy: KeyLenPolicy::Reject,
+            order: set.order,
+            alphabet: set.alphabet,
+            bucket_aligned: set.bucket_aligned,
+            #[cfg(feature = "spill")]
+            spill: None,
+            spilled: 0,
+            on_progress: None,
+        }
+    }
+
     /// Pushes a key back to the dictionary.
     ///
     /// # Arguments
@@ -58,47 +374,957 @@ impl Builder {
     ///
     /// [`anyhow::Result`] will be returned when
     ///
-    ///  - `key` is no more than the last one, or
-    ///  - `key` contains [`END_MARKER`].
+    ///  - `key` is no more than the last one,
+    ///  - `key` contains [`END_MARKER`],
+    ///  - `key` is not valid UTF-8 while [`Builder::require_utf8`] is enabled,
+    ///  - `key` contains a byte out of [`Builder::alphabet`],
+    ///  - `key` is longer than [`Builder::max_key_len`] under [`KeyLenPolicy::Reject`], or
+    ///  - the previous keys were added with another method such as [`Builder::add_with_id`].
     pub fn add(&mut self, key: &[u8]) -> Result<()> {
-        if utils::contains_end_marker(key) {
+        self.check_method(false, false, false)?;
+        self.push_key(key)
+    }
+
+    /// Pushes a key back to the dictionary without validating it.
+    ///
+    /// It skips the ordering check, the [`END_MARKER`] scan, and the UTF-8 validation,
+    /// computing the LCP with the last key only for encoding.
+    /// It is intended for input from a trusted source such as another dictionary.
+    /// If `key` is no more than the last one, contains [`END_MARKER`], or contains a byte
+    /// out of [`Builder::alphabet`], the resulting dictionary is broken;
+    /// this is checked only in debug builds.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be added.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the previous keys were added with
+    /// another method such as [`Builder::add_with_id`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    /// use fcsd::Set;
+    ///
+    /// let source = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let mut builder = Builder::new(2).unwrap();
+    /// for (_, key) in source.iter() {
+    ///     builder.add_unchecked(&key).unwrap();
+    /// }
+    /// let set = builder.finish();
+    /// assert_eq!(set.locator().run(b"ICML"), Some(1));
+    /// ```
+    pub fn add_unchecked(&mut self, key: &[u8]) -> Result<()> {
+        self.check_method(false, false, false)?;
+        let key = self.fit_key(key);
+        debug_assert!(self.check_key(key).is_none());
+        self.encode_key(key)
+    }
+
+    /// Pushes a key back to the dictionary with a user-supplied id.
+    ///
+    /// The id can be retrieved with [`Set::external_id`], and the key can be searched
+    /// from the id with [`Set::internal_id`]. External ids should be distinct;
+    /// otherwise, the reverse lookup returns one of the duplicated keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be added.
+    ///  - `external_id`: Id associated with the key.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `key` is no more than the last one,
+    ///  - `key` contains [`END_MARKER`], or
+    ///  - the previous keys were added with another method such as [`Builder::add`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_id(b"ICDM", 20).unwrap();
+    /// builder.add_with_id(b"ICML", 10).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// assert_eq!(set.external_id(1), Some(10));
+    /// assert_eq!(set.internal_id(20), Some(0));
+    /// ```
+    pub fn add_with_id(&mut self, key: &[u8], external_id: u64) -> Result<()> {
+        self.check_method(true, false, false)?;
+        self.push_key(key)?;
+        self.external_ids.push(external_id);
+        Ok(())
+    }
+
+    /// Pushes a key back to the dictionary with a fixed-width integer payload.
+    ///
+    /// Payloads are stored in a bit-packed array whose width is determined by the maximum one,
+    /// and can be retrieved with [`Set::payload`] and
+    /// [`Locator::run_with_payload`](crate::locator::Locator::run_with_payload).
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be added.
+    ///  - `payload`: Integer associated with the key.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `key` is no more than the last one,
+    ///  - `key` contains [`END_MARKER`], or
+    ///  - the previous keys were added with another method such as [`Builder::add`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 2001).unwrap();
+    /// builder.add_with_payload(b"ICML", 1980).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// assert_eq!(set.payload(0), Some(2001));
+    /// assert_eq!(set.locator().run_with_payload(b"ICML"), Some((1, 1980)));
+    /// ```
+    pub fn add_with_payload(&mut self, key: &[u8], payload: u64) -> Result<()> {
+        self.check_method(false, true, false)?;
+        self.push_key(key)?;
+        self.payloads.push(payload);
+        Ok(())
+    }
+
+    /// Adds the distinct values in a column of a CSV file as keys.
+    ///
+    /// The values are sorted with external sorting, spilling sorted runs to temporary files,
+    /// so the whole column does not have to fit in memory. Empty values are skipped.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream of the CSV file.
+    ///  - `column_index`: Index of the column to be added.
+    ///  - `has_headers`: Whether the first record is a header to be skipped.
+    ///
+    /// # Returns
+    ///
+    /// The number of keys added.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - the file is malformed or the column is missing in a record,
+    ///  - a value is no more than the last key already added,
+    ///  - a value contains [`END_MARKER`], or
+    ///  - an I/O error occurs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let data = "venue,year\nSIGIR,2021\nICML,2021\nSIGIR,2022\n";
+    /// let mut builder = Builder::new(4).unwrap();
+    /// assert_eq!(builder.add_csv_column(data.as_bytes(), 0, true).unwrap(), 2);
+    /// let set = builder.finish();
+    ///
+    /// assert_eq!(set.locator().run(b"ICML"), Some(0));
+    /// assert_eq!(set.locator().run(b"SIGIR"), Some(1));
+    /// ```
+    #[cfg(feature = "csv")]
+    pub fn add_csv_column<R>(
+        &mut self,
+        reader: R,
+        column_index: usize,
+        has_headers: bool,
+    ) -> Result<usize>
+    where
+        R: std::io::Read,
+    {
+        self.add_delimited_column(reader, b',', column_index, has_headers)
+    }
+
+    /// Adds the distinct values in a column of a delimited file, such as TSV, as keys.
+    ///
+    /// It is the same as [`Builder::add_csv_column`] except for the delimiter.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream of the delimited file.
+    ///  - `delimiter`: Field delimiter such as `b'\t'`.
+    ///  - `column_index`: Index of the column to be added.
+    ///  - `has_headers`: Whether the first record is a header to be skipped.
+    #[cfg(feature = "csv")]
+    pub fn add_delimited_column<R>(
+        &mut self,
+        reader: R,
+        delimiter: u8,
+        column_index: usize,
+        has_headers: bool,
+    ) -> Result<usize>
+    where
+        R: std::io::Read,
+    {
+        use crate::external_sort::{ExternalSorter, RUN_BYTES};
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(has_headers)
+            .flexible(true)
+            .from_reader(reader);
+        let mut sorter = ExternalSorter::new(RUN_BYTES);
+        let mut record = csv::ByteRecord::new();
+        while reader.read_byte_record(&mut record)? {
+            let value = record.get(column_index).ok_or_else(|| {
+                anyhow!(
+                    "The record at line {} has no column {}.",
+                    record.position().map_or(0, |p| p.line()),
+                    column_index
+                )
+            })?;
+            if !value.is_empty() {
+                sorter.push(value.to_vec())?;
+            }
+        }
+
+        let mut num_added = 0;
+        for key in sorter.finish()? {
+            self.add(&key?)?;
+            num_added += 1;
+        }
+        Ok(num_added)
+    }
+
+    /// Pushes keys back to the dictionary in order.
+    ///
+    /// When a key cannot be added, the keys before it remain added, and
+    /// the error can be downcast to [`AddError`] to inspect the offending key.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: String keys to be added.
+    ///
+    /// # Returns
+    ///
+    /// The number of keys added.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - a key is no more than the last one,
+    ///  - a key contains [`END_MARKER`], or
+    ///  - the previous keys were added with another method such as [`Builder::add_with_id`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::{AddError, AddErrorKind, Builder};
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// assert_eq!(builder.extend(["ICDM", "ICML"]).unwrap(), 2);
+    ///
+    /// let err = builder.extend(["SIGIR", "SIGIR"]).unwrap_err();
+    /// let err = err.downcast_ref::<AddError>().unwrap();
+    /// assert_eq!(err.index, 1);
+    /// assert_eq!(err.key, b"SIGIR".to_vec());
+    /// assert_eq!(err.kind, AddErrorKind::Unsorted);
+    /// assert_eq!(builder.len(), 3);
+    /// ```
+    pub fn extend<I, P>(&mut self, keys: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        self.check_method(false, false, false)?;
+        let mut num_added = 0;
+        for (index, key) in keys.into_iter().enumerate() {
+            let key = self.fit_key(key.as_ref());
+            if let Some(kind) = self.check_key(key) {
+                return Err(AddError {
+                    index,
+                    key: key.to_vec(),
+                    kind,
+                }
+                .into());
+            }
+            self.push_key(key)?;
+            num_added += 1;
+        }
+        Ok(num_added)
+    }
+
+    /// Pushes back the keys in the columnar layout of concatenated bytes and offsets,
+    /// such as a string column from Arrow or Parquet readers.
+    ///
+    /// The keys are encoded directly from `bytes` without being copied into intermediate buffers,
+    /// and are validated as in [`Builder::extend`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `bytes`: Concatenation of the keys.
+    ///  - `offsets`: Offsets into `bytes`, where the `i`-th key is `bytes[offsets[i]..offsets[i + 1]]`.
+    ///
+    /// # Returns
+    ///
+    /// The number of keys added.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `offsets` is decreasing or out of bounds of `bytes`, or
+    ///  - a key cannot be added as in [`Builder::extend`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// assert_eq!(builder.add_columnar(b"ICDMICMLSIGIR", &[0, 4, 8, 13]).unwrap(), 3);
+    /// assert!(builder.add_columnar(b"SIGKDD", &[0, 7]).is_err());
+    ///
+    /// let set = builder.finish();
+    /// assert_eq!(set.locator().run(b"ICML"), Some(1));
+    /// ```
+    pub fn add_columnar(&mut self, bytes: &[u8], offsets: &[usize]) -> Result<usize> {
+        if let Some(i) = (1..offsets.len()).find(|&i| offsets[i] < offsets[i - 1]) {
             return Err(anyhow!(
-                "The input key must not contain END_MARKER (={}).",
-                END_MARKER
+                "The offsets must not decrease, but offsets[{}] = {} is followed by {}.",
+                i - 1,
+                offsets[i - 1],
+                offsets[i]
             ));
         }
+        if let Some(&last) = offsets.last().filter(|&&last| bytes.len() < last) {
+            return Err(anyhow!(
+                "The offset {} is out of bounds for {} bytes.",
+                last,
+                bytes.len()
+            ));
+        }
+        self.extend(offsets.windows(2).map(|w| &bytes[w[0]..w[1]]))
+    }
+
+    /// Pushes back the keys in the plain-text front-coded interchange format.
+    ///
+    /// Each line is the LCP with the key of the previous line in decimal, a tab, and the suffix,
+    /// such as `3\tKDD` following `SIGIR`. The LCP of the first line must be zero.
+    /// The keys are restored and validated as in [`Builder::extend`],
+    /// so dictionaries front-coded by other tools can be converted losslessly.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Buffered stream of the lines.
+    ///
+    /// # Returns
+    ///
+    /// The number of keys added.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - a line has no tab or an invalid LCP longer than the previous key,
+    ///  - a key cannot be added as in [`Builder::extend`], or
+    ///  - an I/O error occurs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let data = "0\tICDM\n2\tML\n0\tSIGIR\n3\tKDD\n3\tMOD\n";
+    /// let mut builder = Builder::new(4).unwrap();
+    /// assert_eq!(builder.add_front_coded(data.as_bytes()).unwrap(), 5);
+    ///
+    /// let set = builder.finish();
+    /// assert_eq!(set.locator().run(b"SIGKDD"), Some(3));
+    /// ```
+    pub fn add_front_coded<R>(&mut self, mut reader: R) -> Result<usize>
+    where
+        R: std::io::BufRead,
+    {
+        self.check_method(false, false, false)?;
+        let (mut line, mut key) = (Vec::new(), Vec::new());
+        let mut num_added = 0;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            let lineno = num_added + 1;
+            let tab = memchr::memchr(b'\t', &line)
+                .ok_or_else(|| anyhow!("The line {} has no tab.", lineno))?;
+            let lcp = std::str::from_utf8(&line[..tab])
+                .ok()
+                .and_then(|lcp| lcp.parse::<usize>().ok())
+                .filter(|&lcp| lcp <= key.len())
+                .ok_or_else(|| anyhow!("The line {} has an invalid LCP.", lineno))?;
+            key.truncate(lcp);
+            key.extend_from_slice(&line[tab + 1..]);
+
+            let fitted = self.fit_key(&key);
+            if let Some(kind) = self.check_key(fitted) {
+                return Err(AddError {
+                    index: num_added,
+                    key: fitted.to_vec(),
+                    kind,
+                }
+                .into());
+            }
+            self.push_key(fitted)?;
+            num_added += 1;
+        }
+        Ok(num_added)
+    }
+
+    /// Enables or disables the validation that every key is valid UTF-8.
+    ///
+    /// When enabled, keys that are not valid UTF-8 are rejected, and the dictionary records
+    /// that its keys are valid UTF-8, which can be checked with [`Set::is_utf8`] without decoding.
+    /// It should be called before adding keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `yes`: Whether to validate keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap().require_utf8(true);
+    /// builder.add("ICDM".as_bytes()).unwrap();
+    /// assert!(builder.add(&[0xFF]).is_err());
+    ///
+    /// let set = builder.finish();
+    /// assert!(set.is_utf8());
+    /// ```
+    pub const fn require_utf8(mut self, yes: bool) -> Self {
+        self.utf8 = yes;
+        self
+    }
+
+    /// Sets the order of keys, which is [`KeyOrder::Lexicographic`] by default.
+    ///
+    /// Keys must be added in the order, and the dictionary is searched in it.
+    /// It should be called before adding keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `order`: Order of keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    /// use fcsd::order::KeyOrder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap().key_order(KeyOrder::Natural);
+    /// builder.extend(["file1", "file2", "file10"]).unwrap();
+    ///
+    /// let set = builder.finish();
+    /// assert_eq!(set.key_order(), KeyOrder::Natural);
+    /// assert_eq!(set.locator().run(b"file10"), Some(2));
+    /// ```
+    pub const fn key_order(mut self, order: KeyOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets the limit of the key length in bytes, which is recorded in the dictionary.
+    ///
+    /// Longer keys are handled according to [`Builder::key_len_policy`].
+    /// It should be called before adding keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `max_key_len`: The maximum length of keys in bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap().max_key_len(5);
+    /// builder.add(b"ICDM").unwrap();
+    /// assert!(builder.add(b"SIGSPATIAL").is_err());
+    ///
+    /// let set = builder.finish();
+    /// assert_eq!(set.max_key_len(), Some(5));
+    /// ```
+    pub const fn max_key_len(mut self, max_key_len: usize) -> Self {
+        self.max_key_len = Some(max_key_len);
+        self
+    }
+
+    /// Sets the policy for keys longer than [`Builder::max_key_len`],
+    /// which is [`KeyLenPolicy::Reject`] by default.
+    ///
+    /// # Arguments
+    ///
+    ///  - `policy`: Policy for over-long keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::{Builder, KeyLenPolicy};
+    ///
+    /// let mut builder = Builder::new(4)
+    ///     .unwrap()
+    ///     .max_key_len(5)
+    ///     .key_len_policy(KeyLenPolicy::Truncate);
+    /// builder.add(b"ICDM").unwrap();
+    /// builder.add(b"SIGSPATIAL").unwrap();
+    ///
+    /// let set = builder.finish();
+    /// assert_eq!(set.locator().run(b"SIGSP"), Some(1));
+    /// ```
+    pub const fn key_len_policy(mut self, policy: KeyLenPolicy) -> Self {
+        self.key_len_policy = policy;
+        self
+    }
+
+    /// Sets the alphabet of keys, with which the suffixes of keys are packed
+    /// in [`Alphabet::bits_per_symbol`] bits per byte.
+    ///
+    /// Keys containing bytes out of the alphabet are rejected with [`AddErrorKind::OutOfAlphabet`].
+    /// The alphabet is recorded in the dictionary, and the queries work on the original keys.
+    /// It has no effect after keys have been added.
+    ///
+    /// # Arguments
+    ///
+    ///  - `alphabet`: Alphabet of keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::alphabet::Alphabet;
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap().alphabet(Alphabet::dna());
+    /// builder.extend(["AACGT", "ACGTTA", "GATTACA"]).unwrap();
+    /// assert!(builder.add(b"TNT").is_err());
+    ///
+    /// let set = builder.finish();
+    /// assert_eq!(set.alphabet(), Some(&Alphabet::dna()));
+    /// assert_eq!(set.locator().run(b"ACGTTA"), Some(1));
+    /// assert_eq!(set.decoder().run(2), b"GATTACA".to_vec());
+    /// ```
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        if self.len == 0 {
+            self.alphabet = Some(alphabet);
+        }
+        self
+    }
+
+    /// Enables or disables padding the serialized area so that every bucket header
+    /// starts at a multiple of [`CACHE_LINE`] bytes.
+    ///
+    /// When the serialized area is placed at a cache-line boundary, such as in memory-mapped files,
+    /// short buckets are decoded without touching two cache lines,
+    /// which speeds up random queries at the cost of the padding.
+    /// The measured tradeoff is shown in the README of the bench suite.
+    /// It has no effect after keys have been added.
+    ///
+    /// # Arguments
+    ///
+    ///  - `yes`: Whether buckets are aligned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let mut builder = Builder::new(2).unwrap().align_buckets(true);
+    /// builder.extend(keys).unwrap();
+    ///
+    /// let set = builder.finish();
+    /// let unaligned = Set::with_bucket_size(keys, 2).unwrap();
+    /// assert!(unaligned.size_in_bytes() < set.size_in_bytes());
+    /// assert_eq!(set.locator().run(b"SIGMOD"), Some(4));
+    /// ```
+    pub const fn align_buckets(mut self, yes: bool) -> Self {
+        if self.len == 0 {
+            self.bucket_aligned = yes;
+        }
+        self
+    }
+
+    /// Spills the completed buckets to a temporary file whenever the serialized area
+    /// held in memory reaches `threshold` bytes, enabled by the `spill` feature.
+    ///
+    /// The memory for the serialized area is bounded by roughly `threshold` bytes plus a bucket,
+    /// besides the pointer table. [`Builder::finish_into`] streams the spilled buckets
+    /// into a writer, so a dictionary larger than the memory can be built from streaming input.
+    /// [`Builder::finish`] reads them back into memory instead.
+    ///
+    /// # Arguments
+    ///
+    ///  - `threshold`: The number of bytes of the serialized area kept in memory.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let mut builder = Builder::new(2).unwrap().spill_to_disk(4);
+    /// builder.extend(keys).unwrap();
+    ///
+    /// let mut data = vec![];
+    /// builder.finish_into(&mut data).unwrap();
+    /// let set = Set::deserialize_from(&data[..]).unwrap();
+    /// assert_eq!(set.locator().run(b"SIGKDD"), Some(3));
+    /// ```
+    #[cfg(feature = "spill")]
+    pub fn spill_to_disk(mut self, threshold: usize) -> Self {
+        match &mut self.spill {
+            Some(spill) => spill.threshold = threshold,
+            None => {
+                self.spill = Some(Spill {
+                    threshold,
+                    file: None,
+                    segments: Vec::new(),
+                })
+            }
+        }
+        self
+    }
+
+    /// Sets the codec of the value area for [`Builder::add_with_value`],
+    /// which is [`ValueCodec::FrontCoded`] by default.
+    ///
+    /// It has no effect after values have been added.
+    ///
+    /// # Arguments
+    ///
+    ///  - `codec`: Codec of the value area.
+    pub fn with_value_codec(mut self, codec: ValueCodec) -> Self {
+        if self.values.len() == 0 {
+            self.values = Values::new(codec);
+        }
+        self
+    }
+
+    /// Registers a closure called with the progress every 65536 added keys and on [`Builder::finish`].
+    ///
+    /// Clones of the builder share the closure.
+    ///
+    /// # Arguments
+    ///
+    ///  - `f`: Closure receiving the progress.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use fcsd::builder::{Builder, Progress};
+    ///
+    /// let reports = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = reports.clone();
+    /// let mut builder = Builder::new(4)
+    ///     .unwrap()
+    ///     .on_progress(move |progress| sink.lock().unwrap().push(progress));
+    /// builder.add(b"ICDM").unwrap();
+    /// builder.add(b"ICML").unwrap();
+    /// builder.finish();
+    ///
+    /// assert_eq!(*reports.lock().unwrap(), vec![Progress { keys: 2, bytes: 9 }]);
+    /// ```
+    pub fn on_progress<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(Progress) + Send + 'static,
+    {
+        self.on_progress = Some(Arc::new(Mutex::new(f)));
+        self
+    }
+
+    /// Gets the current progress.
+    pub const fn progress(&self) -> Progress {
+        Progress {
+            keys: self.len,
+            bytes: self.spilled + self.serialized.len(),
+        }
+    }
+
+    fn report_progress(&self) {
+        if let Some(f) = &self.on_progress {
+            // A closure that panicked before is still called.
+            let mut f = f.lock().unwrap_or_else(|e| e.into_inner());
+            f(self.progress());
+        }
+    }
+
+    /// Pushes a key back to the dictionary with a byte-string value.
+    ///
+    /// Values are stored in the value area encoded with the codec set by
+    /// [`Builder::with_value_codec`], and can be retrieved with [`Set::value`] and
+    /// [`Locator::run_with_value`](crate::locator::Locator::run_with_value).
+    /// Values may contain any bytes, including [`END_MARKER`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be added.
+    ///  - `value`: Value associated with the key.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `key` is no more than the last one,
+    ///  - `key` contains [`END_MARKER`], or
+    ///  - the previous keys were added with another method such as [`Builder::add`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    /// use fcsd::values::ValueCodec;
+    ///
+    /// let mut builder = Builder::new(4)
+    ///     .unwrap()
+    ///     .with_value_codec(ValueCodec::FrontCoded);
+    /// builder.add_with_value(b"ICDM", b"https://icdm.org").unwrap();
+    /// builder.add_with_value(b"ICML", b"https://icml.cc").unwrap();
+    /// let set = builder.finish();
+    ///
+    /// assert_eq!(set.value(0), Some(b"https://icdm.org".to_vec()));
+    /// assert_eq!(set.value_codec(), Some(ValueCodec::FrontCoded));
+    /// ```
+    pub fn add_with_value(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.check_method(false, false, true)?;
+        self.push_key(key)?;
+        self.values.push(value);
+        Ok(())
+    }
+
+    /// Checks that the same add method is used for all the keys.
+    fn check_method(&self, with_id: bool, with_payload: bool, with_value: bool) -> Result<()> {
+        let expected = |with: bool| if with { self.len } else { 0 };
+        if self.external_ids.len() != expected(with_id)
+            || self.payloads.len() != expected(with_payload)
+            || self.values.len() != expected(with_value)
+        {
+            return Err(anyhow!("The same add method must be used for all keys."));
+        }
+        Ok(())
+    }
+
+    /// Returns the reason why a key cannot be added, if any.
+    fn check_key(&self, key: &[u8]) -> Option<AddErrorKind> {
+        if utils::contains_end_marker(key) {
+            Some(AddErrorKind::EndMarker)
+        } else if let Some(byte) = self.alphabet.as_ref().and_then(|a| a.find_missing(key)) {
+            Some(AddErrorKind::OutOfAlphabet { byte })
+        } else if let Some(max_key_len) = self.max_key_len.filter(|&n| n < key.len()) {
+            Some(AddErrorKind::TooLong {
+                len: key.len(),
+                max_key_len,
+            })
+        } else if self.len != 0 && self.order.compare(&self.last_key, key).is_ge() {
+            Some(AddErrorKind::Unsorted)
+        } else if self.utf8 && std::str::from_utf8(key).is_err() {
+            Some(AddErrorKind::InvalidUtf8)
+        } else {
+            None
+        }
+    }
+
+    /// Truncates the key to the limit under [`KeyLenPolicy::Truncate`].
+    fn fit_key<'k>(&self, key: &'k [u8]) -> &'k [u8] {
+        match self.max_key_len {
+            Some(mut n) if n < key.len() && self.key_len_policy == KeyLenPolicy::Truncate => {
+                if self.utf8 {
+                    // Backs off from continuation bytes of a multi-byte character.
+                    while 0 < n && key[n] & 0xC0 == 0x80 {
+                        n -= 1;
+                    }
+                }
+                &key[..n]
+            }
+            _ => key,
+        }
+    }
 
-        let (lcp, cmp) = utils::get_lcp(&self.last_key, key);
-        if cmp <= 0 {
-            return Err(anyhow!("The input key must be more than the last one.",));
+    fn push_key(&mut self, key: &[u8]) -> Result<()> {
+        let key = self.fit_key(key);
+        if let Some(kind) = self.check_key(key) {
+            return Err(anyhow!("{}", kind));
         }
+        self.encode_key(key)
+    }
 
+    fn encode_key(&mut self, key: &[u8]) -> Result<()> {
         if self.len & self.bucket_mask == 0 {
-            self.pointers.push(self.serialized.len() as u64);
-            self.serialized.extend_from_slice(key);
+            #[cfg(feature = "spill")]
+            let num_buffered = self.serialized.len();
+            #[cfg(feature = "spill")]
+            if let Some(spill) = self
+                .spill
+                .as_mut()
+                .filter(|spill| spill.threshold <= num_buffered)
+            {
+                spill.write(&self.serialized)?;
+                self.spilled += self.serialized.len();
+                self.serialized.clear();
+            }
+            if self.bucket_aligned {
+                let padded = (self.spilled + self.serialized.len()).next_multiple_of(CACHE_LINE);
+                self.serialized.resize(padded - self.spilled, END_MARKER);
+            }
+            self.pointers
+                .push((self.spilled + self.serialized.len()) as u64);
+            append_suffix(&mut self.serialized, self.alphabet.as_ref(), key);
         } else {
+            let lcp = utils::get_lcp(&self.last_key, key).0;
             utils::vbyte::append(&mut self.serialized, lcp);
-            self.serialized.extend_from_slice(&key[lcp..]);
+            append_suffix(&mut self.serialized, self.alphabet.as_ref(), &key[lcp..]);
         }
-        self.serialized.push(END_MARKER);
 
         self.last_key.resize(key.len(), 0);
         self.last_key.copy_from_slice(key);
         self.len += 1;
         self.max_length = std::cmp::max(self.max_length, key.len());
-
+        if self.len.is_multiple_of(PROGRESS_INTERVAL) {
+            self.report_progress();
+        }
         Ok(())
     }
 
+    /// Gets the number of added keys.
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if no keys have been added.
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     /// Builds and returns the dictionary.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the buckets spilled to disk cannot be read back,
+    /// which [`Builder::try_finish`] returns as an error instead.
     pub fn finish(self) -> Set {
+        self.try_finish()
+            .expect("failed to read the spilled serialized area")
+    }
+
+    /// Builds and returns the dictionary, reading back the buckets spilled to disk if any.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the spilled buckets cannot be read back,
+    /// which never happens without spilling.
+    #[cfg_attr(not(feature = "spill"), allow(unused_mut))]
+    pub fn try_finish(mut self) -> Result<Set> {
+        self.report_progress();
+        #[cfg(feature = "spill")]
+        if let Some(spill) = self.spill.take() {
+            let mut serialized = Vec::with_capacity(self.spilled + self.serialized.len());
+            spill.copy_into(&mut serialized)?;
+            serialized.append(&mut self.serialized);
+            self.serialized = serialized;
+            self.spilled = 0;
+        }
+        Ok(self.into_set())
+    }
+
+    /// Builds the dictionary and writes it into a writer in the layout of [`Set::serialize_into`],
+    /// streaming the buckets spilled to disk, if any, without reading them into memory.
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when an I/O error occurs.
+    pub fn finish_into<W>(mut self, writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        self.report_progress();
+        #[cfg(feature = "spill")]
+        let spill = self.spill.take();
+        let serialized = std::mem::take(&mut self.serialized);
+        let serialized_len = self.spilled + serialized.len();
+        let set = self.into_set();
+        set.serialize_padded_with(
+            &mut PaddedWriter::new(writer, 1),
+            serialized_len,
+            |writer| {
+                #[cfg(feature = "spill")]
+                if let Some(spill) = &spill {
+                    spill.copy_into(writer)?;
+                }
+                writer.write_all(&serialized)?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Makes the dictionary of the parts, whose serialized area is `self.serialized`.
+    fn into_set(self) -> Set {
         Set {
-            pointers: IntVector::build(&self.pointers),
-            serialized: self.serialized,
+            pointers: self.pointers,
+            serialized: self.serialized.into(),
             len: self.len,
             bucket_bits: self.bucket_bits,
             bucket_mask: self.bucket_mask,
             max_length: self.max_length,
+            utf8: self.utf8,
+            max_key_len: self.max_key_len,
+            order: self.order,
+            alphabet: self.alphabet,
+            bucket_aligned: self.bucket_aligned,
+            external_ids: if self.external_ids.is_empty() {
+                None
+            } else {
+                Some(ExternalIds::build(&self.external_ids))
+            },
+            payloads: if self.payloads.len() == 0 {
+                None
+            } else {
+                Some(self.payloads)
+            },
+            values: if self.values.len() == 0 {
+                None
+            } else {
+                Some(self.values)
+            },
+        }
+    }
+}
+
+/// Appends the suffix terminated with [`END_MARKER`], or packed with the alphabet if any.
+fn append_suffix(serialized: &mut Vec<u8>, alphabet: Option<&Alphabet>, suffix: &[u8]) {
+    match alphabet {
+        Some(alphabet) => alphabet.pack(suffix, serialized),
+        None => {
+            serialized.extend_from_slice(suffix);
+            serialized.push(END_MARKER);
         }
     }
 }
@@ -0,0 +1,135 @@
+//! Helpers for build scripts to embed dictionaries into binaries.
+//!
+//! [`embed`] serializes a dictionary into `OUT_DIR` and writes a small Rust source
+//! defining an accessor function, which can be included with `include!`.
+//! The accessor lazily constructs the [`Set`] with [`Set::from_static_bytes`] on the first call,
+//! referring to the bytes embedded with `include_bytes!` in place instead of copying them,
+//! and returns the same instance afterwards.
+//!
+//! # Example
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+//! let set = fcsd::Set::new(keys).unwrap();
+//! fcsd::codegen::embed(&set, "venues").unwrap();
+//! ```
+//!
+//! In the crate:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/venues.rs"));
+//!
+//! fn main() {
+//!     assert_eq!(venues().locator().run(b"ICML"), Some(1));
+//! }
+//! ```
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+use crate::{Set, ALIGNMENT};
+
+/// Serializes a dictionary into `OUT_DIR` and writes the accessor source `<name>.rs`,
+/// returning the path of the source.
+///
+/// This is intended to be called from a build script, where Cargo sets `OUT_DIR`.
+///
+/// # Arguments
+///
+///  - `set`: Dictionary to be embedded.
+///  - `name`: Name of the accessor function, which must be a Rust identifier.
+///
+/// # Errors
+///
+/// An error will be returned when
+///
+///  - `OUT_DIR` is not set,
+///  - `name` is not a Rust identifier, or
+///  - the files cannot be written.
+pub fn embed(set: &Set, name: &str) -> Result<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .ok_or_else(|| anyhow!("OUT_DIR is not set. embed must be called from a build script."))?;
+    embed_into(set, out_dir, name)
+}
+
+/// Serializes a dictionary into `out_dir/<name>.fcsd` and writes the accessor source
+/// `out_dir/<name>.rs`, returning the path of the source.
+///
+/// The dictionary is written in the layout of [`Set::serialize_into_aligned`],
+/// and the accessor embeds it at an address aligned to [`ALIGNMENT`]
+/// so that the bucket pointers are also referred to in place.
+///
+/// # Arguments
+///
+///  - `set`: Dictionary to be embedded.
+///  - `out_dir`: Directory where the files are written.
+///  - `name`: Name of the accessor function, which must be a Rust identifier.
+///
+/// # Errors
+///
+/// An error will be returned when `name` is not a Rust identifier or the files cannot be written.
+pub fn embed_into<D>(set: &Set, out_dir: D, name: &str) -> Result<PathBuf>
+where
+    D: AsRef<Path>,
+{
+    if !is_identifier(name) {
+        return Err(anyhow!(
+            "name must be a Rust identifier, but got {:?}.",
+            name
+        ));
+    }
+    let out_dir = out_dir.as_ref();
+
+    let data_path = out_dir.join(format!("{}.fcsd", name));
+    let mut writer = BufWriter::new(File::create(&data_path)?);
+    set.serialize_into_aligned(&mut writer)?;
+    writer.flush()?;
+
+    let data_path = data_path
+        .to_str()
+        .ok_or_else(|| anyhow!("the path of the output directory must be valid UTF-8."))?;
+    let src_path = out_dir.join(format!("{}.rs", name));
+    let mut writer = BufWriter::new(File::create(&src_path)?);
+    write!(
+        writer,
+        r#"/// Returns the dictionary embedded by `fcsd::codegen`.
+pub fn {name}() -> &'static ::fcsd::Set {{
+    #[repr(C, align({alignment}))]
+    struct Aligned<T: ?Sized>(T);
+    static BYTES: &Aligned<[u8]> = &Aligned(*include_bytes!({path:?}));
+    static SET: ::std::sync::OnceLock<::fcsd::Set> = ::std::sync::OnceLock::new();
+    SET.get_or_init(|| {{
+        ::fcsd::Set::from_static_bytes(&BYTES.0).expect("the embedded dictionary is broken.")
+    }})
+}}
+"#,
+        name = name,
+        alignment = ALIGNMENT,
+        path = data_path,
+    )?;
+    writer.flush()?;
+    Ok(src_path)
+}
+
+/// Checks if the name is a Rust identifier other than keywords and `_`.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let head_ok = chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic());
+    head_ok
+        && name != "_"
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && !KEYWORDS.contains(&name)
+}
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
@@ -0,0 +1,80 @@
+//! Compressed framing of serialized dictionaries.
+//!
+//! A compressed file consists of [`COMPRESSED_COOKIE`], the codec id in one byte,
+//! and the ordinary serialized form of [`Set`](crate::Set) compressed with the codec.
+use std::io::{self, Read, Write};
+
+use anyhow::{anyhow, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+/// Serial cookie value of compressed files.
+pub(crate) const COMPRESSED_COOKIE: u32 = 114516;
+
+/// Codec of compressed serialization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// No compression, which only frames the serialized form.
+    #[default]
+    None,
+    /// Zstandard compression with a level (1-22), available with the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd {
+        /// Compression level.
+        level: i32,
+    },
+}
+
+impl Codec {
+    const fn id(self) -> u8 {
+        match self {
+            Self::None => 0,
+            #[cfg(feature = "zstd")]
+            Self::Zstd { .. } => 1,
+        }
+    }
+}
+
+/// Writes the header and calls `f` with a writer compressing the serialized form.
+pub(crate) fn write_compressed<W, F>(mut writer: W, codec: Codec, f: F) -> Result<()>
+where
+    W: Write,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    writer.write_u32::<LittleEndian>(COMPRESSED_COOKIE)?;
+    writer.write_u8(codec.id())?;
+    match codec {
+        Codec::None => f(&mut writer),
+        #[cfg(feature = "zstd")]
+        Codec::Zstd { level } => {
+            let mut encoder = zstd::Encoder::new(writer, level)?;
+            f(&mut encoder)?;
+            encoder.finish()?;
+            Ok(())
+        }
+    }
+}
+
+/// Reads the header and returns a reader decompressing the serialized form.
+///
+/// A reader starting with another cookie is returned as is, so that the ordinary
+/// serialized form is also accepted.
+pub(crate) fn read_compressed<'a, R>(mut reader: R) -> Result<Box<dyn Read + 'a>>
+where
+    R: Read + 'a,
+{
+    let cookie = reader.read_u32::<LittleEndian>()?;
+    if cookie != COMPRESSED_COOKIE {
+        let head = io::Cursor::new(cookie.to_le_bytes());
+        return Ok(Box::new(head.chain(reader)));
+    }
+    match reader.read_u8()? {
+        0 => Ok(Box::new(reader)),
+        #[cfg(feature = "zstd")]
+        1 => Ok(Box::new(zstd::Decoder::new(reader)?)),
+        #[cfg(not(feature = "zstd"))]
+        1 => Err(anyhow!(
+            "The dictionary is compressed with zstd, which requires the zstd feature."
+        )),
+        _ => Err(anyhow!("unknown compression codec")),
+    }
+}
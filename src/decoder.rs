@@ -1,4 +1,11 @@
-use crate::utils;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{anyhow, Result};
+
+use crate::bucket;
+use crate::id::Id;
+use crate::stats::{self, Counter};
 use crate::Set;
 
 /// Decoder class to get string keys associated with given ids.
@@ -6,6 +13,16 @@ use crate::Set;
 pub struct Decoder<'a> {
     set: &'a Set,
     dec: Vec<u8>,
+    cache: Option<BucketCache>,
+}
+
+/// Hit/miss counters of the bucket cache in [`Decoder`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of queries answered from cached buckets.
+    pub hits: u64,
+    /// The number of queries that decoded a bucket.
+    pub misses: u64,
 }
 
 impl<'a> Decoder<'a> {
@@ -18,9 +35,49 @@ impl<'a> Decoder<'a> {
         Self {
             set,
             dec: Vec::with_capacity(set.max_length()),
+            cache: None,
+        }
+    }
+
+    /// Makes a [`Decoder`] with a bounded LRU cache of decoded buckets.
+    ///
+    /// The cache is useful for skewed workloads that repeatedly decode keys in hot buckets.
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///  - `max_buckets`: The maximum number of cached buckets.
+    ///  - `max_bytes`: The maximum number of bytes occupied by cached buckets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    /// use fcsd::decoder::{CacheStats, Decoder};
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 4).unwrap();
+    ///
+    /// let mut decoder = Decoder::with_cache(&set, 16, 1 << 20);
+    /// assert_eq!(decoder.run(1), b"ICML".to_vec());
+    /// assert_eq!(decoder.run(3), b"SIGKDD".to_vec());
+    /// assert_eq!(decoder.run(4), b"SIGMOD".to_vec());
+    /// assert_eq!(decoder.cache_stats(), Some(CacheStats { hits: 1, misses: 2 }));
+    /// ```
+    pub fn with_cache(set: &'a Set, max_buckets: usize, max_bytes: usize) -> Self {
+        Self {
+            set,
+            dec: Vec::with_capacity(set.max_length()),
+            cache: Some(BucketCache::new(max_buckets, max_bytes)),
         }
     }
 
+    /// Returns the hit/miss counters of the bucket cache,
+    /// or `None` if the decoder was made without a cache.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats)
+    }
+
     /// Returns the string key associated with the given id.
     ///
     /// # Arguments
@@ -39,16 +96,320 @@ impl<'a> Decoder<'a> {
         assert!(id < set.len());
 
         let (bi, bj) = (set.bucket_id(id), set.pos_in_bucket(id));
-        let mut pos = set.decode_header(bi, dec);
+        if let Some(cache) = self.cache.as_mut() {
+            return cache.get(set, bi, bj).to_vec();
+        }
 
-        for _ in 0..bj {
-            let (lcp, num) = utils::vbyte::decode(&set.serialized[pos..]);
-            pos += num;
+        set.decode_into(id, dec);
+        dec.clone()
+    }
+
+    /// Returns the string key associated with the given id as [`String`].
+    ///
+    /// The decoded bytes are moved into the string without being copied.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the key is not valid UTF-8,
+    /// which never happens for dictionaries flagged with [`Set::is_utf8`].
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let mut decoder = set.decoder();
+    /// assert_eq!(decoder.run_str(2).unwrap(), "SIGIR");
+    ///
+    /// let set = Set::new([b"\xFF"]).unwrap();
+    /// assert!(set.decoder().run_str(0).is_err());
+    /// ```
+    pub fn run_str(&mut self, id: usize) -> Result<String> {
+        Ok(String::from_utf8(self.run(id))?)
+    }
+
+    /// Returns the string key associated with the given id as [`str`],
+    /// replacing invalid UTF-8 sequences with `U+FFFD`.
+    ///
+    /// The key borrows the internal buffer of the decoder and is allocated only if it is not valid UTF-8.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new([&b"ICDM"[..], b"IC\xFF"]).unwrap();
+    /// let mut decoder = set.decoder();
+    /// assert!(matches!(decoder.run_str_lossy(0), Cow::Borrowed("ICDM")));
+    /// assert_eq!(decoder.run_str_lossy(1), "IC\u{FFFD}");
+    /// ```
+    pub fn run_str_lossy(&mut self, id: usize) -> Cow<'_, str> {
+        let (set, dec) = (&self.set, &mut self.dec);
+        assert!(id < set.len());
+
+        let (bi, bj) = (set.bucket_id(id), set.pos_in_bucket(id));
+        if let Some(cache) = self.cache.as_mut() {
+            return String::from_utf8_lossy(cache.get(set, bi, bj));
+        }
+
+        set.decode_into(id, dec);
+        String::from_utf8_lossy(dec)
+    }
+
+    /// Returns the first `n` bytes of the string key associated with the given id,
+    /// or the whole key if it is shorter.
+    ///
+    /// Only the first `n` bytes of the keys in the bucket are reconstructed,
+    /// and the suffixes of the keys sharing them are skipped without being copied.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///  - `n`: The maximum number of bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut decoder = set.decoder();
+    /// assert_eq!(decoder.run_prefix(3, 4), b"SIGK".to_vec());
+    /// assert_eq!(decoder.run_prefix(1, 10), b"ICML".to_vec());
+    /// ```
+    pub fn run_prefix(&mut self, id: usize, n: usize) -> Vec<u8> {
+        let (set, dec) = (&self.set, &mut self.dec);
+        assert!(id < set.len());
 
-            dec.resize(lcp, 0);
-            pos = set.decode_next(pos, dec);
+        let (bi, bj) = (set.bucket_id(id), set.pos_in_bucket(id));
+        if let Some(cache) = self.cache.as_mut() {
+            let key = cache.get(set, bi, bj);
+            return key[..n.min(key.len())].to_vec();
         }
 
+        dec.clear();
+        let mut pos = set.decode_next_bounded(set.pointers.get(bi) as usize, dec, n);
+        for _ in 0..bj {
+            let (lcp, next_pos) = set.decode_lcp(pos);
+            if n <= lcp {
+                // The first n bytes are shared with the previous key.
+                pos = set.skip_next(next_pos).1;
+                continue;
+            }
+            dec.truncate(lcp);
+            pos = set.decode_next_bounded(next_pos, dec, n);
+        }
         dec.clone()
     }
+
+    /// Returns the string key associated with the given id of the integer type `I`.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut decoder = set.decoder();
+    /// assert_eq!(decoder.run_as(3u32), b"SIGKDD".to_vec());
+    /// ```
+    pub fn run_as<I>(&mut self, id: I) -> Vec<u8>
+    where
+        I: Id,
+    {
+        self.run(id.to_usize().expect("The id does not fit in usize."))
+    }
+
+    /// Returns the string key associated with the given id, or an error instead of panicking.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `id` is no less than the number of keys, or
+    ///  - the dictionary data is structurally broken.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut decoder = set.decoder();
+    /// assert_eq!(decoder.try_run(3).unwrap(), b"SIGKDD".to_vec());
+    /// assert!(decoder.try_run(5).is_err());
+    /// ```
+    pub fn try_run(&mut self, id: usize) -> Result<Vec<u8>> {
+        let (set, dec) = (&self.set, &mut self.dec);
+        if set.len() <= id {
+            return Err(anyhow!(
+                "The id {} is out of range for {} keys.",
+                id,
+                set.len()
+            ));
+        }
+
+        let (bi, bj) = (set.bucket_id(id), set.pos_in_bucket(id));
+        let broken = || anyhow!("The dictionary data is broken around the id {}.", id);
+        let mut pos = set.try_decode_header(bi, dec).ok_or_else(broken)?;
+        for _ in 0..bj {
+            let (lcp, next_pos) = set.try_decode_lcp(pos, dec.len()).ok_or_else(broken)?;
+            dec.truncate(lcp);
+            pos = set.try_decode_next(next_pos, dec).ok_or_else(broken)?;
+        }
+        Ok(dec.clone())
+    }
+
+    /// Returns the string key associated with the given user-supplied id.
+    ///
+    /// # Arguments
+    ///
+    ///  - `external_id`: Id given in [`Builder::add_with_id`](crate::builder::Builder::add_with_id).
+    ///
+    /// # Returns
+    ///
+    /// `None` if `external_id` is not found or the keys were not added with
+    /// [`Builder::add_with_id`](crate::builder::Builder::add_with_id).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_id(b"ICDM", 20).unwrap();
+    /// builder.add_with_id(b"ICML", 10).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// let mut decoder = set.decoder();
+    /// assert_eq!(decoder.run_external(20), Some(b"ICDM".to_vec()));
+    /// assert_eq!(decoder.run_external(30), None);
+    /// ```
+    pub fn run_external(&mut self, external_id: u64) -> Option<Vec<u8>> {
+        let id = self.set.internal_id(external_id)?;
+        Some(self.run(id))
+    }
+}
+
+#[derive(Clone)]
+struct CachedBucket {
+    keys: Vec<u8>,
+    ends: Vec<usize>,
+    last_used: u64,
+}
+
+impl CachedBucket {
+    fn decode(set: &Set, bi: usize) -> Self {
+        let mut keys = Vec::new();
+        let mut ends = Vec::with_capacity(set.bucket_size());
+        bucket::decode_bucket(set, bi, &mut keys, &mut ends);
+        Self {
+            keys,
+            ends,
+            last_used: 0,
+        }
+    }
+
+    fn key(&self, bj: usize) -> &[u8] {
+        let beg = if bj == 0 { 0 } else { self.ends[bj - 1] };
+        &self.keys[beg..self.ends[bj]]
+    }
+
+    const fn heap_bytes(&self) -> usize {
+        self.keys.len() + self.ends.len() * std::mem::size_of::<usize>()
+    }
+}
+
+/// LRU cache of decoded buckets.
+#[derive(Clone)]
+struct BucketCache {
+    max_buckets: usize,
+    max_bytes: usize,
+    bytes: usize,
+    tick: u64,
+    buckets: HashMap<usize, CachedBucket>,
+    // Bucket ids ordered by their last use.
+    lru: BTreeMap<u64, usize>,
+    stats: CacheStats,
+}
+
+impl BucketCache {
+    fn new(max_buckets: usize, max_bytes: usize) -> Self {
+        Self {
+            max_buckets,
+            max_bytes,
+            bytes: 0,
+            tick: 0,
+            buckets: HashMap::new(),
+            lru: BTreeMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn get(&mut self, set: &Set, bi: usize, bj: usize) -> &[u8] {
+        self.tick += 1;
+        if let Some(bucket) = self.buckets.get_mut(&bi) {
+            self.stats.hits += 1;
+            stats::record(Counter::CacheHits, 1);
+            self.lru.remove(&bucket.last_used);
+            self.lru.insert(self.tick, bi);
+            bucket.last_used = self.tick;
+        } else {
+            self.stats.misses += 1;
+            stats::record(Counter::CacheMisses, 1);
+            let mut bucket = CachedBucket::decode(set, bi);
+            bucket.last_used = self.tick;
+            let bytes = bucket.heap_bytes();
+            while !self.buckets.is_empty()
+                && (self.max_buckets <= self.buckets.len() || self.max_bytes < self.bytes + bytes)
+            {
+                let (_, evicted) = self.lru.pop_first().unwrap();
+                self.bytes -= self.buckets.remove(&evicted).unwrap().heap_bytes();
+            }
+            self.bytes += bytes;
+            self.lru.insert(self.tick, bi);
+            self.buckets.insert(bi, bucket);
+        }
+        self.buckets[&bi].key(bj)
+    }
 }
@@ -0,0 +1,72 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
+use crate::iter::Iter;
+use crate::order::KeyOrder;
+use crate::Set;
+
+/// Event reported by [`Diff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffItem {
+    /// Key stored only in the new dictionary.
+    Added(Vec<u8>),
+    /// Key stored only in the old dictionary.
+    Removed(Vec<u8>),
+    /// Key stored in both dictionaries, with its ids in the old and new ones.
+    Common(usize, usize, Vec<u8>),
+}
+
+/// Iterator to enumerate differences between two dictionaries in the order of their keys.
+#[derive(Clone)]
+pub struct Diff<'a> {
+    old: Peekable<Iter<'a>>,
+    new: Peekable<Iter<'a>>,
+    order: KeyOrder,
+}
+
+impl<'a> Diff<'a> {
+    /// Makes an iterator [`Diff`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `old`: Front-coding dictionay of the old snapshot.
+    ///  - `new`: Front-coding dictionay of the new snapshot.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the dictionaries are sorted in different [`KeyOrder`]s.
+    pub fn new(old: &'a Set, new: &'a Set) -> Self {
+        assert_eq!(
+            old.key_order(),
+            new.key_order(),
+            "The dictionaries must be sorted in the same order."
+        );
+        Self {
+            old: old.iter().peekable(),
+            new: new.iter().peekable(),
+            order: old.key_order(),
+        }
+    }
+}
+
+impl<'a> Iterator for Diff<'a> {
+    type Item = DiffItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ord = match (self.old.peek(), self.new.peek()) {
+            (Some((_, a)), Some((_, b))) => self.order.compare(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+        match ord {
+            Ordering::Less => self.old.next().map(|(_, key)| DiffItem::Removed(key)),
+            Ordering::Greater => self.new.next().map(|(_, key)| DiffItem::Added(key)),
+            Ordering::Equal => {
+                let (id_a, key) = self.old.next().unwrap();
+                let (id_b, _) = self.new.next().unwrap();
+                Some(DiffItem::Common(id_a, id_b, key))
+            }
+        }
+    }
+}
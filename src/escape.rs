@@ -0,0 +1,119 @@
+//! Escapers to store keys containing [`END_MARKER`](crate::END_MARKER).
+//!
+//! The same [`Escaper`] must be applied to the keys given to the builder and to the queries,
+//! and its inverse to the decoded keys.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::escape::Escaper;
+//! use fcsd::Set;
+//!
+//! let keys: [&[u8]; 3] = [b"\x00\x01", b"\x01", b"ab\x00"];
+//! let escaper = Escaper::ByteStuffing;
+//! let set = Set::new(escaper.escape_keys(keys)).unwrap();
+//!
+//! let mut locator = set.locator();
+//! assert_eq!(locator.run(escaper.escape(b"ab\x00")), Some(2));
+//!
+//! let key = set.decoder().run(0);
+//! assert_eq!(escaper.unescape(&key).unwrap().as_ref(), b"\x00\x01");
+//! ```
+use std::borrow::Cow;
+
+use anyhow::{anyhow, Result};
+
+use crate::END_MARKER;
+
+/// Byte starting an escape sequence of [`Escaper::ByteStuffing`].
+const ESCAPE: u8 = END_MARKER + 1;
+
+/// Scheme to remove [`END_MARKER`] from keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Escaper {
+    /// Removes [`END_MARKER`] bytes, which cannot be restored.
+    ///
+    /// Keys differing only in the removed bytes collide,
+    /// and the order of keys can change, which the builder reports as unsorted.
+    Strip,
+    /// Replaces `0x00` with `0x01 0x01` and `0x01` with `0x01 0x02`.
+    ///
+    /// The escaped keys preserve the lexicographical and natural orders and can be restored.
+    ByteStuffing,
+}
+
+impl Escaper {
+    /// Escapes a key, borrowing it if nothing has to be replaced.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: Key to be escaped.
+    pub fn escape<'k>(&self, key: &'k [u8]) -> Cow<'k, [u8]> {
+        let first = match self {
+            Self::Strip => memchr::memchr(END_MARKER, key),
+            Self::ByteStuffing => memchr::memchr2(END_MARKER, ESCAPE, key),
+        };
+        let first = match first {
+            Some(first) => first,
+            None => return Cow::Borrowed(key),
+        };
+        let mut escaped = Vec::with_capacity(key.len() + 1);
+        escaped.extend_from_slice(&key[..first]);
+        for &c in &key[first..] {
+            match (self, c) {
+                (Self::Strip, END_MARKER) => {}
+                (Self::ByteStuffing, END_MARKER | ESCAPE) => {
+                    escaped.extend_from_slice(&[ESCAPE, c + 1]);
+                }
+                _ => escaped.push(c),
+            }
+        }
+        Cow::Owned(escaped)
+    }
+
+    /// Restores a key escaped by this escaper.
+    ///
+    /// [`Escaper::Strip`] returns the key as it is.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: Escaped key, such as one decoded from a dictionary.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when `key` has a broken escape sequence.
+    pub fn unescape<'k>(&self, key: &'k [u8]) -> Result<Cow<'k, [u8]>> {
+        let first = match (self, memchr::memchr(ESCAPE, key)) {
+            (Self::ByteStuffing, Some(first)) => first,
+            _ => return Ok(Cow::Borrowed(key)),
+        };
+        let mut unescaped = Vec::with_capacity(key.len());
+        unescaped.extend_from_slice(&key[..first]);
+        let mut bytes = key[first..].iter();
+        while let Some(&c) = bytes.next() {
+            if c == ESCAPE {
+                match bytes.next() {
+                    Some(&d) if d == END_MARKER + 1 || d == ESCAPE + 1 => unescaped.push(d - 1),
+                    _ => return Err(anyhow!("broken escape sequence")),
+                }
+            } else {
+                unescaped.push(c);
+            }
+        }
+        Ok(Cow::Owned(unescaped))
+    }
+
+    /// Makes an iterator escaping keys, which can be given to the builder.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: Keys to be escaped.
+    pub fn escape_keys<I, P>(self, keys: I) -> impl Iterator<Item = Vec<u8>>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        keys.into_iter()
+            .map(move |key| self.escape(key.as_ref()).into_owned())
+    }
+}
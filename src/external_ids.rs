@@ -0,0 +1,68 @@
+use crate::intvec::IntVector;
+use std::io;
+
+/// Two-way mapping between internal ids and user-supplied external ids.
+#[derive(Clone)]
+pub struct ExternalIds {
+    // to_external[i] is the external id of internal id i.
+    to_external: IntVector,
+    // Internal ids sorted by their external ids, for reverse lookups.
+    by_external: IntVector,
+}
+
+impl ExternalIds {
+    pub fn build(ids: &[u64]) -> Self {
+        let mut by_external: Vec<u64> = (0..ids.len() as u64).collect();
+        by_external.sort_by_key(|&i| ids[i as usize]);
+        Self {
+            to_external: IntVector::build(ids),
+            by_external: IntVector::build(&by_external),
+        }
+    }
+
+    #[inline(always)]
+    pub fn get(&self, id: usize) -> u64 {
+        self.to_external.get(id)
+    }
+
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.to_external.len()
+    }
+
+    pub fn find(&self, external_id: u64) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.by_external.len());
+        while lo < hi {
+            let mi = (lo + hi) / 2;
+            let id = self.by_external.get(mi) as usize;
+            match self.to_external.get(id).cmp(&external_id) {
+                std::cmp::Ordering::Less => lo = mi + 1,
+                std::cmp::Ordering::Greater => hi = mi,
+                std::cmp::Ordering::Equal => return Some(id),
+            }
+        }
+        None
+    }
+
+    pub const fn heap_bytes(&self) -> usize {
+        self.to_external.heap_bytes() + self.by_external.heap_bytes()
+    }
+
+    pub const fn size_in_bytes(&self) -> usize {
+        self.to_external.size_in_bytes() + self.by_external.size_in_bytes()
+    }
+
+    pub fn serialize_into<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        self.to_external.serialize_into(&mut writer)?;
+        self.by_external.serialize_into(&mut writer)
+    }
+
+    pub fn deserialize_from<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let to_external = IntVector::deserialize_from(&mut reader)?;
+        let by_external = IntVector::deserialize_from(&mut reader)?;
+        Ok(Self {
+            to_external,
+            by_external,
+        })
+    }
+}
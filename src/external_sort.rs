@@ -0,0 +1,120 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use anyhow::Result;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+/// Number of bytes of keys buffered in memory before being spilled as a sorted run.
+pub const RUN_BYTES: usize = 64 << 20;
+
+/// Sorter of byte strings spilling sorted runs to temporary files.
+pub struct ExternalSorter {
+    run_bytes: usize,
+    buffer: Vec<Vec<u8>>,
+    buffered_bytes: usize,
+    runs: Vec<File>,
+}
+
+impl ExternalSorter {
+    pub const fn new(run_bytes: usize) -> Self {
+        Self {
+            run_bytes,
+            buffer: Vec::new(),
+            buffered_bytes: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, key: Vec<u8>) -> Result<()> {
+        self.buffered_bytes += key.len();
+        self.buffer.push(key);
+        if self.run_bytes <= self.buffered_bytes {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the sorted and deduplicated keys.
+    pub fn finish(mut self) -> Result<SortedKeys> {
+        if self.runs.is_empty() {
+            self.buffer.sort_unstable();
+            self.buffer.dedup();
+            return Ok(SortedKeys::InMemory(self.buffer.into_iter()));
+        }
+        self.spill()?;
+        let mut runs = Vec::with_capacity(self.runs.len());
+        let mut heap = BinaryHeap::with_capacity(self.runs.len());
+        for (i, mut file) in self.runs.into_iter().enumerate() {
+            file.seek(SeekFrom::Start(0))?;
+            let mut run = BufReader::new(file);
+            if let Some(key) = read_key(&mut run)? {
+                heap.push(Reverse((key, i)));
+            }
+            runs.push(run);
+        }
+        Ok(SortedKeys::Merged {
+            runs,
+            heap,
+            last: None,
+        })
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.buffer.sort_unstable();
+        self.buffer.dedup();
+        let mut writer = BufWriter::new(tempfile::tempfile()?);
+        for key in self.buffer.drain(..) {
+            writer.write_u64::<LittleEndian>(key.len() as u64)?;
+            writer.write_all(&key)?;
+        }
+        self.runs
+            .push(writer.into_inner().map_err(|e| e.into_error())?);
+        self.buffered_bytes = 0;
+        Ok(())
+    }
+}
+
+fn read_key<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let len = match reader.read_u64::<LittleEndian>() {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut key = vec![0; len];
+    reader.read_exact(&mut key)?;
+    Ok(Some(key))
+}
+
+/// Iterator over sorted and deduplicated keys.
+pub enum SortedKeys {
+    InMemory(std::vec::IntoIter<Vec<u8>>),
+    Merged {
+        runs: Vec<BufReader<File>>,
+        heap: BinaryHeap<Reverse<(Vec<u8>, usize)>>,
+        last: Option<Vec<u8>>,
+    },
+}
+
+impl Iterator for SortedKeys {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::InMemory(keys) => keys.next().map(Ok),
+            Self::Merged { runs, heap, last } => loop {
+                let Reverse((key, i)) = heap.pop()?;
+                match read_key(&mut runs[i]) {
+                    Ok(Some(next)) => heap.push(Reverse((next, i))),
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(e.into())),
+                }
+                if last.as_ref() != Some(&key) {
+                    *last = Some(key.clone());
+                    return Some(Ok(key));
+                }
+            },
+        }
+    }
+}
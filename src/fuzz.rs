@@ -0,0 +1,89 @@
+//! Support for structure-aware fuzzing, enabled by the `arbitrary` feature.
+//!
+//! [`Set`] implements [`Arbitrary`] by building a dictionary from an arbitrary
+//! keyset, and [`round_trip`] exercises building, serialization, deserialization,
+//! and queries on arbitrary input. A [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz)
+//! target only has to forward its input:
+//!
+//! ```ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//!
+//! fuzz_target!(|data: &[u8]| fcsd::fuzz::round_trip(data));
+//! ```
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::Set;
+
+impl<'a> Arbitrary<'a> for Set {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let bucket_size = 1 << u.int_in_range(0..=6)?;
+        let mut keys: Vec<Vec<u8>> = u.arbitrary()?;
+        for key in keys.iter_mut() {
+            key.retain(|&c| c != crate::END_MARKER);
+        }
+        keys.sort();
+        keys.dedup();
+        Self::with_bucket_size(keys, bucket_size).map_err(|_| arbitrary::Error::IncorrectFormat)
+    }
+}
+
+/// Checks that a dictionary built from arbitrary input survives a serialization round trip.
+///
+/// It also checks that queries are answered consistently, and that arbitrary bytes never make
+/// deserialization, verification, or queries on the deserialized dictionary panic or abort.
+///
+/// # Arguments
+///
+///  - `data`: Arbitrary input, typically given by a fuzzer.
+///
+/// # Panics
+///
+/// It panics if any inconsistency is found.
+///
+/// # Example
+///
+/// ```
+/// fcsd::fuzz::round_trip(b"fuzzing input");
+/// ```
+pub fn round_trip(data: &[u8]) {
+    // Arbitrary bytes are rejected or accepted, but never make queries panic,
+    // even if the keys are broken.
+    if let Ok(set) = Set::deserialize_from(data) {
+        let _ = set.verify();
+        let mut locator = set.locator();
+        let mut decoder = set.decoder();
+        for id in 0..set.len() {
+            if let Ok(key) = decoder.try_run(id) {
+                let _ = locator.run(&key);
+            }
+            let _ = decoder.run(id);
+        }
+        for _ in set.iter() {}
+        for _ in set.predictive_iter(data.get(..1).unwrap_or_default()) {}
+    }
+
+    let mut u = Unstructured::new(data);
+    let set = match Set::arbitrary(&mut u) {
+        Ok(set) => set,
+        Err(_) => return,
+    };
+
+    let mut bytes = Vec::new();
+    set.serialize_into(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), set.size_in_bytes());
+    let other = Set::deserialize_from(&bytes[..]).unwrap();
+    assert_eq!(set.len(), other.len());
+
+    let mut locator = other.locator();
+    let mut decoder = other.decoder();
+    for (id, key) in set.iter() {
+        assert_eq!(locator.run(&key), Some(id));
+        assert_eq!(decoder.run(id), key);
+    }
+    if let Ok(query) = u.arbitrary::<Vec<u8>>() {
+        if let Some(id) = locator.run(&query) {
+            assert_eq!(decoder.run(id), query);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::automaton::Automaton;
+
+/// Automaton accepting the keys that have a prefix within a given edit distance
+/// (Levenshtein distance over bytes) of a query.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+/// let set = Set::new(keys).unwrap();
+///
+/// let mut iter = set.fuzzy_prefix_iter(b"SIGMD", 1);
+/// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+/// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FuzzyPrefix {
+    query: Vec<u8>,
+    max_distance: usize,
+}
+
+/// State of [`FuzzyPrefix`].
+#[derive(Clone, Debug)]
+pub struct FuzzyPrefixState {
+    // row[j] is the edit distance between the bytes read so far and query[..j].
+    row: Vec<usize>,
+    matched: bool,
+}
+
+impl FuzzyPrefix {
+    /// Makes an automaton [`FuzzyPrefix`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `query`: Query prefix.
+    ///  - `max_distance`: Maximum edit distance allowed.
+    pub fn new<P>(query: P, max_distance: usize) -> Self
+    where
+        P: AsRef<[u8]>,
+    {
+        Self {
+            query: query.as_ref().to_vec(),
+            max_distance,
+        }
+    }
+}
+
+impl Automaton for FuzzyPrefix {
+    type State = FuzzyPrefixState;
+
+    fn start(&self) -> Self::State {
+        let row: Vec<_> = (0..=self.query.len()).collect();
+        let matched = self.query.len() <= self.max_distance;
+        FuzzyPrefixState { row, matched }
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        if state.matched {
+            return state.clone();
+        }
+        let mut row = Vec::with_capacity(state.row.len());
+        row.push(state.row[0] + 1);
+        for (j, &c) in self.query.iter().enumerate() {
+            let cost = usize::from(c != byte);
+            let dist = (state.row[j] + cost)
+                .min(state.row[j + 1] + 1)
+                .min(row[j] + 1);
+            row.push(dist);
+        }
+        let matched = *row.last().unwrap() <= self.max_distance;
+        FuzzyPrefixState { row, matched }
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        state.matched
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        state.matched || state.row.iter().any(|&d| d <= self.max_distance)
+    }
+}
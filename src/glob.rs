@@ -0,0 +1,122 @@
+use crate::automaton::Automaton;
+
+/// Automaton accepting the keys that match a glob-style pattern.
+///
+/// In the pattern, `?` matches any single byte, `*` matches any run of bytes
+/// (including the empty one), and `\` escapes the following byte.
+/// The other bytes match themselves.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+/// let set = Set::new(keys).unwrap();
+///
+/// let mut iter = set.glob_iter(b"SIG*D");
+/// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+/// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+/// assert_eq!(iter.next(), None);
+///
+/// let mut iter = set.glob_iter(b"IC?M");
+/// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Token {
+    Byte(u8),
+    AnyByte,
+    AnyRun,
+}
+
+impl Glob {
+    /// Compiles a pattern into an automaton [`Glob`].
+    ///
+    /// A trailing `\` matches itself.
+    ///
+    /// # Arguments
+    ///
+    ///  - `pattern`: Glob-style pattern.
+    pub fn new<P>(pattern: P) -> Self
+    where
+        P: AsRef<[u8]>,
+    {
+        let mut tokens = Vec::new();
+        let mut bytes = pattern.as_ref().iter();
+        while let Some(&c) = bytes.next() {
+            let token = match c {
+                b'?' => Token::AnyByte,
+                b'*' => Token::AnyRun,
+                b'\\' => Token::Byte(*bytes.next().unwrap_or(&b'\\')),
+                _ => Token::Byte(c),
+            };
+            // Consecutive stars are redundant.
+            if token != Token::AnyRun || tokens.last() != Some(&Token::AnyRun) {
+                tokens.push(token);
+            }
+        }
+        Self { tokens }
+    }
+
+    /// Returns the leading bytes that every matching key starts with.
+    pub fn literal_prefix(&self) -> Vec<u8> {
+        self.tokens
+            .iter()
+            .map_while(|&t| match t {
+                Token::Byte(c) => Some(c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Adds `pos` and the positions reachable by skipping stars to `state`.
+    fn insert_closure(&self, state: &mut Vec<usize>, mut pos: usize) {
+        loop {
+            if !state.contains(&pos) {
+                state.push(pos);
+            }
+            if self.tokens.get(pos) != Some(&Token::AnyRun) {
+                break;
+            }
+            pos += 1;
+        }
+    }
+}
+
+impl Automaton for Glob {
+    /// Positions in the pattern that can be reached.
+    type State = Vec<usize>;
+
+    fn start(&self) -> Self::State {
+        let mut state = Vec::new();
+        self.insert_closure(&mut state, 0);
+        state
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        let mut next = Vec::with_capacity(state.len());
+        for &pos in state {
+            match self.tokens.get(pos) {
+                Some(Token::AnyRun) => self.insert_closure(&mut next, pos),
+                Some(Token::AnyByte) => self.insert_closure(&mut next, pos + 1),
+                Some(&Token::Byte(c)) if c == byte => self.insert_closure(&mut next, pos + 1),
+                _ => {}
+            }
+        }
+        next
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        state.contains(&self.tokens.len())
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        !state.is_empty()
+    }
+}
@@ -0,0 +1,53 @@
+use std::hash::BuildHasher;
+use std::iter::FusedIterator;
+
+use crate::iter::Iter;
+use crate::Set;
+
+/// Iterator to enumerate ids and fingerprints of keys stored in the dictionary.
+///
+/// The fingerprint of a key is `hasher.hash_one(key)` for `key: &[u8]`,
+/// computed on the decoding buffer without returning the key.
+#[derive(Clone)]
+pub struct HashIter<'a, S> {
+    iter: Iter<'a>,
+    hasher: S,
+}
+
+impl<'a, S> HashIter<'a, S>
+where
+    S: BuildHasher,
+{
+    /// Makes an iterator [`HashIter`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///  - `hasher`: Builder of hashers computing fingerprints.
+    pub fn new(set: &'a Set, hasher: S) -> Self {
+        Self {
+            iter: set.iter(),
+            hasher,
+        }
+    }
+}
+
+impl<'a, S> Iterator for HashIter<'a, S>
+where
+    S: BuildHasher,
+{
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.iter.advance()?;
+        Some((id, self.hasher.hash_one(self.iter.key())))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<S> ExactSizeIterator for HashIter<'_, S> where S: BuildHasher {}
+
+impl<S> FusedIterator for HashIter<'_, S> where S: BuildHasher {}
@@ -0,0 +1,50 @@
+//! Integer types usable for key ids.
+//!
+//! [`Set`](crate::Set) is not parameterized over the id type. An id is the position of a key
+//! computed from the bucket and the offset in it, and no area of the dictionary stores ids,
+//! so a type parameter would not shrink the dictionary but only the ids passing its API.
+//! Instead, the typed methods, such as [`Locator::run_as`](crate::locator::Locator::run_as),
+//! [`Locator::run_many_as`](crate::locator::Locator::run_many_as), and
+//! [`Set::build_id_mapping_as`](crate::Set::build_id_mapping_as), convert the ids at the boundary,
+//! so that parallel arrays of ids can be kept in the narrow type.
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+    impl Sealed for usize {}
+}
+
+/// Integer type usable for key ids, implemented for [`u32`], [`u64`], and [`usize`].
+///
+/// This trait is sealed and cannot be implemented outside of the crate.
+pub trait Id: private::Sealed + Copy + Debug + Eq + Ord + Hash + Send + Sync + 'static {
+    /// Converts a `usize` id, returning `None` if it does not fit in the type.
+    fn from_usize(x: usize) -> Option<Self>;
+
+    /// Converts the id into `usize`, returning `None` if it does not fit in `usize`.
+    fn to_usize(self) -> Option<usize>;
+}
+
+macro_rules! impl_id {
+    ($($t:ty),*) => {
+        $(
+            impl Id for $t {
+                #[inline(always)]
+                fn from_usize(x: usize) -> Option<Self> {
+                    <$t>::try_from(x).ok()
+                }
+
+                #[inline(always)]
+                fn to_usize(self) -> Option<usize> {
+                    usize::try_from(self).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_id!(u32, u64, usize);
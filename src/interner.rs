@@ -0,0 +1,241 @@
+//! String interner backed by a front-coding dictionary.
+//!
+//! [`Interner`] assigns a stable symbol to each distinct key. Keys of the dictionary
+//! are resolved in its compressed form, and new keys are kept in a side table
+//! that is periodically compacted into a rebuilt dictionary.
+//! The symbols never change through compaction.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::interner::Interner;
+//! use fcsd::Set;
+//!
+//! let set = Set::new(["ICDM", "SIGIR", "SIGMOD"]).unwrap();
+//! let mut interner = Interner::new(set).unwrap();
+//! assert_eq!(interner.intern(b"SIGIR").unwrap(), 1);
+//! assert_eq!(interner.intern(b"SIGKDD").unwrap(), 3);
+//! assert_eq!(interner.intern(b"ICML").unwrap(), 4);
+//! assert_eq!(interner.num_overflow(), 2);
+//!
+//! interner.compact().unwrap();
+//! assert_eq!(interner.num_overflow(), 0);
+//! assert_eq!(interner.get(b"SIGKDD"), Some(3));
+//! assert_eq!(interner.resolve(4), Some(b"ICML".to_vec()));
+//! ```
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::builder::AddErrorKind;
+use crate::{utils, Set};
+
+/// The default number of overflowed keys triggering compaction.
+pub const DEFAULT_COMPACTION_THRESHOLD: usize = 1 << 12;
+
+/// String interner storing most keys in a front-coding dictionary.
+#[derive(Clone)]
+pub struct Interner {
+    set: Set,
+    // Symbols of the overflowed keys, which are not stored in the dictionary.
+    overflow: HashMap<Vec<u8>, u64>,
+    // The overflowed keys in the order of symbols, starting from `first_overflow`.
+    overflow_keys: Vec<Vec<u8>>,
+    first_overflow: u64,
+    compaction_threshold: usize,
+}
+
+impl Interner {
+    /// Makes an [`Interner`] from a dictionary.
+    ///
+    /// The symbols of the stored keys are their external ids if the dictionary has them,
+    /// or their ids otherwise. New keys get symbols following the largest one.
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary has payloads or values,
+    /// which new keys lack.
+    pub fn new(set: Set) -> Result<Self> {
+        if set.payloads.is_some() || set.values.is_some() {
+            return Err(anyhow!(
+                "Dictionaries with payloads or values cannot be used for interning."
+            ));
+        }
+        let first_overflow = set.external_ids.as_ref().map_or(set.len() as u64, |ids| {
+            (0..ids.len()).map(|id| ids.get(id) + 1).max().unwrap_or(0)
+        });
+        Ok(Self {
+            set,
+            overflow: HashMap::new(),
+            overflow_keys: vec![],
+            first_overflow,
+            compaction_threshold: DEFAULT_COMPACTION_THRESHOLD,
+        })
+    }
+
+    /// Sets the number of overflowed keys triggering compaction in [`Self::intern`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `threshold`: Number of overflowed keys (default: [`DEFAULT_COMPACTION_THRESHOLD`]).
+    pub const fn compaction_threshold(mut self, threshold: usize) -> Self {
+        self.compaction_threshold = threshold;
+        self
+    }
+
+    /// Returns the symbol of a key, assigning a new one if it is not interned yet.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be interned.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `key` cannot be stored in the dictionary, such as one containing
+    ///    [`END_MARKER`](crate::END_MARKER), or
+    ///  - the compaction triggered by `key` fails.
+    pub fn intern<P>(&mut self, key: P) -> Result<u64>
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        if let Some(symbol) = self.get(key) {
+            return Ok(symbol);
+        }
+        if let Some(kind) = self.check_key(key) {
+            return Err(anyhow!("{}", kind));
+        }
+        let symbol = self.first_overflow + self.overflow_keys.len() as u64;
+        self.overflow.insert(key.to_vec(), symbol);
+        self.overflow_keys.push(key.to_vec());
+        if self.compaction_threshold <= self.overflow_keys.len() {
+            self.compact()?;
+        }
+        Ok(symbol)
+    }
+
+    /// Returns the symbol of a key if it is interned.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    pub fn get<P>(&self, key: P) -> Option<u64>
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        if let Some(id) = self.set.locate(key) {
+            return Some(self.set.external_id(id).unwrap_or(id as u64));
+        }
+        self.overflow.get(key).copied()
+    }
+
+    /// Returns the key of a symbol if it is assigned.
+    ///
+    /// # Arguments
+    ///
+    ///  - `symbol`: Symbol to be resolved.
+    pub fn resolve(&self, symbol: u64) -> Option<Vec<u8>> {
+        if self.first_overflow <= symbol {
+            let i = symbol - self.first_overflow;
+            return (i < self.overflow_keys.len() as u64)
+                .then(|| self.overflow_keys[i as usize].clone());
+        }
+        if self.set.has_external_ids() {
+            Some(self.set.decode(self.set.internal_id(symbol)?))
+        } else {
+            (symbol < self.set.len() as u64).then(|| self.set.decode(symbol as usize))
+        }
+    }
+
+    /// Gets the number of interned keys.
+    pub const fn len(&self) -> usize {
+        self.set.len() + self.overflow_keys.len()
+    }
+
+    /// Checks if no keys are interned.
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of keys in the side table, which are not compacted yet.
+    pub const fn num_overflow(&self) -> usize {
+        self.overflow_keys.len()
+    }
+
+    /// Gets the dictionary of the compacted keys.
+    pub const fn set(&self) -> &Set {
+        &self.set
+    }
+
+    /// Rebuilds the dictionary with the overflowed keys, with the same configuration as the old one.
+    /// The keys are stored with their symbols as external ids.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary cannot be rebuilt.
+    pub fn compact(&mut self) -> Result<()> {
+        if self.overflow_keys.is_empty() {
+            return Ok(());
+        }
+        let set = &self.set;
+        let mut staged: Vec<_> = self.overflow.iter().collect();
+        staged.sort_unstable_by(|(a, _), (b, _)| set.order.compare(a, b));
+
+        let mut builder = set.new_builder(set.bucket_size())?;
+        let mut staged = staged.into_iter().peekable();
+        let mut iter = set.iter();
+        while let Some(id) = iter.advance() {
+            let key = iter.key();
+            while let Some((new_key, symbol)) =
+                staged.next_if(|(new_key, _)| set.order.compare(new_key, key).is_lt())
+            {
+                builder.add_with_id(new_key, *symbol)?;
+            }
+            builder.add_with_id(key, set.external_id(id).unwrap_or(id as u64))?;
+        }
+        for (new_key, &symbol) in staged {
+            builder.add_with_id(new_key, symbol)?;
+        }
+        self.set = builder.finish();
+        self.overflow.clear();
+        self.first_overflow += self.overflow_keys.len() as u64;
+        self.overflow_keys.clear();
+        Ok(())
+    }
+
+    /// Compacts the overflowed keys and returns the dictionary.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary cannot be rebuilt.
+    pub fn into_set(mut self) -> Result<Set> {
+        self.compact()?;
+        Ok(self.set)
+    }
+
+    /// Checks if `key` can be stored in the dictionary, returning the reason if not.
+    fn check_key(&self, key: &[u8]) -> Option<AddErrorKind> {
+        let set = &self.set;
+        if utils::contains_end_marker(key) {
+            Some(AddErrorKind::EndMarker)
+        } else if let Some(byte) = set.alphabet.as_ref().and_then(|a| a.find_missing(key)) {
+            Some(AddErrorKind::OutOfAlphabet { byte })
+        } else if let Some(max_key_len) = set.max_key_len.filter(|&n| n < key.len()) {
+            Some(AddErrorKind::TooLong {
+                len: key.len(),
+                max_key_len,
+            })
+        } else if set.utf8 && std::str::from_utf8(key).is_err() {
+            Some(AddErrorKind::InvalidUtf8)
+        } else {
+            None
+        }
+    }
+}
@@ -1,40 +1,106 @@
+use crate::storage::{Bytes, Words};
 use crate::utils;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::convert::TryInto;
 use std::io;
 
+/// The number of words converted at once in (de)serialization.
+const SERIAL_BLOCK_LEN: usize = 1024;
+
+/// The number of integers unpacked at once by [`IntVector::get_range`] in scans.
+pub const RANGE_BLOCK_LEN: usize = 64;
+
 #[derive(Clone)]
 pub struct IntVector {
-    chunks: Vec<u64>,
+    chunks: Words,
     len: usize,
     bits: usize,
     mask: u64,
 }
 
+impl Default for IntVector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IntVector {
+    pub const fn new() -> Self {
+        Self::with_bits(1)
+    }
+
     pub fn build(input: &[u64]) -> Self {
-        let len = input.len();
-        let bits = utils::needed_bits(*input.iter().max().unwrap());
-        let mask = (1 << bits) - 1;
-
-        let mut chunks = vec![0; Self::words_for(len * bits)];
-
-        for (i, &x) in input.iter().enumerate() {
-            let (q, m) = Self::decompose(i * bits);
-            chunks[q] &= !(mask << m);
-            chunks[q] |= (x & mask) << m;
-            if 64 < m + bits {
-                let diff = 64 - m;
-                chunks[q + 1] &= !(mask >> diff);
-                chunks[q + 1] |= (x & mask) >> diff;
-            }
+        let bits = utils::needed_bits(input.iter().cloned().max().unwrap_or(0));
+        let mut iv = Self::with_bits(bits);
+        iv.chunks
+            .to_mut()
+            .reserve(Self::words_for(input.len() * bits));
+        for &x in input {
+            iv.push(x);
+        }
+        iv
+    }
+
+    /// Appends an integer, widening the bit width of all the integers if needed.
+    pub fn push(&mut self, x: u64) {
+        let bits = utils::needed_bits(x);
+        if self.bits < bits {
+            self.widen(bits);
+        }
+        let words = Self::words_for((self.len + 1) * self.bits);
+        if self.chunks.len() < words {
+            self.chunks.to_mut().resize(words, 0);
+        }
+        self.len += 1;
+        self.set(self.len - 1, x);
+    }
+
+    /// Overwrites an integer, widening the bit width of all the integers if needed.
+    pub fn update(&mut self, i: usize, x: u64) {
+        assert!(i < self.len);
+        let bits = utils::needed_bits(x);
+        if self.bits < bits {
+            self.widen(bits);
         }
+        self.set(i, x);
+    }
 
+    #[inline(always)]
+    pub fn set(&mut self, i: usize, x: u64) {
+        debug_assert!(utils::needed_bits(x) <= self.bits);
+        let (q, m) = Self::decompose(i * self.bits);
+        let chunks = self.chunks.to_mut();
+        chunks[q] &= !(self.mask << m);
+        chunks[q] |= (x & self.mask) << m;
+        if 64 < m + self.bits {
+            let diff = 64 - m;
+            chunks[q + 1] &= !(self.mask >> diff);
+            chunks[q + 1] |= (x & self.mask) >> diff;
+        }
+    }
+
+    const fn with_bits(bits: usize) -> Self {
         Self {
-            chunks,
-            len,
+            chunks: Words::new(),
+            len: 0,
             bits,
-            mask,
+            mask: Self::mask_for(bits),
+        }
+    }
+
+    fn widen(&mut self, bits: usize) {
+        let mut other = Self::with_bits(bits);
+        other.chunks = vec![0; Self::words_for(self.len * bits)].into();
+        other.len = self.len;
+        let mut buf = [0; RANGE_BLOCK_LEN];
+        for start in (0..self.len).step_by(RANGE_BLOCK_LEN) {
+            let len = std::cmp::min(RANGE_BLOCK_LEN, self.len - start);
+            self.get_range(start, len, &mut buf);
+            for (i, &x) in buf[..len].iter().enumerate() {
+                other.set(start + i, x);
+            }
         }
+        *self = other;
     }
 
     #[inline(always)]
@@ -47,38 +113,109 @@ impl IntVector {
         }
     }
 
+    /// Unpacks the `len` integers from position `start` into `out[..len]`,
+    /// advancing the word position incrementally instead of recomputing it per integer.
+    pub fn get_range(&self, start: usize, len: usize, out: &mut [u64]) {
+        assert!(start + len <= self.len);
+        let (mut q, mut m) = Self::decompose(start * self.bits);
+        for x in &mut out[..len] {
+            let mut v = self.chunks[q] >> m;
+            if 64 < m + self.bits {
+                v |= self.chunks[q + 1] << (64 - m);
+            }
+            *x = v & self.mask;
+            m += self.bits;
+            if 64 <= m {
+                m -= 64;
+                q += 1;
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn prefetch(&self, i: usize) {
+        let (q, _) = Self::decompose(i * self.bits);
+        utils::prefetch(self.chunks[q..].as_ptr());
+    }
+
     #[inline(always)]
     pub const fn len(&self) -> usize {
         self.len
     }
 
-    pub fn size_in_bytes(&self) -> usize {
+    pub const fn heap_bytes(&self) -> usize {
+        self.chunks.heap_bytes()
+    }
+
+    pub const fn size_in_bytes(&self) -> usize {
         8 + self.chunks.len() * 8 + 8 * 3
     }
 
     pub fn serialize_into<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
         writer.write_u64::<LittleEndian>(self.chunks.len() as u64)?;
-        for &x in &self.chunks {
-            writer.write_u64::<LittleEndian>(x)?;
+        let mut buf = Vec::with_capacity(SERIAL_BLOCK_LEN * 8);
+        for block in self.chunks.chunks(SERIAL_BLOCK_LEN) {
+            buf.clear();
+            for &x in block {
+                buf.extend_from_slice(&x.to_le_bytes());
+            }
+            writer.write_all(&buf)?;
         }
         writer.write_u64::<LittleEndian>(self.len as u64)?;
         writer.write_u64::<LittleEndian>(self.bits as u64)?;
-        writer.write_u64::<LittleEndian>(self.mask as u64)?;
+        writer.write_u64::<LittleEndian>(self.mask)?;
         Ok(())
     }
 
+    /// Deserializes the integers, checking that the chunks hold all of them.
     pub fn deserialize_from<R: io::Read>(mut reader: R) -> io::Result<Self> {
         let chunks = {
-            let len = reader.read_u64::<LittleEndian>()? as usize;
-            let mut chunks = vec![0; len];
-            for x in chunks.iter_mut() {
-                *x = reader.read_u64::<LittleEndian>()?;
+            let len = utils::read_usize(&mut reader)?;
+            // The chunks are grown block by block so that a broken length fails at
+            // the end of the input instead of allocating the memory in advance.
+            let mut chunks = Vec::new();
+            let mut buf = vec![0; SERIAL_BLOCK_LEN * 8];
+            while chunks.len() < len {
+                let num = std::cmp::min(SERIAL_BLOCK_LEN, len - chunks.len());
+                let buf = &mut buf[..num * 8];
+                reader.read_exact(buf)?;
+                chunks.extend(
+                    buf.chunks_exact(8)
+                        .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap())),
+                );
             }
             chunks
         };
-        let len = reader.read_u64::<LittleEndian>()? as usize;
-        let bits = reader.read_u64::<LittleEndian>()? as usize;
+        Self::deserialize_fields_from(reader, chunks.into())
+    }
+
+    /// Deserializes the integers like [`IntVector::deserialize_from`],
+    /// loading the chunks of `len` bytes with `read_chunks`.
+    pub fn deserialize_with<R, F>(reader: &mut R, read_chunks: F) -> anyhow::Result<Self>
+    where
+        R: io::Read,
+        F: FnOnce(&mut R, usize) -> anyhow::Result<Bytes>,
+    {
+        let len = utils::read_usize(&mut *reader)?
+            .checked_mul(8)
+            .ok_or_else(Self::broken)?;
+        let chunks = Words::from_le_bytes(read_chunks(reader, len)?);
+        Ok(Self::deserialize_fields_from(reader, chunks)?)
+    }
+
+    /// Deserializes the fields following the chunks, checking that the chunks hold all the integers.
+    fn deserialize_fields_from<R: io::Read>(mut reader: R, chunks: Words) -> io::Result<Self> {
+        let len = utils::read_usize(&mut reader)?;
+        let bits = utils::read_usize(&mut reader)?;
         let mask = reader.read_u64::<LittleEndian>()?;
+        if !(1..=64).contains(&bits)
+            || mask != Self::mask_for(bits)
+            || len
+                .checked_mul(bits)
+                .is_none_or(|n| chunks.len() < Self::words_for(n))
+        {
+            return Err(Self::broken());
+        }
         Ok(Self {
             chunks,
             len,
@@ -87,9 +224,65 @@ impl IntVector {
         })
     }
 
+    /// Writes the integers, which are non-decreasing from zero, as the vbyte-encoded
+    /// differences between adjacent ones. The leading zero and the length are not written.
+    pub fn serialize_deltas_into<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        debug_assert!(self.len == 0 || self.get(0) == 0);
+        let mut bytes = Vec::with_capacity(SERIAL_BLOCK_LEN * 2);
+        let mut buf = [0; RANGE_BLOCK_LEN];
+        let mut prev = 0;
+        for start in (0..self.len).step_by(RANGE_BLOCK_LEN) {
+            let len = std::cmp::min(RANGE_BLOCK_LEN, self.len - start);
+            self.get_range(start, len, &mut buf);
+            // Skips the leading zero.
+            for &x in &buf[usize::from(start == 0)..len] {
+                debug_assert!(prev <= x);
+                utils::vbyte::append(&mut bytes, (x - prev) as usize);
+                prev = x;
+            }
+            if SERIAL_BLOCK_LEN <= bytes.len() {
+                writer.write_all(&bytes)?;
+                bytes.clear();
+            }
+        }
+        writer.write_all(&bytes)
+    }
+
+    /// Reads `len` integers written by [`IntVector::serialize_deltas_into`],
+    /// reconstructing the packed form.
+    pub fn deserialize_deltas_from<R: io::Read>(mut reader: R, len: usize) -> io::Result<Self> {
+        let mut iv = Self::new();
+        if len == 0 {
+            return Ok(iv);
+        }
+        iv.push(0);
+        let mut prev = 0u64;
+        for _ in 1..len {
+            let delta = utils::vbyte::read(&mut reader)? as u64;
+            prev = prev
+                .checked_add(delta)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "overflowed deltas"))?;
+            iv.push(prev);
+        }
+        Ok(iv)
+    }
+
+    fn broken() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "broken integer vector")
+    }
+
     #[inline(always)]
     const fn words_for(bits: usize) -> usize {
-        (bits + 63) / 64
+        bits.div_ceil(64)
+    }
+
+    #[inline(always)]
+    const fn mask_for(bits: usize) -> u64 {
+        if bits == 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        }
     }
 
     #[inline(always)]
@@ -97,3 +290,72 @@ impl IntVector {
         (x / 64, x % 64)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    #[test]
+    fn test_push() {
+        let mut rng = ChaChaRng::seed_from_u64(7);
+        let mut values: Vec<u64> = (0..1000).map(|i| rng.gen::<u64>() >> (i % 64)).collect();
+        values.sort_unstable();
+
+        let built = IntVector::build(&values);
+        let mut pushed = IntVector::new();
+        for &x in &values {
+            pushed.push(x);
+        }
+        assert_eq!(built.len(), pushed.len());
+        assert_eq!(built.size_in_bytes(), pushed.size_in_bytes());
+        for (i, &x) in values.iter().enumerate() {
+            assert_eq!(built.get(i), x);
+            assert_eq!(pushed.get(i), x);
+        }
+    }
+
+    #[test]
+    fn test_deltas() {
+        let mut rng = ChaChaRng::seed_from_u64(13);
+        for len in [0, 1, 2, 1000] {
+            let mut values: Vec<u64> = (0..len).map(|_| rng.gen::<u64>() >> 40).collect();
+            values.sort_unstable();
+            if let Some(x) = values.first_mut() {
+                *x = 0;
+            }
+            let iv = IntVector::build(&values);
+            let mut bytes = vec![];
+            iv.serialize_deltas_into(&mut bytes).unwrap();
+            assert!(bytes.len() < iv.size_in_bytes());
+
+            let other = IntVector::deserialize_deltas_from(&bytes[..], len).unwrap();
+            assert_eq!(other.len(), len);
+            for (i, &x) in values.iter().enumerate() {
+                assert_eq!(other.get(i), x);
+            }
+            if len != 0 {
+                assert!(IntVector::deserialize_deltas_from(&bytes[..], len + 1).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_range() {
+        let mut rng = ChaChaRng::seed_from_u64(11);
+        for bits in [1, 7, 13, 32, 63, 64] {
+            let values: Vec<u64> = (0..300)
+                .map(|_| rng.gen::<u64>() & IntVector::mask_for(bits))
+                .collect();
+            let iv = IntVector::build(&values);
+            let mut out = vec![0; values.len()];
+            for start in [0, 1, 63, 64, 100] {
+                let len = values.len() - start;
+                iv.get_range(start, len, &mut out);
+                assert_eq!(&out[..len], &values[start..]);
+            }
+            iv.get_range(values.len(), 0, &mut out);
+        }
+    }
+}
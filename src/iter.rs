@@ -1,12 +1,14 @@
+use std::iter::FusedIterator;
+
+use anyhow::{anyhow, Result};
+
 use crate::Set;
 
 /// Iterator to enumerate keys stored in the dictionary.
 #[derive(Clone)]
 pub struct Iter<'a> {
     set: &'a Set,
-    dec: Vec<u8>,
-    pos: usize,
-    id: usize,
+    state: IterState,
 }
 
 impl<'a> Iter<'a> {
@@ -18,33 +20,180 @@ impl<'a> Iter<'a> {
     pub fn new(set: &'a Set) -> Self {
         Self {
             set,
-            dec: Vec::with_capacity(set.max_length()),
-            pos: 0,
-            id: 0,
+            state: IterState::new(set),
         }
     }
 }
 
+impl<'a> Iter<'a> {
+    /// Moves the iterator so that the next key is the one associated with `id`.
+    ///
+    /// It jumps to the bucket of `id` via the pointer table and decodes only the keys
+    /// before `id` in the bucket. If `id` is no less than the number of keys,
+    /// the iterator reaches the end.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Id of the next key.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 2).unwrap();
+    ///
+    /// let mut iter = set.iter();
+    /// iter.seek_id(3);
+    /// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+    /// iter.seek_id(1);
+    /// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
+    /// iter.seek_id(5);
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn seek_id(&mut self, id: usize) {
+        self.state.seek_id(self.set, id);
+    }
+
+    /// Returns the next id and key like [`Iterator::next`], but reports broken data as an error.
+    ///
+    /// When the serialized area is inconsistent with the number of keys or cannot be decoded,
+    /// an error is returned once, and the iterator is fused afterward.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML"]).unwrap();
+    /// let mut iter = set.iter();
+    /// assert_eq!(iter.try_next().unwrap(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.try_next().unwrap(), Some((1, b"ICML".to_vec())));
+    /// assert_eq!(iter.try_next().unwrap(), None);
+    /// ```
+    pub fn try_next(&mut self) -> Result<Option<(usize, Vec<u8>)>> {
+        self.state.try_next(self.set)
+    }
+
+    /// Decodes the next key without cloning it, returning its id.
+    /// The key can be borrowed with [`Iter::key`].
+    pub(crate) fn advance(&mut self) -> Option<usize> {
+        self.try_advance().ok().flatten()
+    }
+
+    pub(crate) fn try_advance(&mut self) -> Result<Option<usize>> {
+        self.state.try_advance(self.set)
+    }
+
+    /// Gets the key decoded last.
+    pub(crate) fn key(&self) -> &[u8] {
+        &self.state.dec
+    }
+}
+
 impl<'a> Iterator for Iter<'a> {
     type Item = (usize, Vec<u8>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.pos == self.set.serialized.len() {
-            return None;
+        let id = self.advance()?;
+        Some((id, self.state.dec.clone()))
+    }
+
+    /// Skips `n` keys by jumping to the bucket via [`Iter::seek_id`]
+    /// instead of decoding every intermediate key.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.state.nth(self.set, n)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.state.num_remaining(self.set);
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl FusedIterator for Iter<'_> {}
+
+/// State of [`Iter`] independent of how the dictionary is held,
+/// whose methods take the dictionary.
+#[derive(Clone)]
+pub(crate) struct IterState {
+    dec: Vec<u8>,
+    pos: usize,
+    id: usize,
+}
+
+impl IterState {
+    pub(crate) fn new(set: &Set) -> Self {
+        Self {
+            dec: Vec::with_capacity(set.max_length()),
+            pos: 0,
+            id: 0,
         }
-        if self.set.pos_in_bucket(self.id) == 0 {
-            self.dec.clear();
-        } else {
-            let (lcp, next_pos) = self.set.decode_lcp(self.pos);
-            self.pos = next_pos;
-            self.dec.resize(lcp, 0);
+    }
+
+    pub(crate) fn seek_id(&mut self, set: &Set, id: usize) {
+        if set.len() <= id {
+            self.pos = set.serialized.len();
+            self.id = set.len();
+            return;
+        }
+        let bi = set.bucket_id(id);
+        self.pos = set.pointers.get(bi) as usize;
+        self.id = bi * set.bucket_size();
+        self.dec.clear();
+        while self.id < id {
+            if self.decode_next(set).is_none() {
+                self.pos = set.serialized.len();
+                return;
+            }
+            self.id += 1;
+        }
+    }
+
+    pub(crate) fn try_next(&mut self, set: &Set) -> Result<Option<(usize, Vec<u8>)>> {
+        Ok(self.try_advance(set)?.map(|id| (id, self.dec.clone())))
+    }
+
+    pub(crate) fn nth(&mut self, set: &Set, n: usize) -> Option<(usize, Vec<u8>)> {
+        self.seek_id(set, self.id.saturating_add(n));
+        self.try_next(set).ok().flatten()
+    }
+
+    pub(crate) fn try_advance(&mut self, set: &Set) -> Result<Option<usize>> {
+        let at_end = self.pos == set.serialized.len();
+        if at_end && set.len() <= self.id {
+            return Ok(None);
+        }
+        if at_end || set.len() <= self.id || self.decode_next(set).is_none() {
+            // Fuses the iterator on broken data.
+            self.pos = set.serialized.len();
+            self.id = set.len();
+            return Err(anyhow!("broken serialized area"));
         }
-        self.pos = self.set.decode_next(self.pos, &mut self.dec);
         self.id += 1;
-        Some((self.id - 1, self.dec.clone()))
+        Ok(Some(self.id - 1))
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (self.set.len(), Some(self.set.len()))
+    pub(crate) const fn num_remaining(&self, set: &Set) -> usize {
+        set.len() - self.id
+    }
+
+    fn decode_next(&mut self, set: &Set) -> Option<()> {
+        if set.pos_in_bucket(self.id) == 0 {
+            self.dec.clear();
+            if set.bucket_aligned {
+                // Skips the padding before the bucket.
+                self.pos = set.pointers.get(set.bucket_id(self.id)) as usize;
+            }
+        } else {
+            let (lcp, next_pos) = set.try_decode_lcp(self.pos, self.dec.len())?;
+            self.pos = next_pos;
+            self.dec.truncate(lcp);
+        }
+        self.pos = set.try_decode_next(self.pos, &mut self.dec)?;
+        Some(())
     }
 }
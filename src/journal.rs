@@ -0,0 +1,219 @@
+//! Append-only journal of a growing dictionary.
+//!
+//! A journal is a sequence of records, each of which holds the buckets appended since
+//! the previous record and a footer with the latest metadata.
+//! Snapshotting a dictionary extended with [`Set::append_greater`](crate::Set::append_greater)
+//! writes only the new part.
+//!
+//! Each record is framed by its length and a checksum of its body, and loading stops at
+//! the first record torn by a crash or otherwise corrupted, ignoring it and the following ones.
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::alphabet::Alphabet;
+use crate::utils;
+use crate::{FLAG_ALPHABET, FLAG_MAX_KEY_LEN};
+
+/// Cookie value starting each record, which differs from those of the other formats.
+const RECORD_COOKIE: u32 = 114517;
+
+/// Offset of the body in a record, following the cookie and the length.
+const BODY_OFFSET: usize = 12;
+
+/// Position of a dictionary written to a journal so far.
+///
+/// It is updated by [`Set::serialize_dirty_into`](crate::Set::serialize_dirty_into)
+/// and returned by [`Set::deserialize_journal_from`](crate::Set::deserialize_journal_from).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Journal {
+    pub(crate) num_buckets: usize,
+    pub(crate) num_bytes: usize,
+}
+
+impl Journal {
+    /// Creates an empty [`Journal`], whose first record holds the whole dictionary.
+    pub const fn new() -> Self {
+        Self {
+            num_buckets: 0,
+            num_bytes: 0,
+        }
+    }
+
+    /// Gets the number of buckets written so far.
+    pub const fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    /// Gets the number of bytes of the serialized area written so far.
+    pub const fn num_bytes(&self) -> usize {
+        self.num_bytes
+    }
+}
+
+/// Record of the buckets appended since the previous one, followed by the footer.
+///
+/// A record is written as the cookie in `u32`, the length of the body in `u64`, the body,
+/// and the FNV-1a hash of the body in `u64`.
+pub(crate) struct Record {
+    pub flags: u8,
+    pub max_key_len: Option<usize>,
+    pub alphabet: Option<Alphabet>,
+    pub bucket_bits: usize,
+    pub first_bucket: usize,
+    pub pointers: Vec<u64>,
+    pub first_byte: usize,
+    pub bytes: Vec<u8>,
+    pub len: usize,
+    pub max_length: usize,
+}
+
+impl Record {
+    pub fn write_into<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        // The record is written at once, so that a crash leaves at most one torn record.
+        let mut buf = Vec::with_capacity(64 + self.pointers.len() * 8 + self.bytes.len());
+        buf.write_u32::<LittleEndian>(RECORD_COOKIE)?;
+        // The length of the body is filled after writing it.
+        buf.write_u64::<LittleEndian>(0)?;
+        buf.write_u8(self.flags)?;
+        if let Some(max_key_len) = self.max_key_len {
+            buf.write_u64::<LittleEndian>(max_key_len as u64)?;
+        }
+        if let Some(alphabet) = &self.alphabet {
+            alphabet.serialize_into(&mut buf)?;
+        }
+        buf.write_u64::<LittleEndian>(self.bucket_bits as u64)?;
+        buf.write_u64::<LittleEndian>(self.first_bucket as u64)?;
+        buf.write_u64::<LittleEndian>(self.pointers.len() as u64)?;
+        for &ptr in &self.pointers {
+            buf.write_u64::<LittleEndian>(ptr)?;
+        }
+        buf.write_u64::<LittleEndian>(self.first_byte as u64)?;
+        buf.write_u64::<LittleEndian>(self.bytes.len() as u64)?;
+        buf.extend_from_slice(&self.bytes);
+        buf.write_u64::<LittleEndian>(self.len as u64)?;
+        buf.write_u64::<LittleEndian>(self.max_length as u64)?;
+        let body_len = (buf.len() - BODY_OFFSET) as u64;
+        buf[4..BODY_OFFSET].copy_from_slice(&body_len.to_le_bytes());
+        let checksum = fnv1a(&buf[BODY_OFFSET..]);
+        buf.write_u64::<LittleEndian>(checksum)?;
+        writer.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Reads the next record, returning `None` at the end or on a torn or corrupted record.
+    ///
+    /// An unknown cookie value is an error for the `first` record,
+    /// since the data is not a journal, and a corrupted record for the others.
+    pub fn read_from<R>(mut reader: R, first: bool) -> Result<Option<Self>>
+    where
+        R: io::Read,
+    {
+        let cookie = match reader.read_u32::<LittleEndian>() {
+            Ok(cookie) => cookie,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if cookie != RECORD_COOKIE {
+            if first {
+                return Err(anyhow!("unknown cookie value"));
+            }
+            return Ok(None);
+        }
+        let body = {
+            let len = match reader.read_u64::<LittleEndian>() {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            // The body grows with the bytes actually read, so a broken length
+            // cannot allocate more memory than the input has.
+            let mut body = Vec::new();
+            Read::take(&mut reader, len).read_to_end(&mut body)?;
+            if body.len() as u64 != len {
+                return Ok(None);
+            }
+            body
+        };
+        let checksum = match reader.read_u64::<LittleEndian>() {
+            Ok(checksum) => checksum,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if checksum != fnv1a(&body) {
+            return Ok(None);
+        }
+        // The body passing the checksum is complete, so any error is a broken record.
+        let mut rest = &body[..];
+        let record = Self::read_body(&mut rest).map_err(|_| anyhow!("broken journal record"))?;
+        if !rest.is_empty() {
+            return Err(anyhow!("broken journal record"));
+        }
+        Ok(Some(record))
+    }
+
+    fn read_body<R>(mut reader: R) -> io::Result<Self>
+    where
+        R: io::Read,
+    {
+        let flags = reader.read_u8()?;
+        let max_key_len = if flags & FLAG_MAX_KEY_LEN != 0 {
+            Some(utils::read_usize(&mut reader)?)
+        } else {
+            None
+        };
+        let alphabet = if flags & FLAG_ALPHABET != 0 {
+            Some(Alphabet::deserialize_from(&mut reader).map_err(io::Error::other)?)
+        } else {
+            None
+        };
+        let bucket_bits = utils::read_usize(&mut reader)?;
+        let first_bucket = utils::read_usize(&mut reader)?;
+        let pointers = {
+            let len = reader.read_u64::<LittleEndian>()?;
+            let mut buf = Vec::new();
+            Read::take(&mut reader, len.saturating_mul(8)).read_to_end(&mut buf)?;
+            if buf.len() as u64 != len.saturating_mul(8) {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            buf.chunks_exact(8)
+                .map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+                .collect()
+        };
+        let first_byte = utils::read_usize(&mut reader)?;
+        let bytes = {
+            let len = reader.read_u64::<LittleEndian>()?;
+            let mut bytes = Vec::new();
+            Read::take(&mut reader, len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            bytes
+        };
+        let len = utils::read_usize(&mut reader)?;
+        let max_length = utils::read_usize(&mut reader)?;
+        Ok(Self {
+            flags,
+            max_key_len,
+            alphabet,
+            bucket_bits,
+            first_bucket,
+            pointers,
+            first_byte,
+            bytes,
+            len,
+            max_length,
+        })
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of the bytes.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |h, &c| {
+        (h ^ u64::from(c)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,57 @@
+use std::io;
+
+use crate::iter::Iter;
+use crate::Set;
+
+/// Reader streaming the keys in the order of ids, each followed by a delimiter.
+///
+/// The keys are decoded on demand, so the whole dictionary can be piped into
+/// any sink expecting a byte stream without an intermediate dump.
+#[derive(Clone)]
+pub struct KeyReader<'a> {
+    iter: Iter<'a>,
+    delimiter: u8,
+    // Bytes of the current key and delimiter not read yet.
+    pending: Vec<u8>,
+    consumed: usize,
+}
+
+impl<'a> KeyReader<'a> {
+    /// Makes a reader [`KeyReader`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///  - `delimiter`: Byte written after each key, such as `b'\n'`.
+    pub fn new(set: &'a Set, delimiter: u8) -> Self {
+        Self {
+            iter: set.iter(),
+            delimiter,
+            pending: Vec::with_capacity(set.max_length() + 1),
+            consumed: 0,
+        }
+    }
+}
+
+impl io::Read for KeyReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut num = 0;
+        while num < buf.len() {
+            if self.consumed == self.pending.len() {
+                if self.iter.advance().is_none() {
+                    break;
+                }
+                self.pending.clear();
+                self.pending.extend_from_slice(self.iter.key());
+                self.pending.push(self.delimiter);
+                self.consumed = 0;
+            }
+            let src = &self.pending[self.consumed..];
+            let len = src.len().min(buf.len() - num);
+            buf[num..num + len].copy_from_slice(&src[..len]);
+            self.consumed += len;
+            num += len;
+        }
+        Ok(num)
+    }
+}
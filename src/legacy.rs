@@ -0,0 +1,90 @@
+//! Loader of the original serialized layout, written before user-supplied ids were supported.
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Result};
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::builder::Builder;
+use crate::{Set, END_MARKER};
+
+/// Serial cookie value of the original layout.
+pub const SERIAL_COOKIE: u32 = 114514;
+
+/// Deserializes the rest of the original layout following [`SERIAL_COOKIE`].
+///
+/// The layout has only plain keys, so the dictionary is rebuilt from the decoded keys.
+pub fn deserialize_from<R>(mut reader: R) -> Result<Set>
+where
+    R: io::Read,
+{
+    // The bucket pointers are recomputed by the builder.
+    let num_chunks = reader.read_u64::<LittleEndian>()?;
+    let skipped = num_chunks
+        .checked_add(3)
+        .and_then(|n| n.checked_mul(8))
+        .ok_or_else(|| anyhow!("broken pointers"))?;
+    if io::copy(&mut reader.by_ref().take(skipped), &mut io::sink())? != skipped {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    let serialized = {
+        let len = reader.read_u64::<LittleEndian>()?;
+        let mut serialized = Vec::new();
+        reader.by_ref().take(len).read_to_end(&mut serialized)?;
+        if serialized.len() as u64 != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        serialized
+    };
+    let len = reader.read_u64::<LittleEndian>()?;
+    let _bucket_bits = reader.read_u64::<LittleEndian>()?;
+    let bucket_mask = reader.read_u64::<LittleEndian>()?;
+    let _max_length = reader.read_u64::<LittleEndian>()?;
+
+    let bucket_size = usize::try_from(bucket_mask)
+        .ok()
+        .and_then(|mask| mask.checked_add(1))
+        .ok_or_else(|| anyhow!("broken bucket parameters"))?;
+    let mut builder = Builder::new(bucket_size)?;
+    let mut key = Vec::new();
+    let mut rest = &serialized[..];
+    for id in 0..len {
+        if id & bucket_mask == 0 {
+            key.clear();
+        } else {
+            let lcp = read_vbyte(&mut rest)?;
+            if key.len() < lcp {
+                return Err(anyhow!("broken keys"));
+            }
+            key.truncate(lcp);
+        }
+        let end = rest
+            .iter()
+            .position(|&c| c == END_MARKER)
+            .ok_or_else(|| anyhow!("broken keys"))?;
+        key.extend_from_slice(&rest[..end]);
+        rest = &rest[end + 1..];
+        builder.add(&key)?;
+    }
+    if !rest.is_empty() {
+        return Err(anyhow!("broken keys"));
+    }
+    Ok(builder.finish())
+}
+
+/// Decodes a vbyte-encoded integer from the front of `bytes`, advancing it.
+fn read_vbyte(bytes: &mut &[u8]) -> Result<usize> {
+    let mut val = 0;
+    for (i, &c) in bytes.iter().enumerate() {
+        let shift = 7 * i as u32;
+        if usize::BITS <= shift {
+            break;
+        }
+        val |= usize::from(c & 0x7F) << shift;
+        if c & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Ok(val);
+        }
+    }
+    Err(anyhow!("broken keys"))
+}
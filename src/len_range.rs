@@ -0,0 +1,75 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::automaton::Automaton;
+
+/// Automaton accepting the keys whose lengths fall in a range.
+///
+/// Since the keys longer than the range are rejected at the first byte over
+/// the range, all the keys sharing that prefix are skipped at once.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+/// let set = Set::new(keys).unwrap();
+///
+/// let mut iter = set.iter_len_range(5..);
+/// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+/// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+/// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct LenRange {
+    min: usize,
+    max: usize,
+}
+
+impl LenRange {
+    /// Makes an automaton [`LenRange`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `range`: Range of key lengths.
+    pub fn new<R>(range: R) -> Self
+    where
+        R: RangeBounds<usize>,
+    {
+        let min = match range.start_bound() {
+            Bound::Included(&x) => x,
+            Bound::Excluded(&x) => x.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        // An empty range is represented by min > max.
+        let (min, max) = match range.end_bound() {
+            Bound::Included(&x) => (min, x),
+            Bound::Excluded(&0) => (1, 0),
+            Bound::Excluded(&x) => (min, x - 1),
+            Bound::Unbounded => (min, usize::MAX),
+        };
+        Self { min, max }
+    }
+}
+
+impl Automaton for LenRange {
+    /// Number of bytes read so far.
+    type State = usize;
+
+    fn start(&self) -> Self::State {
+        0
+    }
+
+    fn accept(&self, state: &Self::State, _byte: u8) -> Self::State {
+        state.saturating_add(1)
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        self.min <= *state && *state <= self.max
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        self.min <= self.max && *state <= self.max
+    }
+}
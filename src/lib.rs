@@ -9,29 +9,116 @@
 //!  - `Decode` gets the string with an index.
 //!  - `Predict` enumerates the strings starting from a prefix.
 //!
+//! ## Portability
+//!
+//! The serialized forms are independent of the target. All the integers are written in
+//! little endian with fixed widths or in vbyte, and are never reinterpreted from memory,
+//! so a dictionary serialized on x86_64 can be loaded on big-endian and 32-bit targets.
+//! Values that do not fit in `usize` of the target are rejected instead of being truncated.
+//! The serialization is deterministic, and [`Set::from_bytes_portable`] checks that
+//! given bytes are exactly what the loaded dictionary serializes into.
+//!
 //! ## References
 //!
 //!  - Martínez-Prieto et al., [Practical compressed string dictionaries](https://doi.org/10.1016/j.is.2015.08.008), INFOSYS 2016
+pub mod alphabet;
+pub mod analysis;
+pub mod arc_iter;
+pub mod automaton;
+pub mod bitmap;
+pub mod bucket;
+pub mod bucket_order;
+pub mod budget;
 pub mod builder;
+#[cfg(feature = "build-util")]
+pub mod codegen;
+pub mod compression;
 pub mod decoder;
+pub mod diff;
+pub mod escape;
+mod external_ids;
+#[cfg(feature = "csv")]
+mod external_sort;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+pub mod fuzzy;
+pub mod glob;
+pub mod hash_iter;
+pub mod id;
+pub mod interner;
 mod intvec;
 pub mod iter;
+pub mod journal;
+pub mod key_reader;
+mod legacy;
+pub mod len_range;
+mod libcsd;
 pub mod locator;
+#[cfg(feature = "merge")]
+pub mod merge;
+pub mod multi_locator;
+pub mod no_alloc;
+pub mod observer;
+pub mod order;
+mod padding;
 pub mod predictive_iter;
+#[cfg(feature = "regex-automata")]
+pub mod regex;
+#[cfg(feature = "rand")]
+pub mod sample;
+mod scratch;
+#[cfg(all(unix, feature = "shm"))]
+pub mod shm;
+pub mod stability;
+pub mod staging;
+pub mod stats;
+mod storage;
+pub mod store;
+pub mod term_dict;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 mod utils;
+pub mod values;
+#[cfg(feature = "rayon")]
+pub mod verify;
 
 use std::cmp::Ordering;
-use std::io;
+use std::io::{self, Read, Write};
+use std::ops::{ControlFlow, Range, RangeBounds};
 
 use anyhow::{anyhow, Result};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use builder::Builder;
+use alphabet::Alphabet;
+use automaton::{Automaton, AutomatonIter};
+use bitmap::Bitmap;
+use bucket::{BucketView, RawBucket};
+use bucket_order::BucketOrderIter;
+use budget::{Budget, Budgeted};
+use builder::{BuildConfig, Builder, KeyLenPolicy, Progress};
+use compression::Codec;
 use decoder::Decoder;
+use diff::Diff;
+use external_ids::ExternalIds;
+use fuzzy::FuzzyPrefix;
+use glob::Glob;
+use hash_iter::HashIter;
+use id::Id;
 use intvec::IntVector;
 use iter::Iter;
+use journal::{Journal, Record};
+use key_reader::KeyReader;
+use len_range::LenRange;
 use locator::Locator;
+use observer::QueryObserver;
+use order::KeyOrder;
+use padding::{PaddedReader, PaddedWriter};
 use predictive_iter::PredictiveIter;
+use stability::StabilityReport;
+use staging::Staging;
+use stats::Counter;
+use storage::Bytes;
+use values::{ValueCodec, Values};
 
 /// Special terminator, which must not be contained in stored keys.
 pub const END_MARKER: u8 = 0;
@@ -40,7 +127,41 @@ pub const END_MARKER: u8 = 0;
 pub const DEFAULT_BUCKET_SIZE: usize = 8;
 
 /// Serial cookie value for serialization.
-const SERIAL_COOKIE: u32 = 114514;
+const SERIAL_COOKIE: u32 = 114518;
+
+/// Header flag indicating that all the keys are valid UTF-8.
+const FLAG_UTF8: u8 = 1;
+
+/// Header flag indicating that the limit of the key length follows the flags.
+const FLAG_MAX_KEY_LEN: u8 = 2;
+
+/// Header flag indicating that the keys are sorted in [`KeyOrder::Natural`].
+const FLAG_NATURAL_ORDER: u8 = 4;
+
+/// Header flag indicating that the alphabet follows the limit of the key length,
+/// with which the suffixes are packed.
+const FLAG_ALPHABET: u8 = 8;
+
+/// Header flag indicating that the sections are padded to [`ALIGNMENT`],
+/// whose version and alignment follow the flags.
+const FLAG_ALIGNED: u8 = 16;
+
+/// Header flag indicating that the metadata is encoded in the compact layout
+/// written by [`Set::serialize_into_compact`].
+const FLAG_COMPACT: u8 = 32;
+
+/// Header flag indicating that the bucket headers start at multiples of [`CACHE_LINE`]
+/// in the serialized area, set with [`Builder::align_buckets`].
+const FLAG_BUCKET_ALIGNED: u8 = 64;
+
+/// Version of the aligned layout written by [`Set::serialize_into_aligned`].
+const ALIGNED_VERSION: u8 = 1;
+
+/// Alignment of the sections in bytes written by [`Set::serialize_into_aligned`].
+pub const ALIGNMENT: usize = 8;
+
+/// Size of cache lines in bytes to which buckets are aligned by [`Builder::align_buckets`].
+pub const CACHE_LINE: usize = 64;
 
 /// Fast and compact indexed string set using front coding.
 ///
@@ -56,6 +177,7 @@ const SERIAL_COOKIE: u32 = 114514;
 /// ## Limitations
 ///
 /// Input keys must not contain `\0` character because the character is used for the terminator.
+/// Such keys can be stored after being escaped with [`escape::Escaper`].
 ///
 /// # Example
 ///
@@ -106,11 +228,19 @@ const SERIAL_COOKIE: u32 = 114514;
 #[derive(Clone)]
 pub struct Set {
     pointers: IntVector,
-    serialized: Vec<u8>,
+    serialized: Bytes,
     len: usize,
     bucket_bits: usize,
     bucket_mask: usize,
     max_length: usize,
+    utf8: bool,
+    max_key_len: Option<usize>,
+    order: KeyOrder,
+    alphabet: Option<Alphabet>,
+    bucket_aligned: bool,
+    external_ids: Option<ExternalIds>,
+    payloads: Option<IntVector>,
+    values: Option<Values>,
 }
 
 impl Set {
@@ -164,9 +294,7 @@ impl Set {
         P: AsRef<[u8]>,
     {
         let mut builder = Builder::new(bucket_size)?;
-        for key in keys {
-            builder.add(key.as_ref())?;
-        }
+        builder.extend(keys)?;
         Ok(builder.finish())
     }
 
@@ -179,14 +307,64 @@ impl Set {
     ///
     /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
     /// let set = Set::new(keys).unwrap();
-    /// assert_eq!(set.size_in_bytes(), 110);
+    /// assert_eq!(set.size_in_bytes(), 114);
     /// ```
-    pub fn size_in_bytes(&self) -> usize {
+    pub const fn size_in_bytes(&self) -> usize {
         let mut bytes = 0;
         bytes += 4; // SERIAL_COOKIE
+        bytes += 1; // flags
+        if self.max_key_len.is_some() {
+            bytes += 8;
+        }
+        if let Some(alphabet) = &self.alphabet {
+            bytes += alphabet.size_in_bytes();
+        }
         bytes += self.pointers.size_in_bytes(); // pointers
         bytes += 8 + self.serialized.len(); // serialized
-        bytes + 8 * 4
+        bytes += 8 * 4;
+        bytes += 1; // external_ids flag
+        if let Some(external_ids) = &self.external_ids {
+            bytes += external_ids.size_in_bytes();
+        }
+        bytes += 1; // payloads flag
+        if let Some(payloads) = &self.payloads {
+            bytes += payloads.size_in_bytes();
+        }
+        bytes += 1; // values flag
+        if let Some(values) = &self.values {
+            bytes += values.size_in_bytes();
+        }
+        bytes
+    }
+
+    /// Returns the number of bytes allocated on the heap by the dictionary.
+    ///
+    /// Different from [`Set::size_in_bytes`], which describes the serialized form,
+    /// this reports the actual capacity of the in-memory buffers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert!(set.heap_bytes() >= 28);
+    /// ```
+    pub const fn heap_bytes(&self) -> usize {
+        let mut bytes = 0;
+        bytes += self.pointers.heap_bytes();
+        bytes += self.serialized.heap_bytes();
+        if let Some(external_ids) = &self.external_ids {
+            bytes += external_ids.heap_bytes();
+        }
+        if let Some(payloads) = &self.payloads {
+            bytes += payloads.heap_bytes();
+        }
+        if let Some(values) = &self.values {
+            bytes += values.heap_bytes();
+        }
+        bytes
     }
 
     /// Serializes the dictionary into a writer.
@@ -205,27 +383,187 @@ impl Set {
     ///
     /// let mut data = Vec::<u8>::new();
     /// set.serialize_into(&mut data).unwrap();
-    /// assert_eq!(data.len(), 110);
+    /// assert_eq!(data.len(), 114);
+    /// ```
+    pub fn serialize_into<W>(&self, writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        self.serialize_padded(&mut PaddedWriter::new(writer, 1))
+    }
+
+    /// Serializes the dictionary into a writer, padding every section to [`ALIGNMENT`] bytes.
+    ///
+    /// When the written data is placed at an aligned address, such as a memory-mapped file,
+    /// the pointer table and the serialized area start at aligned offsets,
+    /// so the chunks of the pointer table can be reinterpreted as `u64`s in place.
+    /// The alignment and the version of the layout are recorded in the header,
+    /// and [`Set::deserialize_from`] accepts both layouts.
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::{Set, ALIGNMENT};
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_into_aligned(&mut data).unwrap();
+    /// assert_eq!(data.len() % ALIGNMENT, 0);
+    ///
+    /// let other = Set::deserialize_from(&data[..]).unwrap();
+    /// assert_eq!(other.locator().run(b"SIGIR"), Some(2));
+    /// ```
+    pub fn serialize_into_aligned<W>(&self, writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        self.serialize_padded(&mut PaddedWriter::new(writer, ALIGNMENT))
+    }
+
+    /// Serializes the dictionary into a writer in the compact layout for small dictionaries.
+    ///
+    /// The counts and sizes in the metadata are written in vbyte,
+    /// and the fields derivable from the others, such as the bucket mask
+    /// and the number of bucket pointers, are omitted.
+    /// The bucket pointers and the offsets of values are written as vbyte-encoded differences.
+    /// The layout is recorded in the header, and [`Set::deserialize_from`] accepts it.
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_into_compact(&mut data).unwrap();
+    /// assert_eq!(data.len(), 36);
+    ///
+    /// let other = Set::deserialize_from(&data[..]).unwrap();
+    /// assert_eq!(other.locator().run(b"SIGIR"), Some(2));
     /// ```
-    pub fn serialize_into<W>(&self, mut writer: W) -> Result<()>
+    pub fn serialize_into_compact<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_u32::<LittleEndian>(SERIAL_COOKIE)?;
+        writer.write_u8(self.flags() | FLAG_COMPACT)?;
+        if let Some(max_key_len) = self.max_key_len {
+            utils::vbyte::write(&mut writer, max_key_len)?;
+        }
+        if let Some(alphabet) = &self.alphabet {
+            alphabet.serialize_into(&mut writer)?;
+        }
+        utils::vbyte::write(&mut writer, self.len)?;
+        writer.write_u8(self.bucket_bits as u8)?;
+        utils::vbyte::write(&mut writer, self.max_length)?;
+        utils::vbyte::write(&mut writer, self.serialized.len())?;
+        writer.write_all(&self.serialized)?;
+        self.pointers.serialize_deltas_into(&mut writer)?;
+        let mut sections = 0;
+        if self.external_ids.is_some() {
+            sections |= 1;
+        }
+        if self.payloads.is_some() {
+            sections |= 2;
+        }
+        if self.values.is_some() {
+            sections |= 8;
+        }
+        writer.write_u8(sections)?;
+        if let Some(external_ids) = &self.external_ids {
+            external_ids.serialize_into(&mut writer)?;
+        }
+        if let Some(payloads) = &self.payloads {
+            payloads.serialize_into(&mut writer)?;
+        }
+        if let Some(values) = &self.values {
+            values.serialize_into_compact(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    fn serialize_padded<W>(&self, writer: &mut PaddedWriter<W>) -> Result<()>
+    where
+        W: io::Write,
+    {
+        self.serialize_padded_with(writer, self.serialized.len(), |writer| {
+            writer.write_all(&self.serialized)?;
+            Ok(())
+        })
+    }
+
+    /// Serializes the dictionary like [`Set::serialize_padded`], writing the serialized area
+    /// of `serialized_len` bytes with `write_serialized` instead of `self.serialized`.
+    fn serialize_padded_with<W, F>(
+        &self,
+        writer: &mut PaddedWriter<W>,
+        serialized_len: usize,
+        write_serialized: F,
+    ) -> Result<()>
     where
         W: io::Write,
+        F: FnOnce(&mut PaddedWriter<W>) -> Result<()>,
     {
+        let aligned = ALIGNMENT <= writer.alignment();
         writer.write_u32::<LittleEndian>(SERIAL_COOKIE)?;
-        self.pointers.serialize_into(&mut writer)?;
-        writer.write_u64::<LittleEndian>(self.serialized.len() as u64)?;
-        for &x in &self.serialized {
-            writer.write_u8(x)?;
+        if aligned {
+            writer.write_u8(self.flags() | FLAG_ALIGNED)?;
+            writer.write_u8(ALIGNED_VERSION)?;
+            writer.write_u8(ALIGNMENT as u8)?;
+        } else {
+            writer.write_u8(self.flags())?;
+        }
+        writer.pad()?;
+        if let Some(max_key_len) = self.max_key_len {
+            writer.write_u64::<LittleEndian>(max_key_len as u64)?;
         }
+        if let Some(alphabet) = &self.alphabet {
+            alphabet.serialize_into(&mut *writer)?;
+            writer.pad()?;
+        }
+        self.pointers.serialize_into(&mut *writer)?;
+        writer.write_u64::<LittleEndian>(serialized_len as u64)?;
+        write_serialized(&mut *writer)?;
+        writer.pad()?;
         writer.write_u64::<LittleEndian>(self.len as u64)?;
         writer.write_u64::<LittleEndian>(self.bucket_bits as u64)?;
         writer.write_u64::<LittleEndian>(self.bucket_mask as u64)?;
         writer.write_u64::<LittleEndian>(self.max_length as u64)?;
+        writer.write_u8(u8::from(self.external_ids.is_some()))?;
+        writer.pad()?;
+        if let Some(external_ids) = &self.external_ids {
+            external_ids.serialize_into(&mut *writer)?;
+        }
+        writer.write_u8(u8::from(self.payloads.is_some()))?;
+        writer.pad()?;
+        if let Some(payloads) = &self.payloads {
+            payloads.serialize_into(&mut *writer)?;
+        }
+        writer.write_u8(u8::from(self.values.is_some()))?;
+        writer.pad()?;
+        if let Some(values) = &self.values {
+            values.serialize_into(&mut *writer)?;
+            writer.pad()?;
+        }
         Ok(())
     }
 
     /// Deserializes the dictionary from a reader.
     ///
+    /// The original layout, written before user-supplied ids were supported, is also loaded.
+    ///
     /// # Arguments
     ///
     ///  - `reader`: Readable stream.
@@ -243,150 +581,630 @@ impl Set {
     /// let other = Set::deserialize_from(&data[..]).unwrap();
     /// assert_eq!(set.size_in_bytes(), other.size_in_bytes());
     /// ```
-    pub fn deserialize_from<R>(mut reader: R) -> Result<Self>
+    pub fn deserialize_from<R>(reader: R) -> Result<Self>
+    where
+        R: io::Read,
+    {
+        Self::deserialize_with(reader, |reader, len| {
+            let mut serialized = Vec::new();
+            reader.take(len as u64).read_to_end(&mut serialized)?;
+            if serialized.len() != len {
+                return Err(anyhow!("truncated serialized area"));
+            }
+            Ok(serialized.into())
+        })
+    }
+
+    /// Deserializes the dictionary from bytes, checking that they are in the canonical form.
+    ///
+    /// The dictionary is serialized again in the layout of `bytes`, such as the aligned or compact one,
+    /// and compared with them. It ensures that the bytes have no trailing data and that
+    /// every value has been loaded without loss on this target.
+    ///
+    /// # Arguments
+    ///
+    ///  - `bytes`: Bytes written by [`Set::serialize_into`], [`Set::serialize_into_aligned`],
+    ///    or [`Set::serialize_into_compact`].
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the bytes are broken or not in the canonical form.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"]).unwrap();
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_into_compact(&mut data).unwrap();
+    /// let other = Set::from_bytes_portable(&data).unwrap();
+    /// assert_eq!(other.locator().run(b"SIGKDD"), Some(3));
+    ///
+    /// data.push(0);
+    /// assert!(Set::from_bytes_portable(&data).is_err());
+    /// ```
+    pub fn from_bytes_portable(bytes: &[u8]) -> Result<Self> {
+        let set = Self::deserialize_from(bytes)?;
+        let mut canonical = Vec::with_capacity(bytes.len());
+        let flags = bytes[4];
+        if flags & FLAG_ALIGNED != 0 {
+            set.serialize_into_aligned(&mut canonical)?;
+        } else if flags & FLAG_COMPACT != 0 {
+            set.serialize_into_compact(&mut canonical)?;
+        } else {
+            set.serialize_into(&mut canonical)?;
+        }
+        if canonical != bytes {
+            return Err(anyhow!(
+                "The bytes are not in the canonical form, such as having trailing data."
+            ));
+        }
+        Ok(set)
+    }
+
+    /// Deserializes the dictionary, loading the serialized area of `len` bytes
+    /// with `read_serialized`.
+    fn deserialize_with<R, F>(reader: R, mut read_serialized: F) -> Result<Self>
     where
         R: io::Read,
+        F: FnMut(&mut PaddedReader<R>, usize) -> Result<Bytes>,
     {
+        let mut reader = PaddedReader::new(reader);
         let cookie = reader.read_u32::<LittleEndian>()?;
+        if cookie == legacy::SERIAL_COOKIE {
+            return legacy::deserialize_from(reader);
+        }
         if cookie != SERIAL_COOKIE {
             return Err(anyhow!("unknown cookie value"));
         }
-        let pointers = IntVector::deserialize_from(&mut reader)?;
+        let flags = reader.read_u8()?;
+        if flags
+            & !(FLAG_UTF8
+                | FLAG_MAX_KEY_LEN
+                | FLAG_NATURAL_ORDER
+                | FLAG_ALPHABET
+                | FLAG_ALIGNED
+                | FLAG_COMPACT
+                | FLAG_BUCKET_ALIGNED)
+            != 0
+        {
+            return Err(anyhow!("unknown header flags"));
+        }
+        if flags & FLAG_COMPACT != 0 {
+            if flags & FLAG_ALIGNED != 0 {
+                return Err(anyhow!("unknown header flags"));
+            }
+            return Self::deserialize_compact(reader, flags, read_serialized);
+        }
+        if flags & FLAG_ALIGNED != 0 {
+            let version = reader.read_u8()?;
+            if version != ALIGNED_VERSION {
+                return Err(anyhow!("unsupported aligned layout version {}", version));
+            }
+            let alignment = reader.read_u8()? as usize;
+            if alignment != ALIGNMENT {
+                return Err(anyhow!("unsupported alignment {}", alignment));
+            }
+            reader.set_alignment(alignment);
+            reader.pad()?;
+        }
+        let max_key_len = if flags & FLAG_MAX_KEY_LEN != 0 {
+            Some(utils::read_usize(&mut reader)?)
+        } else {
+            None
+        };
+        let alphabet = if flags & FLAG_ALPHABET != 0 {
+            let alphabet = Alphabet::deserialize_from(&mut reader)?;
+            reader.pad()?;
+            Some(alphabet)
+        } else {
+            None
+        };
+        let aligned = flags & FLAG_ALIGNED != 0;
+        let pointers = Self::deserialize_ints(&mut reader, aligned, &mut read_serialized)?;
         let serialized = {
-            let len = reader.read_u64::<LittleEndian>()? as usize;
-            let mut serialized = vec![0; len];
-            for x in serialized.iter_mut() {
-                *x = reader.read_u8()?;
+            let len = utils::read_usize(&mut reader)?;
+            read_serialized(&mut reader, len)?
+        };
+        reader.pad()?;
+
+        let len = utils::read_usize(&mut reader)?;
+        let bucket_bits = utils::read_usize(&mut reader)?;
+        let bucket_mask = utils::read_usize(&mut reader)?;
+        let max_length = utils::read_usize(&mut reader)?;
+        let has_external_ids = reader.read_u8()?;
+        reader.pad()?;
+        let external_ids = match has_external_ids {
+            0 => None,
+            1 => Some(ExternalIds::deserialize_from(&mut reader)?),
+            _ => return Err(anyhow!("unknown external_ids flag")),
+        };
+        let has_payloads = reader.read_u8()?;
+        reader.pad()?;
+        let payloads = match has_payloads {
+            0 => None,
+            1 => Some(Self::deserialize_ints(
+                &mut reader,
+                aligned,
+                &mut read_serialized,
+            )?),
+            _ => return Err(anyhow!("unknown payloads flag")),
+        };
+        let has_values = reader.read_u8()?;
+        reader.pad()?;
+        let values = match has_values {
+            0 => None,
+            1 => {
+                let values = Values::deserialize_from(&mut reader)?;
+                reader.pad()?;
+                Some(values)
             }
-            serialized
+            _ => return Err(anyhow!("unknown values flag")),
+        };
+
+        let set = Self {
+            pointers,
+            serialized,
+            len,
+            bucket_bits,
+            bucket_mask,
+            max_length,
+            utf8: flags & FLAG_UTF8 != 0,
+            max_key_len,
+            order: if flags & FLAG_NATURAL_ORDER != 0 {
+                KeyOrder::Natural
+            } else {
+                KeyOrder::Lexicographic
+            },
+            alphabet,
+            bucket_aligned: flags & FLAG_BUCKET_ALIGNED != 0,
+            external_ids,
+            payloads,
+            values,
         };
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// Deserializes an integer vector, whose chunks in the aligned layout are loaded like
+    /// the serialized area, so those in shared memory are referred to in place.
+    fn deserialize_ints<R, F>(
+        reader: &mut PaddedReader<R>,
+        aligned: bool,
+        read_serialized: F,
+    ) -> Result<IntVector>
+    where
+        R: io::Read,
+        F: FnOnce(&mut PaddedReader<R>, usize) -> Result<Bytes>,
+    {
+        if aligned {
+            IntVector::deserialize_with(reader, read_serialized)
+        } else {
+            Ok(IntVector::deserialize_from(reader)?)
+        }
+    }
 
-        let len = reader.read_u64::<LittleEndian>()? as usize;
-        let bucket_bits = reader.read_u64::<LittleEndian>()? as usize;
-        let bucket_mask = reader.read_u64::<LittleEndian>()? as usize;
-        let max_length = reader.read_u64::<LittleEndian>()? as usize;
+    /// Deserializes the rest of the compact layout following the header flags.
+    fn deserialize_compact<R, F>(
+        mut reader: PaddedReader<R>,
+        flags: u8,
+        mut read_serialized: F,
+    ) -> Result<Self>
+    where
+        R: io::Read,
+        F: FnMut(&mut PaddedReader<R>, usize) -> Result<Bytes>,
+    {
+        let max_key_len = if flags & FLAG_MAX_KEY_LEN != 0 {
+            Some(utils::vbyte::read(&mut reader)?)
+        } else {
+            None
+        };
+        let alphabet = if flags & FLAG_ALPHABET != 0 {
+            Some(Alphabet::deserialize_from(&mut reader)?)
+        } else {
+            None
+        };
+        let len = utils::vbyte::read(&mut reader)?;
+        let bucket_bits = reader.read_u8()? as usize;
+        if 64 <= bucket_bits {
+            return Err(anyhow!("broken bucket parameters"));
+        }
+        let bucket_mask = (1 << bucket_bits) - 1;
+        let max_length = utils::vbyte::read(&mut reader)?;
+        let serialized = {
+            let len = utils::vbyte::read(&mut reader)?;
+            read_serialized(&mut reader, len)?
+        };
+        let num_buckets = len.div_ceil(bucket_mask + 1);
+        let pointers = IntVector::deserialize_deltas_from(&mut reader, num_buckets)?;
+        // Values are written in the standard form with 4 before the compact one with 8.
+        let sections = reader.read_u8()?;
+        if sections & !15 != 0 || sections & 12 == 12 {
+            return Err(anyhow!("unknown section flags"));
+        }
+        let external_ids = if sections & 1 != 0 {
+            Some(ExternalIds::deserialize_from(&mut reader)?)
+        } else {
+            None
+        };
+        let payloads = if sections & 2 != 0 {
+            Some(IntVector::deserialize_from(&mut reader)?)
+        } else {
+            None
+        };
+        let values = if sections & 4 != 0 {
+            Some(Values::deserialize_from(&mut reader)?)
+        } else if sections & 8 != 0 {
+            Some(Values::deserialize_compact_from(&mut reader)?)
+        } else {
+            None
+        };
 
-        Ok(Self {
+        let set = Self {
             pointers,
             serialized,
             len,
             bucket_bits,
             bucket_mask,
             max_length,
-        })
+            utf8: flags & FLAG_UTF8 != 0,
+            max_key_len,
+            order: if flags & FLAG_NATURAL_ORDER != 0 {
+                KeyOrder::Natural
+            } else {
+                KeyOrder::Lexicographic
+            },
+            alphabet,
+            bucket_aligned: flags & FLAG_BUCKET_ALIGNED != 0,
+            external_ids,
+            payloads,
+            values,
+        };
+        set.validate()?;
+        Ok(set)
     }
 
-    /// Makes a class to get ids of given string keys.
+    /// Places the dictionary into a new POSIX shared-memory segment,
+    /// which other processes open with [`Set::open_shm`].
+    ///
+    /// The segment is written in the layout of [`Set::serialize_into_aligned`],
+    /// and it remains until [`shm::remove`] is called or the host reboots.
+    ///
+    /// # Arguments
+    ///
+    ///  - `name`: Name of the segment, such as `/vocab`.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the segment already exists or cannot be created.
     ///
     /// # Example
     ///
     /// ```
-    /// use fcsd::Set;
+    /// use fcsd::{shm, Set};
     ///
-    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
-    /// let set = Set::new(keys).unwrap();
+    /// let name = format!("/fcsd-doc-{}", std::process::id());
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// set.create_shm(&name).unwrap();
     ///
-    /// let mut locator = set.locator();
-    /// assert_eq!(locator.run(b"ICML"), Some(1));
-    /// assert_eq!(locator.run(b"SIGMOD"), Some(4));
-    /// assert_eq!(locator.run(b"SIGSPATIAL"), None);
+    /// // In other processes.
+    /// let other = Set::open_shm(&name).unwrap();
+    /// assert_eq!(other.locator().run(b"ICML"), Some(1));
+    ///
+    /// shm::remove(&name).unwrap();
     /// ```
-    pub fn locator(&self) -> Locator {
-        Locator::new(self)
+    #[cfg(all(unix, feature = "shm"))]
+    pub fn create_shm(&self, name: &str) -> Result<()> {
+        let mut counter = PaddedWriter::new(io::sink(), ALIGNMENT);
+        self.serialize_padded(&mut counter)?;
+        let mut segment = shm::Segment::create(name, counter.position())?;
+        let written =
+            self.serialize_padded(&mut PaddedWriter::new(segment.as_mut_slice(), ALIGNMENT));
+        if written.is_err() {
+            shm::remove(name)?;
+        }
+        written
     }
 
-    /// Makes a class to decode stored keys associated with given ids.
+    /// Opens the dictionary placed by [`Set::create_shm`] from a POSIX shared-memory segment.
     ///
-    /// # Example
+    /// The segment is mapped read-only, and the serialized area is referred to in place
+    /// instead of being copied, so the processes opening the same segment
+    /// share one physical copy of the keys.
+    /// On little-endian targets, the chunks of the bucket pointers are also referred to in place.
+    /// Modifying the dictionary, such as with [`Set::append_greater`], copies the areas.
     ///
-    /// ```
-    /// use fcsd::Set;
+    /// Only the header and the bucket pointers are checked, and the keys are read on demand
+    /// by queries, so opening does not touch the pages of the serialized area.
+    /// Call [`Set::verify`] to check the keys of a segment from an untrusted process.
     ///
-    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
-    /// let set = Set::new(keys).unwrap();
+    /// # Arguments
     ///
-    /// let mut decoder = set.decoder();
-    /// assert_eq!(decoder.run(0), b"ICDM".to_vec());
-    /// assert_eq!(decoder.run(3), b"SIGKDD".to_vec());
-    /// ```
-    pub fn decoder(&self) -> Decoder {
-        Decoder::new(self)
+    ///  - `name`: Name of the segment, such as `/vocab`.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the segment does not exist or is broken.
+    #[cfg(all(unix, feature = "shm"))]
+    pub fn open_shm(name: &str) -> Result<Self> {
+        let segment = std::sync::Arc::new(shm::Segment::open(name)?);
+        Self::deserialize_with(segment.as_slice(), |reader, len| {
+            let start = reader.position();
+            reader.skip(len)?;
+            Ok(Bytes::shared(segment.clone(), start..start + len))
+        })
     }
 
-    /// Makes an iterator to enumerate keys stored in the dictionary.
+    /// Deserializes the dictionary from static bytes, such as those embedded with `include_bytes!`.
     ///
-    /// The keys will be reported in the lexicographical order.
+    /// The serialized area is referred to in place instead of being copied.
+    /// When the bytes are written by [`Set::serialize_into_aligned`] and placed at an address
+    /// aligned to [`ALIGNMENT`], the chunks of the bucket pointers on little-endian targets are also
+    /// referred to in place. Modifying the dictionary, such as with [`Set::append_greater`], copies the areas.
+    ///
+    /// # Arguments
+    ///
+    ///  - `bytes`: Bytes written by [`Set::serialize_into`], [`Set::serialize_into_aligned`],
+    ///    or [`Set::serialize_into_compact`].
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the bytes are broken.
     ///
     /// # Example
     ///
     /// ```
     /// use fcsd::Set;
     ///
-    /// let keys = ["ICDM", "ICML", "SIGIR"];
-    /// let set = Set::new(keys).unwrap();
+    /// static DATA: &[u8] = &[];
+    /// assert!(Set::from_static_bytes(DATA).is_err());
     ///
-    /// let mut iter = set.iter();
-    /// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
-    /// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
-    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
-    /// assert_eq!(iter.next(), None);
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_into(&mut data).unwrap();
+    /// let other = Set::from_static_bytes(Box::leak(data.into_boxed_slice())).unwrap();
+    /// assert_eq!(other.locator().run(b"ICML"), Some(1));
     /// ```
-    pub fn iter(&self) -> Iter {
-        Iter::new(self)
+    pub fn from_static_bytes(bytes: &'static [u8]) -> Result<Self> {
+        Self::deserialize_with(bytes, |reader, len| {
+            let start = reader.position();
+            reader.skip(len)?;
+            Ok(Bytes::from_static(&bytes[start..start + len]))
+        })
     }
 
-    /// Makes a predictive iterator to enumerate keys starting from a given string.
+    /// Serializes the dictionary into a writer, compressing it with a codec.
     ///
-    /// The keys will be reported in the lexicographical order.
+    /// The codec is recorded in the header, so [`Set::deserialize_from_compressed`]
+    /// needs no other information to load it.
     ///
     /// # Arguments
     ///
-    ///  - `prefix`: Prefix of keys to be predicted.
+    ///  - `writer`: Writable stream.
+    ///  - `codec`: Codec of compression.
     ///
     /// # Example
     ///
     /// ```
+    /// use fcsd::compression::Codec;
     /// use fcsd::Set;
     ///
     /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
     /// let set = Set::new(keys).unwrap();
     ///
-    /// let mut iter = set.predictive_iter(b"SIG");
-    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
-    /// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
-    /// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
-    /// assert_eq!(iter.next(), None);
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_into_compressed(&mut data, Codec::None).unwrap();
+    /// let other = Set::deserialize_from_compressed(&data[..]).unwrap();
+    /// assert_eq!(other.locator().run(b"SIGIR"), Some(2));
     /// ```
-    pub fn predictive_iter<P>(&self, prefix: P) -> PredictiveIter
+    pub fn serialize_into_compressed<W>(&self, writer: W, codec: Codec) -> Result<()>
     where
-        P: AsRef<[u8]>,
+        W: io::Write,
     {
-        PredictiveIter::new(self, prefix)
+        compression::write_compressed(writer, codec, |w| self.serialize_into(w))
     }
 
-    /// Gets the number of stored keys.
+    /// Deserializes the dictionary written by [`Set::serialize_into_compressed`] from a reader.
     ///
-    /// # Example
+    /// The ordinary form written by [`Set::serialize_into`] is also accepted.
     ///
-    /// ```
-    /// use fcsd::Set;
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the data is broken or
+    /// compressed with a codec whose feature is disabled.
+    pub fn deserialize_from_compressed<R>(reader: R) -> Result<Self>
+    where
+        R: io::Read,
+    {
+        Self::deserialize_from(compression::read_compressed(reader)?)
+    }
+
+    /// Appends the part of the dictionary changed since the last record to a journal.
+    ///
+    /// The record holds only the buckets appended after the position in `journal`,
+    /// such as by [`Set::append_greater`], and a footer with the latest metadata.
+    /// The first record for [`Journal::new`] holds the whole dictionary.
+    /// The dictionary is restored from the concatenated records with [`Set::deserialize_journal_from`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream appending to the journal.
+    ///  - `journal`: Position written so far, which is advanced.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - the dictionary has external ids, payloads, or values, or
+    ///  - the dictionary is smaller than the position in `journal`.
+    ///
+    /// # Example
     ///
-    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
-    /// let set = Set::new(keys).unwrap();
-    /// assert_eq!(set.len(), keys.len());
     /// ```
-    #[inline(always)]
-    pub const fn len(&self) -> usize {
-        self.len
+    /// use fcsd::journal::Journal;
+    /// use fcsd::Set;
+    ///
+    /// let mut set = Set::with_bucket_size(["ICDM", "ICML", "SIGIR"], 2).unwrap();
+    /// let mut journal = Journal::new();
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_dirty_into(&mut data, &mut journal).unwrap();
+    ///
+    /// let len = data.len();
+    /// set.append_greater(["SIGKDD", "SIGMOD"]).unwrap();
+    /// set.serialize_dirty_into(&mut data, &mut journal).unwrap();
+    /// assert!(data.len() - len < len);
+    ///
+    /// let (other, _) = Set::deserialize_journal_from(&data[..]).unwrap();
+    /// assert_eq!(other.len(), 5);
+    /// assert_eq!(other.locator().run(b"SIGMOD"), Some(4));
+    /// ```
+    pub fn serialize_dirty_into<W>(&self, writer: W, journal: &mut Journal) -> Result<()>
+    where
+        W: io::Write,
+    {
+        if self.has_external_ids() || self.payloads.is_some() || self.values.is_some() {
+            return Err(anyhow!(
+                "Dictionaries with external ids, payloads, or values cannot be journaled."
+            ));
+        }
+        if self.num_buckets() < journal.num_buckets || self.serialized.len() < journal.num_bytes {
+            return Err(anyhow!(
+                "The dictionary is smaller than the position in the journal."
+            ));
+        }
+        let record = Record {
+            flags: self.flags(),
+            max_key_len: self.max_key_len,
+            alphabet: self.alphabet.clone(),
+            bucket_bits: self.bucket_bits,
+            first_bucket: journal.num_buckets,
+            pointers: (journal.num_buckets..self.num_buckets())
+                .map(|bi| self.pointers.get(bi))
+                .collect(),
+            first_byte: journal.num_bytes,
+            bytes: self.serialized[journal.num_bytes..].to_vec(),
+            len: self.len,
+            max_length: self.max_length,
+        };
+        record.write_into(writer)?;
+        journal.num_buckets = self.num_buckets();
+        journal.num_bytes = self.serialized.len();
+        Ok(())
     }
 
-    /// Checks if the set is empty.
-    #[inline(always)]
-    pub const fn is_empty(&self) -> bool {
-        self.len == 0
+    /// Deserializes the dictionary from a journal written by [`Set::serialize_dirty_into`].
+    ///
+    /// The records are applied in order, and the replay stops at the first record
+    /// torn or corrupted, such as one left by a crash while writing, which is ignored
+    /// along with the following ones.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream of the journal.
+    ///
+    /// # Returns
+    ///
+    /// The dictionary and the position of the journal to continue appending records.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the journal has no complete record,
+    /// the records are inconsistent, or the data is broken.
+    pub fn deserialize_journal_from<R>(mut reader: R) -> Result<(Self, Journal)>
+    where
+        R: io::Read,
+    {
+        let mut last = None;
+        let mut pointers = IntVector::new();
+        let mut serialized = Vec::new();
+        while let Some(record) = Record::read_from(&mut reader, last.is_none())? {
+            if record.first_bucket != pointers.len()
+                || record.first_byte != serialized.len()
+                || last
+                    .as_ref()
+                    .is_some_and(|last: &Record| last.bucket_bits != record.bucket_bits)
+            {
+                return Err(anyhow!("inconsistent journal records"));
+            }
+            for &ptr in &record.pointers {
+                pointers.push(ptr);
+            }
+            serialized.extend_from_slice(&record.bytes);
+            last = Some(record);
+        }
+        let last = last.ok_or_else(|| anyhow!("no complete journal record"))?;
+        if last.flags
+            & !(FLAG_UTF8
+                | FLAG_MAX_KEY_LEN
+                | FLAG_NATURAL_ORDER
+                | FLAG_ALPHABET
+                | FLAG_BUCKET_ALIGNED)
+            != 0
+            || 64 <= last.bucket_bits
+        {
+            return Err(anyhow!("broken journal footer"));
+        }
+        let journal = Journal {
+            num_buckets: pointers.len(),
+            num_bytes: serialized.len(),
+        };
+        let set = Self {
+            pointers,
+            serialized: serialized.into(),
+            len: last.len,
+            bucket_bits: last.bucket_bits,
+            bucket_mask: (1 << last.bucket_bits) - 1,
+            max_length: last.max_length,
+            utf8: last.flags & FLAG_UTF8 != 0,
+            max_key_len: last.max_key_len,
+            order: if last.flags & FLAG_NATURAL_ORDER != 0 {
+                KeyOrder::Natural
+            } else {
+                KeyOrder::Lexicographic
+            },
+            alphabet: last.alphabet,
+            bucket_aligned: last.flags & FLAG_BUCKET_ALIGNED != 0,
+            external_ids: None,
+            payloads: None,
+            values: None,
+        };
+        set.validate()?;
+        Ok((set, journal))
     }
 
-    /// Gets the number of defined buckets.
+    /// Exports the dictionary in the binary format of `StringDictionaryPFC` of libCSD,
+    /// the reference C++ library of front-coding dictionaries.
+    ///
+    /// All integers are little-endian, and the layout is
+    ///
+    ///  1. the type tag `2` in `u32`, the number of keys in `u64`,
+    ///     the maximum key length in `u32`, the bucket size in `u32`,
+    ///     the number of buckets in `u64`, and the number of bytes of the text in `u64`,
+    ///  2. the text, in which each bucket header is stored as it is and the other keys as
+    ///     the LCPs in VByte flagging the last byte followed by the suffixes,
+    ///     every key terminated with [`END_MARKER`], and
+    ///  3. the offsets of the buckets in the text followed by the text length,
+    ///     written as a `LogSequence` of the number of entries in `u64`,
+    ///     the bit width in `u32`, and the words in `u64`.
+    ///
+    /// Note that libCSD numbers the keys from 1.
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - the dictionary has external ids, payloads, or values, which libCSD cannot store,
+    ///  - the keys are not in [`KeyOrder::Lexicographic`], or
+    ///  - an I/O error occurs.
     ///
     /// # Example
     ///
@@ -395,14 +1213,253 @@ impl Set {
     ///
     /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
     /// let set = Set::with_bucket_size(keys, 4).unwrap();
-    /// assert_eq!(set.num_buckets(), 2);
+    ///
+    /// let mut data = Vec::<u8>::new();
+    /// set.export_libcsd(&mut data).unwrap();
+    /// assert_eq!(&data[36..48], b"ICDM\0\x82ML\0\x80SI");
     /// ```
-    #[inline(always)]
-    pub const fn num_buckets(&self) -> usize {
-        self.pointers.len()
+    pub fn export_libcsd<W>(&self, writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        if self.has_external_ids() || self.payloads.is_some() || self.values.is_some() {
+            return Err(anyhow!(
+                "Dictionaries with external ids, payloads, or values cannot be exported to libCSD."
+            ));
+        }
+        if !matches!(self.order, KeyOrder::Lexicographic) {
+            return Err(anyhow!(
+                "Only dictionaries in the lexicographical order can be exported to libCSD."
+            ));
+        }
+        libcsd::write_pfc(self, writer)?;
+        Ok(())
     }
 
-    /// Gets the bucket size.
+    /// Returns the header flags.
+    const fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.utf8 {
+            flags |= FLAG_UTF8;
+        }
+        if self.max_key_len.is_some() {
+            flags |= FLAG_MAX_KEY_LEN;
+        }
+        if matches!(self.order, KeyOrder::Natural) {
+            flags |= FLAG_NATURAL_ORDER;
+        }
+        if self.alphabet.is_some() {
+            flags |= FLAG_ALPHABET;
+        }
+        if self.bucket_aligned {
+            flags |= FLAG_BUCKET_ALIGNED;
+        }
+        flags
+    }
+
+    /// Checks if the keys starting with the prefix are contiguous in the order of keys,
+    /// which fails only for prefixes ending with a digit in [`KeyOrder::Natural`].
+    fn is_prefix_contiguous(&self, prefix: &[u8]) -> bool {
+        self.order == KeyOrder::Lexicographic || !prefix.last().is_some_and(u8::is_ascii_digit)
+    }
+
+    /// Checks the consistency of the header and the bucket pointers of the deserialized data,
+    /// in time independent of the total length of the keys.
+    ///
+    /// The keys are checked only by [`Set::verify`].
+    fn validate(&self) -> Result<()> {
+        if 64 <= self.bucket_bits || self.bucket_mask != (1 << self.bucket_bits) - 1 {
+            return Err(anyhow!("broken bucket parameters"));
+        }
+        let num_buckets = self
+            .len
+            .checked_add(self.bucket_mask)
+            .map(|n| n >> self.bucket_bits);
+        if num_buckets != Some(self.num_buckets()) {
+            return Err(anyhow!("broken number of buckets"));
+        }
+        // Every key takes at least one byte of the serialized area.
+        if self.serialized.len() < self.len {
+            return Err(anyhow!("broken number of keys"));
+        }
+        // Packed suffixes have no terminators, and they are checked by Set::verify.
+        if self.alphabet.is_none()
+            && self
+                .serialized
+                .last()
+                .map_or(0 < self.len, |&c| c != END_MARKER)
+        {
+            return Err(anyhow!("broken terminator of the serialized area"));
+        }
+        if self.max_key_len.is_some_and(|n| n < self.max_length) {
+            return Err(anyhow!("broken limit of the key length"));
+        }
+        // Every byte of a key takes at least one bit of the serialized area, even when packed,
+        // and buffers of the maximum length are allocated in advance.
+        if self.serialized.len().saturating_mul(8) < self.max_length {
+            return Err(anyhow!("broken maximum length of the keys"));
+        }
+        let mut prev = None;
+        let mut buf = [0; intvec::RANGE_BLOCK_LEN];
+        for start in (0..self.num_buckets()).step_by(buf.len()) {
+            let len = std::cmp::min(buf.len(), self.num_buckets() - start);
+            self.pointers.get_range(start, len, &mut buf);
+            for &ptr in &buf[..len] {
+                let ptr = ptr as usize;
+                if self.serialized.len() <= ptr
+                    || prev.map_or(ptr != 0, |p| ptr <= p)
+                    || (self.bucket_aligned && !ptr.is_multiple_of(CACHE_LINE))
+                {
+                    return Err(anyhow!("broken bucket pointers"));
+                }
+                prev = Some(ptr);
+            }
+        }
+        if let Some(external_ids) = &self.external_ids {
+            if external_ids.len() != self.len {
+                return Err(anyhow!("broken external ids"));
+            }
+        }
+        if let Some(payloads) = &self.payloads {
+            if payloads.len() != self.len {
+                return Err(anyhow!("broken payloads"));
+            }
+        }
+        if let Some(values) = &self.values {
+            if values.len() != self.len {
+                return Err(anyhow!("broken values"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that all the keys are decoded from the serialized area
+    /// and consistent with the header, such as being valid UTF-8 for [`Set::is_utf8`].
+    ///
+    /// Deserialization checks only the header and the bucket pointers, so that loading,
+    /// especially from shared memory with [`Set::open_shm`], never reads the keys.
+    /// Broken keys are not detected until this method is called. Queries on them never panic,
+    /// but their results are meaningless, so it should be used for dictionaries
+    /// from untrusted sources before querying them.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - a key cannot be decoded or the serialized area has trailing bytes,
+    ///  - a key is not valid UTF-8 in a dictionary flagged as UTF-8,
+    ///  - a key is longer than [`Set::max_length`], or
+    ///  - the keys are not strictly increasing in [`Set::key_order`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"]).unwrap();
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_into(&mut data).unwrap();
+    ///
+    /// // Swaps "ICDM" into "ICXM", which is greater than the next key "ICML".
+    /// let pos = data.windows(4).position(|w| w == b"ICDM").unwrap();
+    /// data[pos + 2] = b'X';
+    /// let other = Set::deserialize_from(&data[..]).unwrap();
+    /// assert!(other.verify().is_err());
+    /// assert!(set.verify().is_ok());
+    /// ```
+    pub fn verify(&self) -> Result<()> {
+        let mut prev: Option<Vec<u8>> = None;
+        let mut iter = self.iter();
+        while iter.try_advance()?.is_some() {
+            let key = iter.key();
+            if self.utf8 && std::str::from_utf8(key).is_err() {
+                return Err(anyhow!(
+                    "invalid UTF-8 key in a dictionary flagged as UTF-8"
+                ));
+            }
+            if self.max_length < key.len() {
+                return Err(anyhow!("broken maximum length of keys"));
+            }
+            if let Some(prev) = &mut prev {
+                if self.order.compare(prev, key).is_ge() {
+                    return Err(anyhow!("keys are not sorted"));
+                }
+                prev.clear();
+                prev.extend_from_slice(key);
+            } else {
+                prev = Some(key.to_vec());
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends keys greater than all the stored ones to the dictionary.
+    ///
+    /// The serialized area and the pointer table are extended in place without rebuilding,
+    /// so the ids of the stored keys are kept.
+    /// When a key cannot be added, the keys before it remain appended.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: String keys to be appended in the lexicographical order.
+    ///
+    /// # Returns
+    ///
+    /// The number of keys appended.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - a key is no more than the last one,
+    ///  - a key contains [`END_MARKER`], or
+    ///  - the dictionary has external ids, payloads, or values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let mut set = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// assert_eq!(set.append_greater(["SIGKDD", "SIGMOD"]).unwrap(), 2);
+    /// assert!(set.append_greater(["KDD"]).is_err());
+    ///
+    /// assert_eq!(set.len(), 5);
+    /// assert_eq!(set.locator().run(b"SIGKDD"), Some(3));
+    /// ```
+    pub fn append_greater<I, P>(&mut self, keys: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        if self.has_external_ids() || self.payloads.is_some() || self.values.is_some() {
+            return Err(anyhow!(
+                "Keys cannot be appended to a dictionary with external ids, payloads, or values."
+            ));
+        }
+        let empty = Self {
+            pointers: IntVector::new(),
+            serialized: Bytes::default(),
+            len: 0,
+            bucket_bits: self.bucket_bits,
+            bucket_mask: self.bucket_mask,
+            max_length: 0,
+            utf8: self.utf8,
+            max_key_len: self.max_key_len,
+            order: self.order,
+            alphabet: None,
+            bucket_aligned: false,
+            external_ids: None,
+            payloads: None,
+            values: None,
+        };
+        let mut builder = Builder::resume(std::mem::replace(self, empty));
+        let result = builder.extend(keys);
+        *self = builder.finish();
+        result
+    }
+
+    /// Makes a class to get ids of given string keys.
     ///
     /// # Example
     ///
@@ -410,222 +1467,5278 @@ impl Set {
     /// use fcsd::Set;
     ///
     /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
-    /// let set = Set::with_bucket_size(keys, 4).unwrap();
-    /// assert_eq!(set.bucket_size(), 4);
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.run(b"ICML"), Some(1));
+    /// assert_eq!(locator.run(b"SIGMOD"), Some(4));
+    /// assert_eq!(locator.run(b"SIGSPATIAL"), None);
     /// ```
-    #[inline(always)]
-    pub const fn bucket_size(&self) -> usize {
-        self.bucket_mask + 1
+    pub const fn locator(&self) -> Locator<'_> {
+        Locator::new(self)
+    }
+
+    /// Makes a class to get ids of given string keys, reporting each query to an observer.
+    ///
+    /// The observer receives the bucket, the number of header probes, and the result of each query,
+    /// which can be used to learn hot prefixes and pre-warm caches.
+    /// See [`observer`] for an example.
+    ///
+    /// # Arguments
+    ///
+    ///  - `observer`: Observer called for each query.
+    pub const fn locator_with_observer<O>(&self, observer: O) -> Locator<'_, O>
+    where
+        O: QueryObserver,
+    {
+        Locator::with_observer(self, observer)
+    }
+
+    /// Makes a staging buffer recording the keys not stored in the dictionary,
+    /// which can be merged into a new dictionary later.
+    /// See [`staging`] for an example.
+    pub fn staging(&self) -> Staging<'_> {
+        Staging::new(self)
+    }
+
+    /// Returns the id of the given key.
+    ///
+    /// Different from [`Locator::run`], it needs no mutable state,
+    /// so it can be called from many threads sharing the dictionary.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// std::thread::scope(|s| {
+    ///     s.spawn(|| assert_eq!(set.locate(b"ICML"), Some(1)));
+    ///     s.spawn(|| assert_eq!(set.locate(b"SIGSPATIAL"), None));
+    /// });
+    /// ```
+    pub fn locate<P>(&self, key: P) -> Option<usize>
+    where
+        P: AsRef<[u8]>,
+    {
+        self.locator().run(key)
+    }
+
+    /// Returns the string key associated with the given id.
+    ///
+    /// Different from [`Decoder::run`], it needs no mutable state,
+    /// so it can be called from many threads sharing the dictionary.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.decode(3), b"SIGKDD".to_vec());
+    /// ```
+    pub fn decode(&self, id: usize) -> Vec<u8> {
+        let mut key = Vec::with_capacity(self.max_length());
+        self.decode_into(id, &mut key);
+        key
+    }
+
+    /// Writes the string key associated with the given id into a buffer, replacing its content.
+    ///
+    /// It does not allocate memory if the buffer has enough capacity.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///  - `key`: Buffer to store the key.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut key = Vec::new();
+    /// set.decode_into(4, &mut key);
+    /// assert_eq!(key, b"SIGMOD");
+    /// ```
+    pub fn decode_into(&self, id: usize, key: &mut Vec<u8>) {
+        assert!(id < self.len());
+        let (bi, bj) = (self.bucket_id(id), self.pos_in_bucket(id));
+        let mut pos = self.decode_header(bi, key);
+        for _ in 0..bj {
+            let (lcp, next_pos) = self.decode_lcp(pos);
+            key.truncate(lcp);
+            pos = self.decode_next(next_pos, key);
+        }
+    }
+
+    /// Returns the length of the key associated with the given id.
+    ///
+    /// The length is computed from the LCPs and the suffix lengths in the bucket
+    /// without building the key.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.key_len(2), 5);
+    /// assert_eq!(set.key_len(3), 6);
+    /// ```
+    pub fn key_len(&self, id: usize) -> usize {
+        assert!(id < self.len());
+        let (bi, bj) = (self.bucket_id(id), self.pos_in_bucket(id));
+        let (mut len, mut pos) = self.skip_next(self.pointers.get(bi) as usize);
+        for _ in 0..bj {
+            let (lcp, next_pos) = self.decode_lcp(pos);
+            let (suffix_len, next_pos) = self.skip_next(next_pos);
+            len = lcp + suffix_len;
+            pos = next_pos;
+        }
+        len
     }
 
-    #[inline(always)]
-    const fn max_length(&self) -> usize {
-        self.max_length
+    /// Compares the key associated with the given id with a query in the order of keys.
+    ///
+    /// The stored keys in the bucket are compared in place, tracking the LCP with the query,
+    /// so the key is neither allocated nor decoded.
+    /// It is a primitive to build custom search strategies on the dictionary.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id.
+    ///  - `query`: String key to be compared.
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.cmp_key(3, b"SIGKDD"), Ordering::Equal);
+    /// assert_eq!(set.cmp_key(3, b"SIGIR"), Ordering::Greater);
+    /// assert_eq!(set.cmp_key(1, b"ICMLA"), Ordering::Less);
+    ///
+    /// // Galloping search for the first key no less than a query.
+    /// let mut hi = 1;
+    /// while hi < set.len() && set.cmp_key(hi, b"SIGK").is_lt() {
+    ///     hi *= 2;
+    /// }
+    /// let lo = hi / 2;
+    /// let hi = hi.min(set.len());
+    /// let first = lo + (lo..hi).take_while(|&i| set.cmp_key(i, b"SIGK").is_lt()).count();
+    /// assert_eq!(first, 3);
+    /// ```
+    pub fn cmp_key<P>(&self, id: usize, query: P) -> Ordering
+    where
+        P: AsRef<[u8]>,
+    {
+        let query = query.as_ref();
+        assert!(id < self.len());
+        if self.order != KeyOrder::Lexicographic || self.alphabet.is_some() {
+            return scratch::with_buffer(|key| {
+                self.decode_into(id, key);
+                self.order.compare(key, query)
+            });
+        }
+
+        let (bi, bj) = (self.bucket_id(id), self.pos_in_bucket(id));
+        let header = self.get_header(bi);
+        let (mut lcp, mut cmp) = utils::get_lcp(query, header);
+        let mut pos = self.pointers.get(bi) as usize + header.len() + 1;
+        for _ in 0..bj {
+            let (dec_lcp, next_pos) = self.decode_lcp(pos);
+            let suffix = &self.serialized[next_pos..];
+            let suffix_len = utils::get_strlen(suffix);
+            pos = next_pos + suffix_len + 1;
+
+            match dec_lcp.cmp(&lcp) {
+                // The key shares the mismatching byte with the previous one.
+                Ordering::Greater => {}
+                // The key is greater than the previous one at the byte shared with the query.
+                Ordering::Less => {
+                    lcp = dec_lcp;
+                    cmp = 1;
+                }
+                Ordering::Equal => {
+                    let (next_lcp, next_cmp) = utils::get_lcp(&query[lcp..], &suffix[..suffix_len]);
+                    lcp += next_lcp;
+                    cmp = next_cmp;
+                }
+            }
+        }
+        cmp.cmp(&0)
+    }
+
+    /// Makes a class to decode stored keys associated with given ids.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut decoder = set.decoder();
+    /// assert_eq!(decoder.run(0), b"ICDM".to_vec());
+    /// assert_eq!(decoder.run(3), b"SIGKDD".to_vec());
+    /// ```
+    pub fn decoder(&self) -> Decoder<'_> {
+        Decoder::new(self)
+    }
+
+    /// Makes an iterator to enumerate keys stored in the dictionary.
+    ///
+    /// The keys will be reported in the lexicographical order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.iter();
+    /// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
+    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(self)
+    }
+
+    /// Makes a reader streaming all the keys in the order of ids, each followed by a delimiter.
+    ///
+    /// The keys are decoded on demand, so the dictionary can be piped into compressors or
+    /// uploaders without an intermediate dump file.
+    ///
+    /// # Arguments
+    ///
+    ///  - `delimiter`: Byte written after each key, such as `b'\n'`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut text = String::new();
+    /// set.reader(b'\n').read_to_string(&mut text).unwrap();
+    /// assert_eq!(text, "ICDM\nICML\nSIGIR\n");
+    /// ```
+    pub fn reader(&self, delimiter: u8) -> KeyReader<'_> {
+        KeyReader::new(self, delimiter)
+    }
+
+    /// Makes an iterator to enumerate ids and 64-bit fingerprints of all the keys.
+    ///
+    /// The fingerprints are computed during the streaming decode without cloning keys,
+    /// which is useful to join the dictionary against datasets hashed with the same hasher.
+    ///
+    /// # Arguments
+    ///
+    ///  - `hasher`: Builder of hashers, with which the fingerprint of `key` is `hasher.hash_one(key)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::hash_map::RandomState;
+    /// use std::hash::BuildHasher;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let hasher = RandomState::new();
+    /// let fingerprint = hasher.hash_one(b"SIGIR".as_slice());
+    /// let found = set.hash_iter(hasher).find(|&(_, h)| h == fingerprint);
+    /// assert_eq!(found.map(|(id, _)| id), Some(2));
+    /// ```
+    pub fn hash_iter<S>(&self, hasher: S) -> HashIter<'_, S>
+    where
+        S: std::hash::BuildHasher,
+    {
+        HashIter::new(self, hasher)
+    }
+
+    /// Makes an iterator to decode given ids in the order of storage.
+    ///
+    /// The ids are grouped by bucket, and each bucket is decoded at most once,
+    /// which is cache-friendly for joins decoding many ids at a time.
+    ///
+    /// # Arguments
+    ///
+    ///  - `ids`: Ids to be decoded, which can be in any order and duplicated.
+    ///
+    /// # Panics
+    ///
+    /// If an id is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 2).unwrap();
+    ///
+    /// let mut iter = set.iter_bucket_order(&[4, 0, 3]);
+    /// assert_eq!(iter.next(), Some((1, 0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.next(), Some((2, 3, b"SIGKDD".to_vec())));
+    /// assert_eq!(iter.next(), Some((0, 4, b"SIGMOD".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter_bucket_order(&self, ids: &[usize]) -> BucketOrderIter<'_> {
+        BucketOrderIter::new(self, ids)
+    }
+
+    /// Makes an iterator to enumerate keys accepted by a given automaton.
+    ///
+    /// The keys will be reported in the lexicographical order.
+    /// All the keys under a prefix are skipped once the automaton cannot match from the prefix.
+    ///
+    /// # Arguments
+    ///
+    ///  - `aut`: Automaton to be run.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    /// use fcsd::fuzzy::FuzzyPrefix;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.automaton_iter(FuzzyPrefix::new(b"IDM", 1));
+    /// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn automaton_iter<A>(&self, aut: A) -> AutomatonIter<'_, A>
+    where
+        A: Automaton,
+    {
+        AutomatonIter::new(self, aut)
+    }
+
+    /// Makes an iterator to enumerate keys having a prefix within a given edit distance of a query.
+    ///
+    /// The keys will be reported in the lexicographical order.
+    ///
+    /// # Arguments
+    ///
+    ///  - `query`: Query prefix.
+    ///  - `max_distance`: Maximum edit distance allowed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.fuzzy_prefix_iter(b"SOG", 1);
+    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+    /// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+    /// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn fuzzy_prefix_iter<P>(
+        &self,
+        query: P,
+        max_distance: usize,
+    ) -> AutomatonIter<'_, FuzzyPrefix>
+    where
+        P: AsRef<[u8]>,
+    {
+        AutomatonIter::new(self, FuzzyPrefix::new(query, max_distance))
+    }
+
+    /// Makes an iterator to enumerate keys matching a glob-style pattern.
+    ///
+    /// `?` matches any single byte, `*` matches any run of bytes, and `\` escapes the following byte.
+    /// The keys will be reported in the lexicographical order,
+    /// starting from the range of the literal prefix of the pattern.
+    ///
+    /// # Arguments
+    ///
+    ///  - `pattern`: Glob-style pattern.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.glob_iter(b"*I*");
+    /// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
+    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+    /// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+    /// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn glob_iter<P>(&self, pattern: P) -> AutomatonIter<'_, Glob>
+    where
+        P: AsRef<[u8]>,
+    {
+        let glob = Glob::new(pattern);
+        let literal_prefix = glob.literal_prefix();
+        let start = if self.is_prefix_contiguous(&literal_prefix) {
+            self.prefix_range(literal_prefix).start
+        } else {
+            0
+        };
+        let mut iter = AutomatonIter::new(self, glob);
+        iter.seek(start);
+        iter
+    }
+
+    /// Makes an iterator to enumerate keys matching a regular expression as a whole.
+    ///
+    /// The pattern is compiled into a DFA with `regex-automata` and run over the dictionary,
+    /// skipping all the keys under a prefix once the DFA reaches the dead state.
+    /// The keys will be reported in the lexicographical order.
+    ///
+    /// # Arguments
+    ///
+    ///  - `pattern`: Regular expression in the syntax of the `regex` crate.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the pattern cannot be compiled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.regex_iter("[A-Z]+D{2}").unwrap();
+    /// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    ///
+    /// assert!(set.regex_iter("SIG(").is_err());
+    /// ```
+    #[cfg(feature = "regex-automata")]
+    pub fn regex_iter(&self, pattern: &str) -> Result<AutomatonIter<'_, regex::Regex>> {
+        Ok(AutomatonIter::new(self, regex::Regex::new(pattern)?))
+    }
+
+    /// Makes an iterator to enumerate keys whose lengths fall in a given range.
+    ///
+    /// The keys will be reported in the lexicographical order.
+    /// The keys out of the range are not cloned, and the keys sharing a prefix
+    /// longer than the range are skipped at once.
+    ///
+    /// # Arguments
+    ///
+    ///  - `range`: Range of key lengths.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.iter_len_range(4..=5);
+    /// assert_eq!(iter.next(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.next(), Some((1, b"ICML".to_vec())));
+    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn iter_len_range<R>(&self, range: R) -> AutomatonIter<'_, LenRange>
+    where
+        R: RangeBounds<usize>,
+    {
+        AutomatonIter::new(self, LenRange::new(range))
+    }
+
+    /// Makes a predictive iterator to enumerate keys starting from a given string.
+    ///
+    /// The keys will be reported in the lexicographical order.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys to be predicted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.predictive_iter(b"SIG");
+    /// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+    /// assert_eq!(iter.next(), Some((3, b"SIGKDD".to_vec())));
+    /// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+    /// assert_eq!(iter.next(), None);
+    /// ```
+    pub fn predictive_iter<P>(&self, prefix: P) -> PredictiveIter<'_>
+    where
+        P: AsRef<[u8]>,
+    {
+        PredictiveIter::new(self, prefix)
+    }
+
+    /// Makes a predictive iterator bounded by a budget,
+    /// which yields [`budget::BudgetExceeded`] once the budget is used up.
+    /// See [`budget`] for an example.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys to be predicted.
+    ///  - `budget`: Limits of the enumerated keys and bytes.
+    pub fn predictive_iter_with_budget<P>(
+        &self,
+        prefix: P,
+        budget: Budget,
+    ) -> Budgeted<PredictiveIter<'_>>
+    where
+        P: AsRef<[u8]>,
+    {
+        Budgeted::new(self.predictive_iter(prefix), budget)
+    }
+
+    /// Calls `f` with the id and key of each key starting with a given prefix,
+    /// in the same order as [`Set::predictive_iter`].
+    ///
+    /// The keys are borrowed from the decoding buffer without cloning them,
+    /// and the enumeration stops as soon as `f` returns [`ControlFlow::Break`],
+    /// which makes it cheaper than the iterator for first-N and existence queries.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys to be enumerated.
+    ///  - `f`: Callback receiving each id and key.
+    ///
+    /// # Returns
+    ///
+    /// The [`ControlFlow::Break`] returned by `f`, or [`ControlFlow::Continue`]
+    /// if all the keys are enumerated.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut ids = vec![];
+    /// set.scan_prefix(b"SIG", |id, _| {
+    ///     ids.push(id);
+    ///     if ids.len() < 2 {
+    ///         ControlFlow::Continue(())
+    ///     } else {
+    ///         ControlFlow::Break(())
+    ///     }
+    /// });
+    /// assert_eq!(ids, vec![2, 3]);
+    ///
+    /// let found = set.scan_prefix(b"IC", |id, key| {
+    ///     if key.ends_with(b"ML") {
+    ///         ControlFlow::Break(id)
+    ///     } else {
+    ///         ControlFlow::Continue(())
+    ///     }
+    /// });
+    /// assert_eq!(found, ControlFlow::Break(1));
+    /// ```
+    pub fn scan_prefix<P, F, B>(&self, prefix: P, mut f: F) -> ControlFlow<B>
+    where
+        P: AsRef<[u8]>,
+        F: FnMut(usize, &[u8]) -> ControlFlow<B>,
+    {
+        let mut iter = self.predictive_iter(prefix);
+        while let Some(id) = iter.advance() {
+            f(id, iter.current_key())?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Builds a new [`Set`] containing only the keys for which `f` returns `true`.
+    ///
+    /// The keys are streamed into the new dictionary with the same bucket size,
+    /// preserving user-supplied ids if any.
+    ///
+    /// # Arguments
+    ///
+    ///  - `f`: Predicate for keys to be retained.
+    ///
+    /// # Returns
+    ///
+    /// A pair of the new dictionary and the mapping from old ids to new ids,
+    /// where removed keys are mapped to `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let (other, ids) = set.retain(|key| key.starts_with(b"SIG")).unwrap();
+    /// assert_eq!(other.len(), 3);
+    /// assert_eq!(ids, vec![None, None, Some(0), Some(1), Some(2)]);
+    ///
+    /// let (other, _) = set.retain(|_| false).unwrap();
+    /// assert!(other.is_empty());
+    /// assert_eq!(other.locator().run(b"ICML"), None);
+    /// ```
+    pub fn retain<F>(&self, mut f: F) -> Result<(Self, Vec<Option<usize>>)>
+    where
+        F: FnMut(&[u8]) -> bool,
+    {
+        let mut builder = self.new_builder(self.bucket_size())?;
+        let mut ids = Vec::with_capacity(self.len());
+        for (id, key) in self.iter() {
+            if f(&key) {
+                ids.push(Some(builder.len()));
+                self.copy_key_into(&mut builder, id, &key)?;
+            } else {
+                ids.push(None);
+            }
+        }
+        Ok((builder.finish(), ids))
+    }
+
+    /// Builds a new [`Set`] by mapping or filtering each key with `f`,
+    /// such as lowercasing keys or stripping a common prefix.
+    ///
+    /// The mapped keys are streamed into the new dictionary with the same configuration
+    /// and attributes while they stay in order. Once a mapped key breaks the order,
+    /// the keys are sorted in memory instead, where a duplicated key keeps the smallest old id.
+    ///
+    /// # Arguments
+    ///
+    ///  - `f`: Closure returning the new key, or `None` to drop the key.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when a mapped key cannot be added,
+    /// such as one containing [`END_MARKER`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let other = set
+    ///     .rebuild_with(|key| key.strip_prefix(b"SIG").map(|key| key.to_ascii_lowercase()))
+    ///     .unwrap();
+    /// assert_eq!(other.len(), 3);
+    /// assert_eq!(other.locator().run(b"kdd"), Some(1));
+    ///
+    /// // Reversing keys breaks the order.
+    /// let other = set.rebuild_with(|key| Some(key.iter().rev().cloned().collect())).unwrap();
+    /// assert_eq!(other.locator().run(b"MDCI"), Some(3));
+    /// assert_eq!(other.locator().run(b"DOMGIS"), Some(1));
+    /// ```
+    pub fn rebuild_with<F>(&self, mut f: F) -> Result<Self>
+    where
+        F: FnMut(&[u8]) -> Option<Vec<u8>>,
+    {
+        let mut builder = self.new_builder(self.bucket_size())?;
+        let mut last_key = Vec::new();
+        let mut iter = self.iter();
+        let mut unsorted = None;
+        while let Some(id) = iter.advance() {
+            let Some(key) = f(iter.key()) else {
+                continue;
+            };
+            if !builder.is_empty() && self.order.compare(&last_key, &key).is_ge() {
+                unsorted = Some((key, id));
+                break;
+            }
+            self.add_key_with_attrs(&mut builder, id, &key)?;
+            last_key.clear();
+            last_key.extend_from_slice(&key);
+        }
+        let Some(first_unsorted) = unsorted else {
+            return Ok(builder.finish());
+        };
+
+        // Falls back to sorting the keys added so far and the rest.
+        let partial = builder.finish();
+        let mut keys: Vec<_> = partial
+            .iter()
+            .map(|(id, key)| (key, &partial, id))
+            .collect();
+        keys.push((first_unsorted.0, self, first_unsorted.1));
+        while let Some(id) = iter.advance() {
+            if let Some(key) = f(iter.key()) {
+                keys.push((key, self, id));
+            }
+        }
+        // The stable sort keeps the keys of smaller old ids first.
+        keys.sort_by(|(a, _, _), (b, _, _)| self.order.compare(a, b));
+        keys.dedup_by(|(a, _, _), (b, _, _)| self.order.compare(a, b).is_eq());
+        let mut builder = self.new_builder(self.bucket_size())?;
+        for (key, set, id) in keys {
+            set.add_key_with_attrs(&mut builder, id, &key)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// Builds a new [`Set`] containing the keys whose ids fall in the given range.
+    ///
+    /// Only the buckets overlapping the range are decoded, and the keys are streamed into
+    /// the new dictionary with the same bucket size, preserving their order.
+    ///
+    /// # Arguments
+    ///
+    ///  - `range`: Range of ids to be extracted.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when `range` is out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let other = set.slice(1..4).unwrap();
+    /// assert_eq!(other.len(), 3);
+    /// assert_eq!(other.decoder().run(0), b"ICML".to_vec());
+    /// assert_eq!(other.decoder().run(2), b"SIGKDD".to_vec());
+    /// ```
+    pub fn slice(&self, range: Range<usize>) -> Result<Self> {
+        if range.end < range.start || self.len() < range.end {
+            return Err(anyhow!(
+                "The range {:?} is out of bounds for {} keys.",
+                range,
+                self.len()
+            ));
+        }
+        let mut builder = self.new_builder(self.bucket_size())?;
+        let mut iter = self.iter();
+        iter.seek_id(range.start);
+        for (id, key) in iter.take(range.len()) {
+            self.copy_key_into(&mut builder, id, &key)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// Builds a new [`Set`] containing only the keys starting with a given prefix.
+    ///
+    /// Only the buckets overlapping the range of the prefix are decoded, and the keys are
+    /// streamed into the new dictionary with the same bucket size and attributes.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys to be extracted.
+    ///  - `strip_prefix`: Whether the prefix is removed from the stored keys.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the stripped keys are not in the key order,
+    /// which can happen only in [`KeyOrder::Natural`] with a prefix ending with a digit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let other = set.extract_prefix_subdict(b"SIG", false).unwrap();
+    /// assert_eq!(other.len(), 3);
+    /// assert_eq!(other.locator().run(b"SIGKDD"), Some(1));
+    ///
+    /// let other = set.extract_prefix_subdict(b"SIG", true).unwrap();
+    /// assert_eq!(other.locator().run(b"KDD"), Some(1));
+    /// assert_eq!(other.locator().run(b"SIGKDD"), None);
+    /// ```
+    pub fn extract_prefix_subdict<P>(&self, prefix: P, strip_prefix: bool) -> Result<Self>
+    where
+        P: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        let mut builder = self.new_builder(self.bucket_size())?;
+        let mut add = |id: usize, key: &[u8]| {
+            if strip_prefix {
+                // The stripped keys may be out of order, so they are checked.
+                self.add_key_with_attrs(&mut builder, id, &key[prefix.len()..])
+            } else {
+                self.copy_key_into(&mut builder, id, key)
+            }
+        };
+        if self.is_prefix_contiguous(prefix) {
+            let range = self.prefix_range(prefix);
+            let mut iter = self.iter();
+            iter.seek_id(range.start);
+            for (id, key) in iter.take(range.len()) {
+                add(id, &key)?;
+            }
+        } else {
+            for (id, key) in self.predictive_iter(prefix) {
+                add(id, &key)?;
+            }
+        }
+        Ok(builder.finish())
+    }
+
+    /// Builds a new [`Set`] without the keys starting with a given prefix.
+    ///
+    /// It is the complement of [`Set::extract_prefix_subdict`].
+    /// The keys before and after the range of the prefix are streamed into the new dictionary
+    /// with the same bucket size and attributes, skipping the range without decoding it.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys to be removed.
+    ///
+    /// # Returns
+    ///
+    /// A pair of the new dictionary and the mapping from old ids to new ids,
+    /// where removed keys are mapped to `None`, as in [`Set::retain`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let (other, ids) = set.without_prefix(b"ICM").unwrap();
+    /// assert_eq!(other.len(), 4);
+    /// assert_eq!(ids, vec![Some(0), None, Some(1), Some(2), Some(3)]);
+    /// assert_eq!(other.locator().run(b"ICML"), None);
+    /// ```
+    pub fn without_prefix<P>(&self, prefix: P) -> Result<(Self, Vec<Option<usize>>)>
+    where
+        P: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        if !self.is_prefix_contiguous(prefix) {
+            return self.retain(|key| !key.starts_with(prefix));
+        }
+        let range = self.prefix_range(prefix);
+        let mut builder = self.new_builder(self.bucket_size())?;
+        let mut iter = self.iter();
+        for (id, key) in iter.by_ref().take(range.start) {
+            self.copy_key_into(&mut builder, id, &key)?;
+        }
+        iter.seek_id(range.end);
+        for (id, key) in iter {
+            self.copy_key_into(&mut builder, id, &key)?;
+        }
+        let ids = (0..self.len())
+            .map(|id| {
+                if id < range.start {
+                    Some(id)
+                } else if id < range.end {
+                    None
+                } else {
+                    Some(id - range.len())
+                }
+            })
+            .collect();
+        Ok((builder.finish(), ids))
+    }
+
+    /// Exports all the keys in the columnar layout of concatenated bytes and offsets
+    /// in a single streaming pass.
+    ///
+    /// # Returns
+    ///
+    /// A pair of the concatenation of the keys in the order of ids and the offsets,
+    /// where the key with id `i` is `bytes[offsets[i]..offsets[i + 1]]`
+    /// and `offsets` has `len() + 1` elements starting with zero.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let (bytes, offsets) = set.to_columns();
+    /// assert_eq!(bytes, b"ICDMICMLSIGIR".to_vec());
+    /// assert_eq!(offsets, vec![0, 4, 8, 13]);
+    /// ```
+    pub fn to_columns(&self) -> (Vec<u8>, Vec<usize>) {
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::with_capacity(self.len() + 1);
+        offsets.push(0);
+        let mut iter = self.iter();
+        while iter.advance().is_some() {
+            bytes.extend_from_slice(iter.key());
+            offsets.push(bytes.len());
+        }
+        (bytes, offsets)
+    }
+
+    /// Makes a read-only view of the encoded content of a bucket,
+    /// for custom traversals, debuggers, and exporters to other formats.
+    /// See [`RawBucket`] for an example.
+    ///
+    /// # Arguments
+    ///
+    ///  - `bi`: Bucket id.
+    ///
+    /// # Panics
+    ///
+    /// If `bi` is no less than the number of buckets, `panic!` will occur.
+    pub fn raw_bucket(&self, bi: usize) -> RawBucket<'_> {
+        assert!(bi < self.num_buckets());
+        RawBucket::new(self, bi)
+    }
+
+    /// Calls a closure with the keys of each bucket decoded at once, in the order of buckets.
+    ///
+    /// It is the sequential version of [`Set::for_each_bucket_par`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `f`: Closure receiving the bucket id and the view of its keys.
+    pub fn for_each_bucket<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, BucketView<'_>),
+    {
+        let (mut keys, mut ends) = (Vec::new(), Vec::new());
+        for bi in 0..self.num_buckets() {
+            bucket::decode_bucket(self, bi, &mut keys, &mut ends);
+            f(bi, BucketView::new(bi * self.bucket_size(), &keys, &ends));
+        }
+    }
+
+    /// Calls a closure with the keys of each bucket decoded at once, in parallel,
+    /// which is the granularity to build derived indexes over the whole dictionary.
+    ///
+    /// The buckets are processed in an unspecified order on the global thread pool of rayon,
+    /// reusing the decoding buffers of each worker.
+    ///
+    /// # Arguments
+    ///
+    ///  - `f`: Closure receiving the bucket id and the view of its keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 2).unwrap();
+    ///
+    /// let total = AtomicUsize::new(0);
+    /// set.for_each_bucket_par(|_, bucket| {
+    ///     let bytes: usize = bucket.iter().map(|(_, key)| key.len()).sum();
+    ///     total.fetch_add(bytes, Ordering::Relaxed);
+    /// });
+    /// assert_eq!(total.into_inner(), 25);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn for_each_bucket_par<F>(&self, f: F)
+    where
+        F: Fn(usize, BucketView<'_>) + Sync + Send,
+    {
+        use rayon::prelude::*;
+
+        (0..self.num_buckets()).into_par_iter().for_each_init(
+            || (Vec::new(), Vec::new()),
+            |(keys, ends), bi| {
+                bucket::decode_bucket(self, bi, keys, ends);
+                f(bi, BucketView::new(bi * self.bucket_size(), keys, ends));
+            },
+        );
+    }
+
+    /// Maps the keys of each bucket in parallel and reduces the results,
+    /// like [`Set::for_each_bucket_par`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `map`: Closure mapping the bucket id and the view of its keys to a result.
+    ///  - `reduce`: Associative closure combining two results.
+    ///
+    /// # Returns
+    ///
+    /// The reduced result, or `None` if the dictionary is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 2).unwrap();
+    ///
+    /// let longest = set.map_reduce_buckets_par(
+    ///     |_, bucket| bucket.iter().map(|(_, key)| key.len()).max().unwrap(),
+    ///     std::cmp::max,
+    /// );
+    /// assert_eq!(longest, Some(6));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn map_reduce_buckets_par<T, M, R>(&self, map: M, reduce: R) -> Option<T>
+    where
+        T: Send,
+        M: Fn(usize, BucketView<'_>) -> T + Sync + Send,
+        R: Fn(T, T) -> T + Sync + Send,
+    {
+        use rayon::prelude::*;
+
+        (0..self.num_buckets())
+            .into_par_iter()
+            .map_init(
+                || (Vec::new(), Vec::new()),
+                |(keys, ends), bi| {
+                    bucket::decode_bucket(self, bi, keys, ends);
+                    map(bi, BucketView::new(bi * self.bucket_size(), keys, ends))
+                },
+            )
+            .reduce_with(reduce)
+    }
+
+    /// Checks in parallel that the dictionary stores exactly a given keyset with dense ids.
+    ///
+    /// The buckets are decoded and compared with the corresponding keys on the global thread
+    /// pool of rayon, which is a cheap audit after a build.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: Keyset expected to be stored, whose `i`-th key is expected to have id `i`.
+    ///
+    /// # Returns
+    ///
+    /// The discrepancy with the smallest id, or `None` if the keyset matches the dictionary.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 2).unwrap();
+    /// assert_eq!(set.verify_against(&keys), None);
+    ///
+    /// let discrepancy = set.verify_against(&["ICDM", "ICML", "SIGKDD"]).unwrap();
+    /// assert_eq!(discrepancy.id, 2);
+    /// assert_eq!(discrepancy.expected, Some(b"SIGKDD".to_vec()));
+    /// assert_eq!(discrepancy.actual, Some(b"SIGIR".to_vec()));
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn verify_against<P>(&self, keys: &[P]) -> Option<verify::Discrepancy>
+    where
+        P: AsRef<[u8]> + Sync,
+    {
+        let found = self
+            .map_reduce_buckets_par(
+                |_, bucket| {
+                    bucket.iter().find_map(|(id, key)| {
+                        let expected = keys.get(id).map(|k| k.as_ref());
+                        (expected != Some(key)).then(|| verify::Discrepancy {
+                            id,
+                            expected: expected.map(|k| k.to_vec()),
+                            actual: Some(key.to_vec()),
+                        })
+                    })
+                },
+                |a, b| match (a, b) {
+                    (Some(a), Some(b)) => Some(if a.id < b.id { a } else { b }),
+                    (a, b) => a.or(b),
+                },
+            )
+            .flatten();
+        found.or_else(|| {
+            keys.get(self.len()).map(|key| verify::Discrepancy {
+                id: self.len(),
+                expected: Some(key.as_ref().to_vec()),
+                actual: None,
+            })
+        })
+    }
+
+    /// Rebuilds the dictionary with a different bucket size.
+    ///
+    /// The keys are streamed into the new dictionary without materializing the whole keyset.
+    ///
+    /// # Arguments
+    ///
+    ///  - `bucket_size`: The number of strings in each bucket, which must be a power of two.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when `bucket_size` is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 4).unwrap();
+    ///
+    /// let other = set.rebucket(2).unwrap();
+    /// assert_eq!(other.bucket_size(), 2);
+    /// assert_eq!(other.num_buckets(), 3);
+    /// assert_eq!(other.locator().run(b"SIGKDD"), Some(3));
+    /// ```
+    pub fn rebucket(&self, bucket_size: usize) -> Result<Self> {
+        self.rebucket_with_progress(bucket_size, |_| {})
+    }
+
+    /// Rebuilds the dictionary with a different bucket size like [`Set::rebucket`],
+    /// calling a closure with the progress every 65536 keys and at the end.
+    ///
+    /// # Arguments
+    ///
+    ///  - `bucket_size`: The number of strings in each bucket, which must be a power of two.
+    ///  - `on_progress`: Closure receiving the progress.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when `bucket_size` is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 4).unwrap();
+    ///
+    /// let mut num_keys = 0;
+    /// let other = set.rebucket_with_progress(2, |p| num_keys = p.keys).unwrap();
+    /// assert_eq!(num_keys, other.len());
+    /// ```
+    pub fn rebucket_with_progress<F>(&self, bucket_size: usize, mut on_progress: F) -> Result<Self>
+    where
+        F: FnMut(Progress),
+    {
+        let mut builder = self.new_builder(bucket_size)?;
+        for (id, key) in self.iter() {
+            self.copy_key_into(&mut builder, id, &key)?;
+            if builder.len().is_multiple_of(builder::PROGRESS_INTERVAL) {
+                on_progress(builder.progress());
+            }
+        }
+        on_progress(builder.progress());
+        Ok(builder.finish())
+    }
+
+    /// Rebuilds the dictionary packing the suffixes with the alphabet of the bytes in the keys.
+    ///
+    /// It is effective for keys over small alphabets, such as DNA sequences,
+    /// and the queries work as before.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary has no bytes in the keys
+    /// or more than [`alphabet::MAX_SYMBOLS`] distinct bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["AACGT", "ACGTTA", "CATCAT", "GATTACA", "TTAGGG"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let other = set.remap_alphabet().unwrap();
+    /// assert_eq!(other.alphabet().unwrap().symbols(), b"ACGT");
+    /// assert!(other.size_in_bytes() < set.size_in_bytes());
+    /// assert_eq!(other.locator().run(b"GATTACA"), Some(3));
+    /// ```
+    pub fn remap_alphabet(&self) -> Result<Self> {
+        let alphabet = Alphabet::from_keys(self.iter().map(|(_, key)| key))?;
+        let mut builder = self.new_builder(self.bucket_size())?.alphabet(alphabet);
+        for (id, key) in self.iter() {
+            self.copy_key_into(&mut builder, id, &key)?;
+        }
+        Ok(builder.finish())
+    }
+
+    /// Creates a [`Builder`] storing the same kind of attributes as this dictionary.
+    fn new_builder(&self, bucket_size: usize) -> Result<Builder> {
+        Builder::with_config(BuildConfig {
+            bucket_size,
+            ..self.build_config()
+        })
+    }
+
+    /// Adds the key of `id` into `builder` with the attributes associated with it.
+    fn copy_key_into(&self, builder: &mut Builder, id: usize, key: &[u8]) -> Result<()> {
+        if self.external_ids.is_none() && self.payloads.is_none() && self.values.is_none() {
+            // The keys of this dictionary have already been validated.
+            builder.add_unchecked(key)
+        } else {
+            self.add_key_with_attrs(builder, id, key)
+        }
+    }
+
+    /// Adds `key`, which may differ from that of `id`, into `builder`
+    /// with the attributes associated with `id`.
+    fn add_key_with_attrs(&self, builder: &mut Builder, id: usize, key: &[u8]) -> Result<()> {
+        if let Some(external_ids) = &self.external_ids {
+            builder.add_with_id(key, external_ids.get(id))
+        } else if let Some(payloads) = &self.payloads {
+            builder.add_with_payload(key, payloads.get(id))
+        } else if let Some(values) = &self.values {
+            let value = values.get(id).ok_or_else(|| anyhow!("broken values"))?;
+            builder.add_with_value(key, &value)
+        } else {
+            builder.add(key)
+        }
+    }
+
+    /// Makes an iterator to enumerate differences from this dictionary to another one.
+    ///
+    /// The events are reported in the order of keys,
+    /// regarding this dictionary as the old snapshot and `other` as the new one.
+    ///
+    /// # Arguments
+    ///
+    ///  - `other`: Dictionary of the new snapshot.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the dictionaries are sorted in different [`KeyOrder`]s.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    /// use fcsd::diff::DiffItem;
+    ///
+    /// let old = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let new = Set::new(["ICML", "SIGIR", "SIGKDD"]).unwrap();
+    ///
+    /// let mut diff = old.diff(&new);
+    /// assert_eq!(diff.next(), Some(DiffItem::Removed(b"ICDM".to_vec())));
+    /// assert_eq!(diff.next(), Some(DiffItem::Common(1, 0, b"ICML".to_vec())));
+    /// assert_eq!(diff.next(), Some(DiffItem::Common(2, 1, b"SIGIR".to_vec())));
+    /// assert_eq!(diff.next(), Some(DiffItem::Added(b"SIGKDD".to_vec())));
+    /// assert_eq!(diff.next(), None);
+    /// ```
+    pub fn diff<'a>(&'a self, other: &'a Self) -> Diff<'a> {
+        Diff::new(self, other)
+    }
+
+    /// Builds the mapping from the ids of an old dictionary to those of a new one,
+    /// by a single merge pass over both dictionaries.
+    ///
+    /// It helps to migrate stored id columns between vocabulary versions.
+    /// If the dictionaries are sorted in different [`KeyOrder`]s,
+    /// each key of `old` is located in `new` instead.
+    ///
+    /// # Arguments
+    ///
+    ///  - `old`: Dictionary of the old vocabulary.
+    ///  - `new`: Dictionary of the new vocabulary.
+    ///
+    /// # Returns
+    ///
+    /// The vector whose `i`-th element is the id in `new` of the key of id `i` in `old`,
+    /// or `None` if the key is removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let old = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let new = Set::new(["ICML", "KDD", "SIGIR", "SIGKDD"]).unwrap();
+    /// assert_eq!(Set::build_id_mapping(&old, &new), vec![None, Some(0), Some(2)]);
+    /// ```
+    pub fn build_id_mapping(old: &Self, new: &Self) -> Vec<Option<usize>> {
+        if old.order != new.order {
+            let mut locator = new.locator();
+            return old.iter().map(|(_, key)| locator.run(key)).collect();
+        }
+        let mut mapping = Vec::with_capacity(old.len());
+        let mut mine = old.iter();
+        let mut theirs = new.iter();
+        let mut their_id = theirs.advance();
+        while mine.advance().is_some() {
+            let mut found = None;
+            while let Some(id) = their_id {
+                match new.order.compare(theirs.key(), mine.key()) {
+                    Ordering::Less => their_id = theirs.advance(),
+                    Ordering::Equal => {
+                        found = Some(id);
+                        their_id = theirs.advance();
+                        break;
+                    }
+                    Ordering::Greater => break,
+                }
+            }
+            mapping.push(found);
+        }
+        mapping
+    }
+
+    /// Builds the mapping of ids like [`Set::build_id_mapping`], as the integer type `I`.
+    ///
+    /// # Arguments
+    ///
+    ///  - `old`: Dictionary of the old vocabulary.
+    ///  - `new`: Dictionary of the new vocabulary.
+    ///
+    /// # Panics
+    ///
+    /// If an id in `new` does not fit in `I`, `panic!` will occur.
+    /// It never occurs if [`Set::fits_id`] returns `true` for `new`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let old = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let new = Set::new(["ICML", "KDD", "SIGIR", "SIGKDD"]).unwrap();
+    /// let mapping: Vec<Option<u32>> = Set::build_id_mapping_as(&old, &new);
+    /// assert_eq!(mapping, vec![None, Some(0), Some(2)]);
+    /// ```
+    pub fn build_id_mapping_as<I>(old: &Self, new: &Self) -> Vec<Option<I>>
+    where
+        I: Id,
+    {
+        Self::build_id_mapping(old, new)
+            .into_iter()
+            .map(|id| {
+                id.map(|id| I::from_usize(id).expect("The id does not fit in the integer type."))
+            })
+            .collect()
+    }
+
+    /// Reports how the ids of the keys will change if the dictionary is rebuilt from a new keyset.
+    ///
+    /// The dictionary and the keyset are merged in a single pass without building anything,
+    /// so that the cost of invalidated ids can be estimated before committing to a rebuild.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: Keyset of the next build, sorted in the key order of this dictionary without duplicates.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when `keys` is not sorted or has duplicates.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR", "SIGMOD"]).unwrap();
+    /// let report = set
+    ///     .stability_report(["ICDM", "ICML", "KDD", "SIGIR", "SIGKDD", "SIGMOD"])
+    ///     .unwrap();
+    /// assert_eq!(report.num_added, 2);
+    /// assert_eq!(report.num_moved, 2);
+    /// assert_eq!(report.max_shift, 2);
+    /// assert_eq!(report.new_id(2), Some(3));
+    /// assert_eq!(report.new_id(3), Some(5));
+    /// ```
+    pub fn stability_report<I, P>(&self, keys: I) -> Result<StabilityReport>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let mut report = StabilityReport::default();
+        let mut iter = self.iter();
+        let mut old_id = iter.advance();
+        let mut prev: Option<Vec<u8>> = None;
+        for (new_id, key) in keys.into_iter().enumerate() {
+            let key = key.as_ref();
+            if let Some(prev) = &prev {
+                if self.order.compare(prev, key).is_ge() {
+                    return Err(anyhow!(
+                        "The keys must be sorted without duplicates, but {:?} is followed by {:?}.",
+                        String::from_utf8_lossy(prev),
+                        String::from_utf8_lossy(key)
+                    ));
+                }
+            }
+            let mut found = false;
+            while let Some(id) = old_id {
+                match self.order.compare(iter.key(), key) {
+                    Ordering::Less => old_id = iter.advance(),
+                    Ordering::Equal => {
+                        report.push(id, new_id);
+                        found = true;
+                        old_id = iter.advance();
+                        break;
+                    }
+                    Ordering::Greater => break,
+                }
+            }
+            if !found {
+                report.num_added += 1;
+            }
+            prev = Some(key.to_vec());
+        }
+        report.num_removed = self.len() - report.num_kept;
+        Ok(report)
+    }
+
+    /// Checks if all the keys in this dictionary are stored in another one.
+    ///
+    /// Both dictionaries are merged in a streaming manner, and the buckets of `other`
+    /// whose keys are all less than the next key are skipped using their headers.
+    ///
+    /// # Arguments
+    ///
+    ///  - `other`: Dictionary to be compared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let old = Set::new(["ICML", "SIGIR"]).unwrap();
+    /// let new = Set::new(["ICML", "SIGIR", "SIGKDD"]).unwrap();
+    /// assert!(old.is_subset(&new));
+    /// assert!(!new.is_subset(&old));
+    /// ```
+    pub fn is_subset(&self, other: &Self) -> bool {
+        if other.len() < self.len() {
+            return false;
+        }
+        if self.order != other.order {
+            let mut locator = other.locator();
+            return self.iter().all(|(_, key)| locator.run(key).is_some());
+        }
+        let mut mine = self.iter();
+        let mut theirs = other.iter();
+        let mut next_id = 0;
+        while mine.advance().is_some() {
+            let key = mine.key();
+            let next_bi = other.bucket_id(next_id) + 1;
+            if next_bi < other.num_buckets()
+                && other.with_header(next_bi, |header| other.order.compare(header, key).is_le())
+            {
+                let (bi, _) = other.search_bucket(key);
+                next_id = bi * other.bucket_size();
+                theirs.seek_id(next_id);
+            }
+            loop {
+                let Some(id) = theirs.advance() else {
+                    return false;
+                };
+                next_id = id + 1;
+                match other.order.compare(theirs.key(), key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => break,
+                    Ordering::Greater => return false,
+                }
+            }
+        }
+        true
+    }
+
+    /// Checks if all the keys in another dictionary are stored in this one.
+    ///
+    /// # Arguments
+    ///
+    ///  - `other`: Dictionary to be compared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let old = Set::new(["ICML", "SIGIR"]).unwrap();
+    /// let new = Set::new(["ICML", "SIGIR", "SIGKDD"]).unwrap();
+    /// assert!(new.is_superset(&old));
+    /// assert!(!old.is_superset(&new));
+    /// ```
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns the number of keys less than a given key,
+    /// which is the id of the key if stored and the insertion point otherwise.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key.
+    ///
+    /// # Complexity
+    ///
+    ///  - Logarithmic over the number of keys
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.rank(b"ICML"), 1);
+    /// assert_eq!(set.rank(b"KDD"), 2);
+    /// assert_eq!(set.rank(b"WWW"), 5);
+    /// ```
+    pub fn rank<P>(&self, key: P) -> usize
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        self.partition_point(|k| self.order.compare(k, key).is_lt())
+    }
+
+    /// Returns the number of keys in the half-open range `[lo, hi)`.
+    ///
+    /// # Arguments
+    ///
+    ///  - `lo`: Inclusive lower bound.
+    ///  - `hi`: Exclusive upper bound.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.count_range(b"ICML", b"SIGMOD"), 3);
+    /// assert_eq!(set.count_range(b"J", b"Z"), 3);
+    /// assert_eq!(set.count_range(b"Z", b"J"), 0);
+    /// ```
+    pub fn count_range<P, Q>(&self, lo: P, hi: Q) -> usize
+    where
+        P: AsRef<[u8]>,
+        Q: AsRef<[u8]>,
+    {
+        self.rank(hi).saturating_sub(self.rank(lo))
+    }
+
+    /// Returns the range of ids of the keys starting with a given prefix.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys.
+    ///
+    /// # Complexity
+    ///
+    ///  - Logarithmic over the number of keys
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.prefix_range(b"SIG"), 2..5);
+    /// assert_eq!(set.prefix_range(b"ICM"), 1..2);
+    /// assert!(set.prefix_range(b"KDD").is_empty());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// It panics if the dictionary is in [`KeyOrder::Natural`] and `prefix` ends with an ASCII digit,
+    /// because such keys are not contiguous.
+    /// Use [`Set::predictive_iter`] instead.
+    pub fn prefix_range<P>(&self, prefix: P) -> Range<usize>
+    where
+        P: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        assert!(
+            self.is_prefix_contiguous(prefix),
+            "The keys starting with a prefix ending with a digit are not contiguous in natural order."
+        );
+        let less = |key: &[u8]| self.order.compare(key, prefix).is_lt();
+        let beg = self.partition_point(less);
+        let end = self.partition_point(|key| less(key) || key.starts_with(prefix));
+        beg..end
+    }
+
+    /// Returns the ranges of ids of the keys starting with each of given prefixes.
+    ///
+    /// The prefixes are sorted and answered in one pass, where the binary search for each prefix
+    /// starts from the range of the previous one, so the buckets are walked monotonically.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefixes`: Prefixes of keys.
+    ///
+    /// # Returns
+    ///
+    /// The range for each prefix in the input order, or `None` if no key starts with it.
+    ///
+    /// # Panics
+    ///
+    /// It panics if the dictionary is in [`KeyOrder::Natural`] and a prefix ends with an ASCII digit,
+    /// as [`Set::prefix_range`] does.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(
+    ///     set.prefix_ranges(&["SIG", "KDD", "IC", "SIGM"]),
+    ///     vec![Some(2..5), None, Some(0..2), Some(4..5)]
+    /// );
+    /// ```
+    pub fn prefix_ranges<P>(&self, prefixes: &[P]) -> Vec<Option<Range<usize>>>
+    where
+        P: AsRef<[u8]>,
+    {
+        let mut order: Vec<_> = (0..prefixes.len()).collect();
+        order.sort_by(|&i, &j| {
+            self.order
+                .compare(prefixes[i].as_ref(), prefixes[j].as_ref())
+        });
+
+        let mut ranges = vec![None; prefixes.len()];
+        let mut beg = 0;
+        for i in order {
+            let prefix = prefixes[i].as_ref();
+            assert!(
+                self.is_prefix_contiguous(prefix),
+                "The keys starting with a prefix ending with a digit are not contiguous in natural order."
+            );
+            let less = |key: &[u8]| self.order.compare(key, prefix).is_lt();
+            beg = self.partition_point_from(beg, less);
+            let end = self.partition_point_from(beg, |key| less(key) || key.starts_with(prefix));
+            if beg < end {
+                ranges[i] = Some(beg..end);
+            }
+        }
+        ranges
+    }
+
+    /// Checks which of given prefixes have at least one key starting with them.
+    ///
+    /// It is [`Set::prefix_membership_into`] returning a new bitmap.
+    /// See [`Bitmap`] for an example.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefixes`: Prefixes of keys.
+    pub fn prefix_membership<P>(&self, prefixes: &[P]) -> Bitmap
+    where
+        P: AsRef<[u8]>,
+    {
+        let mut bits = Bitmap::new();
+        self.prefix_membership_into(prefixes, &mut bits);
+        bits
+    }
+
+    /// Checks which of given prefixes have at least one key starting with them,
+    /// writing the answers into a bitmap reused across queries.
+    ///
+    /// The prefixes are answered in one pass in the order of keys,
+    /// where the binary search for each prefix starts from the position of the previous one.
+    /// If the prefixes are already sorted in the order of keys, no heap allocation is performed
+    /// once `bits` has enough capacity; otherwise, their order is sorted in a temporary vector.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefixes`: Prefixes of keys.
+    ///  - `bits`: Bitmap whose `i`-th bit is set if a key starts with `prefixes[i]`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::bitmap::Bitmap;
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"]).unwrap();
+    /// let mut bits = Bitmap::new();
+    /// set.prefix_membership_into(&["IC", "ICMLA", "SIGK", "VLDB"], &mut bits);
+    /// assert_eq!(bits.ones().collect::<Vec<_>>(), vec![0, 2]);
+    /// ```
+    pub fn prefix_membership_into<P>(&self, prefixes: &[P], bits: &mut Bitmap)
+    where
+        P: AsRef<[u8]>,
+    {
+        bits.reset(prefixes.len());
+        let is_sorted = prefixes
+            .windows(2)
+            .all(|w| self.order.compare(w[0].as_ref(), w[1].as_ref()).is_le());
+        if is_sorted {
+            self.mark_prefixes(prefixes, 0..prefixes.len(), bits);
+        } else {
+            let mut order: Vec<_> = (0..prefixes.len()).collect();
+            order.sort_by(|&i, &j| {
+                self.order
+                    .compare(prefixes[i].as_ref(), prefixes[j].as_ref())
+            });
+            self.mark_prefixes(prefixes, order, bits);
+        }
+    }
+
+    /// Sets the bits of the prefixes having keys, visiting them in the order of keys given by `order`.
+    fn mark_prefixes<P, I>(&self, prefixes: &[P], order: I, bits: &mut Bitmap)
+    where
+        P: AsRef<[u8]>,
+        I: IntoIterator<Item = usize>,
+    {
+        let mut beg = 0;
+        for i in order {
+            let prefix = prefixes[i].as_ref();
+            if !self.is_prefix_contiguous(prefix) {
+                if self.contains_prefix(prefix) {
+                    bits.set(i);
+                }
+                continue;
+            }
+            let less = |key: &[u8]| self.order.compare(key, prefix).is_lt();
+            beg = self.partition_point_from(beg, less);
+            if beg < self.partition_point_from(beg, |key| less(key) || key.starts_with(prefix)) {
+                bits.set(i);
+            }
+        }
+    }
+
+    /// Checks if any key starts with a given prefix.
+    ///
+    /// It stops at the first evidence, such as a bucket header or a key in the bucket
+    /// starting with the prefix, which is compared in place without decoding a key.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys.
+    ///
+    /// # Complexity
+    ///
+    ///  - Logarithmic over the number of keys
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert!(set.contains_prefix(b"SIGK"));
+    /// assert!(set.contains_prefix(b"ICML"));
+    /// assert!(!set.contains_prefix(b"ICMLA"));
+    /// assert!(!set.contains_prefix(b"KDD"));
+    /// ```
+    pub fn contains_prefix<P>(&self, prefix: P) -> bool
+    where
+        P: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        if self.is_empty() || prefix.is_empty() {
+            return !self.is_empty();
+        }
+        if self.order != KeyOrder::Lexicographic || self.alphabet.is_some() {
+            if self.is_prefix_contiguous(prefix) {
+                return !self.prefix_range(prefix).is_empty();
+            }
+            return self.predictive_iter(prefix).next().is_some();
+        }
+
+        let (bi, found) = self.search_bucket(prefix);
+        if found {
+            return true;
+        }
+        let header = self.get_header(bi);
+        let (mut lcp, cmp) = utils::get_lcp(prefix, header);
+        if 0 <= cmp {
+            return lcp == prefix.len();
+        }
+        // The first key no less than the prefix may be the next header.
+        if bi + 1 < self.num_buckets() && self.get_header(bi + 1).starts_with(prefix) {
+            return true;
+        }
+
+        let mut pos = self.pointers.get(bi) as usize + header.len() + 1;
+        for _ in 1..self.bucket_size() {
+            if pos == self.serialized.len() {
+                break;
+            }
+            let (dec_lcp, next_pos) = self.decode_lcp(pos);
+            let suffix = &self.serialized[next_pos..];
+            let suffix_len = utils::get_strlen(suffix);
+            pos = next_pos + suffix_len + 1;
+
+            match dec_lcp.cmp(&lcp) {
+                // The key shares the mismatching byte with the previous one.
+                Ordering::Greater => {}
+                // The key is greater than the prefix without starting with it.
+                Ordering::Less => return false,
+                Ordering::Equal => {
+                    let (next_lcp, cmp) = utils::get_lcp(&prefix[lcp..], &suffix[..suffix_len]);
+                    if 0 <= cmp {
+                        return lcp + next_lcp == prefix.len();
+                    }
+                    lcp += next_lcp;
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns the `n`-th key (0-origin) starting with a given prefix and its id.
+    ///
+    /// The key is found by the id range of the prefix without enumerating preceding keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys.
+    ///  - `n`: Rank of the key among the keys starting with `prefix`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.select_in_prefix(b"SIG", 1), Some((3, b"SIGKDD".to_vec())));
+    /// assert_eq!(set.select_in_prefix(b"SIG", 3), None);
+    /// ```
+    pub fn select_in_prefix<P>(&self, prefix: P, n: usize) -> Option<(usize, Vec<u8>)>
+    where
+        P: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        if !self.is_prefix_contiguous(prefix) {
+            return self.predictive_iter(prefix).nth(n);
+        }
+        let range = self.prefix_range(prefix);
+        if range.len() <= n {
+            return None;
+        }
+        let id = range.start + n;
+        Some((id, self.decoder().run(id)))
+    }
+
+    /// Picks a key uniformly at random and returns it with its id,
+    /// or `None` if the dictionary is empty.
+    ///
+    /// # Arguments
+    ///
+    ///  - `rng`: Random number generator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let (id, key) = set.choose(&mut rand::thread_rng()).unwrap();
+    /// assert_eq!(key, keys[id].as_bytes());
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn choose<R>(&self, rng: &mut R) -> Option<(usize, Vec<u8>)>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        self.sample_in_prefix(b"", 1, rng).pop()
+    }
+
+    /// Picks distinct keys starting with a given prefix uniformly at random,
+    /// returning them with their ids in ascending order of ids.
+    ///
+    /// The ids are drawn from the id range of the prefix, and only the picked keys are decoded.
+    /// If the keys of the prefix are not contiguous, which can happen only in [`KeyOrder::Natural`]
+    /// with a prefix ending with a digit, they are enumerated with reservoir sampling.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys.
+    ///  - `amount`: Number of keys to be picked, which is capped by the number of keys with the prefix.
+    ///  - `rng`: Random number generator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let sample = set.sample_in_prefix(b"SIG", 2, &mut rand::thread_rng());
+    /// assert_eq!(sample.len(), 2);
+    /// assert!(sample.iter().all(|(id, key)| 2 <= *id && key.starts_with(b"SIG")));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn sample_in_prefix<P, R>(
+        &self,
+        prefix: P,
+        amount: usize,
+        rng: &mut R,
+    ) -> Vec<(usize, Vec<u8>)>
+    where
+        P: AsRef<[u8]>,
+        R: rand::Rng + ?Sized,
+    {
+        let prefix = prefix.as_ref();
+        if !self.is_prefix_contiguous(prefix) {
+            return sample::reservoir(self.predictive_iter(prefix), amount, rng);
+        }
+        let range = self.prefix_range(prefix);
+        let amount = amount.min(range.len());
+        let mut ids: Vec<_> = rand::seq::index::sample(rng, range.len(), amount)
+            .into_iter()
+            .map(|i| range.start + i)
+            .collect();
+        ids.sort_unstable();
+        self.iter_bucket_order(&ids)
+            .map(|(_, id, key)| (id, key))
+            .collect()
+    }
+
+    /// Picks a key at random with the probability proportional to its payload,
+    /// returning it with its id, or `None` if all the payloads are zero.
+    ///
+    /// It scans the payloads once. To pick many keys,
+    /// use [`sample::WeightedSampler`] made by [`Set::weighted_sampler`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `rng`: Random number generator.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary has no payloads
+    /// or the sum of the payloads overflows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 0).unwrap();
+    /// builder.add_with_payload(b"SIGIR", 3).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// let chosen = set.choose_weighted(&mut rand::thread_rng()).unwrap();
+    /// assert_eq!(chosen, Some((1, b"SIGIR".to_vec())));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn choose_weighted<R>(&self, rng: &mut R) -> Result<Option<(usize, Vec<u8>)>>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        let payloads = self
+            .payloads
+            .as_ref()
+            .ok_or_else(|| anyhow!("The dictionary has no payloads as weights."))?;
+        let mut total = 0u64;
+        for id in 0..self.len() {
+            total = total
+                .checked_add(payloads.get(id))
+                .ok_or_else(|| anyhow!("The sum of the weights overflows."))?;
+        }
+        if total == 0 {
+            return Ok(None);
+        }
+        let mut target = rng.gen_range(0..total);
+        for id in 0..self.len() {
+            let weight = payloads.get(id);
+            if target < weight {
+                return Ok(Some((id, self.decoder().run(id))));
+            }
+            target -= weight;
+        }
+        unreachable!()
+    }
+
+    /// Makes a [`sample::WeightedSampler`] picking keys with the probabilities
+    /// proportional to their payloads, in logarithmic time per key.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary has no payloads
+    /// or the sum of the payloads overflows.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 5).unwrap();
+    /// builder.add_with_payload(b"SIGIR", 0).unwrap();
+    /// builder.add_with_payload(b"SIGMOD", 2).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// let sampler = set.weighted_sampler().unwrap();
+    /// let mut rng = rand::thread_rng();
+    /// assert_eq!(sampler.choose_in_prefix(b"SIG", &mut rng), Some((2, b"SIGMOD".to_vec())));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn weighted_sampler(&self) -> Result<sample::WeightedSampler<'_>> {
+        sample::WeightedSampler::new(self)
+    }
+
+    /// Returns the distinct bytes following a given prefix among the stored keys, in ascending order.
+    ///
+    /// Each child byte is found by one decoding and one search,
+    /// so the keys under the child are skipped without being decoded.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.children(b"SIG"), b"IKM".to_vec());
+    /// assert_eq!(set.children(b""), b"IS".to_vec());
+    /// assert!(set.children(b"ICML").is_empty());
+    /// ```
+    pub fn children<P>(&self, prefix: P) -> Vec<u8>
+    where
+        P: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        if self.order != KeyOrder::Lexicographic {
+            // The keys under a digit child are not contiguous.
+            let mut children: Vec<_> = self
+                .predictive_iter(prefix)
+                .filter_map(|(_, key)| key.get(prefix.len()).copied())
+                .collect();
+            children.sort_unstable();
+            children.dedup();
+            return children;
+        }
+        let range = self.prefix_range(prefix);
+        let mut decoder = self.decoder();
+        let mut children = Vec::new();
+        let mut child = prefix.to_vec();
+        let mut id = range.start;
+        while id < range.end {
+            let key = decoder.run(id);
+            // A key shorter than the prefix is found only in an unverified dictionary.
+            if key.len() <= prefix.len() {
+                id += 1;
+                continue;
+            }
+            child.truncate(prefix.len());
+            child.push(key[prefix.len()]);
+            children.push(key[prefix.len()]);
+            let child = &child[..];
+            // Always advances, even on unsorted keys of an unverified dictionary.
+            id = std::cmp::max(
+                id + 1,
+                self.partition_point(|key| key < child || key.starts_with(child)),
+            );
+        }
+        children
+    }
+
+    /// Counts the keys under each prefix of `depth` bytes, in the order of keys,
+    /// which helps to choose balanced shard boundaries.
+    ///
+    /// A key shorter than `depth` bytes is counted under the key itself.
+    /// Each prefix is found by one decoding and one search,
+    /// so the keys under the prefix are skipped without being decoded.
+    ///
+    /// # Arguments
+    ///
+    ///  - `depth`: Length of prefixes in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The pairs of the distinct prefixes and the numbers of keys under them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["I", "ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(
+    ///     set.prefix_histogram(2),
+    ///     vec![(b"I".to_vec(), 1), (b"IC".to_vec(), 2), (b"SI".to_vec(), 3)]
+    /// );
+    /// assert_eq!(set.prefix_histogram(0), vec![(vec![], 6)]);
+    /// ```
+    pub fn prefix_histogram(&self, depth: usize) -> Vec<(Vec<u8>, usize)> {
+        if self.order != KeyOrder::Lexicographic {
+            // The keys under a prefix ending with a digit are not contiguous.
+            let mut counts = std::collections::HashMap::new();
+            for (_, mut key) in self.iter() {
+                key.truncate(depth);
+                *counts.entry(key).or_insert(0) += 1;
+            }
+            let mut histogram: Vec<_> = counts.into_iter().collect();
+            histogram.sort_unstable_by(|(a, _), (b, _)| self.order.compare(a, b));
+            return histogram;
+        }
+        let mut decoder = self.decoder();
+        let mut histogram = Vec::new();
+        let mut id = 0;
+        while id < self.len() {
+            let mut prefix = decoder.run(id);
+            if prefix.len() < depth {
+                histogram.push((prefix, 1));
+                id += 1;
+                continue;
+            }
+            prefix.truncate(depth);
+            let p = &prefix[..];
+            // Always advances, even on unsorted keys of an unverified dictionary.
+            let end = std::cmp::max(
+                id + 1,
+                self.partition_point(|key| key < p || key.starts_with(p)),
+            );
+            histogram.push((prefix, end - id));
+            id = end;
+        }
+        histogram
+    }
+
+    /// Checks if the dictionary was built with [`Builder::require_utf8`],
+    /// which ensures that all the keys are valid UTF-8.
+    ///
+    /// Loaders can use it to reject non-text dictionaries without decoding keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML"]).unwrap();
+    /// assert!(!set.is_utf8());
+    /// ```
+    pub const fn is_utf8(&self) -> bool {
+        self.utf8
+    }
+
+    /// Gets the limit of the key length in bytes set with
+    /// [`Builder::max_key_len`](crate::builder::Builder::max_key_len), if any.
+    pub const fn max_key_len(&self) -> Option<usize> {
+        self.max_key_len
+    }
+
+    /// Gets the length of the longest key in bytes,
+    /// which is enough for the capacity of a buffer given to [`Set::decode_into`].
+    #[inline(always)]
+    pub const fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// Gets the order of the keys set with
+    /// [`Builder::key_order`](crate::builder::Builder::key_order).
+    pub const fn key_order(&self) -> KeyOrder {
+        self.order
+    }
+
+    /// Gets the alphabet with which the suffixes are packed, if any.
+    pub const fn alphabet(&self) -> Option<&Alphabet> {
+        self.alphabet.as_ref()
+    }
+
+    /// Gets the configuration with which the dictionary was built.
+    ///
+    /// [`BuildConfig::key_len_policy`] is not recorded and is always the default,
+    /// and [`BuildConfig::value_codec`] is the default if the keys have no values.
+    pub fn build_config(&self) -> BuildConfig {
+        BuildConfig {
+            bucket_size: self.bucket_size(),
+            require_utf8: self.utf8,
+            key_order: self.order,
+            max_key_len: self.max_key_len,
+            key_len_policy: KeyLenPolicy::default(),
+            alphabet: self.alphabet.clone(),
+            align_buckets: self.bucket_aligned,
+            value_codec: self.value_codec().unwrap_or_default(),
+        }
+    }
+
+    /// Gets the number of stored keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.len(), keys.len());
+    /// ```
+    #[inline(always)]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the set is empty.
+    #[inline(always)]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the first key and its id, decoding only the first bucket header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.first(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(Set::new(Vec::<&str>::new()).unwrap().first(), None);
+    /// ```
+    pub fn first(&self) -> Option<(usize, Vec<u8>)> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut key = Vec::with_capacity(self.max_length);
+        self.decode_header(0, &mut key);
+        Some((0, key))
+    }
+
+    /// Returns the last key and its id, decoding only the last bucket.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert_eq!(set.last(), Some((4, b"SIGMOD".to_vec())));
+    /// assert_eq!(Set::new(Vec::<&str>::new()).unwrap().last(), None);
+    /// ```
+    pub fn last(&self) -> Option<(usize, Vec<u8>)> {
+        let mut iter = self.iter();
+        iter.seek_id(self.len.checked_sub(1)?);
+        iter.next()
+    }
+
+    /// Gets the number of defined buckets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 4).unwrap();
+    /// assert_eq!(set.num_buckets(), 2);
+    /// ```
+    #[inline(always)]
+    pub const fn num_buckets(&self) -> usize {
+        self.pointers.len()
+    }
+
+    /// Gets the bucket size.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::with_bucket_size(keys, 4).unwrap();
+    /// assert_eq!(set.bucket_size(), 4);
+    /// ```
+    #[inline(always)]
+    pub const fn bucket_size(&self) -> usize {
+        self.bucket_mask + 1
+    }
+
+    /// Checks if all the ids in the dictionary fit in the integer type `I`.
+    ///
+    /// If so, the typed query methods such as [`Locator::run_as`] never panic.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    /// assert!(set.fits_id::<u32>());
+    /// ```
+    pub fn fits_id<I: Id>(&self) -> bool {
+        self.is_empty() || I::from_usize(self.len() - 1).is_some()
+    }
+
+    /// Checks if the keys were added with user-supplied ids.
+    ///
+    /// See [`Builder::add_with_id`].
+    #[inline(always)]
+    pub const fn has_external_ids(&self) -> bool {
+        self.external_ids.is_some()
+    }
+
+    /// Gets the user-supplied id associated with the given id.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id assigned in the lexicographical order.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the keys were not added with [`Builder::add_with_id`].
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_id(b"ICDM", 20).unwrap();
+    /// builder.add_with_id(b"ICML", 10).unwrap();
+    /// let set = builder.finish();
+    /// assert_eq!(set.external_id(0), Some(20));
+    /// assert_eq!(set.external_id(1), Some(10));
+    /// ```
+    pub fn external_id(&self, id: usize) -> Option<u64> {
+        assert!(id < self.len());
+        self.external_ids.as_ref().map(|ids| ids.get(id))
+    }
+
+    /// Gets the id associated with the given user-supplied id.
+    ///
+    /// # Arguments
+    ///
+    ///  - `external_id`: Id given in [`Builder::add_with_id`].
+    ///
+    /// # Returns
+    ///
+    /// `None` if `external_id` is not found or the keys were not added with
+    /// [`Builder::add_with_id`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_id(b"ICDM", 20).unwrap();
+    /// builder.add_with_id(b"ICML", 10).unwrap();
+    /// let set = builder.finish();
+    /// assert_eq!(set.internal_id(10), Some(1));
+    /// assert_eq!(set.internal_id(30), None);
+    /// ```
+    pub fn internal_id(&self, external_id: u64) -> Option<usize> {
+        self.external_ids.as_ref()?.find(external_id)
+    }
+
+    /// Gets the payload associated with the given id.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id of the key.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the keys were not added with [`Builder::add_with_payload`].
+    ///
+    /// # Panics
+    ///
+    /// If `id` is no less than the number of keys, `panic!` will occur.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 2001).unwrap();
+    /// builder.add_with_payload(b"ICML", 1980).unwrap();
+    /// let set = builder.finish();
+    /// assert_eq!(set.payload(1), Some(1980));
+    /// ```
+    pub fn payload(&self, id: usize) -> Option<u64> {
+        assert!(id < self.len());
+        self.payloads.as_ref().map(|payloads| payloads.get(id))
+    }
+
+    /// Gets the value associated with the key of `id`.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id to be decoded.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the keys were not added with [`Builder::add_with_value`].
+    ///
+    /// # Panics
+    ///
+    /// It will panic if `id` is no less than the number of keys.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_value(b"ICDM", b"Data Mining").unwrap();
+    /// builder.add_with_value(b"ICML", b"Machine Learning").unwrap();
+    /// let set = builder.finish();
+    /// assert_eq!(set.value(1), Some(b"Machine Learning".to_vec()));
+    /// ```
+    pub fn value(&self, id: usize) -> Option<Vec<u8>> {
+        assert!(id < self.len());
+        self.values.as_ref().and_then(|values| values.get(id))
+    }
+
+    /// Gets the codec of the value area, or `None` if the keys have no values.
+    pub fn value_codec(&self) -> Option<ValueCodec> {
+        self.values.as_ref().map(|values| values.codec())
+    }
+
+    /// Overwrites the payload associated with the given id.
+    ///
+    /// Only the payload area is modified, and the front-coded keys are untouched.
+    /// The bit width of the payload area is widened if needed.
+    /// The payload area referred to in place, such as by [`Set::from_static_bytes`],
+    /// is copied on the first update, and the borrowed bytes are never written.
+    ///
+    /// # Arguments
+    ///
+    ///  - `id`: Integer id of the key.
+    ///  - `payload`: New payload.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - `id` is no less than the number of keys, or
+    ///  - the keys were not added with [`Builder::add_with_payload`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 0).unwrap();
+    /// builder.add_with_payload(b"ICML", 0).unwrap();
+    /// let mut set = builder.finish();
+    ///
+    /// set.set_payload(1, 12345).unwrap();
+    /// assert_eq!(set.payload(0), Some(0));
+    /// assert_eq!(set.payload(1), Some(12345));
+    /// ```
+    pub fn set_payload(&mut self, id: usize, payload: u64) -> Result<()> {
+        if self.len() <= id {
+            return Err(anyhow!(
+                "The id {} is out of range for {} keys.",
+                id,
+                self.len()
+            ));
+        }
+        let payloads = self
+            .payloads
+            .as_mut()
+            .ok_or_else(|| anyhow!("The keys have no payloads."))?;
+        payloads.update(id, payload);
+        Ok(())
+    }
+
+    /// Serializes only the payload area into a writer.
+    ///
+    /// Together with [`Set::deserialize_payloads_from`], evolving payloads can be saved
+    /// and restored without rewriting the front-coded keys.
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the keys were not added with
+    /// [`Builder::add_with_payload`], or an I/O error occurs.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 0).unwrap();
+    /// builder.add_with_payload(b"ICML", 0).unwrap();
+    /// let mut set = builder.finish();
+    /// let mut other = set.clone();
+    ///
+    /// set.set_payload(0, 7).unwrap();
+    /// let mut data = Vec::<u8>::new();
+    /// set.serialize_payloads_into(&mut data).unwrap();
+    ///
+    /// other.deserialize_payloads_from(&data[..]).unwrap();
+    /// assert_eq!(other.payload(0), Some(7));
+    /// ```
+    pub fn serialize_payloads_into<W>(&self, writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        let payloads = self
+            .payloads
+            .as_ref()
+            .ok_or_else(|| anyhow!("The keys have no payloads."))?;
+        payloads.serialize_into(writer)?;
+        Ok(())
+    }
+
+    /// Replaces the payload area with the one deserialized from a reader.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream written by [`Set::serialize_payloads_into`].
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - the payload area is broken, such as having fewer chunks than the payloads need,
+    ///  - the number of payloads does not match the number of keys,
+    ///  - the keys were not added with [`Builder::add_with_payload`], or
+    ///  - an I/O error occurs.
+    ///
+    /// The payload area is kept unchanged on errors.
+    pub fn deserialize_payloads_from<R>(&mut self, reader: R) -> Result<()>
+    where
+        R: io::Read,
+    {
+        if self.payloads.is_none() {
+            return Err(anyhow!("The keys have no payloads."));
+        }
+        let payloads = IntVector::deserialize_from(reader)?;
+        if payloads.len() != self.len() {
+            return Err(anyhow!(
+                "The number of payloads {} does not match the number of keys {}.",
+                payloads.len(),
+                self.len()
+            ));
+        }
+        self.payloads = Some(payloads);
+        Ok(())
+    }
+
+    #[inline(always)]
+    const fn bucket_id(&self, id: usize) -> usize {
+        id >> self.bucket_bits
+    }
+
+    #[inline(always)]
+    const fn pos_in_bucket(&self, id: usize) -> usize {
+        id & self.bucket_mask
+    }
+
+    /// Gets the header of the bucket in place, which is available without the alphabet.
+    #[inline(always)]
+    fn get_header(&self, bi: usize) -> &[u8] {
+        debug_assert!(self.alphabet.is_none());
+        let header = &self.serialized[self.pointers.get(bi) as usize..];
+        &header[..utils::get_strlen(header)]
+    }
+
+    /// Calls `f` with the header of the bucket, which is decoded if the suffixes are packed.
+    #[inline(always)]
+    fn with_header<F, R>(&self, bi: usize, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        if self.alphabet.is_some() {
+            return scratch::with_buffer(|dec| {
+                self.decode_header(bi, dec);
+                f(dec)
+            });
+        }
+        f(self.get_header(bi))
+    }
+
+    #[inline(always)]
+    fn prefetch_header(&self, bi: usize) {
+        utils::prefetch(self.serialized[self.pointers.get(bi) as usize..].as_ptr());
+    }
+
+    #[inline(always)]
+    fn decode_header(&self, bi: usize, dec: &mut Vec<u8>) -> usize {
+        dec.clear();
+        self.decode_next(self.pointers.get(bi) as usize, dec)
+    }
+
+    #[inline(always)]
+    fn try_decode_header(&self, bi: usize, dec: &mut Vec<u8>) -> Option<usize> {
+        if self.num_buckets() <= bi {
+            return None;
+        }
+        dec.clear();
+        self.try_decode_next(self.pointers.get(bi) as usize, dec)
+    }
+
+    /// Checked version of [`Set::decode_lcp`] verifying that `lcp` is no more than `dec_len`.
+    #[inline(always)]
+    fn try_decode_lcp(&self, pos: usize, dec_len: usize) -> Option<(usize, usize)> {
+        let (lcp, num) = utils::vbyte::try_decode(self.serialized.get(pos..)?)?;
+        if dec_len < lcp {
+            return None;
+        }
+        Some((lcp, pos + num))
+    }
+
+    #[inline(always)]
+    fn try_decode_next(&self, pos: usize, dec: &mut Vec<u8>) -> Option<usize> {
+        let suffix = self.serialized.get(pos..)?;
+        if let Some(alphabet) = &self.alphabet {
+            let dec_len = dec.len();
+            let num = alphabet.unpack(suffix, dec)?;
+            stats::record(Counter::BytesDecoded, (dec.len() - dec_len) as u64);
+            return Some(pos + num);
+        }
+        let len = memchr::memchr(END_MARKER, suffix)?;
+        dec.extend_from_slice(&suffix[..len]);
+        stats::record(Counter::BytesDecoded, len as u64);
+        Some(pos + len + 1)
+    }
+
+    /// Gets the serialized area from `pos`, which is empty past the end.
+    #[inline(always)]
+    fn suffix_at(&self, pos: usize) -> &[u8] {
+        self.serialized.get(pos..).unwrap_or_default()
+    }
+
+    /// Decodes the LCP at `pos`. Broken keys, which unverified dictionaries may have,
+    /// are decoded without panicking as if the serialized area ended there.
+    #[inline(always)]
+    fn decode_lcp(&self, pos: usize) -> (usize, usize) {
+        utils::vbyte::try_decode(self.suffix_at(pos))
+            .map_or((0, self.serialized.len()), |(lcp, num)| (lcp, pos + num))
+    }
+
+    #[inline(always)]
+    fn decode_next(&self, pos: usize, dec: &mut Vec<u8>) -> usize {
+        if self.alphabet.is_some() {
+            // Broken packed suffixes are not detected until Set::verify.
+            return self
+                .try_decode_next(pos, dec)
+                .unwrap_or(self.serialized.len());
+        }
+        let suffix = self.suffix_at(pos);
+        let len = utils::get_strlen(suffix);
+        dec.extend_from_slice(&suffix[..len]);
+        stats::record(Counter::BytesDecoded, len as u64);
+        pos + len + 1
+    }
+
+    /// Decodes the suffix at `pos` like [`Set::decode_next`], but appends it
+    /// only until `dec` has `n` bytes.
+    #[inline(always)]
+    fn decode_next_bounded(&self, pos: usize, dec: &mut Vec<u8>, n: usize) -> usize {
+        if self.alphabet.is_some() {
+            let next_pos = self.decode_next(pos, dec);
+            dec.truncate(n);
+            return next_pos;
+        }
+        let suffix = self.suffix_at(pos);
+        let len = utils::get_strlen(suffix);
+        let copied = std::cmp::min(len, n.saturating_sub(dec.len()));
+        dec.extend_from_slice(&suffix[..copied]);
+        stats::record(Counter::BytesDecoded, copied as u64);
+        pos + len + 1
+    }
+
+    /// Returns the length of the suffix at `pos` and the position following it, without decoding it.
+    #[inline(always)]
+    fn skip_next(&self, pos: usize) -> (usize, usize) {
+        let suffix = self.suffix_at(pos);
+        if let Some(alphabet) = &self.alphabet {
+            return alphabet
+                .packed_len(suffix)
+                .map_or((0, self.serialized.len()), |(len, num)| (len, pos + num));
+        }
+        let len = utils::get_strlen(suffix);
+        (len, pos + len + 1)
+    }
+
+    /// Returns the number of keys satisfying `pred`,
+    /// assuming that all the keys satisfying it precede the others.
+    fn partition_point<F>(&self, pred: F) -> usize
+    where
+        F: Fn(&[u8]) -> bool,
+    {
+        self.partition_point_from(0, pred)
+    }
+
+    /// Returns the same as [`Set::partition_point`],
+    /// assuming that the keys with ids less than `first_id` satisfy `pred`.
+    fn partition_point_from<F>(&self, first_id: usize, pred: F) -> usize
+    where
+        F: Fn(&[u8]) -> bool,
+    {
+        let (mut lo, mut hi) = (first_id / self.bucket_size(), self.num_buckets());
+        while lo < hi {
+            let mi = (lo + hi) / 2;
+            if self.with_header(mi, &pred) {
+                lo = mi + 1;
+            } else {
+                hi = mi;
+            }
+        }
+        if lo == 0 {
+            return 0;
+        }
+
+        let bi = lo - 1;
+        let beg = bi * self.bucket_size();
+        let end = std::cmp::min(beg + self.bucket_size(), self.len());
+        scratch::with_buffer(|dec| {
+            let mut pos = self.decode_header(bi, dec);
+            for id in beg + 1..end {
+                let (lcp, next_pos) = self.decode_lcp(pos);
+                dec.truncate(lcp);
+                pos = self.decode_next(next_pos, dec);
+                if !pred(dec) {
+                    return id;
+                }
+            }
+            end
+        })
+    }
+
+    fn search_bucket(&self, key: &[u8]) -> (usize, bool) {
+        let (bi, found, _) = self.search_bucket_counted(key);
+        (bi, found)
+    }
+
+    /// Returns the same as [`Set::search_bucket`] with the number of compared headers.
+    fn search_bucket_counted(&self, key: &[u8]) -> (usize, bool, usize) {
+        let mut probes = 0;
+        let mut cmp = 0;
+        let (mut lo, mut hi, mut mi) = (0, self.num_buckets(), 0);
+        while lo < hi {
+            mi = (lo + hi) / 2;
+            probes += 1;
+            stats::record(Counter::BucketProbes, 1);
+            cmp = self.with_header(mi, |header| match self.order {
+                KeyOrder::Lexicographic => utils::get_lcp(key, header).1,
+                KeyOrder::Natural => self.order.compare(header, key) as isize,
+            });
+            match cmp.cmp(&0) {
+                Ordering::Less => lo = mi + 1,
+                Ordering::Greater => hi = mi,
+                Ordering::Equal => return (mi, true, probes),
+            }
+        }
+        if cmp < 0 || mi == 0 {
+            (mi, false, probes)
+        } else {
+            (mi - 1, false, probes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arc_iter::{ArcIter, ArcPredictiveIter};
+    use byteorder::ByteOrder;
+    use locator::Lookup;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+    use staging::Staged;
+    use std::sync::Arc;
+
+    fn gen_random_keys(num: usize, max_len: usize, seed: u64) -> Vec<Vec<u8>> {
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        let mut keys = Vec::with_capacity(num);
+        for _ in 0..num {
+            let len = (rng.gen::<usize>() % (max_len - 1)) + 1;
+            keys.push((0..len).map(|_| (rng.gen::<u8>() % 4) + 1).collect());
+        }
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    #[test]
+    #[allow(clippy::needless_range_loop)]
+    fn test_toy() {
+        let keys = [
+            "deal",
+            "idea",
+            "ideal",
+            "ideas",
+            "ideology",
+            "tea",
+            "techie",
+            "technology",
+            "tie",
+            "trie",
+        ];
+
+        assert!(Builder::new(0).is_err());
+        assert!(Builder::new(3).is_err());
+        let mut builder = Builder::new(4).unwrap();
+
+        for &key in &keys {
+            builder.add(key.as_bytes()).unwrap();
+        }
+        assert!(builder.add("tri".as_bytes()).is_err());
+        assert!(builder.add(&[0xFF, 0x00]).is_err());
+
+        let set = builder.finish();
+
+        let mut locator = set.locator();
+        for i in 0..keys.len() {
+            let id = locator.run(keys[i].as_bytes()).unwrap();
+            assert_eq!(i, id);
+        }
+        assert!(locator.run("aaa".as_bytes()).is_none());
+        assert!(locator.run("tell".as_bytes()).is_none());
+        assert!(locator.run("techno".as_bytes()).is_none());
+        assert!(locator.run("zzz".as_bytes()).is_none());
+
+        let mut decoder = set.decoder();
+        for i in 0..keys.len() {
+            assert_eq!(keys[i].as_bytes(), &decoder.run(i));
+        }
+
+        let mut iterator = set.iter();
+        for i in 0..keys.len() {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(i, id);
+            assert_eq!(keys[i].as_bytes(), &dec);
+        }
+        assert!(iterator.next().is_none());
+
+        let mut iterator = set.predictive_iter("idea".as_bytes());
+        {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(1, id);
+            assert_eq!(keys[1].as_bytes(), &dec);
+        }
+        {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(2, id);
+            assert_eq!(keys[2].as_bytes(), &dec);
+        }
+        {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(3, id);
+            assert_eq!(keys[3].as_bytes(), &dec);
+        }
+        assert!(iterator.next().is_none());
+
+        let mut buffer = vec![];
+        set.serialize_into(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), set.size_in_bytes());
+
+        let other = Set::deserialize_from(&buffer[..]).unwrap();
+        let mut iterator = other.iter();
+        for i in 0..keys.len() {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(i, id);
+            assert_eq!(keys[i].as_bytes(), &dec);
+        }
+        assert!(iterator.next().is_none());
+    }
+
+    #[test]
+    #[allow(clippy::needless_range_loop)]
+    fn test_random() {
+        let keys = gen_random_keys(10000, 8, 11);
+        let mut builder = Builder::new(8).unwrap();
+
+        for key in &keys {
+            builder.add(key).unwrap();
+        }
+        let set = builder.finish();
+
+        let mut locator = set.locator();
+        for i in 0..keys.len() {
+            let id = locator.run(&keys[i]).unwrap();
+            assert_eq!(i, id);
+        }
+
+        let queries = gen_random_keys(1000, 8, 12);
+        let expected: Vec<_> = queries.iter().map(|q| locator.run(q)).collect();
+        assert_eq!(locator.run_many(&queries), expected);
+        for (key, query) in keys.iter().zip(&queries) {
+            let mid = key.len() / 2;
+            assert_eq!(
+                locator.run_parts(&[&key[..mid], &[], &key[mid..]]),
+                locator.run(key)
+            );
+            assert_eq!(
+                locator.run_parts(&[&query[..1], &query[1..]]),
+                locator.run(query)
+            );
+        }
+        assert_eq!(locator.run_parts(&[b"\x01"]), locator.run(b"\x01"));
+
+        let mut decoder = set.decoder();
+        for i in 0..keys.len() {
+            let dec = decoder.run(i);
+            assert_eq!(&keys[i], &dec);
+        }
+
+        let mut decoder = Decoder::with_cache(&set, 4, 256);
+        let mut rng = ChaChaRng::seed_from_u64(19);
+        for _ in 0..keys.len() {
+            let i = rng.gen_range(0..keys.len());
+            let dec = decoder.run(i);
+            assert_eq!(&keys[i], &dec);
+        }
+
+        let mut iterator = set.iter();
+        for i in 0..keys.len() {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(i, id);
+            assert_eq!(&keys[i], &dec);
+        }
+        assert!(iterator.next().is_none());
+
+        assert_eq!(set.first(), Some((0, keys[0].clone())));
+        let mut uncached = set.decoder();
+        let queries = gen_random_keys(100, 8, 13);
+        for _ in 0..1000 {
+            let i = rng.gen_range(0..keys.len());
+            let query = &queries[i % queries.len()];
+            assert_eq!(set.cmp_key(i, query), keys[i].cmp(query));
+            assert_eq!(set.cmp_key(i, &keys[i]), Ordering::Equal);
+            assert_eq!(
+                set.cmp_key(i, &keys[i][..keys[i].len() - 1]),
+                Ordering::Greater
+            );
+        }
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(set.key_len(i), key.len());
+            for n in [0, 1, 4, 8] {
+                assert_eq!(uncached.run_prefix(i, n), key[..n.min(key.len())]);
+                assert_eq!(decoder.run_prefix(i, n), key[..n.min(key.len())]);
+            }
+            let expected = std::str::from_utf8(key).unwrap();
+            assert_eq!(uncached.run_str(i).unwrap(), expected);
+            assert_eq!(set.locator().lookup(key), Lookup::Found(i));
+            assert_eq!(uncached.run_str_lossy(i), expected);
+            assert_eq!(decoder.run_str_lossy(i), expected);
+        }
+        assert_eq!(
+            set.last(),
+            Some((keys.len() - 1, keys[keys.len() - 1].clone()))
+        );
+        let ids: Vec<_> = (0..1000).map(|_| rng.gen_range(0..keys.len())).collect();
+        let mut decoded = vec![None; ids.len()];
+        let mut prev_id = 0;
+        for (index, id, key) in set.iter_bucket_order(&ids) {
+            assert!(prev_id <= id);
+            assert_eq!(ids[index], id);
+            decoded[index] = Some(key);
+            prev_id = id;
+        }
+        for (i, &id) in ids.iter().enumerate() {
+            assert_eq!(decoded[i].as_ref(), Some(&keys[id]));
+        }
+        let mut streamed = vec![];
+        let mut reader = set.reader(0xFF);
+        let mut buf = [0; 7];
+        loop {
+            let num = reader.read(&mut buf).unwrap();
+            if num == 0 {
+                break;
+            }
+            streamed.extend_from_slice(&buf[..num]);
+        }
+        let mut expected = keys.join(&0xFF);
+        expected.push(0xFF);
+        assert_eq!(streamed, expected);
+        let (bytes, offsets) = set.to_columns();
+        assert_eq!(offsets.len(), keys.len() + 1);
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(&bytes[offsets[i]..offsets[i + 1]], &key[..]);
+        }
+        let mut front_coded = vec![];
+        for (i, key) in keys.iter().enumerate() {
+            let lcp = if i == 0 {
+                0
+            } else {
+                utils::get_lcp(&keys[i - 1], key).0
+            };
+            front_coded.extend_from_slice(format!("{}\t", lcp).as_bytes());
+            front_coded.extend_from_slice(&key[lcp..]);
+            front_coded.push(b'\n');
+        }
+        let mut builder = Builder::new(8).unwrap();
+        builder.add_front_coded(&front_coded[..]).unwrap();
+        assert_eq!(builder.finish().serialized[..], set.serialized[..]);
+        for broken in ["0\tA\n2\tB\n", "0\tA\nB\n", "x\tA\n"] {
+            assert!(Builder::new(8)
+                .unwrap()
+                .add_front_coded(broken.as_bytes())
+                .is_err());
+        }
+
+        let mut builder = Builder::new(8).unwrap();
+        builder.add_columnar(&bytes, &offsets).unwrap();
+        let other = builder.finish();
+        assert_eq!(other.serialized[..], set.serialized[..]);
+        assert!(Builder::new(8)
+            .unwrap()
+            .add_columnar(&bytes, &[2, 1])
+            .is_err());
+
+        let mut iterator = set.iter();
+        let mut i = 0;
+        for n in [0, 3, 8, 100, 1] {
+            assert_eq!(iterator.nth(n), Some((i + n, keys[i + n].clone())));
+            i += n + 1;
+        }
+        assert!(iterator.nth(keys.len()).is_none());
+
+        let hasher = std::collections::hash_map::RandomState::new();
+        let expected: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, k)| (i, std::hash::BuildHasher::hash_one(&hasher, k.as_slice())))
+            .collect();
+        assert_eq!(set.hash_iter(hasher).collect::<Vec<_>>(), expected);
+
+        let mut buffer = vec![];
+        set.serialize_into(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), set.size_in_bytes());
+
+        let other = Set::deserialize_from(&buffer[..]).unwrap();
+        let mut iterator = other.iter();
+        for i in 0..keys.len() {
+            let (id, dec) = iterator.next().unwrap();
+            assert_eq!(i, id);
+            assert_eq!(&keys[i], &dec);
+        }
+        assert!(iterator.next().is_none());
+    }
+
+    #[test]
+    fn test_predictive_iter() {
+        // The first match is the header of the next bucket.
+        let set = Set::with_bucket_size(["a", "b", "ca", "cb"], 2).unwrap();
+        let expected = vec![(2, b"ca".to_vec()), (3, b"cb".to_vec())];
+        assert_eq!(set.predictive_iter(b"c").collect::<Vec<_>>(), expected);
+        assert!(set.contains_prefix(b"c") && !set.contains_prefix(b"bb"));
+
+        let keys = gen_random_keys(3000, 10, 23);
+        let prefixes = gen_random_keys(300, 4, 29);
+        for bucket_size in [1, 4, 32] {
+            let set = Set::with_bucket_size(&keys, bucket_size).unwrap();
+            let remapped = set.remap_alphabet().unwrap();
+            for prefix in &prefixes {
+                let expected: Vec<_> = keys
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, k)| k.starts_with(prefix))
+                    .map(|(i, k)| (i, k.clone()))
+                    .collect();
+                assert_eq!(set.predictive_iter(prefix).collect::<Vec<_>>(), expected);
+                assert_eq!(
+                    remapped.predictive_iter(prefix).collect::<Vec<_>>(),
+                    expected
+                );
+                assert_eq!(set.contains_prefix(prefix), !expected.is_empty());
+                assert_eq!(remapped.contains_prefix(prefix), !expected.is_empty());
+
+                let mut iter = remapped.predictive_iter(prefix);
+                let mut buf = prefix.clone();
+                for (id, key) in &expected {
+                    assert_eq!(iter.next_into(&mut buf), Some(*id));
+                    assert_eq!(&buf, key);
+                }
+                assert_eq!(iter.next_into(&mut buf), None);
+
+                let mut iter = set.predictive_iter(prefix);
+                for n in (0..=expected.len()).rev() {
+                    assert_eq!(iter.len(), n);
+                    iter.next();
+                }
+                assert_eq!(iter.next(), None);
+            }
+
+            let mut iter = set.iter();
+            iter.nth(10);
+            assert_eq!(iter.len(), keys.len() - 11);
+        }
+    }
+
+    #[test]
+    fn test_arc_iter() {
+        let keys = gen_random_keys(1000, 8, 31);
+        let set = Arc::new(Set::with_bucket_size(&keys, 4).unwrap());
+
+        let mut iter = ArcIter::new(set.clone());
+        assert_eq!(iter.len(), keys.len());
+        assert_eq!(iter.nth(10), Some((10, keys[10].clone())));
+        iter.seek_id(500);
+        let handle = std::thread::spawn(move || iter.collect::<Vec<_>>());
+        assert_eq!(
+            handle.join().unwrap(),
+            set.iter().skip(500).collect::<Vec<_>>()
+        );
+
+        let prefix = [1, 2];
+        let iter = ArcPredictiveIter::new(set.clone(), prefix);
+        assert_eq!(iter.len(), set.prefix_range(prefix).len());
+        let handle = std::thread::spawn(move || iter.collect::<Vec<_>>());
+        assert_eq!(
+            handle.join().unwrap(),
+            set.predictive_iter(prefix).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_for_each_bucket() {
+        let keys = gen_random_keys(3000, 10, 37);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+
+        let mut decoded = vec![];
+        set.for_each_bucket(|bi, bucket| {
+            assert_eq!(bucket.first_id(), bi * 8);
+            decoded.extend(bucket.iter().map(|(id, key)| (id, key.to_vec())));
+        });
+        assert_eq!(decoded, set.iter().collect::<Vec<_>>());
+
+        #[cfg(feature = "rayon")]
+        {
+            let num_keys = set.map_reduce_buckets_par(|_, bucket| bucket.len(), |a, b| a + b);
+            assert_eq!(num_keys, Some(keys.len()));
+            assert_eq!(set.verify_against(&keys), None);
+            let mut broken = keys.clone();
+            broken[keys.len() / 2].push(0);
+            assert_eq!(set.verify_against(&broken).unwrap().id, keys.len() / 2);
+            let discrepancy = set.verify_against(&keys[..keys.len() - 1]).unwrap();
+            assert_eq!(discrepancy.id, keys.len() - 1);
+            assert_eq!(discrepancy.expected, None);
+            let mut longer = keys.clone();
+            longer.push(vec![9]);
+            assert_eq!(set.verify_against(&longer).unwrap().actual, None);
+            let sums = std::sync::Mutex::new(vec![0; set.num_buckets()]);
+            set.for_each_bucket_par(|bi, bucket| {
+                sums.lock().unwrap()[bi] = bucket.iter().map(|(_, key)| key.len()).sum();
+            });
+            assert_eq!(
+                sums.into_inner().unwrap().iter().sum::<usize>(),
+                keys.iter().map(|k| k.len()).sum::<usize>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_external_ids() {
+        let keys = gen_random_keys(1000, 8, 13);
+        let mut rng = ChaChaRng::seed_from_u64(17);
+        let ext_ids: Vec<u64> = (0..keys.len()).map(|_| rng.gen::<u64>() >> 8).collect();
+
+        let mut builder = Builder::new(8).unwrap();
+        for (key, &ext_id) in keys.iter().zip(ext_ids.iter()) {
+            builder.add_with_id(key, ext_id).unwrap();
+        }
+        assert!(builder.add(&[0xFF]).is_err());
+        let set = builder.finish();
+
+        let mut buffer = vec![];
+        set.serialize_into(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), set.size_in_bytes());
+        let other = Set::deserialize_from(&buffer[..]).unwrap();
+
+        let mut locator = other.locator();
+        let mut decoder = other.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(other.external_id(i), Some(ext_ids[i]));
+            assert_eq!(other.internal_id(ext_ids[i]), Some(i));
+            assert_eq!(locator.run_external(key), Some(ext_ids[i]));
+            assert_eq!(decoder.run_external(ext_ids[i]).as_ref(), Some(key));
+        }
+    }
+
+    #[test]
+    fn test_payloads() {
+        let keys = gen_random_keys(1000, 8, 31);
+        let mut builder = Builder::new(8).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            builder.add_with_payload(key, (i * i) as u64).unwrap();
+        }
+        assert!(builder.add_with_id(&[0xFF], 0).is_err());
+        let set = builder.finish();
+
+        let mut buffer = vec![];
+        set.serialize_into(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), set.size_in_bytes());
+        let other = Set::deserialize_from(&buffer[..]).unwrap();
+
+        let mut locator = other.locator();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(other.payload(i), Some((i * i) as u64));
+            assert_eq!(locator.run_with_payload(key), Some((i, (i * i) as u64)));
+        }
+
+        let other = set.rebucket(4).unwrap();
+        for i in 0..keys.len() {
+            assert_eq!(other.payload(i), Some((i * i) as u64));
+        }
+
+        let mut other = set.clone();
+        other.set_payload(5, u64::MAX).unwrap();
+        for i in 0..keys.len() {
+            let expected = if i == 5 { u64::MAX } else { (i * i) as u64 };
+            assert_eq!(other.payload(i), Some(expected));
+        }
+        assert!(other.set_payload(keys.len(), 0).is_err());
+
+        // Broken payload areas are rejected without changing the payloads.
+        let mut data = vec![];
+        other.serialize_payloads_into(&mut data).unwrap();
+        let num_chunks = (&data[..]).read_u64::<LittleEndian>().unwrap() as usize;
+        let tail = 8 + num_chunks * 8;
+        for (pos, x) in [(0, 0), (tail, keys.len() as u64 + 1), (tail + 8, 65)] {
+            let mut broken = data.clone();
+            broken[pos..pos + 8].copy_from_slice(&x.to_le_bytes());
+            let mut restored = set.clone();
+            assert!(restored.deserialize_payloads_from(&broken[..]).is_err());
+            assert_eq!(restored.payload(5), Some(25));
+        }
+        let mut restored = set.clone();
+        restored.deserialize_payloads_from(&data[..]).unwrap();
+        assert_eq!(restored.payload(5), Some(u64::MAX));
+    }
+
+    #[test]
+    fn test_values() {
+        let keys = gen_random_keys(1000, 8, 113);
+        for &codec in &[values::ValueCodec::Raw, values::ValueCodec::FrontCoded] {
+            let mut builder = Builder::new(8).unwrap().with_value_codec(codec);
+            for (i, key) in keys.iter().enumerate() {
+                let value = format!("https://example.com/{}/\0{}", i / 7, i);
+                builder.add_with_value(key, value.as_bytes()).unwrap();
+            }
+            assert!(builder.add(&[0xFF]).is_err());
+            let set = builder.finish();
+
+            let mut buffer = vec![];
+            set.serialize_into(&mut buffer).unwrap();
+            assert_eq!(buffer.len(), set.size_in_bytes());
+            let other = Set::deserialize_from(&buffer[..]).unwrap();
+            assert_eq!(other.value_codec(), Some(codec));
+
+            let mut locator = other.locator();
+            let rebucketed = set.rebucket(2).unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                let value = format!("https://example.com/{}/\0{}", i / 7, i).into_bytes();
+                assert_eq!(other.value(i).as_ref(), Some(&value));
+                assert_eq!(rebucketed.value(i).as_ref(), Some(&value));
+                assert_eq!(locator.run_with_value(key), Some((i, value)));
+            }
+            assert_eq!(rebucketed.value_codec(), Some(codec));
+        }
+
+        // Broken lengths of the data, following the raw codec, fail at the end of the input.
+        let mut bytes = vec![0];
+        IntVector::new().serialize_into(&mut bytes).unwrap();
+        bytes.extend_from_slice(&u64::to_le_bytes(1 << 40));
+        assert!(values::Values::deserialize_from(&bytes[..]).is_err());
+        let mut bytes = vec![0];
+        utils::vbyte::append(&mut bytes, 0);
+        utils::vbyte::append(&mut bytes, usize::MAX >> 1);
+        assert!(values::Values::deserialize_compact_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn test_utf8() {
+        let keys = ["Kanda", "Martínez-Prieto", "ナヴァロ"];
+        let mut builder = Builder::new(2).unwrap().require_utf8(true);
+        builder.extend(keys).unwrap();
+        let err = builder.extend([&[0xE3, 0x83, 0xFF][..]]).unwrap_err();
+        let err = err.downcast_ref::<builder::AddError>().unwrap();
+        assert_eq!(err.kind, builder::AddErrorKind::InvalidUtf8);
+        let set = builder.finish();
+        assert!(set.is_utf8());
+
+        let mut data = Vec::new();
+        set.serialize_into(&mut data).unwrap();
+        assert_eq!(data.len(), set.size_in_bytes());
+        assert!(Set::deserialize_from(&data[..]).unwrap().is_utf8());
+        assert!(set.rebucket(4).unwrap().is_utf8());
+
+        #[cfg(feature = "merge")]
+        {
+            let mut merged = Vec::new();
+            merge::merge_serialized(vec![&data[..], &data[..]], &mut merged).unwrap();
+            assert!(Set::deserialize_from(&merged[..]).unwrap().is_utf8());
+        }
+
+        // A broken flag is detected by verification, not on loading.
+        let mut data = Vec::new();
+        Set::new([&[0xFF][..]])
+            .unwrap()
+            .serialize_into(&mut data)
+            .unwrap();
+        data[4] = 1;
+        let set = Set::deserialize_from(&data[..]).unwrap();
+        assert!(set.is_utf8());
+        assert!(set.verify().is_err());
+    }
+
+    #[test]
+    fn test_max_key_len() {
+        let mut builder = Builder::new(2).unwrap().max_key_len(6);
+        builder.extend(["ICDM", "SIGKDD"]).unwrap();
+        let err = builder.extend(["SIGMOD2", "SIGMOD"]).unwrap_err();
+        let err = err.downcast_ref::<builder::AddError>().unwrap();
+        assert_eq!(
+            err.kind,
+            builder::AddErrorKind::TooLong {
+                len: 7,
+                max_key_len: 6
+            }
+        );
+        let set = builder.finish();
+        assert_eq!(set.max_key_len(), Some(6));
+
+        let mut data = Vec::new();
+        set.serialize_into(&mut data).unwrap();
+        assert_eq!(data.len(), set.size_in_bytes());
+        assert_eq!(
+            Set::deserialize_from(&data[..]).unwrap().max_key_len(),
+            Some(6)
+        );
+        assert_eq!(set.rebucket(4).unwrap().max_key_len(), Some(6));
+
+        #[cfg(feature = "merge")]
+        {
+            let other = Builder::new(2).unwrap().max_key_len(8).finish();
+            let mut other_data = Vec::new();
+            other.serialize_into(&mut other_data).unwrap();
+            let mut merged = Vec::new();
+            merge::merge_serialized(vec![&data[..], &other_data[..]], &mut merged).unwrap();
+            let merged = Set::deserialize_from(&merged[..]).unwrap();
+            assert_eq!(merged.max_key_len(), Some(8));
+            assert_eq!(merged.len(), 2);
+        }
+
+        // Truncation never splits a character of UTF-8 keys.
+        let mut builder = Builder::new(2)
+            .unwrap()
+            .require_utf8(true)
+            .max_key_len(5)
+            .key_len_policy(builder::KeyLenPolicy::Truncate);
+        builder.extend(["Kanda", "Martínez-Prieto"]).unwrap();
+        assert!(builder.add("Martínez".as_bytes()).is_err());
+        let set = builder.finish();
+        assert_eq!(set.decoder().run(1), "Mart".as_bytes());
+    }
+
+    #[test]
+    fn test_natural_order() {
+        let mut keys: Vec<String> = (0..300)
+            .flat_map(|i| {
+                [
+                    format!("f{}", i),
+                    format!("f{}x{}", i, i % 7),
+                    format!("g{:03}", i),
+                ]
+            })
+            .collect();
+        keys.sort_by(|a, b| KeyOrder::Natural.compare(a.as_bytes(), b.as_bytes()));
+        assert_eq!(&keys[..3], ["f0", "f0x0", "f1"]);
+
+        let mut builder = Builder::new(4).unwrap().key_order(KeyOrder::Natural);
+        builder.extend(&keys).unwrap();
+        assert!(builder.add(b"f299").is_err());
+        let set = builder.finish();
+
+        let mut locator = set.locator();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(locator.run(key), Some(i));
+            assert_eq!(set.rank(key), i);
+        }
+        let queries: Vec<_> = keys.iter().map(|k| format!("{}y", k)).collect();
+        assert!(locator.run_many(&queries).iter().all(Option::is_none));
+        assert_eq!(locator.run_many(&keys)[10], Some(10));
+        let id = keys.iter().position(|k| k == "f12x5");
+        assert_eq!(locator.run_parts(&["f1", "2x", "5"]), id);
+
+        let expected: Vec<_> = keys
+            .iter()
+            .enumerate()
+            .filter(|(_, k)| k.starts_with("f1"))
+            .map(|(i, k)| (i, k.as_bytes().to_vec()))
+            .collect();
+        assert_eq!(set.predictive_iter(b"f1").collect::<Vec<_>>(), expected);
+        assert_eq!(set.predictive_iter(b"f1").len(), expected.len());
+        let mut iter = set.predictive_iter(b"f1");
+        for (id, key) in &expected {
+            assert_eq!(iter.next_suffix(), Some((*id, &key[2..])));
+        }
+        assert_eq!(set.select_in_prefix(b"f1", 3), Some(expected[3].clone()));
+        assert_eq!(set.prefix_range(b"g").len(), 300);
+        assert_eq!(set.children(b"f29"), b"0123456789x".to_vec());
+
+        let mut data = Vec::new();
+        set.serialize_into(&mut data).unwrap();
+        let other = Set::deserialize_from(&data[..]).unwrap();
+        assert_eq!(other.key_order(), KeyOrder::Natural);
+        assert!(other.is_subset(&set));
+        assert_eq!(set.rebucket(8).unwrap().key_order(), KeyOrder::Natural);
+
+        #[cfg(feature = "merge")]
+        {
+            let mut merged = Vec::new();
+            merge::merge_serialized(vec![&data[..], &data[..]], &mut merged).unwrap();
+            let merged = Set::deserialize_from(&merged[..]).unwrap();
+            assert_eq!(
+                merged.iter().collect::<Vec<_>>(),
+                set.iter().collect::<Vec<_>>()
+            );
+        }
+
+        let lex = Set::new(["f1", "f10"]).unwrap();
+        assert!(lex.is_subset(&set));
+        #[cfg(feature = "merge")]
+        {
+            let mut lex_data = Vec::new();
+            lex.serialize_into(&mut lex_data).unwrap();
+            assert!(merge::merge_serialized(vec![&data[..], &lex_data[..]], Vec::new()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_alphabet() {
+        let keys = gen_random_keys(10000, 30, 19);
+        let plain = Set::with_bucket_size(&keys, 16).unwrap();
+        let set = plain.remap_alphabet().unwrap();
+        assert_eq!(set.alphabet().unwrap().bits_per_symbol(), 2);
+        assert!(set.size_in_bytes() < plain.size_in_bytes());
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            plain.iter().collect::<Vec<_>>()
+        );
+
+        let mut locator = set.locator();
+        let mut decoder = set.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(locator.run(key), Some(i));
+            assert_eq!(decoder.run(i), *key);
+            assert_eq!(set.rank(key), i);
+            assert_eq!(set.key_len(i), key.len());
+            assert_eq!(decoder.run_prefix(i, 7), key[..7.min(key.len())]);
+            assert_eq!(set.cmp_key(i, &keys[i / 2]), key.cmp(&keys[i / 2]));
+        }
+        assert_eq!(locator.run(b"\x05"), None);
+        assert_eq!(locator.run_many(&keys)[100], Some(100));
+        assert_eq!(
+            set.predictive_iter([1, 2]).collect::<Vec<_>>(),
+            plain.predictive_iter([1, 2]).collect::<Vec<_>>()
+        );
+        assert!(plain.is_subset(&set) && set.is_subset(&plain));
+
+        let mut data = Vec::new();
+        set.serialize_into(&mut data).unwrap();
+        assert_eq!(data.len(), set.size_in_bytes());
+        let other = Set::deserialize_from(&data[..]).unwrap();
+        assert_eq!(other.alphabet(), set.alphabet());
+        assert_eq!(other.rebucket(4).unwrap().alphabet(), set.alphabet());
+        #[cfg(feature = "merge")]
+        assert!(merge::merge_serialized(vec![&data[..]], Vec::new()).is_err());
+
+        let mut builder = Builder::new(4).unwrap().alphabet(Alphabet::dna());
+        let err = builder.extend(["ACGT", "ANT"]).unwrap_err();
+        let err = err.downcast_ref::<builder::AddError>().unwrap();
+        assert_eq!(
+            err.kind,
+            builder::AddErrorKind::OutOfAlphabet { byte: b'N' }
+        );
+        assert!(Alphabet::new(&[0, 1]).is_err());
+        assert!(Alphabet::from_keys([(1..=255).collect::<Vec<u8>>()]).is_err());
+
+        // Broken packed suffixes are rejected by verification, but never make queries panic.
+        assert!(Set::deserialize_from(&data[..]).unwrap().verify().is_ok());
+        let first = 4 + 1 + 5 + set.pointers.size_in_bytes() + 8;
+        for len in [&[0x7F][..], &[0xFF, 0xFF, 0xFF, 0x7F]] {
+            let mut data = data.clone();
+            data[first..first + len.len()].copy_from_slice(len);
+            let broken = Set::deserialize_from(&data[..]).unwrap();
+            assert!(broken.verify().is_err());
+            let mut locator = broken.locator();
+            let mut decoder = broken.decoder();
+            for (i, key) in keys.iter().enumerate().step_by(7) {
+                let _ = locator.run(key);
+                let _ = decoder.run(i);
+                let _ = broken.key_len(i);
+                let _ = broken.cmp_key(i, key);
+            }
+            assert!(broken.iter().count() <= keys.len());
+            assert!(broken.predictive_iter([1, 2]).count() <= keys.len());
+        }
+    }
+
+    #[test]
+    fn test_aligned() {
+        let keys = gen_random_keys(1000, 8, 57);
+        let mut builder = Builder::new(8).unwrap().max_key_len(100);
+        for (i, key) in keys.iter().enumerate() {
+            builder
+                .add_with_value(key, format!("{}", i * 3).as_bytes())
+                .unwrap();
+        }
+        let set = builder.finish();
+
+        let mut buffer = vec![];
+        set.serialize_into_aligned(&mut buffer).unwrap();
+        assert_eq!(buffer.len() % ALIGNMENT, 0);
+        assert_eq!(buffer[4] & FLAG_ALIGNED, FLAG_ALIGNED);
+        // The chunks of the pointer table follow the header, the key limit, and their number.
+        let num_chunks = (&buffer[16..24]).read_u64::<LittleEndian>().unwrap() as usize;
+        assert_eq!(num_chunks * 8, set.pointers.size_in_bytes() - 32);
+
+        let other = Set::deserialize_from(&buffer[..]).unwrap();
+        let mut plain = vec![];
+        other.serialize_into(&mut plain).unwrap();
+        assert_eq!(plain.len(), set.size_in_bytes());
+        let mut locator = other.locator();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(
+                locator.run_with_value(key),
+                Some((i, format!("{}", i * 3).into_bytes()))
+            );
+        }
+
+        let mut broken = buffer.clone();
+        broken[7] = 1;
+        assert!(Set::deserialize_from(&broken[..]).is_err());
+        let mut broken = buffer.clone();
+        broken[5] = ALIGNED_VERSION + 1;
+        assert!(Set::deserialize_from(&broken[..]).is_err());
+
+        let mut buffer = vec![];
+        Set::new(["ICDM"])
+            .unwrap()
+            .serialize_into_aligned(&mut buffer)
+            .unwrap();
+        #[cfg(feature = "merge")]
+        assert!(merge::merge_serialized(vec![&buffer[..]], &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_bucket_aligned() {
+        let keys = gen_random_keys(1000, 20, 58);
+        let mut builder = Builder::new(4).unwrap().align_buckets(true);
+        builder.extend(&keys).unwrap();
+        let set = builder.finish();
+        assert!(set.build_config().align_buckets);
+        for bi in 0..set.num_buckets() {
+            assert!((set.pointers.get(bi) as usize).is_multiple_of(CACHE_LINE));
+        }
+
+        let mut locator = set.locator();
+        let mut decoder = set.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(locator.run(key), Some(i));
+            assert_eq!(&decoder.run(i), key);
+        }
+        assert!(set.iter().map(|(_, key)| key).eq(keys.iter().cloned()));
+        let prefix = &keys[500][..1];
+        assert_eq!(
+            set.predictive_iter(prefix).count(),
+            keys.iter().filter(|key| key.starts_with(prefix)).count()
+        );
+
+        for compact in [false, true] {
+            let mut data = vec![];
+            if compact {
+                set.serialize_into_compact(&mut data).unwrap();
+            } else {
+                set.serialize_into(&mut data).unwrap();
+            }
+            let other = Set::deserialize_from(&data[..]).unwrap();
+            assert!(other.build_config().align_buckets);
+            assert_eq!(other.serialized[..], set.serialized[..]);
+        }
+        let rebucketed = set.rebucket(8).unwrap();
+        assert!((rebucketed.pointers.get(1) as usize).is_multiple_of(CACHE_LINE));
+
+        let mut data = vec![];
+        set.serialize_into(&mut data).unwrap();
+        #[cfg(feature = "merge")]
+        assert!(merge::merge_serialized(vec![&data[..]], &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_compact() {
+        let keys = gen_random_keys(1000, 8, 61);
+        let mut builder = Builder::new(4).unwrap().key_order(KeyOrder::Natural);
+        let mut natural_keys = keys.clone();
+        natural_keys.sort_by(|a, b| KeyOrder::Natural.compare(a, b));
+        for (i, key) in natural_keys.iter().enumerate() {
+            builder.add_with_payload(key, i as u64 * 7).unwrap();
+        }
+        let set = builder.finish();
+
+        let mut plain = vec![];
+        set.serialize_into(&mut plain).unwrap();
+        let mut compact = vec![];
+        set.serialize_into_compact(&mut compact).unwrap();
+        assert!(compact.len() < plain.len());
+
+        let other = Set::deserialize_from(&compact[..]).unwrap();
+        let mut buffer = vec![];
+        other.serialize_into(&mut buffer).unwrap();
+        assert_eq!(buffer, plain);
+
+        for n in [5, compact.len() / 2, compact.len() - 1] {
+            assert!(Set::deserialize_from(&compact[..n]).is_err());
+        }
+
+        let tiny = Set::new(["ICDM"]).unwrap();
+        let mut compact = vec![];
+        tiny.serialize_into_compact(&mut compact).unwrap();
+        assert!(compact.len() * 4 < tiny.size_in_bytes());
+        let other = Set::deserialize_from(&compact[..]).unwrap();
+        assert_eq!(other.locator().run(b"ICDM"), Some(0));
+
+        let empty = Set::new(Vec::<&str>::new()).unwrap();
+        let mut compact = vec![];
+        empty.serialize_into_compact(&mut compact).unwrap();
+        assert!(Set::deserialize_from(&compact[..]).unwrap().is_empty());
+
+        for codec in [ValueCodec::Raw, ValueCodec::FrontCoded] {
+            let mut builder = Builder::new(4).unwrap().with_value_codec(codec);
+            for key in &keys {
+                builder.add_with_value(key, &key.repeat(3)).unwrap();
+            }
+            let set = builder.finish();
+            let mut plain = vec![];
+            set.serialize_into(&mut plain).unwrap();
+            let mut compact = vec![];
+            set.serialize_into_compact(&mut compact).unwrap();
+            assert!(compact.len() < plain.len());
+            let other = Set::from_bytes_portable(&compact).unwrap();
+            for (i, key) in keys.iter().enumerate() {
+                assert_eq!(other.value(i), Some(key.repeat(3)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_journal() {
+        let mut keys = gen_random_keys(1000, 8, 67);
+        keys.sort();
+        keys.dedup();
+        let (head, tail) = keys.split_at(keys.len() - 10);
+
+        let mut set = Set::with_bucket_size(head, 4).unwrap();
+        let mut journal = Journal::new();
+        let mut data = vec![];
+        set.serialize_dirty_into(&mut data, &mut journal).unwrap();
+        let first_len = data.len();
+        set.append_greater(tail).unwrap();
+        set.serialize_dirty_into(&mut data, &mut journal).unwrap();
+        assert!(data.len() - first_len < first_len / 10);
+
+        let (other, other_journal) = Set::deserialize_journal_from(&data[..]).unwrap();
+        assert_eq!(other_journal, journal);
+        let mut expected = vec![];
+        set.serialize_into(&mut expected).unwrap();
+        let mut buffer = vec![];
+        other.serialize_into(&mut buffer).unwrap();
+        assert_eq!(buffer, expected);
+
+        // A torn record at the end is ignored.
+        let (torn, _) = Set::deserialize_journal_from(&data[..data.len() - 3]).unwrap();
+        assert_eq!(torn.len(), head.len());
+        assert!(Set::deserialize_journal_from(&data[..first_len - 3]).is_err());
+
+        // The replay stops at the first corrupted record, ignoring the following ones.
+        let mut corrupted = data.clone();
+        corrupted[first_len + 20] ^= 1;
+        let mut journal = other_journal;
+        set.serialize_dirty_into(&mut corrupted, &mut journal)
+            .unwrap();
+        let (stopped, stopped_journal) = Set::deserialize_journal_from(&corrupted[..]).unwrap();
+        assert_eq!(stopped.len(), head.len());
+        let (_, first_journal) = Set::deserialize_journal_from(&data[..first_len]).unwrap();
+        assert_eq!(stopped_journal, first_journal);
+        let mut garbage = data.clone();
+        garbage.extend_from_slice(&[0xFF; 16]);
+        let (other, _) = Set::deserialize_journal_from(&garbage[..]).unwrap();
+        assert_eq!(other.len(), keys.len());
+        let mut corrupted = data.clone();
+        corrupted[first_len - 1] ^= 1;
+        assert!(Set::deserialize_journal_from(&corrupted[..]).is_err());
+
+        // Records must continue from the previous ones.
+        let mut skipped = data[..first_len].to_vec();
+        let mut journal = Journal::new();
+        set.serialize_dirty_into(&mut skipped, &mut journal)
+            .unwrap();
+        assert!(Set::deserialize_journal_from(&skipped[..]).is_err());
+        assert!(set.serialize_dirty_into(vec![], &mut journal).is_ok());
+        assert!(Set::with_bucket_size(head, 4)
+            .unwrap()
+            .serialize_dirty_into(vec![], &mut journal)
+            .is_err());
+
+        // A journal and a store are not confused with each other.
+        let mut store = store::DictStore::new();
+        store.insert("head", Set::with_bucket_size(head, 4).unwrap());
+        let mut stored = vec![];
+        store.serialize_into(&mut stored).unwrap();
+        let e = Set::deserialize_journal_from(&stored[..]).err().unwrap();
+        assert_eq!(e.to_string(), "unknown cookie value");
+        let e = store::DictStore::deserialize_from(&data[..]).err().unwrap();
+        assert_eq!(e.to_string(), "unknown cookie value");
+    }
+
+    #[test]
+    fn test_portable() {
+        let keys = [
+            "ICDM",
+            "ICML",
+            "SIGIR",
+            "SIGKDD",
+            "SIGMOD",
+            "SIGSPATIAL",
+            "WSDM",
+            "WWW",
+        ];
+        let mut builder = Builder::new(4).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            builder
+                .add_with_payload(key.as_bytes(), (i as u64) << 40 | 0x1234)
+                .unwrap();
+        }
+        let set = builder.finish();
+
+        // The fixtures were written on x86_64 and must be reproduced on every target.
+        let mut plain = vec![];
+        set.serialize_into(&mut plain).unwrap();
+        assert_eq!(&plain[..], include_bytes!("../testdata/golden.fcsd"));
+        let mut aligned = vec![];
+        set.serialize_into_aligned(&mut aligned).unwrap();
+        assert_eq!(
+            &aligned[..],
+            include_bytes!("../testdata/golden_aligned.fcsd")
+        );
+        let mut compact = vec![];
+        set.serialize_into_compact(&mut compact).unwrap();
+        assert_eq!(
+            &compact[..],
+            include_bytes!("../testdata/golden_compact.fcsd")
+        );
+
+        for bytes in [plain, aligned, compact] {
+            let other = Set::from_bytes_portable(&bytes).unwrap();
+            let mut locator = other.locator();
+            for (i, key) in keys.iter().enumerate() {
+                assert_eq!(
+                    locator.run_with_payload(key),
+                    Some((i, (i as u64) << 40 | 0x1234))
+                );
+            }
+            let mut trailing = bytes;
+            trailing.push(0);
+            assert!(Set::from_bytes_portable(&trailing).is_err());
+        }
+    }
+
+    #[test]
+    fn test_from_static_bytes() {
+        // Placed like the bytes embedded by the accessors of codegen::embed.
+        #[repr(C, align(8))]
+        struct Aligned<T: ?Sized>(T);
+        static ALIGNED: &Aligned<[u8]> =
+            &Aligned(*include_bytes!("../testdata/golden_aligned.fcsd"));
+        static PLAIN: &[u8] = include_bytes!("../testdata/golden.fcsd");
+
+        for bytes in [&ALIGNED.0, PLAIN] {
+            let set = Set::from_static_bytes(bytes).unwrap();
+            assert_eq!(set.serialized.heap_bytes(), 0);
+            assert_eq!(set.len(), 8);
+            assert_eq!(set.locator().run(b"SIGKDD"), Some(3));
+        }
+        let mut set = Set::from_static_bytes(&ALIGNED.0).unwrap();
+        if cfg!(target_endian = "little") {
+            assert_eq!(set.pointers.heap_bytes(), 0);
+            assert_eq!(set.heap_bytes(), 0);
+        }
+
+        // The payloads are copied on write, leaving the static bytes untouched.
+        set.set_payload(1, 7).unwrap();
+        assert_eq!(set.payload(1), Some(7));
+        assert_ne!(set.heap_bytes(), 0);
+        let other = Set::from_static_bytes(&ALIGNED.0).unwrap();
+        assert_eq!(other.payload(1), Some(1 << 40 | 0x1234));
+        assert!(Set::from_static_bytes(&PLAIN[..PLAIN.len() - 1]).is_err());
+    }
+
+    #[cfg(all(unix, feature = "shm"))]
+    #[test]
+    fn test_shm() {
+        let name = format!("/fcsd-test-{}", std::process::id());
+        let keys = gen_random_keys(3000, 8, 67);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        set.create_shm(&name).unwrap();
+        assert!(set.create_shm(&name).is_err());
+
+        let mut other = Set::open_shm(&name).unwrap();
+        shm::remove(&name).unwrap();
+        assert!(Set::open_shm(&name).is_err());
+        assert!(other.heap_bytes() < set.heap_bytes() - set.serialized.len());
+        if cfg!(target_endian = "little") {
+            assert_eq!(other.pointers.heap_bytes(), 0);
+        }
+
+        let mut decoder = other.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(&decoder.run(i), key);
+        }
+        // Appending a bucket copies the shared pointers.
+        other
+            .append_greater((3..11).map(|n| vec![0xFF; n]))
+            .unwrap();
+        assert_eq!(other.locator().run([0xFF; 3]), Some(keys.len()));
+        assert_ne!(other.pointers.heap_bytes(), 0);
+        let mut decoder = other.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(&decoder.run(i), key);
+        }
+        assert!(Set::open_shm("no-slash").is_err());
+
+        // Opening reads no keys, so broken keys are found only by verification.
+        let mut data = vec![];
+        Set::new([&[0xFF][..]])
+            .unwrap()
+            .serialize_into_aligned(&mut data)
+            .unwrap();
+        data[4] |= FLAG_UTF8;
+        let mut segment = shm::Segment::create(&name, data.len()).unwrap();
+        segment.as_mut_slice().copy_from_slice(&data);
+        drop(segment);
+        let broken = Set::open_shm(&name).unwrap();
+        shm::remove(&name).unwrap();
+        assert!(broken.is_utf8());
+        assert!(broken.verify().is_err());
+    }
+
+    #[test]
+    fn test_build_config() {
+        let config = BuildConfig {
+            bucket_size: 16,
+            max_key_len: Some(20),
+            key_len_policy: KeyLenPolicy::Truncate,
+            alphabet: Some(Alphabet::dna()),
+            value_codec: ValueCodec::Raw,
+            ..Default::default()
+        };
+        let mut builder = Builder::with_config(config.clone()).unwrap();
+        assert_eq!(builder.config(), config);
+        builder.add_with_value(b"ACGT", b"x").unwrap();
+        builder.add_with_value(b"GATTACA", b"y").unwrap();
+        let set = builder.finish();
+
+        let mut data = vec![];
+        set.serialize_into(&mut data).unwrap();
+        let other = Set::deserialize_from(&data[..]).unwrap();
+        let recorded = BuildConfig {
+            key_len_policy: KeyLenPolicy::Reject,
+            ..config
+        };
+        assert_eq!(other.build_config(), recorded);
+        assert_eq!(
+            other.rebucket(4).unwrap().build_config(),
+            BuildConfig {
+                bucket_size: 4,
+                ..recorded
+            }
+        );
+        assert!(Builder::with_config(BuildConfig {
+            bucket_size: 3,
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_compression() {
+        let keys = gen_random_keys(10000, 16, 131);
+        let set = Set::new(&keys).unwrap();
+        let mut plain = Vec::new();
+        set.serialize_into(&mut plain).unwrap();
+
+        let codecs = [
+            Codec::None,
+            #[cfg(feature = "zstd")]
+            Codec::Zstd { level: 3 },
+        ];
+        for &codec in &codecs {
+            let mut data = Vec::new();
+            set.serialize_into_compressed(&mut data, codec).unwrap();
+            if codec != Codec::None {
+                assert!(data.len() < plain.len());
+            }
+            let other = Set::deserialize_from_compressed(&data[..]).unwrap();
+            assert_eq!(
+                other.iter().collect::<Vec<_>>(),
+                set.iter().collect::<Vec<_>>()
+            );
+        }
+
+        let other = Set::deserialize_from_compressed(&plain[..]).unwrap();
+        assert_eq!(other.len(), set.len());
+    }
+
+    #[test]
+    fn test_shared_queries() {
+        let keys = gen_random_keys(1000, 8, 137);
+        let set = Set::with_bucket_size(&keys, 16).unwrap();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let (set, keys) = (&set, &keys);
+                s.spawn(move || {
+                    let mut key = Vec::new();
+                    for (i, k) in keys.iter().enumerate().skip(t).step_by(4) {
+                        assert_eq!(set.locate(k), Some(i));
+                        assert_eq!(set.decode(i), *k);
+                        set.decode_into(i, &mut key);
+                        assert_eq!(key, *k);
+                        assert_eq!(set.rank(k), i);
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_analysis() {
+        let keys = gen_random_keys(1000, 8, 127);
+        let analysis = analysis::analyze(&keys);
+        assert_eq!(analysis.num_keys, keys.len());
+        assert_eq!(analysis.lcp_histogram.iter().sum::<usize>(), keys.len());
+        for &bucket_size in &analysis::BUCKET_SIZES {
+            let set = Set::with_bucket_size(&keys, bucket_size).unwrap();
+            let plain = analysis.projected_bytes(analysis::Codec::PlainFc, bucket_size);
+            assert_eq!(plain, Some(set.size_in_bytes()));
+            // Keys over four symbols are compressible.
+            let htfc = analysis.projected_bytes(analysis::Codec::Htfc, bucket_size);
+            assert!(htfc < plain);
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let keys = gen_random_keys(1000, 8, 89);
+        let set = Set::with_bucket_size(&keys, 16).unwrap();
+        let queries = gen_random_keys(100, 8, 97);
+        for query in &queries {
+            let expected = keys.iter().filter(|&k| k < query).count();
+            assert_eq!(set.rank(query), expected);
+        }
+        for (lo, hi) in queries.iter().zip(queries.iter().rev()) {
+            let expected = keys.iter().filter(|&k| lo <= k && k < hi).count();
+            assert_eq!(set.count_range(lo, hi), expected);
+        }
+    }
+
+    #[test]
+    fn test_build_id_mapping() {
+        let old_keys = gen_random_keys(2000, 6, 41);
+        let new_keys = gen_random_keys(2000, 6, 43);
+        let old = Set::with_bucket_size(&old_keys, 4).unwrap();
+        let new = Set::with_bucket_size(&new_keys, 8).unwrap();
+
+        let expected: Vec<_> = old_keys
+            .iter()
+            .map(|k| new_keys.binary_search(k).ok())
+            .collect();
+        assert!(expected.iter().any(Option::is_some) && expected.iter().any(Option::is_none));
+        assert_eq!(Set::build_id_mapping(&old, &new), expected);
+
+        let mut builder = Builder::new(8).unwrap().key_order(KeyOrder::Natural);
+        let mut natural_keys = new_keys.clone();
+        natural_keys.sort_by(|a, b| KeyOrder::Natural.compare(a, b));
+        builder.extend(&natural_keys).unwrap();
+        let natural = builder.finish();
+        let mapping = Set::build_id_mapping(&old, &natural);
+        for (key, id) in old_keys.iter().zip(mapping) {
+            assert_eq!(
+                id.map(|id| &natural_keys[id]),
+                new_keys.binary_search(key).ok().map(|_| key)
+            );
+        }
+    }
+
+    #[test]
+    fn test_subset() {
+        let keys = gen_random_keys(1000, 8, 101);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        let mut rng = ChaChaRng::seed_from_u64(103);
+        for ratio in [1, 2, 10, 100] {
+            let sub: Vec<_> = keys
+                .iter()
+                .filter(|_| rng.gen::<usize>() % ratio == 0)
+                .cloned()
+                .collect();
+            let sub_set = Set::with_bucket_size(&sub, 4).unwrap();
+            assert!(sub_set.is_subset(&set));
+            assert!(set.is_superset(&sub_set));
+            assert_eq!(set.is_subset(&sub_set), sub.len() == keys.len());
+
+            let mut extra = sub.clone();
+            extra.push(vec![5]);
+            let extra_set = Set::with_bucket_size(&extra, 4).unwrap();
+            assert!(!extra_set.is_subset(&set));
+        }
+        let empty = Set::new(Vec::<&[u8]>::new()).unwrap();
+        assert!(empty.is_subset(&set));
+        assert!(!set.is_subset(&empty));
+    }
+
+    #[test]
+    fn test_prefix_range() {
+        let keys = gen_random_keys(1000, 8, 37);
+        let set = Set::with_bucket_size(&keys, 16).unwrap();
+        for prefix in gen_random_keys(100, 4, 41) {
+            let expected: Vec<_> = (0..keys.len())
+                .filter(|&i| keys[i].starts_with(&prefix))
+                .collect();
+            let range = set.prefix_range(&prefix);
+            assert_eq!(range.clone().collect::<Vec<_>>(), expected);
+            for (n, &i) in expected.iter().enumerate() {
+                let (id, key) = set.select_in_prefix(&prefix, n).unwrap();
+                assert_eq!(id, i);
+                assert_eq!(key, keys[id]);
+            }
+            assert!(set.select_in_prefix(&prefix, range.len()).is_none());
+
+            let mut expected: Vec<_> = expected
+                .iter()
+                .filter_map(|&i| keys[i].get(prefix.len()).copied())
+                .collect();
+            expected.dedup();
+            assert_eq!(set.children(&prefix), expected);
+        }
+
+        let mut rng = ChaChaRng::seed_from_u64(43);
+        let mut prefixes = gen_random_keys(200, 5, 47);
+        prefixes.shuffle(&mut rng);
+        prefixes.push(vec![]);
+        let expected: Vec<_> = prefixes
+            .iter()
+            .map(|prefix| Some(set.prefix_range(prefix)).filter(|range| !range.is_empty()))
+            .collect();
+        assert_eq!(set.prefix_ranges(&prefixes), expected);
+    }
+
+    fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+        let mut row: Vec<_> = (0..=b.len()).collect();
+        for &x in a {
+            let mut next = vec![row[0] + 1];
+            for (j, &y) in b.iter().enumerate() {
+                let dist = (row[j] + usize::from(x != y))
+                    .min(row[j + 1] + 1)
+                    .min(next[j] + 1);
+                next.push(dist);
+            }
+            row = next;
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn test_fuzzy_prefix() {
+        let keys = gen_random_keys(1000, 8, 43);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        for (query, max_distance) in gen_random_keys(20, 5, 47).into_iter().zip(0..) {
+            let max_distance = max_distance % 3;
+            let expected: Vec<_> = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| {
+                    (0..=key.len()).any(|i| edit_distance(&key[..i], &query) <= max_distance)
+                })
+                .map(|(i, key)| (i, key.clone()))
+                .collect();
+            let results: Vec<_> = set.fuzzy_prefix_iter(&query, max_distance).collect();
+            assert_eq!(results, expected);
+        }
+    }
+
+    fn glob_match(pattern: &[u8], key: &[u8]) -> bool {
+        match pattern.split_first() {
+            None => key.is_empty(),
+            Some((b'*', rest)) => (0..=key.len()).any(|i| glob_match(rest, &key[i..])),
+            Some((&c, rest)) => match key.split_first() {
+                Some((&d, key)) => (c == b'?' || c == d) && glob_match(rest, key),
+                None => false,
+            },
+        }
+    }
+
+    #[cfg(feature = "regex-automata")]
+    #[test]
+    fn test_regex() {
+        let keys = gen_random_keys(1000, 8, 71);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        let check = |pattern: &str, pred: fn(&[u8]) -> bool| {
+            let expected: Vec<_> = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| pred(key))
+                .map(|(i, key)| (i, key.clone()))
+                .collect();
+            let results: Vec<_> = set.regex_iter(pattern).unwrap().collect();
+            assert_eq!(results, expected);
+        };
+        check(r"\x01+", |key| key.iter().all(|&c| c == 1));
+        check(r"(?s).*\x02\x03.*", |key| {
+            key.windows(2).any(|w| w == [2, 3])
+        });
+        check(r"[\x01\x02]{3}", |key| {
+            key.len() == 3 && key.iter().all(|&c| c <= 2)
+        });
+        check(r"\x04.?", |key| key[0] == 4 && key.len() <= 2);
+        assert!(set.regex_iter("(").is_err());
+    }
+
+    #[test]
+    fn test_glob() {
+        let keys = gen_random_keys(1000, 8, 53);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        let mut rng = ChaChaRng::seed_from_u64(59);
+        for _ in 0..50 {
+            let len = rng.gen::<usize>() % 6;
+            let pattern: Vec<u8> = (0..len)
+                .map(|_| match rng.gen::<u8>() % 6 {
+                    0 => b'*',
+                    1 => b'?',
+                    c => c - 1,
+                })
+                .collect();
+            let expected: Vec<_> = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| glob_match(&pattern, key))
+                .map(|(i, key)| (i, key.clone()))
+                .collect();
+            let results: Vec<_> = set.glob_iter(&pattern).collect();
+            assert_eq!(results, expected);
+        }
+    }
+
+    #[test]
+    fn test_iter_len_range() {
+        let keys = gen_random_keys(1000, 8, 61);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        for min in 0..9 {
+            for max in min..9 {
+                let expected: Vec<_> = keys
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, key)| (min..max).contains(&key.len()))
+                    .map(|(i, key)| (i, key.clone()))
+                    .collect();
+                let results: Vec<_> = set.iter_len_range(min..max).collect();
+                assert_eq!(results, expected);
+            }
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_fuzz_round_trip() {
+        let mut rng = ChaChaRng::seed_from_u64(67);
+        for _ in 0..100 {
+            let len = rng.gen::<usize>() % 1000;
+            let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            crate::fuzz::round_trip(&data);
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_fuzz_regressions() {
+        // A pointer table of 2^40 chunks following the cookie and the header flags.
+        let mut data = SERIAL_COOKIE.to_le_bytes().to_vec();
+        data.push(0);
+        data.extend_from_slice(&u64::to_le_bytes(1 << 40));
+        assert_eq!(data.len(), 13);
+        assert!(Set::deserialize_from(&data[..]).is_err());
+        crate::fuzz::round_trip(&data);
+
+        // Broken fields of a dictionary whose pointers are packed in one chunk.
+        let set = Set::with_bucket_size(["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"], 2).unwrap();
+        let mut seed = vec![];
+        set.serialize_into(&mut seed).unwrap();
+        assert_eq!(seed[4], 0);
+        assert_eq!((&seed[5..]).read_u64::<LittleEndian>().unwrap(), 1);
+        let tail = 53 + set.serialized.len();
+        for (pos, x) in [
+            // Number of chunks
+            (5, 0),
+            // Number of pointers
+            (21, 1 << 20),
+            // Bits per pointer
+            (29, 0),
+            // Length of the serialized area
+            (45, u64::MAX),
+            // Number of keys
+            (tail, 1 << 40),
+            // Maximum length of the keys
+            (tail + 24, 1 << 40),
+        ] {
+            let mut data = seed.clone();
+            data[pos..pos + 8].copy_from_slice(&u64::to_le_bytes(x));
+            assert!(Set::deserialize_from(&data[..]).is_err());
+            crate::fuzz::round_trip(&data);
+        }
+    }
+
+    #[test]
+    fn test_bucket_size_one() {
+        let keys = gen_random_keys(100, 8, 71);
+        let set = Set::with_bucket_size(&keys, 1).unwrap();
+        assert_eq!(set.bucket_size(), 1);
+        let mut locator = set.locator();
+        let mut decoder = set.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(locator.run(key), Some(i));
+            assert_eq!(&decoder.run(i), key);
+        }
+        for key in gen_random_keys(100, 8, 73) {
+            assert_eq!(locator.run(&key), keys.binary_search(&key).ok());
+        }
+        let mut data = Vec::new();
+        set.serialize_into(&mut data).unwrap();
+        assert_eq!(Set::deserialize_from(&data[..]).unwrap().len(), keys.len());
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_external_sort() {
+        let mut keys = gen_random_keys(1000, 8, 79);
+        let mut rng = ChaChaRng::seed_from_u64(83);
+        keys.extend(keys.clone());
+        keys.shuffle(&mut rng);
+        let mut sorter = crate::external_sort::ExternalSorter::new(100);
+        for key in &keys {
+            sorter.push(key.clone()).unwrap();
+        }
+        let sorted: Vec<_> = sorter.finish().unwrap().map(|k| k.unwrap()).collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(sorted, keys);
+    }
+
+    #[cfg(feature = "build-util")]
+    #[test]
+    fn test_codegen() {
+        let keys = gen_random_keys(100, 8, 89);
+        let set = Set::new(&keys).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let src = codegen::embed_into(&set, dir.path(), "words").unwrap();
+        assert!(std::fs::read_to_string(src)
+            .unwrap()
+            .contains("pub fn words() -> &'static ::fcsd::Set"));
+        let data = std::fs::read(dir.path().join("words.fcsd")).unwrap();
+        assert_eq!(data[4] & FLAG_ALIGNED, FLAG_ALIGNED);
+        let other = Set::deserialize_from(&data[..]).unwrap();
+        assert_eq!(
+            other.iter().collect::<Vec<_>>(),
+            set.iter().collect::<Vec<_>>()
+        );
+        assert!(codegen::embed_into(&set, dir.path(), "fn").is_err());
+        assert!(codegen::embed_into(&set, dir.path(), "a-b").is_err());
+    }
+
+    #[test]
+    fn test_progress() {
+        let keys: Vec<_> = (0..builder::PROGRESS_INTERVAL * 2 + 10)
+            .map(|i| format!("{:08}", i))
+            .collect();
+        let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let mut builder = Builder::new(8)
+            .unwrap()
+            .on_progress(move |p| sink.lock().unwrap().push(p));
+        for key in &keys {
+            builder.add(key.as_bytes()).unwrap();
+        }
+        let set = builder.finish();
+        let reports = reports.lock().unwrap().clone();
+        let keys_reported: Vec<_> = reports.iter().map(|p| p.keys).collect();
+        let n = builder::PROGRESS_INTERVAL;
+        assert_eq!(keys_reported, vec![n, 2 * n, set.len()]);
+        assert!(reports.windows(2).all(|w| w[0].bytes < w[1].bytes));
+
+        let mut rebuilt = Vec::new();
+        set.rebucket_with_progress(4, |p| rebuilt.push(p)).unwrap();
+        assert_eq!(rebuilt.len(), 3);
+        assert_eq!(rebuilt.last().unwrap().keys, set.len());
+    }
+
+    #[test]
+    fn test_empty_key() {
+        let keys: [&[u8]; 4] = [b"", b"a", b"ab", b"b"];
+        for bucket_size in [1, 2, 4] {
+            let set = Set::with_bucket_size(keys, bucket_size).unwrap();
+            let mut data = Vec::new();
+            set.serialize_into(&mut data).unwrap();
+            let set = Set::deserialize_from(&data[..]).unwrap();
+            assert_eq!(set.len(), keys.len());
+
+            let mut locator = set.locator();
+            let mut decoder = set.decoder();
+            for (i, &key) in keys.iter().enumerate() {
+                assert_eq!(locator.run(key), Some(i));
+                assert_eq!(decoder.run(i), key);
+            }
+            assert_eq!(
+                locator.run_many(&keys),
+                vec![Some(0), Some(1), Some(2), Some(3)]
+            );
+
+            let ids: Vec<_> = set.iter().map(|(i, _)| i).collect();
+            assert_eq!(ids, vec![0, 1, 2, 3]);
+            let ids: Vec<_> = set.predictive_iter(b"").map(|(i, _)| i).collect();
+            assert_eq!(ids, vec![0, 1, 2, 3]);
+            let ids: Vec<_> = set.predictive_iter(b"a").map(|(i, _)| i).collect();
+            assert_eq!(ids, vec![1, 2]);
+            assert_eq!(set.prefix_range(b""), 0..4);
+            assert_eq!(set.children(b""), b"ab".to_vec());
+        }
+
+        let set = Set::new(["a", "b"]).unwrap();
+        assert_eq!(set.locator().run(b""), None);
+        assert_eq!(set.predictive_iter(b"").count(), 2);
+
+        let mut builder = Builder::new(4).unwrap();
+        builder.add(b"").unwrap();
+        assert!(builder.add(b"").is_err());
+    }
+
+    #[cfg(feature = "merge")]
+    #[test]
+    fn test_merge_serialized() {
+        let mut all = Vec::new();
+        let mut inputs = Vec::new();
+        for (i, &bucket_size) in [1, 4, 16].iter().enumerate() {
+            let keys = gen_random_keys(300, 8, 107 + i as u64);
+            let mut data = Vec::new();
+            Set::with_bucket_size(&keys, bucket_size)
+                .unwrap()
+                .serialize_into(&mut data)
+                .unwrap();
+            inputs.push(data);
+            all.extend(keys);
+        }
+        all.sort();
+        all.dedup();
+
+        let mut merged = Vec::new();
+        let readers = inputs.iter().map(|data| &data[..]).collect();
+        let len = merge::merge_serialized_with_bucket_size(readers, &mut merged, 8).unwrap();
+        assert_eq!(len, all.len());
+
+        let mut expected = Vec::new();
+        Set::with_bucket_size(&all, 8)
+            .unwrap()
+            .serialize_into(&mut expected)
+            .unwrap();
+        assert_eq!(merged, expected);
+
+        let mut with_ids = Vec::new();
+        let mut builder = Builder::new(4).unwrap();
+        builder.add_with_id(b"a", 1).unwrap();
+        builder.finish().serialize_into(&mut with_ids).unwrap();
+        let readers = vec![&with_ids[..], &inputs[0][..]];
+        assert!(merge::merge_serialized(readers, &mut Vec::new()).is_err());
+
+        let legacy = include_bytes!("../testdata/legacy.fcsd");
+        assert!(merge::merge_serialized(vec![&legacy[..]], &mut Vec::new()).is_err());
+        let set = Set::with_bucket_size(["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"], 4).unwrap();
+        let mut aligned = Vec::new();
+        set.serialize_into_aligned(&mut aligned).unwrap();
+        assert!(merge::merge_serialized(vec![&aligned[..]], &mut Vec::new()).is_err());
+        let mut compact = Vec::new();
+        set.serialize_into_compact(&mut compact).unwrap();
+        assert!(merge::merge_serialized(vec![&compact[..]], &mut Vec::new()).is_err());
+
+        // Broken lengths of the serialized area, before and after the last pointer.
+        let mut data = Vec::new();
+        set.serialize_into(&mut data).unwrap();
+        let len = set.serialized.len() as u64;
+        assert_eq!((&data[45..]).read_u64::<LittleEndian>().unwrap(), len);
+        for len in [1, u64::MAX] {
+            let mut broken = data.clone();
+            broken[45..53].copy_from_slice(&len.to_le_bytes());
+            assert!(merge::merge_serialized(vec![&broken[..]], &mut Vec::new()).is_err());
+        }
     }
 
-    #[inline(always)]
-    const fn bucket_id(&self, id: usize) -> usize {
-        id >> self.bucket_bits
+    #[test]
+    fn test_append_greater() {
+        let keys = gen_random_keys(1000, 8, 109);
+        for &bucket_size in &[1, 4, 16] {
+            let mut expected = Vec::new();
+            Set::with_bucket_size(&keys, bucket_size)
+                .unwrap()
+                .serialize_into(&mut expected)
+                .unwrap();
+
+            let mut set = Set::with_bucket_size(&[] as &[&[u8]], bucket_size).unwrap();
+            for chunk in keys.chunks(77) {
+                assert_eq!(set.append_greater(chunk).unwrap(), chunk.len());
+            }
+            let mut data = Vec::new();
+            set.serialize_into(&mut data).unwrap();
+            assert_eq!(data, expected);
+        }
     }
 
-    #[inline(always)]
-    const fn pos_in_bucket(&self, id: usize) -> usize {
-        id & self.bucket_mask
+    #[test]
+    fn test_slice() {
+        let keys = gen_random_keys(1000, 8, 29);
+        let set = Set::with_bucket_size(&keys, 16).unwrap();
+        let n = keys.len();
+        for &(beg, end) in &[(0, 0), (0, n), (3, 17), (16, 32), (500, n - 1)] {
+            let other = set.slice(beg..end).unwrap();
+            assert_eq!(other.len(), end - beg);
+            let decoded: Vec<_> = other.iter().map(|(_, key)| key).collect();
+            assert_eq!(&decoded[..], &keys[beg..end]);
+        }
+        assert!(set.slice(0..n + 1).is_err());
     }
 
-    #[inline(always)]
-    fn get_header(&self, bi: usize) -> &[u8] {
-        let header = &self.serialized[self.pointers.get(bi) as usize..];
-        &header[..utils::get_strlen(header)]
+    #[test]
+    fn test_typed_ids() {
+        let keys = gen_random_keys(1000, 8, 31);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        assert!(set.fits_id::<u32>());
+        let mut locator = set.locator();
+        let ids: Vec<Option<u32>> = locator.run_many_as(&keys);
+        assert_eq!(ids, (0..keys.len() as u32).map(Some).collect::<Vec<_>>());
+        let mut decoder = set.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(locator.run_as::<u32, _>(key), Some(i as u32));
+            assert_eq!(&decoder.run_as(i as u64), key);
+        }
+
+        let new_set = Set::new(&keys[keys.len() / 2..]).unwrap();
+        let mapping: Vec<Option<u32>> = Set::build_id_mapping_as(&set, &new_set);
+        let expected = Set::build_id_mapping(&set, &new_set);
+        assert_eq!(mapping.len(), expected.len());
+        for (&id, &expected) in mapping.iter().zip(&expected) {
+            assert_eq!(id.map(|id| id as usize), expected);
+        }
     }
 
-    #[inline(always)]
-    fn decode_header(&self, bi: usize, dec: &mut Vec<u8>) -> usize {
-        dec.clear();
-        let mut pos = self.pointers.get(bi) as usize;
-        while self.serialized[pos] != END_MARKER {
-            dec.push(self.serialized[pos]);
-            pos += 1;
+    #[test]
+    fn test_stability_report() {
+        let keys = gen_random_keys(1000, 8, 79);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        let mut rng = ChaChaRng::seed_from_u64(83);
+        let mut new_keys: Vec<_> = keys
+            .iter()
+            .filter(|_| rng.gen::<u8>() % 8 != 0)
+            .cloned()
+            .collect();
+        new_keys.extend(gen_random_keys(100, 10, 89));
+        new_keys.sort();
+        new_keys.dedup();
+
+        let report = set.stability_report(&new_keys).unwrap();
+        let new_set = Set::new(&new_keys).unwrap();
+        let mapping = Set::build_id_mapping(&set, &new_set);
+        for (old_id, &new_id) in mapping.iter().enumerate() {
+            assert_eq!(report.new_id(old_id), new_id);
         }
-        pos + 1
+        let num_kept = mapping.iter().flatten().count();
+        assert_eq!(report.num_kept, num_kept);
+        assert_eq!(report.num_removed, keys.len() - num_kept);
+        assert_eq!(report.num_added, new_keys.len() - num_kept);
+        assert!(!report.is_stable());
+
+        let report = set.stability_report(&keys).unwrap();
+        assert!(report.is_stable());
+        assert_eq!(report.shifts.len(), 1);
+        assert!(set.stability_report(["b", "a"]).is_err());
     }
 
-    #[inline(always)]
-    fn decode_lcp(&self, pos: usize) -> (usize, usize) {
-        let (lcp, num) = utils::vbyte::decode(&self.serialized[pos..]);
-        (lcp, pos + num)
+    #[test]
+    fn test_observer() {
+        let keys = gen_random_keys(1000, 8, 109);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        let queries = gen_random_keys(200, 8, 113);
+
+        let mut infos = vec![];
+        let ids = {
+            let mut locator = set.locator_with_observer(|info: &observer::QueryInfo| {
+                infos.push((info.key.to_vec(), info.bucket_id, info.id))
+            });
+            for query in &queries {
+                locator.run(query);
+            }
+            locator.run_many(&queries)
+        };
+
+        assert_eq!(infos.len(), queries.len() * 2);
+        for (i, query) in queries.iter().enumerate() {
+            let bi = set.rank(query).saturating_sub(1) / 8;
+            let bi = if ids[i].is_some() {
+                ids[i].unwrap() / 8
+            } else {
+                bi
+            };
+            assert_eq!(infos[i], (query.clone(), bi, ids[i]));
+            assert_eq!(infos[queries.len() + i], infos[i]);
+        }
     }
 
-    #[inline(always)]
-    fn decode_next(&self, mut pos: usize, dec: &mut Vec<u8>) -> usize {
-        while self.serialized[pos] != END_MARKER {
-            dec.push(self.serialized[pos]);
-            pos += 1;
+    #[test]
+    fn test_lookup() {
+        let keys = gen_random_keys(1000, 8, 101);
+        let queries = gen_random_keys(1000, 10, 103);
+        for order in [KeyOrder::Lexicographic, KeyOrder::Natural] {
+            let mut keys = keys.clone();
+            keys.sort_by(|a, b| order.compare(a, b));
+            let mut builder = Builder::new(8).unwrap().key_order(order);
+            builder.extend(&keys).unwrap();
+            let set = builder.finish();
+            let mut locator = set.locator();
+            for query in &queries {
+                let expected = match keys.binary_search_by(|key| order.compare(key, query)) {
+                    Ok(id) => Lookup::Found(id),
+                    Err(id) => {
+                        let lcp = |i: Option<usize>| {
+                            i.and_then(|i| keys.get(i))
+                                .map_or(0, |key| utils::get_lcp(key, query).0)
+                        };
+                        Lookup::NotFound {
+                            insertion_id: id,
+                            lcp_with_neighbors: (lcp(id.checked_sub(1)), lcp(Some(id))),
+                        }
+                    }
+                };
+                assert_eq!(locator.lookup(query), expected);
+            }
         }
-        pos + 1
+        let empty = Set::new(Vec::<&str>::new()).unwrap();
+        assert_eq!(
+            empty.locator().lookup(b"a"),
+            Lookup::NotFound {
+                insertion_id: 0,
+                lcp_with_neighbors: (0, 0)
+            }
+        );
     }
 
-    fn search_bucket(&self, key: &[u8]) -> (usize, bool) {
-        let mut cmp = 0;
-        let (mut lo, mut hi, mut mi) = (0, self.num_buckets(), 0);
-        while lo < hi {
-            mi = (lo + hi) / 2;
-            cmp = utils::get_lcp(key, self.get_header(mi)).1;
-            match cmp.cmp(&0) {
-                Ordering::Less => lo = mi + 1,
-                Ordering::Greater => hi = mi,
-                Ordering::Equal => return (mi, true),
+    #[test]
+    fn test_escape() {
+        use escape::Escaper;
+
+        // Keys with bytes in 0..4, half of which contain the end marker or the escape byte.
+        let mut keys: Vec<Vec<u8>> = gen_random_keys(1000, 8, 97)
+            .into_iter()
+            .map(|key| key.into_iter().map(|c| c - 1).collect())
+            .collect();
+        keys.sort();
+        keys.dedup();
+        for escaper in [Escaper::ByteStuffing, Escaper::Strip] {
+            let set = Set::new(escaper.escape_keys(&keys));
+            if escaper == Escaper::Strip {
+                // Stripping changes the order of keys.
+                assert!(set.is_err());
+                continue;
+            }
+            let set = set.unwrap();
+            assert_eq!(set.len(), keys.len());
+            let mut locator = set.locator();
+            let mut decoder = set.decoder();
+            for (i, key) in keys.iter().enumerate() {
+                assert_eq!(locator.run(escaper.escape(key)), Some(i));
+                assert_eq!(
+                    escaper.unescape(&decoder.run(i)).unwrap().as_ref(),
+                    &key[..]
+                );
             }
         }
-        if cmp < 0 || mi == 0 {
-            (mi, false)
-        } else {
-            (mi - 1, false)
+        assert_eq!(Escaper::Strip.escape(b"\x00a\x00b").as_ref(), b"ab");
+        assert!(Escaper::ByteStuffing.unescape(b"a\x01").is_err());
+        assert!(Escaper::ByteStuffing.unescape(b"a\x01\x03").is_err());
+    }
+
+    #[test]
+    fn test_extract_prefix_subdict() {
+        let keys = gen_random_keys(1000, 8, 73);
+        let set = Set::with_bucket_size(&keys, 16).unwrap();
+        for prefix in [&[][..], &[1], &[2, 3], &[4, 4, 1], &[5]] {
+            let expected: Vec<_> = keys.iter().filter(|key| key.starts_with(prefix)).collect();
+            let other = set.extract_prefix_subdict(prefix, false).unwrap();
+            let decoded: Vec<_> = other.iter().map(|(_, key)| key).collect();
+            assert_eq!(decoded.iter().collect::<Vec<_>>(), expected);
+
+            let other = set.extract_prefix_subdict(prefix, true).unwrap();
+            let decoded: Vec<_> = other.iter().map(|(_, key)| key).collect();
+            let stripped: Vec<_> = expected.iter().map(|key| &key[prefix.len()..]).collect();
+            assert_eq!(decoded, stripped);
         }
+
+        let mut builder = Builder::new(4).unwrap().key_order(KeyOrder::Natural);
+        builder.extend(["a1b", "a1c", "a10"]).unwrap();
+        let set = builder.finish();
+        assert_eq!(set.extract_prefix_subdict(b"a1", false).unwrap().len(), 3);
+        assert!(set.extract_prefix_subdict(b"a1", true).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rand::{Rng, SeedableRng};
-    use rand_chacha::ChaChaRng;
+    #[test]
+    fn test_without_prefix() {
+        let keys = gen_random_keys(1000, 8, 107);
+        let set = Set::with_bucket_size(&keys, 16).unwrap();
+        for prefix in [&[][..], &[1], &[2, 3], &[4, 4, 1], &[5]] {
+            let (expected, expected_ids) = set.retain(|key| !key.starts_with(prefix)).unwrap();
+            let (other, ids) = set.without_prefix(prefix).unwrap();
+            assert_eq!(ids, expected_ids);
+            assert_eq!(other.serialized[..], expected.serialized[..]);
+        }
+    }
 
-    fn gen_random_keys(num: usize, max_len: usize, seed: u64) -> Vec<Vec<u8>> {
-        let mut rng = ChaChaRng::seed_from_u64(seed);
-        let mut keys = Vec::with_capacity(num);
-        for _ in 0..num {
-            let len = (rng.gen::<usize>() % (max_len - 1)) + 1;
-            keys.push((0..len).map(|_| (rng.gen::<u8>() % 4) + 1).collect());
+    #[test]
+    fn test_export_libcsd() {
+        let keys = gen_random_keys(1000, 20, 13);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        let mut data = vec![];
+        set.export_libcsd(&mut data).unwrap();
+
+        let u64_at = |i: usize| LittleEndian::read_u64(&data[i..]) as usize;
+        assert_eq!(u64_at(4), keys.len());
+        assert_eq!(u64_at(20), set.num_buckets());
+        let text = &data[36..36 + u64_at(28)];
+        let (mut pos, mut key) = (0, vec![]);
+        for (id, expected) in keys.iter().enumerate() {
+            if id % 8 != 0 {
+                let (mut lcp, mut shift) = (0, 0);
+                loop {
+                    lcp |= ((text[pos] & 127) as usize) << shift;
+                    pos += 1;
+                    shift += 7;
+                    if text[pos - 1] & 0x80 != 0 {
+                        break;
+                    }
+                }
+                key.truncate(lcp);
+            } else {
+                key.clear();
+            }
+            let len = utils::get_strlen(&text[pos..]);
+            key.extend_from_slice(&text[pos..pos + len]);
+            pos += len + 1;
+            assert_eq!(&key, expected);
         }
-        keys.sort();
-        keys.dedup();
-        keys
+        assert_eq!(pos, text.len());
+        assert_eq!(u64_at(36 + text.len()), set.num_buckets() + 1);
+
+        let mut builder = Builder::new(8).unwrap().key_order(KeyOrder::Natural);
+        builder.extend(["f2", "f10"]).unwrap();
+        assert!(builder.finish().export_libcsd(vec![]).is_err());
     }
 
+    #[cfg(feature = "rand")]
     #[test]
-    fn test_toy() {
-        let keys = [
-            "deal",
-            "idea",
-            "ideal",
-            "ideas",
-            "ideology",
-            "tea",
-            "techie",
-            "technology",
-            "tie",
-            "trie",
-        ];
+    fn test_sample() {
+        let keys = gen_random_keys(1000, 8, 59);
+        let mut rng = ChaChaRng::seed_from_u64(59);
+        let mut builder = Builder::new(8).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            builder.add_with_payload(key, (i % 3) as u64).unwrap();
+        }
+        let set = builder.finish();
 
-        assert!(Builder::new(0).is_err());
-        assert!(Builder::new(3).is_err());
-        let mut builder = Builder::new(4).unwrap();
+        let (id, key) = set.choose(&mut rng).unwrap();
+        assert_eq!(key, keys[id]);
+        let prefix = &keys[500][..1];
+        let range = set.prefix_range(prefix);
+        let sample = set.sample_in_prefix(prefix, 10, &mut rng);
+        assert_eq!(sample.len(), range.len().min(10));
+        assert!(sample.windows(2).all(|w| w[0].0 < w[1].0));
+        for (id, key) in &sample {
+            assert!(range.contains(id));
+            assert_eq!(key, &keys[*id]);
+        }
+        let all = set.sample_in_prefix(prefix, usize::MAX, &mut rng);
+        assert_eq!(all.len(), range.len());
 
-        for &key in &keys {
-            builder.add(key.as_bytes()).unwrap();
+        let sampler = set.weighted_sampler().unwrap();
+        for _ in 0..100 {
+            let (id, key) = set.choose_weighted(&mut rng).unwrap().unwrap();
+            assert_ne!(id % 3, 0);
+            assert_eq!(key, keys[id]);
+            let (id, _) = sampler.choose_in_prefix(prefix, &mut rng).unwrap();
+            assert!(range.contains(&id) && id % 3 != 0);
         }
-        assert!(builder.add("tri".as_bytes()).is_err());
-        assert!(builder.add(&[0xFF, 0x00]).is_err());
+        assert!(Set::new(["a"]).unwrap().choose_weighted(&mut rng).is_err());
 
+        let keys = ["f1", "f1a", "f2", "f10"];
+        let mut builder = Builder::new(2).unwrap().key_order(KeyOrder::Natural);
+        for (i, key) in keys.iter().enumerate() {
+            builder.add_with_payload(key.as_bytes(), i as u64).unwrap();
+        }
         let set = builder.finish();
+        let sample = set.sample_in_prefix("f1", 5, &mut rng);
+        assert_eq!(sample.len(), 3);
+        let (id, _) = set
+            .weighted_sampler()
+            .unwrap()
+            .choose_in_prefix("f1", &mut rng)
+            .unwrap();
+        assert!(id == 1 || id == 3);
+    }
 
-        let mut locator = set.locator();
-        for i in 0..keys.len() {
-            let id = locator.run(keys[i].as_bytes()).unwrap();
-            assert_eq!(i, id);
+    #[test]
+    fn test_staging() {
+        let keys = gen_random_keys(1000, 8, 60);
+        let (old_keys, new_keys): (Vec<_>, Vec<_>) = keys
+            .iter()
+            .cloned()
+            .enumerate()
+            .partition(|(i, _)| i % 3 != 0);
+        let old_keys: Vec<_> = old_keys.into_iter().map(|(_, key)| key).collect();
+        let set = Set::with_bucket_size(&old_keys, 4).unwrap();
+
+        let mut staging = set.staging();
+        let mut tickets = vec![];
+        for key in keys.iter().rev() {
+            match staging.locate_or_stage(key) {
+                Staged::Found(id) => assert_eq!(&old_keys[id], key),
+                Staged::Pending(ticket) => tickets.push((ticket, key)),
+            }
         }
-        assert!(locator.run("aaa".as_bytes()).is_none());
-        assert!(locator.run("tell".as_bytes()).is_none());
-        assert!(locator.run("techno".as_bytes()).is_none());
-        assert!(locator.run("zzz".as_bytes()).is_none());
+        assert_eq!(staging.len(), new_keys.len());
+        assert_eq!(staging.locate_or_stage(tickets[0].1), Staged::Pending(0));
 
-        let mut decoder = set.decoder();
-        for i in 0..keys.len() {
-            assert_eq!(keys[i].as_bytes(), &decoder.run(i));
+        let merged = staging.merge().unwrap();
+        assert_eq!(merged.set.bucket_size(), 4);
+        assert!(merged
+            .set
+            .iter()
+            .map(|(_, key)| key)
+            .eq(keys.iter().cloned()));
+        for (ticket, key) in tickets {
+            assert_eq!(&keys[merged.pending_ids[ticket]], key);
+        }
+        for (id, key) in old_keys.iter().enumerate() {
+            assert_eq!(&keys[merged.old_ids[id]], key);
         }
 
-        let mut iterator = set.iter();
-        for i in 0..keys.len() {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(i, id);
-            assert_eq!(keys[i].as_bytes(), &dec);
+        let mut staging = set.staging();
+        staging.locate_or_stage(b"a\0");
+        assert!(staging.merge().is_err());
+    }
+
+    #[test]
+    fn test_budget() {
+        let keys = gen_random_keys(1000, 8, 61);
+        let set = Set::new(&keys).unwrap();
+        let prefix = &keys[500][..1];
+        let expected: Vec<_> = set.predictive_iter(prefix).collect();
+        assert!(3 < expected.len());
+
+        let unlimited: Result<Vec<_>, _> = set
+            .predictive_iter_with_budget(prefix, Budget::new())
+            .collect();
+        assert_eq!(unlimited.unwrap(), expected);
+
+        let limited: Result<Vec<_>, _> = set
+            .predictive_iter_with_budget(prefix, Budget::new().max_keys(3))
+            .collect();
+        let err = limited.unwrap_err();
+        assert_eq!(err.keys, 3);
+        assert_eq!(
+            err.bytes,
+            expected[..3]
+                .iter()
+                .map(|(_, key)| key.len())
+                .sum::<usize>()
+        );
+
+        let max_bytes = expected[0].1.len() + expected[1].1.len();
+        let mut iter = Budgeted::new(
+            set.iter().skip(expected[0].0),
+            Budget::new().max_bytes(max_bytes),
+        );
+        assert_eq!(iter.next(), Some(Ok(expected[0].clone())));
+        assert_eq!(iter.next(), Some(Ok(expected[1].clone())));
+        assert!(iter.next().unwrap().is_err());
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn test_multi_locator() {
+        let keys = gen_random_keys(1000, 8, 62);
+        let base_keys: Vec<_> = keys.iter().step_by(2).cloned().collect();
+        let delta_keys: Vec<_> = keys.iter().skip(1).step_by(3).cloned().collect();
+        let base = Set::new(&base_keys).unwrap();
+        let delta = Set::with_bucket_size(&delta_keys, 4).unwrap();
+
+        let mut locator = multi_locator::MultiLocator::new([&delta, &base]).unwrap();
+        assert_eq!(locator.len(), 2);
+        for key in &keys {
+            let expected = delta_keys.binary_search(key).ok().map_or_else(
+                || base_keys.binary_search(key).ok().map(|id| (1, id)),
+                |id| Some((0, id)),
+            );
+            assert_eq!(locator.run(key), expected);
+            let all = locator.run_all(key);
+            assert_eq!(all[1], base_keys.binary_search(key).ok());
+        }
+        let merged: Vec<_> = locator.iter().collect();
+        let num_distinct = (0..keys.len()).filter(|i| i % 2 == 0 || i % 3 == 1).count();
+        assert_eq!(merged.len(), num_distinct);
+        assert!(merged.windows(2).all(|w| w[0].0 < w[1].0));
+        for (key, dict, id) in merged {
+            assert_eq!(locator.run(&key), Some((dict, id)));
         }
-        assert!(iterator.next().is_none());
 
-        let mut iterator = set.predictive_iter("idea".as_bytes());
-        {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(1, id);
-            assert_eq!(keys[1].as_bytes(), &dec);
+        let natural = Builder::new(4)
+            .unwrap()
+            .key_order(KeyOrder::Natural)
+            .finish();
+        assert!(multi_locator::MultiLocator::new([&base, &natural]).is_err());
+    }
+
+    #[test]
+    fn test_interner() {
+        let keys = gen_random_keys(1000, 8, 60);
+        let old_keys: Vec<_> = keys.iter().step_by(2).cloned().collect();
+        let set = Set::with_bucket_size(&old_keys, 4).unwrap();
+
+        let mut interner = interner::Interner::new(set)
+            .unwrap()
+            .compaction_threshold(100);
+        let mut symbols = vec![];
+        for key in keys.iter().rev() {
+            let symbol = interner.intern(key).unwrap();
+            assert_eq!(interner.intern(key).unwrap(), symbol);
+            symbols.push((symbol, key));
         }
-        {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(2, id);
-            assert_eq!(keys[2].as_bytes(), &dec);
+        assert_eq!(interner.len(), keys.len());
+        assert!(interner.num_overflow() < 100);
+        assert_eq!(interner.set().bucket_size(), 4);
+        for &(symbol, key) in &symbols {
+            assert_eq!(interner.get(key), Some(symbol));
+            assert_eq!(interner.resolve(symbol).as_ref(), Some(key));
         }
-        {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(3, id);
-            assert_eq!(keys[3].as_bytes(), &dec);
+        for (id, key) in old_keys.iter().enumerate() {
+            assert_eq!(interner.get(key), Some(id as u64));
         }
-        assert!(iterator.next().is_none());
+        assert_eq!(interner.resolve(keys.len() as u64), None);
+        assert!(interner.intern(b"a\0").is_err());
 
-        let mut buffer = vec![];
-        set.serialize_into(&mut buffer).unwrap();
-        assert_eq!(buffer.len(), set.size_in_bytes());
+        let set = interner.into_set().unwrap();
+        assert!(set.iter().map(|(_, key)| key).eq(keys.iter().cloned()));
+        let interner = interner::Interner::new(set).unwrap();
+        for &(symbol, key) in &symbols {
+            assert_eq!(interner.get(key), Some(symbol));
+        }
+    }
 
-        let other = Set::deserialize_from(&buffer[..]).unwrap();
-        let mut iterator = other.iter();
-        for i in 0..keys.len() {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(i, id);
-            assert_eq!(keys[i].as_bytes(), &dec);
+    #[test]
+    fn test_rebuild_with() {
+        let keys = gen_random_keys(1000, 8, 60);
+        let mut builder = Builder::new(4).unwrap();
+        for (i, key) in keys.iter().enumerate() {
+            builder.add_with_id(key, i as u64 * 10).unwrap();
         }
-        assert!(iterator.next().is_none());
+        let set = builder.finish();
+
+        let other = set
+            .rebuild_with(|key| (key.len() % 2 == 0).then(|| [b"x", key].concat()))
+            .unwrap();
+        assert_eq!(
+            other.len(),
+            keys.iter().filter(|key| key.len() % 2 == 0).count()
+        );
+        assert_eq!(other.bucket_size(), 4);
+        for (id, key) in other.iter() {
+            let old_id = set.locator().run(&key[1..]).unwrap();
+            assert_eq!(other.external_id(id), set.external_id(old_id));
+        }
+
+        // Truncation makes duplicates, which keep the smallest old ids.
+        let other = set
+            .rebuild_with(|key| Some(key[..key.len().min(2)].to_vec()))
+            .unwrap();
+        let mut expected: Vec<_> = keys.iter().map(|key| &key[..key.len().min(2)]).collect();
+        expected.dedup();
+        assert!(other
+            .iter()
+            .map(|(_, key)| key)
+            .eq(expected.iter().map(|key| key.to_vec())));
+        for (id, key) in other.iter() {
+            let old_id = keys.iter().position(|k| k.starts_with(&key)).unwrap();
+            assert_eq!(other.external_id(id), Some(old_id as u64 * 10));
+        }
+
+        // Reversal breaks the order.
+        let other = set
+            .rebuild_with(|key| Some(key.iter().rev().cloned().collect()))
+            .unwrap();
+        assert_eq!(other.len(), keys.len());
+        for (id, key) in other.iter() {
+            let key: Vec<_> = key.into_iter().rev().collect();
+            let old_id = set.locator().run(&key).unwrap();
+            assert_eq!(other.external_id(id), set.external_id(old_id));
+        }
+
+        assert!(set.rebuild_with(|key| Some([key, b"\0"].concat())).is_err());
     }
 
     #[test]
-    fn test_random() {
-        let keys = gen_random_keys(10000, 8, 11);
-        let mut builder = Builder::new(8).unwrap();
+    fn test_scan_prefix() {
+        let keys = gen_random_keys(1000, 8, 61);
+        let set = Set::with_bucket_size(&keys, 8).unwrap();
+        for prefix in [&b""[..], b"\x01", b"\x02\x03", b"\x04\x04\x04"] {
+            let expected: Vec<_> = set.predictive_iter(prefix).collect();
+            let mut scanned = vec![];
+            let result = set.scan_prefix(prefix, |id, key| {
+                scanned.push((id, key.to_vec()));
+                ControlFlow::<()>::Continue(())
+            });
+            assert_eq!(result, ControlFlow::Continue(()));
+            assert_eq!(scanned, expected);
 
-        for key in &keys {
-            builder.add(key).unwrap();
+            let mut scanned = vec![];
+            let result = set.scan_prefix(prefix, |id, key| {
+                scanned.push((id, key.to_vec()));
+                if scanned.len() == 3 {
+                    ControlFlow::Break(id)
+                } else {
+                    ControlFlow::Continue(())
+                }
+            });
+            assert_eq!(scanned[..], expected[..expected.len().min(3)]);
+            if 3 <= expected.len() {
+                assert_eq!(result, ControlFlow::Break(expected[2].0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_raw_bucket() {
+        let keys = gen_random_keys(1000, 8, 62);
+        let mut builder = Builder::new(8).unwrap().align_buckets(true);
+        builder.extend(&keys).unwrap();
+        for set in [Set::with_bucket_size(&keys, 4).unwrap(), builder.finish()] {
+            let mut decoded = vec![];
+            for bi in 0..set.num_buckets() {
+                let bucket = set.raw_bucket(bi);
+                assert_eq!(bucket.first_id(), decoded.len());
+                assert_eq!(bucket.iter().len(), bucket.len());
+                let mut key = vec![];
+                for (lcp, suffix) in bucket.iter() {
+                    key.truncate(lcp);
+                    key.extend_from_slice(suffix);
+                    decoded.push(key.clone());
+                }
+                assert!(bucket.as_bytes().starts_with(bucket.header()));
+            }
+            assert_eq!(decoded, keys);
         }
+
+        let mut builder = Builder::new(4).unwrap().alphabet(Alphabet::dna());
+        builder.extend(["ACGT", "ACTT", "GA"]).unwrap();
         let set = builder.finish();
+        let pairs: Vec<_> = set.raw_bucket(0).iter().map(|(lcp, _)| lcp).collect();
+        assert_eq!(pairs, vec![0, 2, 0]);
+    }
 
-        let mut locator = set.locator();
-        for i in 0..keys.len() {
-            let id = locator.run(&keys[i]).unwrap();
-            assert_eq!(i, id);
+    #[cfg(feature = "spill")]
+    #[test]
+    fn test_spill_to_disk() {
+        let keys = gen_random_keys(1000, 8, 63);
+        for align_buckets in [false, true] {
+            let mut builder = Builder::new(4).unwrap().align_buckets(align_buckets);
+            builder.extend(&keys).unwrap();
+            let mut expected = vec![];
+            builder.finish().serialize_into(&mut expected).unwrap();
+
+            let mut builder = Builder::new(4)
+                .unwrap()
+                .align_buckets(align_buckets)
+                .spill_to_disk(100);
+            builder.extend(&keys[..500]).unwrap();
+            let mut cloned = builder.clone();
+            builder.extend(&keys[500..]).unwrap();
+            cloned.extend(&keys[500..]).unwrap();
+
+            let mut data = vec![];
+            builder.finish_into(&mut data).unwrap();
+            assert_eq!(data, expected);
+            let mut data = vec![];
+            cloned
+                .try_finish()
+                .unwrap()
+                .serialize_into(&mut data)
+                .unwrap();
+            assert_eq!(data, expected);
         }
+    }
 
-        let mut decoder = set.decoder();
-        for i in 0..keys.len() {
-            let dec = decoder.run(i);
-            assert_eq!(&keys[i], &dec);
+    #[test]
+    fn test_prefix_histogram() {
+        let keys = gen_random_keys(1000, 8, 64);
+        let mut natural_keys = keys.clone();
+        natural_keys.sort_by(|a, b| KeyOrder::Natural.compare(a, b));
+        let mut builder = Builder::new(4).unwrap().key_order(KeyOrder::Natural);
+        builder.extend(&natural_keys).unwrap();
+        let sets = [Set::with_bucket_size(&keys, 4).unwrap(), builder.finish()];
+        for set in &sets {
+            for depth in [0, 1, 2, 3, 10] {
+                let mut expected: Vec<(Vec<u8>, usize)> = vec![];
+                for (_, key) in set.iter() {
+                    let prefix = &key[..key.len().min(depth)];
+                    match expected.iter_mut().find(|(p, _)| p == prefix) {
+                        Some((_, count)) => *count += 1,
+                        None => expected.push((prefix.to_vec(), 1)),
+                    }
+                }
+                expected.sort_by(|(a, _), (b, _)| set.key_order().compare(a, b));
+                assert_eq!(set.prefix_histogram(depth), expected);
+            }
         }
+    }
 
-        let mut iterator = set.iter();
-        for i in 0..keys.len() {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(i, id);
-            assert_eq!(&keys[i], &dec);
+    #[cfg(feature = "testkit")]
+    #[test]
+    fn test_testkit() {
+        use testkit::Layout;
+
+        // FNV-1a of all the fixtures, which must not change unless the format changes.
+        let mut hash = 0xcbf29ce484222325u64;
+        for fixture in testkit::fixtures() {
+            let set = fixture.build().unwrap();
+            assert_eq!(set.len(), fixture.keys.len());
+            for layout in Layout::ALL {
+                let data = fixture.serialize(layout).unwrap();
+                testkit::check_bytes(&fixture, layout, &data).unwrap();
+                let other = Set::from_bytes_portable(&data).unwrap();
+                testkit::check_locate(&fixture, |key| other.locate(key)).unwrap();
+                testkit::check_decode(&fixture, |id| other.decode(id)).unwrap();
+                for &c in &data {
+                    hash = (hash ^ u64::from(c)).wrapping_mul(0x100000001b3);
+                }
+
+                let mut broken = data.clone();
+                *broken.last_mut().unwrap() ^= 1;
+                assert!(testkit::check_bytes(&fixture, layout, &broken).is_err());
+                assert!(testkit::check_bytes(&fixture, layout, &data[1..]).is_err());
+            }
+            assert!(testkit::check_locate(&fixture, |_| Some(0)).is_err());
         }
-        assert!(iterator.next().is_none());
+        assert_eq!(hash, 10395893136882479332);
+
+        let dir = tempfile::tempdir().unwrap();
+        testkit::write_fixtures(dir.path()).unwrap();
+        let keys = std::fs::read_to_string(dir.path().join("venues.keys")).unwrap();
+        assert_eq!(keys.lines().nth(1), Some("49434d4c"));
+        let data = std::fs::read(dir.path().join("venues.compact.fcsd")).unwrap();
+        let set = Set::deserialize_from(&data[..]).unwrap();
+        assert_eq!(set.locate(b"ICML"), Some(1));
+    }
+
+    #[test]
+    fn test_prefix_membership() {
+        let keys = gen_random_keys(1000, 8, 65);
+        let mut natural_keys = keys.clone();
+        natural_keys.sort_by(|a, b| KeyOrder::Natural.compare(a, b));
+        let mut builder = Builder::new(4).unwrap().key_order(KeyOrder::Natural);
+        builder.extend(&natural_keys).unwrap();
+        let sets = [Set::with_bucket_size(&keys, 4).unwrap(), builder.finish()];
+
+        let mut prefixes: Vec<Vec<u8>> = keys
+            .iter()
+            .step_by(5)
+            .map(|key| key[..key.len() / 2 + 1].to_vec())
+            .collect();
+        prefixes.extend(
+            keys.iter()
+                .step_by(7)
+                .map(|key| [&key[..], b"\x05"].concat()),
+        );
+        prefixes.extend([vec![], b"0".to_vec(), b"\x0210".to_vec()]);
+        let mut bits = Bitmap::new();
+        for set in &sets {
+            let mut sorted = prefixes.clone();
+            sorted.sort_by(|a, b| set.key_order().compare(a, b));
+            for prefixes in [&prefixes, &sorted] {
+                set.prefix_membership_into(prefixes, &mut bits);
+                assert_eq!(bits.len(), prefixes.len());
+                for (i, prefix) in prefixes.iter().enumerate() {
+                    assert_eq!(
+                        bits.get(i),
+                        set.iter().any(|(_, key)| key.starts_with(prefix))
+                    );
+                }
+                assert_eq!(bits.count_ones(), bits.ones().count());
+            }
+        }
+        assert!(sets[0]
+            .prefix_membership(&[b"\x05"])
+            .ones()
+            .next()
+            .is_none());
+    }
 
+    #[test]
+    fn test_broken() {
+        let keys = gen_random_keys(1000, 8, 23);
+        let set = Set::new(&keys).unwrap();
         let mut buffer = vec![];
         set.serialize_into(&mut buffer).unwrap();
-        assert_eq!(buffer.len(), set.size_in_bytes());
 
-        let other = Set::deserialize_from(&buffer[..]).unwrap();
-        let mut iterator = other.iter();
-        for i in 0..keys.len() {
-            let (id, dec) = iterator.next().unwrap();
-            assert_eq!(i, id);
-            assert_eq!(&keys[i], &dec);
+        // Truncated input.
+        assert!(Set::deserialize_from(&buffer[..buffer.len() / 2]).is_err());
+
+        // Broken pointer tables following the cookie and the header flags.
+        let num_chunks = (&buffer[5..]).read_u64::<LittleEndian>().unwrap() as usize;
+        let tail = 13 + num_chunks * 8;
+        for (pos, x) in [
+            (5, 0),
+            (5, 1 << 40),
+            (tail, u64::MAX),
+            (tail + 8, 0),
+            (tail + 8, 65),
+            (tail + 16, 0),
+        ] {
+            let mut broken = buffer.clone();
+            broken[pos..pos + 8].copy_from_slice(&u64::to_le_bytes(x));
+            assert!(Set::deserialize_from(&broken[..]).is_err());
         }
-        assert!(iterator.next().is_none());
+
+        // Broken lcp values in the serialized area.
+        let mut broken = set.clone();
+        let mut serialized = broken.serialized.to_vec();
+        for x in serialized.iter_mut() {
+            if *x != END_MARKER {
+                *x = 0x7F;
+            }
+        }
+        broken.serialized = serialized.into();
+        let mut decoder = broken.decoder();
+        assert!(decoder.try_run(keys.len()).is_err());
+        assert!(decoder.try_run(0).is_ok());
+        assert!((0..keys.len()).any(|i| decoder.try_run(i).is_err()));
+        assert!(broken.iter().count() < keys.len());
+        assert!(broken.predictive_iter(&keys[0][..1]).count() < keys.len());
+
+        // Broken data is reported once by try_next, and then the iterators are fused.
+        let mut iter = broken.iter();
+        while let Ok(Some(_)) = iter.try_next() {}
+        assert!(matches!(iter.try_next(), Ok(None)));
+        let mut iter = broken.predictive_iter(b"");
+        assert!(iter.try_next().is_ok());
+        assert!(iter.try_next().is_err());
+        assert!(matches!(iter.try_next(), Ok(None)));
+
+        // Fewer keys than recorded.
+        let mut truncated = set.clone();
+        truncated.len += 1;
+        let mut iter = truncated.iter();
+        for _ in 0..keys.len() {
+            assert!(iter.try_next().unwrap().is_some());
+        }
+        assert!(iter.try_next().is_err());
+        assert!(matches!(iter.try_next(), Ok(None)));
+    }
+
+    #[test]
+    fn test_legacy() {
+        // Written by the original layout, before user-supplied ids were supported.
+        let data = include_bytes!("../testdata/legacy.fcsd");
+        let keys = [
+            "ICDM",
+            "ICML",
+            "SIGIR",
+            "SIGKDD",
+            "SIGMOD",
+            "SIGSPATIAL",
+            "WSDM",
+            "WWW",
+        ];
+        let set = Set::deserialize_from(&data[..]).unwrap();
+        assert_eq!(set.len(), keys.len());
+        assert_eq!(set.bucket_size(), 4);
+        let mut decoder = set.decoder();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(decoder.run(i), key.as_bytes());
+        }
+        assert!(Set::deserialize_from(&data[..data.len() - 1]).is_err());
     }
 }
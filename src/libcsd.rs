@@ -0,0 +1,69 @@
+//! Writer of the plain front-coding dictionary (`StringDictionaryPFC`) of libCSD.
+use std::io;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{utils, Set, END_MARKER};
+
+/// Type tag of `StringDictionaryPFC`.
+const PFC: u32 = 2;
+
+/// Appends an integer in the VByte of libCSD, which flags the last byte instead of the others.
+fn append_vbyte(bytes: &mut Vec<u8>, mut val: usize) {
+    while 127 < val {
+        bytes.push((val & 127) as u8);
+        val >>= 7;
+    }
+    bytes.push((val | 0x80) as u8);
+}
+
+/// Writes integers as a `LogSequence`, packed into 64-bit words from the lowest bit.
+fn write_log_sequence<W: io::Write>(mut writer: W, values: &[usize]) -> io::Result<()> {
+    let bits = utils::needed_bits(values.iter().cloned().max().unwrap_or(0) as u64);
+    let mut words = vec![0u64; (values.len() * bits).div_ceil(64)];
+    for (i, &x) in values.iter().enumerate() {
+        let (q, r) = (i * bits / 64, i * bits % 64);
+        words[q] |= (x as u64) << r;
+        if 64 < r + bits {
+            words[q + 1] |= (x as u64) >> (64 - r);
+        }
+    }
+    writer.write_u64::<LittleEndian>(values.len() as u64)?;
+    writer.write_u32::<LittleEndian>(bits as u32)?;
+    for w in words {
+        writer.write_u64::<LittleEndian>(w)?;
+    }
+    Ok(())
+}
+
+/// Writes the dictionary in the layout described in [`Set::export_libcsd`].
+pub fn write_pfc<W: io::Write>(set: &Set, mut writer: W) -> io::Result<()> {
+    let mut text = Vec::with_capacity(set.serialized.len());
+    let mut blocks = Vec::with_capacity(set.num_buckets() + 1);
+    let mut prev = Vec::with_capacity(set.max_length());
+    let mut iter = set.iter();
+    while let Some(id) = iter.advance() {
+        let key = iter.key();
+        if set.pos_in_bucket(id) == 0 {
+            blocks.push(text.len());
+            text.extend_from_slice(key);
+        } else {
+            let (lcp, _) = utils::get_lcp(&prev, key);
+            append_vbyte(&mut text, lcp);
+            text.extend_from_slice(&key[lcp..]);
+        }
+        text.push(END_MARKER);
+        prev.clear();
+        prev.extend_from_slice(key);
+    }
+    blocks.push(text.len());
+
+    writer.write_u32::<LittleEndian>(PFC)?;
+    writer.write_u64::<LittleEndian>(set.len() as u64)?;
+    writer.write_u32::<LittleEndian>(set.max_length() as u32)?;
+    writer.write_u32::<LittleEndian>(set.bucket_size() as u32)?;
+    writer.write_u64::<LittleEndian>(set.num_buckets() as u64)?;
+    writer.write_u64::<LittleEndian>(text.len() as u64)?;
+    writer.write_all(&text)?;
+    write_log_sequence(writer, &blocks)
+}
@@ -1,13 +1,39 @@
 use std::cmp::Ordering;
 
+use crate::id::Id;
+use crate::observer::{NoObserver, QueryInfo, QueryObserver};
+use crate::order::KeyOrder;
+use crate::scratch;
+use crate::stats::{self, Counter};
 use crate::utils;
 use crate::Set;
 
+/// The number of queries whose binary searches are interleaved in [`Locator::run_many`].
+const BATCH_SIZE: usize = 16;
+
+/// Result of [`Locator::lookup`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// The key is stored with the id.
+    Found(usize),
+    /// The key is not stored.
+    NotFound {
+        /// Id that the key would have if it were inserted,
+        /// i.e., the number of stored keys less than it.
+        insertion_id: usize,
+        /// Lengths of the longest common prefixes of the key with
+        /// the previous and next stored keys, which are zero if they do not exist.
+        lcp_with_neighbors: (usize, usize),
+    },
+}
+
 /// Locator class to get ids of given string keys.
+///
+/// The queries are reported to the observer `O`, which does nothing by default.
 #[derive(Clone)]
-pub struct Locator<'a> {
+pub struct Locator<'a, O = NoObserver> {
     set: &'a Set,
-    dec: Vec<u8>,
+    observer: O,
 }
 
 impl<'a> Locator<'a> {
@@ -16,12 +42,37 @@ impl<'a> Locator<'a> {
     /// # Arguments
     ///
     ///  - `set`: Front-coding dictionay.
-    pub fn new(set: &'a Set) -> Self {
+    pub const fn new(set: &'a Set) -> Self {
         Self {
             set,
-            dec: Vec::with_capacity(set.max_length()),
+            observer: NoObserver,
         }
     }
+}
+
+impl<'a, O> Locator<'a, O>
+where
+    O: QueryObserver,
+{
+    /// Makes a [`Locator`] reporting the queries to an observer.
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    ///  - `observer`: Observer called for each query.
+    pub const fn with_observer(set: &'a Set, observer: O) -> Self {
+        Self { set, observer }
+    }
+
+    /// Gets the reference to the observer.
+    pub const fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Gets the observer, consuming the locator.
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
 
     /// Returns the id of the given key.
     ///
@@ -37,68 +88,531 @@ impl<'a> Locator<'a> {
         P: AsRef<[u8]>,
     {
         let key = key.as_ref();
-        if key.is_empty() {
+        stats::record(Counter::Locates, 1);
+        if self.set.is_empty() {
             return None;
         }
 
-        let (set, dec) = (&self.set, &mut self.dec);
-        let (bi, found) = set.search_bucket(key);
+        let (bi, found, probes) = self.set.search_bucket_counted(key);
+        let id = if found {
+            Some(bi * self.set.bucket_size())
+        } else {
+            self.search_in_bucket(bi, key)
+        };
+        self.observer.on_locate(&QueryInfo {
+            key,
+            bucket_id: bi,
+            probes,
+            id,
+        });
+        id
+    }
 
-        if found {
-            return Some(bi * set.bucket_size());
+    /// Searches the given key and reports the context of a miss,
+    /// such as the neighboring keys sharing the longest prefixes, in a single pass.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    ///
+    /// # Complexity
+    ///
+    ///  - Logarithmic over the number of keys
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::locator::Lookup;
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.lookup(b"SIGIR"), Lookup::Found(2));
+    /// assert_eq!(
+    ///     locator.lookup(b"SIGKDE"),
+    ///     Lookup::NotFound {
+    ///         insertion_id: 4,
+    ///         lcp_with_neighbors: (5, 3),
+    ///     }
+    /// );
+    /// ```
+    pub fn lookup<P>(&mut self, key: P) -> Lookup
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let set = self.set;
+        stats::record(Counter::Locates, 1);
+        if set.is_empty() {
+            return Lookup::NotFound {
+                insertion_id: 0,
+                lcp_with_neighbors: (0, 0),
+            };
         }
 
-        let mut pos = set.decode_header(bi, dec);
-        if pos == set.serialized.len() {
-            return None;
+        let (bi, _) = set.search_bucket(key);
+        let base = bi * set.bucket_size();
+        scratch::with_buffer(|dec| {
+            let mut pos = set.decode_header(bi, dec);
+            let mut prev_lcp = 0;
+            for bj in 0..set.bucket_size() {
+                if bj != 0 {
+                    if pos == set.serialized.len() {
+                        break;
+                    }
+                    let (lcp, next_pos) = set.decode_lcp(pos);
+                    dec.truncate(lcp);
+                    pos = set.decode_next(next_pos, dec);
+                }
+                let lcp = utils::get_lcp(key, dec).0;
+                match set.order.compare(dec, key) {
+                    Ordering::Less => prev_lcp = lcp,
+                    Ordering::Equal => return Lookup::Found(base + bj),
+                    Ordering::Greater => {
+                        return Lookup::NotFound {
+                            insertion_id: base + bj,
+                            lcp_with_neighbors: (prev_lcp, lcp),
+                        }
+                    }
+                }
+            }
+            // The key is between the last key in the bucket and the header of the next one.
+            let next_lcp = if bi + 1 < set.num_buckets() {
+                set.with_header(bi + 1, |header| utils::get_lcp(key, header).0)
+            } else {
+                0
+            };
+            Lookup::NotFound {
+                insertion_id: (base + set.bucket_size()).min(set.len()),
+                lcp_with_neighbors: (prev_lcp, next_lcp),
+            }
+        })
+    }
+
+    /// Returns the id of the key given as the concatenation of parts.
+    ///
+    /// The parts are compared with the stored keys as if they were concatenated,
+    /// so composite keys, such as a tenant prefix followed by a term,
+    /// can be searched without joining them into a new buffer.
+    /// In [`KeyOrder::Natural`], the parts are joined into a pooled buffer
+    /// because a run of digits may span parts.
+    ///
+    /// # Arguments
+    ///
+    ///  - `parts`: Parts of the string key to be searched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["acme/ICDM", "acme/ICML", "globex/SIGIR", "globex/SIGKDD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.run_parts(&["globex/", "SIGIR"]), Some(2));
+    /// assert_eq!(locator.run_parts(&["acme", "/", "ICML"]), Some(1));
+    /// assert_eq!(locator.run_parts(&["acme/", "SIGIR"]), None);
+    /// ```
+    pub fn run_parts<P>(&mut self, parts: &[P]) -> Option<usize>
+    where
+        P: AsRef<[u8]>,
+    {
+        let set = self.set;
+        if set.order != KeyOrder::Lexicographic {
+            return scratch::with_buffer(|key| {
+                for part in parts {
+                    key.extend_from_slice(part.as_ref());
+                }
+                self.run(key)
+            });
         }
+        stats::record(Counter::Locates, 1);
 
-        // 1) Process the 1st internal string
-        {
-            let (dec_lcp, next_pos) = set.decode_lcp(pos);
-            pos = next_pos;
-            dec.resize(dec_lcp, 0);
-            pos = set.decode_next(pos, dec);
+        let (mut lo, mut hi) = (0, set.num_buckets());
+        while lo < hi {
+            let mi = (lo + hi) / 2;
+            stats::record(Counter::BucketProbes, 1);
+            match set.with_header(mi, |header| compare_parts(header, parts)) {
+                Ordering::Less => lo = mi + 1,
+                Ordering::Greater => hi = mi,
+                Ordering::Equal => return Some(mi * set.bucket_size()),
+            }
         }
+        let bi = lo.checked_sub(1)?;
+        scratch::with_buffer(|dec| {
+            let mut pos = set.decode_header(bi, dec);
+            for bj in 1..set.bucket_size() {
+                if pos == set.serialized.len() {
+                    break;
+                }
+                let (lcp, next_pos) = set.decode_lcp(pos);
+                dec.truncate(lcp);
+                pos = set.decode_next(next_pos, dec);
+                match compare_parts(dec, parts) {
+                    Ordering::Less => {}
+                    Ordering::Equal => return Some(bi * set.bucket_size() + bj),
+                    Ordering::Greater => return None,
+                }
+            }
+            None
+        })
+    }
+
+    /// Returns the id of the given key as the integer type `I`.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    ///
+    /// # Panics
+    ///
+    /// If the id does not fit in `I`, `panic!` will occur.
+    /// It never occurs if [`Set::fits_id`] returns `true`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.run_as::<u32, _>(b"ICML"), Some(1u32));
+    /// ```
+    pub fn run_as<I, P>(&mut self, key: P) -> Option<I>
+    where
+        I: Id,
+        P: AsRef<[u8]>,
+    {
+        self.run(key)
+            .map(|id| I::from_usize(id).expect("The id does not fit in the integer type."))
+    }
+
+    /// Returns the ids of the given keys.
+    ///
+    /// The queries are processed in small batches whose binary searches over bucket headers
+    /// are interleaved level by level, and the memory needed in the next level is prefetched.
+    /// This hides memory latency on large dictionaries.
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: String keys to be searched.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(
+    ///     locator.run_many(&["SIGMOD", "ICML", "SIGSPATIAL"]),
+    ///     vec![Some(4), Some(1), None]
+    /// );
+    /// ```
+    pub fn run_many<P>(&mut self, keys: &[P]) -> Vec<Option<usize>>
+    where
+        P: AsRef<[u8]>,
+    {
+        let mut results = Vec::with_capacity(keys.len());
+        for batch in keys.chunks(BATCH_SIZE) {
+            let set = self.set;
+            let mut ranges = [(0, 0); BATCH_SIZE];
+            let mut founds = [false; BATCH_SIZE];
+            let mut probes = [0; BATCH_SIZE];
+            for range in ranges.iter_mut().take(batch.len()) {
+                *range = (0, set.num_buckets());
+            }
+
+            loop {
+                let mut active = false;
+                for (j, &(lo, hi)) in ranges.iter().enumerate().take(batch.len()) {
+                    if lo < hi && !founds[j] {
+                        set.prefetch_header((lo + hi) / 2);
+                        active = true;
+                    }
+                }
+                if !active {
+                    break;
+                }
+                for (j, key) in batch.iter().enumerate() {
+                    let (lo, hi) = ranges[j];
+                    if lo >= hi || founds[j] {
+                        continue;
+                    }
+                    let mi = (lo + hi) / 2;
+                    probes[j] += 1;
+                    match set.with_header(mi, |header| set.order.compare(header, key.as_ref())) {
+                        Ordering::Less => ranges[j].0 = mi + 1,
+                        Ordering::Greater => ranges[j].1 = mi,
+                        Ordering::Equal => {
+                            ranges[j] = (mi, mi);
+                            founds[j] = true;
+                        }
+                    }
+                    let (lo, hi) = ranges[j];
+                    if lo < hi {
+                        set.pointers.prefetch((lo + hi) / 2);
+                    }
+                }
+            }
 
-        let (mut lcp, cmp) = utils::get_lcp(key, dec);
-        match cmp.cmp(&0) {
-            Ordering::Equal => {
-                return Some(bi * set.bucket_size() + 1);
+            for (j, key) in batch.iter().enumerate() {
+                let key = key.as_ref();
+                if set.is_empty() {
+                    results.push(None);
+                    continue;
+                }
+                let (bi, id) = if founds[j] {
+                    (ranges[j].0, Some(ranges[j].0 * set.bucket_size()))
+                } else {
+                    let bi = ranges[j].0.saturating_sub(1);
+                    (bi, self.search_in_bucket(bi, key))
+                };
+                self.observer.on_locate(&QueryInfo {
+                    key,
+                    bucket_id: bi,
+                    probes: probes[j],
+                    id,
+                });
+                results.push(id);
             }
-            Ordering::Greater => return None,
-            _ => {}
+        }
+        results
+    }
+
+    /// Returns the ids of the given keys as the integer type `I`, like [`Locator::run_many`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `keys`: String keys to be searched.
+    ///
+    /// # Panics
+    ///
+    /// If an id does not fit in `I`, `panic!` will occur.
+    /// It never occurs if [`Set::fits_id`] returns `true`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut locator = set.locator();
+    /// let ids: Vec<Option<u32>> = locator.run_many_as(&["SIGMOD", "ICML", "SIGSPATIAL"]);
+    /// assert_eq!(ids, vec![Some(4), Some(1), None]);
+    /// ```
+    pub fn run_many_as<I, P>(&mut self, keys: &[P]) -> Vec<Option<I>>
+    where
+        I: Id,
+        P: AsRef<[u8]>,
+    {
+        self.run_many(keys)
+            .into_iter()
+            .map(|id| {
+                id.map(|id| I::from_usize(id).expect("The id does not fit in the integer type."))
+            })
+            .collect()
+    }
+
+    /// Searches the bucket for the key, which must be no less than the bucket header.
+    ///
+    /// The keys are compared in place without being decoded, tracking the LCP between
+    /// the query and the current key. Since the current key is less than the query,
+    /// a following key whose LCP with it is shorter than the tracked one is greater
+    /// than the query, which proves the absence without looking at its suffix.
+    fn search_in_bucket(&self, bi: usize, key: &[u8]) -> Option<usize> {
+        let set = self.set;
+        if set.order != KeyOrder::Lexicographic || set.alphabet.is_some() {
+            return self.search_in_bucket_by_order(bi, key);
         }
 
-        // 2) Process the next strings
-        for bj in 2..set.bucket_size() {
+        let header = set.get_header(bi);
+        let (mut lcp, cmp) = utils::get_lcp(key, header);
+        if cmp > 0 {
+            return None;
+        }
+        let mut pos = set.pointers.get(bi) as usize + header.len() + 1;
+
+        for bj in 1..set.bucket_size() {
             if pos == set.serialized.len() {
                 break;
             }
-
             let (dec_lcp, next_pos) = set.decode_lcp(pos);
-            pos = next_pos;
+            let suffix = &set.serialized[next_pos..];
+            let suffix_len = utils::get_strlen(suffix);
+            pos = next_pos + suffix_len + 1;
 
-            if lcp > dec_lcp {
-                break;
+            match dec_lcp.cmp(&lcp) {
+                // The key shares the mismatching byte with the previous one.
+                Ordering::Greater => {}
+                // The key is greater than the query.
+                Ordering::Less => return None,
+                Ordering::Equal => {
+                    let (next_lcp, cmp) = utils::get_lcp(&key[lcp..], &suffix[..suffix_len]);
+                    match cmp.cmp(&0) {
+                        Ordering::Equal => return Some(bi * set.bucket_size() + bj),
+                        Ordering::Greater => return None,
+                        Ordering::Less => lcp += next_lcp,
+                    }
+                }
             }
+        }
 
-            dec.resize(dec_lcp, 0);
-            pos = set.decode_next(pos, dec);
+        None
+    }
 
-            if lcp == dec_lcp {
-                let (next_lcp, cmp) = utils::get_lcp(key, dec);
-                match cmp.cmp(&0) {
-                    Ordering::Equal => {
-                        return Some(bi * set.bucket_size() + bj);
+    /// Searches the bucket for the key by decoding and comparing the keys in the order,
+    /// used when the LCPs cannot bound the comparisons or the suffixes are packed.
+    fn search_in_bucket_by_order(&self, bi: usize, key: &[u8]) -> Option<usize> {
+        let set = self.set;
+        scratch::with_buffer(|dec| {
+            let mut pos = set.decode_header(bi, dec);
+            for bj in 0..set.bucket_size() {
+                if bj != 0 {
+                    if pos == set.serialized.len() {
+                        break;
                     }
-                    Ordering::Greater => break,
-                    _ => {}
+                    let (lcp, next_pos) = set.decode_lcp(pos);
+                    dec.truncate(lcp);
+                    pos = set.decode_next(next_pos, dec);
+                }
+                match set.order.compare(dec, key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => return Some(bi * set.bucket_size() + bj),
+                    Ordering::Greater => return None,
                 }
-                lcp = next_lcp;
             }
-        }
+            None
+        })
+    }
 
-        None
+    /// Returns the user-supplied id of the given key.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    ///
+    /// # Returns
+    ///
+    /// `None` if `key` is not found or the keys were not added with
+    /// [`Builder::add_with_id`](crate::builder::Builder::add_with_id).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_id(b"ICDM", 20).unwrap();
+    /// builder.add_with_id(b"ICML", 10).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.run_external(b"ICML"), Some(10));
+    /// assert_eq!(locator.run_external(b"SIGIR"), None);
+    /// ```
+    pub fn run_external<P>(&mut self, key: P) -> Option<u64>
+    where
+        P: AsRef<[u8]>,
+    {
+        let ids = self.set.external_ids.as_ref()?;
+        self.run(key).map(|id| ids.get(id))
+    }
+
+    /// Returns the id and payload of the given key.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    ///
+    /// # Returns
+    ///
+    /// `None` if `key` is not found or the keys were not added with
+    /// [`Builder::add_with_payload`](crate::builder::Builder::add_with_payload).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_payload(b"ICDM", 2001).unwrap();
+    /// builder.add_with_payload(b"ICML", 1980).unwrap();
+    /// let set = builder.finish();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.run_with_payload(b"ICDM"), Some((0, 2001)));
+    /// assert_eq!(locator.run_with_payload(b"SIGIR"), None);
+    /// ```
+    pub fn run_with_payload<P>(&mut self, key: P) -> Option<(usize, u64)>
+    where
+        P: AsRef<[u8]>,
+    {
+        let payloads = self.set.payloads.as_ref()?;
+        self.run(key).map(|id| (id, payloads.get(id)))
+    }
+
+    /// Returns the id of the given key and the value associated with it.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    ///
+    /// # Returns
+    ///
+    /// `None` if `key` is not found or the keys were not added with
+    /// [`Builder::add_with_value`](crate::builder::Builder::add_with_value).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::builder::Builder;
+    ///
+    /// let mut builder = Builder::new(4).unwrap();
+    /// builder.add_with_value(b"ICDM", b"Data Mining").unwrap();
+    /// builder.add_with_value(b"ICML", b"Machine Learning").unwrap();
+    /// let set = builder.finish();
+    ///
+    /// let mut locator = set.locator();
+    /// assert_eq!(locator.run_with_value(b"ICDM"), Some((0, b"Data Mining".to_vec())));
+    /// assert_eq!(locator.run_with_value(b"SIGIR"), None);
+    /// ```
+    pub fn run_with_value<P>(&mut self, key: P) -> Option<(usize, Vec<u8>)>
+    where
+        P: AsRef<[u8]>,
+    {
+        let values = self.set.values.as_ref()?;
+        let id = self.run(key)?;
+        values.get(id).map(|value| (id, value))
+    }
+}
+
+/// Compares the key with the concatenation of the parts in the lexicographical order.
+fn compare_parts<P>(mut key: &[u8], parts: &[P]) -> Ordering
+where
+    P: AsRef<[u8]>,
+{
+    for part in parts {
+        let part = part.as_ref();
+        let len = std::cmp::min(key.len(), part.len());
+        match key[..len].cmp(&part[..len]) {
+            Ordering::Equal if key.len() < part.len() => return Ordering::Less,
+            Ordering::Equal => key = &key[len..],
+            cmp => return cmp,
+        }
+    }
+    if key.is_empty() {
+        Ordering::Equal
+    } else {
+        Ordering::Greater
     }
 }
@@ -0,0 +1,431 @@
+//! Streaming merge of serialized dictionaries.
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+
+use anyhow::{anyhow, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::builder::{Progress, PROGRESS_INTERVAL};
+use crate::intvec::IntVector;
+use crate::order::KeyOrder;
+use crate::utils;
+use crate::{
+    DEFAULT_BUCKET_SIZE, END_MARKER, FLAG_ALIGNED, FLAG_ALPHABET, FLAG_BUCKET_ALIGNED,
+    FLAG_COMPACT, FLAG_MAX_KEY_LEN, FLAG_NATURAL_ORDER, FLAG_UTF8, SERIAL_COOKIE,
+};
+
+/// Merges serialized dictionaries into a new serialized dictionary of the union of their keys,
+/// with the bucket size [`DEFAULT_BUCKET_SIZE`].
+///
+/// See [`merge_serialized_with_bucket_size`] for the details.
+///
+/// # Arguments
+///
+///  - `inputs`: Readable streams of serialized dictionaries.
+///  - `out`: Writable stream of the merged dictionary.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+/// use fcsd::merge::merge_serialized;
+///
+/// let mut inputs = vec![];
+/// for keys in [vec!["ICDM", "SIGIR"], vec!["ICML", "SIGIR", "SIGKDD"]] {
+///     let mut data = Vec::new();
+///     Set::new(keys).unwrap().serialize_into(&mut data).unwrap();
+///     inputs.push(data);
+/// }
+///
+/// let mut merged = Vec::new();
+/// let inputs = inputs.iter().map(|data| &data[..]).collect();
+/// assert_eq!(merge_serialized(inputs, &mut merged).unwrap(), 4);
+///
+/// let set = Set::deserialize_from(&merged[..]).unwrap();
+/// assert_eq!(set.locator().run(b"SIGKDD"), Some(3));
+/// ```
+pub fn merge_serialized<R, W>(inputs: Vec<R>, out: W) -> Result<usize>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    merge_serialized_with_bucket_size(inputs, out, DEFAULT_BUCKET_SIZE)
+}
+
+/// Merges serialized dictionaries into a new serialized dictionary of the union of their keys.
+///
+/// The keys are merged without deserializing the inputs: only the pointer table and
+/// one bucket of each input are held in memory, and the merged keys are spilled to a
+/// temporary file until the pointer table of the output is determined.
+///
+/// # Arguments
+///
+///  - `inputs`: Readable streams of serialized dictionaries.
+///  - `out`: Writable stream of the merged dictionary.
+///  - `bucket_size`: The number of strings in each bucket of the output, which must be a power of two.
+///
+/// # Returns
+///
+/// The number of keys in the merged dictionary.
+///
+/// # Errors
+///
+/// [`anyhow::Result`] will be returned when
+///
+///  - `bucket_size` is zero or not a power of two,
+///  - an input is broken or has external ids, payloads, or values,
+///  - an input is in the original layout, written before user-supplied ids were supported,
+///  - an input is in the aligned or compact layout, or has buckets aligned to cache lines,
+///  - an input was built with an [`Alphabet`](crate::alphabet::Alphabet),
+///  - the inputs are sorted in different orders, or
+///  - an I/O error occurs.
+pub fn merge_serialized_with_bucket_size<R, W>(
+    inputs: Vec<R>,
+    out: W,
+    bucket_size: usize,
+) -> Result<usize>
+where
+    R: io::Read,
+    W: io::Write,
+{
+    merge_serialized_with_progress(inputs, out, bucket_size, |_| {})
+}
+
+/// Merges serialized dictionaries like [`merge_serialized_with_bucket_size`],
+/// calling a closure with the progress every 65536 merged keys and at the end.
+///
+/// # Arguments
+///
+///  - `inputs`: Readable streams of serialized dictionaries.
+///  - `out`: Writable stream of the merged dictionary.
+///  - `bucket_size`: The number of strings in each bucket of the output, which must be a power of two.
+///  - `on_progress`: Closure receiving the progress.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+/// use fcsd::builder::Progress;
+/// use fcsd::merge::merge_serialized_with_progress;
+///
+/// let mut inputs = vec![];
+/// for keys in [vec!["ICDM", "SIGIR"], vec!["ICML", "SIGIR", "SIGKDD"]] {
+///     let mut data = Vec::new();
+///     Set::new(keys).unwrap().serialize_into(&mut data).unwrap();
+///     inputs.push(data);
+/// }
+///
+/// let mut reports = vec![];
+/// let inputs = inputs.iter().map(|data| &data[..]).collect();
+/// merge_serialized_with_progress(inputs, Vec::new(), 8, |p| reports.push(p)).unwrap();
+/// assert_eq!(reports.last(), Some(&Progress { keys: 4, bytes: 21 }));
+/// ```
+pub fn merge_serialized_with_progress<R, W, F>(
+    inputs: Vec<R>,
+    mut out: W,
+    bucket_size: usize,
+    mut on_progress: F,
+) -> Result<usize>
+where
+    R: io::Read,
+    W: io::Write,
+    F: FnMut(Progress),
+{
+    if bucket_size == 0 || !utils::is_power_of_two(bucket_size) {
+        return Err(anyhow!("bucket_size must be a power of two."));
+    }
+
+    let mut readers = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        readers.push(SerializedReader::open(input)?);
+    }
+    let mut alive = Vec::with_capacity(readers.len());
+    for reader in readers.iter_mut() {
+        alive.push(reader.next_key()?);
+    }
+
+    // The union of UTF-8 keys is also UTF-8.
+    let utf8 = readers.iter().all(|reader| reader.utf8);
+    // The union of keys within limits is within the largest one.
+    let max_key_len = readers
+        .iter()
+        .map(|reader| reader.max_key_len)
+        .try_fold(0, |acc, n| n.map(|n| acc.max(n)));
+    let order = readers.first().map_or(KeyOrder::Lexicographic, |r| r.order);
+    if readers.iter().any(|reader| reader.order != order) {
+        return Err(anyhow!(
+            "Dictionaries sorted in different orders cannot be merged."
+        ));
+    }
+    let mut writer = SerializedWriter::new(bucket_size, utf8, max_key_len, order)?;
+    let mut min_key = Vec::new();
+    loop {
+        let min_reader = (0..readers.len())
+            .filter(|&i| alive[i])
+            .min_by(|&i, &j| order.compare(&readers[i].key, &readers[j].key));
+        let Some(i) = min_reader else {
+            break;
+        };
+        min_key.clear();
+        min_key.extend_from_slice(&readers[i].key);
+        writer.push(&min_key)?;
+        if writer.len.is_multiple_of(PROGRESS_INTERVAL) {
+            on_progress(writer.progress());
+        }
+        for (reader, alive) in readers.iter_mut().zip(alive.iter_mut()) {
+            if *alive && reader.key == min_key {
+                *alive = reader.next_key()?;
+            }
+        }
+    }
+
+    for reader in readers {
+        reader.finish()?;
+    }
+    on_progress(writer.progress());
+    writer.finish(&mut out)
+}
+
+/// Reader of the keys of a serialized dictionary, bucket by bucket.
+struct SerializedReader<R> {
+    reader: R,
+    pointers: IntVector,
+    serialized_len: usize,
+    // Number of bytes of the serialized area consumed so far.
+    offset: usize,
+    // Number of buckets consumed so far.
+    bi: usize,
+    bucket: Vec<u8>,
+    pos: usize,
+    key: Vec<u8>,
+    num_keys: usize,
+    utf8: bool,
+    max_key_len: Option<usize>,
+    order: KeyOrder,
+}
+
+impl<R> SerializedReader<R>
+where
+    R: io::Read,
+{
+    fn open(mut reader: R) -> Result<Self> {
+        let cookie = reader.read_u32::<LittleEndian>()?;
+        if cookie != SERIAL_COOKIE {
+            return Err(anyhow!("unknown cookie value"));
+        }
+        let flags = reader.read_u8()?;
+        if flags & FLAG_ALPHABET != 0 {
+            return Err(anyhow!(
+                "Dictionaries with packed suffixes cannot be merged."
+            ));
+        }
+        if flags & (FLAG_ALIGNED | FLAG_COMPACT) != 0 {
+            return Err(anyhow!(
+                "Dictionaries in the aligned or compact layout cannot be merged."
+            ));
+        }
+        if flags & FLAG_BUCKET_ALIGNED != 0 {
+            return Err(anyhow!(
+                "Dictionaries with buckets aligned to cache lines cannot be merged."
+            ));
+        }
+        if flags & !(FLAG_UTF8 | FLAG_MAX_KEY_LEN | FLAG_NATURAL_ORDER) != 0 {
+            return Err(anyhow!("unknown header flags"));
+        }
+        let max_key_len = if flags & FLAG_MAX_KEY_LEN != 0 {
+            Some(utils::read_usize(&mut reader)?)
+        } else {
+            None
+        };
+        let pointers = IntVector::deserialize_from(&mut reader)?;
+        let serialized_len = utils::read_usize(&mut reader)?;
+        Ok(Self {
+            reader,
+            pointers,
+            serialized_len,
+            offset: 0,
+            bi: 0,
+            bucket: Vec::new(),
+            pos: 0,
+            key: Vec::new(),
+            num_keys: 0,
+            utf8: flags & FLAG_UTF8 != 0,
+            max_key_len,
+            order: if flags & FLAG_NATURAL_ORDER != 0 {
+                KeyOrder::Natural
+            } else {
+                KeyOrder::Lexicographic
+            },
+        })
+    }
+
+    /// Decodes the next key into `self.key`, returning `false` at the end.
+    fn next_key(&mut self) -> Result<bool> {
+        if self.pos == self.bucket.len() {
+            if self.bi == self.pointers.len() {
+                return Ok(false);
+            }
+            let end = if self.bi + 1 < self.pointers.len() {
+                self.pointers.get(self.bi + 1) as usize
+            } else {
+                self.serialized_len
+            };
+            if self.pointers.get(self.bi) as usize != self.offset
+                || end <= self.offset
+                || self.serialized_len < end
+            {
+                return Err(anyhow!("broken pointers"));
+            }
+            // The bucket grows with the bytes actually read, so a broken length cannot
+            // allocate more memory than the input has.
+            let bucket_len = end - self.offset;
+            self.bucket.clear();
+            (&mut self.reader)
+                .take(bucket_len as u64)
+                .read_to_end(&mut self.bucket)?;
+            if self.bucket.len() != bucket_len {
+                return Err(anyhow!("truncated serialized area"));
+            }
+            self.offset = end;
+            self.bi += 1;
+            self.pos = 0;
+            self.key.clear();
+        } else {
+            let (lcp, num) = utils::vbyte::try_decode(&self.bucket[self.pos..])
+                .filter(|&(lcp, _)| lcp <= self.key.len())
+                .ok_or_else(|| anyhow!("broken serialized area"))?;
+            self.pos += num;
+            self.key.truncate(lcp);
+        }
+        let suffix = &self.bucket[self.pos..];
+        let len =
+            memchr::memchr(END_MARKER, suffix).ok_or_else(|| anyhow!("broken serialized area"))?;
+        self.key.extend_from_slice(&suffix[..len]);
+        self.pos += len + 1;
+        self.num_keys += 1;
+        Ok(true)
+    }
+
+    /// Reads and checks the fields following the serialized area.
+    fn finish(mut self) -> Result<()> {
+        if self.offset != self.serialized_len {
+            return Err(anyhow!("broken pointers"));
+        }
+        let len = utils::read_usize(&mut self.reader)?;
+        let _bucket_bits = self.reader.read_u64::<LittleEndian>()?;
+        let _bucket_mask = self.reader.read_u64::<LittleEndian>()?;
+        let _max_length = self.reader.read_u64::<LittleEndian>()?;
+        if len != self.num_keys {
+            return Err(anyhow!("inconsistent number of keys"));
+        }
+        for _ in 0..3 {
+            if self.reader.read_u8()? != 0 {
+                return Err(anyhow!(
+                    "Dictionaries with external ids, payloads, or values cannot be merged."
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writer of a serialized dictionary spilling the serialized area to a temporary file.
+struct SerializedWriter {
+    file: BufWriter<File>,
+    pointers: IntVector,
+    serialized_len: usize,
+    last_key: Vec<u8>,
+    buf: Vec<u8>,
+    len: usize,
+    bucket_mask: usize,
+    max_length: usize,
+    utf8: bool,
+    max_key_len: Option<usize>,
+    order: KeyOrder,
+}
+
+impl SerializedWriter {
+    fn new(
+        bucket_size: usize,
+        utf8: bool,
+        max_key_len: Option<usize>,
+        order: KeyOrder,
+    ) -> Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(tempfile::tempfile()?),
+            pointers: IntVector::new(),
+            serialized_len: 0,
+            last_key: Vec::new(),
+            buf: Vec::new(),
+            len: 0,
+            bucket_mask: bucket_size - 1,
+            max_length: 0,
+            utf8,
+            max_key_len,
+            order,
+        })
+    }
+
+    fn push(&mut self, key: &[u8]) -> Result<()> {
+        let lcp = utils::get_lcp(&self.last_key, key).0;
+        if self.len != 0 && self.order.compare(&self.last_key, key).is_ge() {
+            return Err(anyhow!("The input keys must be sorted."));
+        }
+        self.buf.clear();
+        if self.len & self.bucket_mask == 0 {
+            self.pointers.push(self.serialized_len as u64);
+            self.buf.extend_from_slice(key);
+        } else {
+            utils::vbyte::append(&mut self.buf, lcp);
+            self.buf.extend_from_slice(&key[lcp..]);
+        }
+        self.buf.push(END_MARKER);
+        self.file.write_all(&self.buf)?;
+        self.serialized_len += self.buf.len();
+
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.len += 1;
+        self.max_length = std::cmp::max(self.max_length, key.len());
+        Ok(())
+    }
+
+    const fn progress(&self) -> Progress {
+        Progress {
+            keys: self.len,
+            bytes: self.serialized_len,
+        }
+    }
+
+    fn finish<W>(self, mut out: W) -> Result<usize>
+    where
+        W: io::Write,
+    {
+        let mut file = self.file.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start(0))?;
+
+        out.write_u32::<LittleEndian>(SERIAL_COOKIE)?;
+        let mut flags = if self.utf8 { FLAG_UTF8 } else { 0 };
+        if self.max_key_len.is_some() {
+            flags |= FLAG_MAX_KEY_LEN;
+        }
+        if self.order == KeyOrder::Natural {
+            flags |= FLAG_NATURAL_ORDER;
+        }
+        out.write_u8(flags)?;
+        if let Some(max_key_len) = self.max_key_len {
+            out.write_u64::<LittleEndian>(max_key_len as u64)?;
+        }
+        self.pointers.serialize_into(&mut out)?;
+        out.write_u64::<LittleEndian>(self.serialized_len as u64)?;
+        io::copy(&mut file, &mut out)?;
+        out.write_u64::<LittleEndian>(self.len as u64)?;
+        out.write_u64::<LittleEndian>(u64::from((self.bucket_mask + 1).trailing_zeros()))?;
+        out.write_u64::<LittleEndian>(self.bucket_mask as u64)?;
+        out.write_u64::<LittleEndian>(self.max_length as u64)?;
+        out.write_u8(0)?; // external_ids
+        out.write_u8(0)?; // payloads
+        out.write_u8(0)?; // values
+        Ok(self.len)
+    }
+}
@@ -0,0 +1,179 @@
+//! Queries over several dictionaries layered in priority order,
+//! such as a base dictionary with deltas or shards.
+//!
+//! A key stored in several dictionaries is resolved to the first of them.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::multi_locator::MultiLocator;
+//! use fcsd::Set;
+//!
+//! let delta = Set::new(["ICML", "SIGKDD"]).unwrap();
+//! let base = Set::new(["ICDM", "SIGIR", "SIGKDD", "SIGMOD"]).unwrap();
+//!
+//! let mut locator = MultiLocator::new([&delta, &base]).unwrap();
+//! assert_eq!(locator.run(b"SIGKDD"), Some((0, 1)));
+//! assert_eq!(locator.run(b"SIGMOD"), Some((1, 3)));
+//! assert_eq!(locator.run(b"SIGIR"), Some((1, 1)));
+//! assert_eq!(locator.run(b"VLDB"), None);
+//!
+//! let merged: Vec<_> = locator.iter().map(|(key, dict, _)| (key, dict)).collect();
+//! assert_eq!(merged[2], (b"SIGIR".to_vec(), 1));
+//! assert_eq!(merged[3], (b"SIGKDD".to_vec(), 0));
+//! assert_eq!(merged.len(), 5);
+//! ```
+use std::cmp::Ordering;
+use std::iter::FusedIterator;
+
+use anyhow::{anyhow, Result};
+
+use crate::iter::Iter;
+use crate::locator::Locator;
+use crate::order::KeyOrder;
+use crate::Set;
+
+/// Locator searching several dictionaries in priority order.
+#[derive(Clone)]
+pub struct MultiLocator<'a> {
+    sets: Vec<&'a Set>,
+    locators: Vec<Locator<'a>>,
+    order: KeyOrder,
+}
+
+impl<'a> MultiLocator<'a> {
+    /// Makes a [`MultiLocator`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `sets`: Dictionaries in priority order, where the first one has the highest priority.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionaries have different orders of keys,
+    /// in which they cannot be merged.
+    pub fn new<I>(sets: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a Set>,
+    {
+        let sets: Vec<_> = sets.into_iter().collect();
+        let order = sets
+            .first()
+            .map_or_else(KeyOrder::default, |set| set.key_order());
+        if sets.iter().any(|set| set.key_order() != order) {
+            return Err(anyhow!(
+                "The dictionaries must have the same order of keys."
+            ));
+        }
+        Ok(Self {
+            locators: sets.iter().map(|set| set.locator()).collect(),
+            sets,
+            order,
+        })
+    }
+
+    /// Gets the number of dictionaries.
+    pub const fn len(&self) -> usize {
+        self.locators.len()
+    }
+
+    /// Checks if no dictionaries are layered.
+    pub const fn is_empty(&self) -> bool {
+        self.locators.is_empty()
+    }
+
+    /// Returns the index of the first dictionary storing the given key and the id in it.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    pub fn run<P>(&mut self, key: P) -> Option<(usize, usize)>
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        self.locators
+            .iter_mut()
+            .enumerate()
+            .find_map(|(i, locator)| locator.run(key).map(|id| (i, id)))
+    }
+
+    /// Returns the ids of the given key in all the dictionaries, which are `None` if not stored.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be searched.
+    pub fn run_all<P>(&mut self, key: P) -> Vec<Option<usize>>
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        self.locators
+            .iter_mut()
+            .map(|locator| locator.run(key))
+            .collect()
+    }
+
+    /// Makes an iterator enumerating the distinct keys of all the dictionaries in the order of keys.
+    pub fn iter(&self) -> MergedIter<'a> {
+        MergedIter::new(self.sets.iter().map(|set| set.iter()).collect(), self.order)
+    }
+}
+
+/// Iterator enumerating the distinct keys of layered dictionaries in the order of keys,
+/// made by [`MultiLocator::iter`].
+///
+/// Each item is the triple of the key, the index of the first dictionary storing it,
+/// and the id in the dictionary.
+#[derive(Clone)]
+pub struct MergedIter<'a> {
+    iters: Vec<Iter<'a>>,
+    // heads[i] is the id of the current key of iters[i], or None if it is exhausted.
+    heads: Vec<Option<usize>>,
+    order: KeyOrder,
+}
+
+impl<'a> MergedIter<'a> {
+    fn new(mut iters: Vec<Iter<'a>>, order: KeyOrder) -> Self {
+        let heads = iters.iter_mut().map(Iter::advance).collect();
+        Self {
+            iters,
+            heads,
+            order,
+        }
+    }
+}
+
+impl Iterator for MergedIter<'_> {
+    type Item = (Vec<u8>, usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The minimum key, whose ties are broken by the priority.
+        let mut min: Option<usize> = None;
+        for i in 0..self.iters.len() {
+            if self.heads[i].is_none() {
+                continue;
+            }
+            let is_less = min.is_none_or(|m| {
+                self.order
+                    .compare(self.iters[i].key(), self.iters[m].key())
+                    .is_lt()
+            });
+            if is_less {
+                min = Some(i);
+            }
+        }
+        let m = min?;
+        let item = (self.iters[m].key().to_vec(), m, self.heads[m].unwrap());
+        for i in 0..self.iters.len() {
+            if self.heads[i].is_some()
+                && self.order.compare(self.iters[i].key(), &item.0) == Ordering::Equal
+            {
+                self.heads[i] = self.iters[i].advance();
+            }
+        }
+        Some(item)
+    }
+}
+
+impl FusedIterator for MergedIter<'_> {}
@@ -0,0 +1,77 @@
+//! Query profile performing no heap allocation, for latency-critical and embedded consumers.
+//!
+//! Queries taking `&self` borrow temporary buffers from a thread-local pool,
+//! which allocates until it is warmed up. A [`Scratch`] holds the buffers up front,
+//! and the following queries perform no heap allocation inside [`Scratch::run`]:
+//!
+//!  - [`Locator::run`](crate::locator::Locator::run),
+//!    [`Locator::run_parts`](crate::locator::Locator::run_parts), and
+//!    [`Locator::lookup`](crate::locator::Locator::lookup),
+//!  - [`Set::decode_into`] with a buffer of capacity [`Set::max_length`] or more,
+//!  - [`Set::key_len`] and [`Set::cmp_key`],
+//!  - [`Set::rank`], [`Set::prefix_range`], and [`Set::contains_prefix`], and
+//!  - [`Set::prefix_membership_into`] with prefixes sorted in the order of keys
+//!    and a bitmap already holding as many bits.
+//!
+//! The guarantee holds regardless of the key order and alphabet, except that
+//! [`Set::contains_prefix`] and [`Set::prefix_membership_into`] enumerate keys for a prefix
+//! ending with a digit in [`KeyOrder::Natural`](crate::order::KeyOrder::Natural).
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::no_alloc::Scratch;
+//! use fcsd::Set;
+//!
+//! let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+//! let set = Set::new(keys).unwrap();
+//!
+//! // Allocates everything before the queries.
+//! let mut scratch = Scratch::new(&set);
+//! let mut locator = set.locator();
+//! let mut key = Vec::with_capacity(set.max_length());
+//!
+//! scratch.run(|| {
+//!     let id = locator.run(b"SIGKDD").unwrap();
+//!     set.decode_into(id, &mut key);
+//! });
+//! assert_eq!(key, b"SIGKDD");
+//! ```
+use crate::{scratch, Set};
+
+/// The number of buffers, which covers the nesting of buffers in a query.
+const NUM_BUFFERS: usize = 4;
+
+/// Buffers lent to the queries so that they perform no heap allocation.
+#[derive(Clone, Debug)]
+pub struct Scratch {
+    bufs: Vec<Vec<u8>>,
+}
+
+impl Scratch {
+    /// Allocates the buffers for queries on a dictionary.
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay to be queried.
+    pub fn new(set: &Set) -> Self {
+        Self {
+            bufs: (0..NUM_BUFFERS)
+                .map(|_| Vec::with_capacity(set.max_length()))
+                .collect(),
+        }
+    }
+
+    /// Calls `f` in which the queries of the dictionary use the buffers
+    /// instead of allocating them.
+    ///
+    /// # Arguments
+    ///
+    ///  - `f`: Closure running the queries.
+    pub fn run<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        scratch::lend(&mut self.bufs, f)
+    }
+}
@@ -0,0 +1,62 @@
+//! Hooks to observe queries of [`Locator`](crate::locator::Locator).
+//!
+//! An observer is a type parameter of the locator, so the default [`NoObserver`]
+//! is compiled away and costs nothing.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::observer::QueryInfo;
+//! use fcsd::Set;
+//!
+//! let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+//! let set = Set::with_bucket_size(keys, 2).unwrap();
+//!
+//! // Counts the queries hitting each bucket.
+//! let mut hits = vec![0; set.num_buckets()];
+//! let mut locator = set.locator_with_observer(|info: &QueryInfo| hits[info.bucket_id] += 1);
+//! assert_eq!(locator.run(b"SIGKDD"), Some(3));
+//! assert_eq!(locator.run(b"SIGIR"), Some(2));
+//! assert_eq!(locator.run(b"ICMM"), None);
+//! assert_eq!(hits, vec![1, 2, 0]);
+//! ```
+
+/// Information of a query reported to [`QueryObserver`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueryInfo<'k> {
+    /// The query key.
+    pub key: &'k [u8],
+    /// Bucket in which the key was searched.
+    pub bucket_id: usize,
+    /// Number of bucket headers compared in the binary search.
+    pub probes: usize,
+    /// Id of the key, or `None` if it is not found.
+    pub id: Option<usize>,
+}
+
+/// Observer called for each query of [`Locator`](crate::locator::Locator).
+///
+/// It is implemented for closures taking [`QueryInfo`].
+pub trait QueryObserver {
+    /// Called after a key is searched in a non-empty dictionary.
+    fn on_locate(&mut self, info: &QueryInfo<'_>);
+}
+
+/// Observer doing nothing, which is the default of [`Locator`](crate::locator::Locator).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NoObserver;
+
+impl QueryObserver for NoObserver {
+    #[inline(always)]
+    fn on_locate(&mut self, _info: &QueryInfo<'_>) {}
+}
+
+impl<F> QueryObserver for F
+where
+    F: FnMut(&QueryInfo<'_>),
+{
+    #[inline(always)]
+    fn on_locate(&mut self, info: &QueryInfo<'_>) {
+        self(info)
+    }
+}
@@ -0,0 +1,80 @@
+//! Orders of keys.
+use std::cmp::Ordering;
+
+/// Order in which keys are sorted and searched.
+///
+/// # Example
+///
+/// ```
+/// use std::cmp::Ordering;
+/// use fcsd::order::KeyOrder;
+///
+/// assert_eq!(KeyOrder::Lexicographic.compare(b"file10", b"file2"), Ordering::Less);
+/// assert_eq!(KeyOrder::Natural.compare(b"file10", b"file2"), Ordering::Greater);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum KeyOrder {
+    /// Bytewise lexicographical order.
+    #[default]
+    Lexicographic,
+    /// Natural order, where runs of ASCII digits are compared as numbers
+    /// (e.g., `"file2" < "file10"`).
+    ///
+    /// Runs of the same number are ordered by the number of leading zeros,
+    /// fewer first (e.g., `"a1" < "a01"`), so distinct keys never compare equal.
+    ///
+    /// The keys starting with a prefix ending with a digit are not contiguous in this order
+    /// (e.g., `"file2"` lies between `"file1"` and `"file10"`), so prefix-based queries
+    /// are slower or unsupported for dictionaries in this order.
+    Natural,
+}
+
+impl KeyOrder {
+    /// Compares two keys in the order.
+    pub fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            Self::Lexicographic => a.cmp(b),
+            Self::Natural => compare_natural(a, b),
+        }
+    }
+}
+
+fn compare_natural(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let a_run = digit_run(&a[i..]);
+            let b_run = digit_run(&b[j..]);
+            let ord = compare_numbers(a_run, b_run).then(a_run.len().cmp(&b_run.len()));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i += a_run.len();
+            j += b_run.len();
+        } else {
+            match a[i].cmp(&b[j]) {
+                Ordering::Equal => {
+                    i += 1;
+                    j += 1;
+                }
+                ord => return ord,
+            }
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j))
+}
+
+fn digit_run(s: &[u8]) -> &[u8] {
+    let len = s
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(s.len());
+    &s[..len]
+}
+
+/// Compares runs of digits as numbers of arbitrary length.
+fn compare_numbers(a: &[u8], b: &[u8]) -> Ordering {
+    let a = &a[a.iter().position(|&c| c != b'0').unwrap_or(a.len())..];
+    let b = &b[b.iter().position(|&c| c != b'0').unwrap_or(b.len())..];
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
@@ -0,0 +1,117 @@
+//! Streams tracking their positions to pad sections to an alignment.
+use std::io::{self, Read, Write};
+
+use anyhow::{anyhow, Result};
+
+/// Writer inserting zero bytes so that the next section starts at a multiple of the alignment.
+pub struct PaddedWriter<W> {
+    inner: W,
+    pos: usize,
+    alignment: usize,
+}
+
+impl<W> PaddedWriter<W>
+where
+    W: io::Write,
+{
+    pub const fn new(inner: W, alignment: usize) -> Self {
+        Self {
+            inner,
+            pos: 0,
+            alignment,
+        }
+    }
+
+    pub const fn alignment(&self) -> usize {
+        self.alignment
+    }
+
+    /// Gets the number of bytes written so far.
+    #[cfg(all(unix, feature = "shm"))]
+    pub const fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn pad(&mut self) -> io::Result<()> {
+        const ZEROS: [u8; 64] = [0; 64];
+        let num = self.pos.next_multiple_of(self.alignment) - self.pos;
+        self.write_all(&ZEROS[..num])
+    }
+}
+
+impl<W> Write for PaddedWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let num = self.inner.write(buf)?;
+        self.pos += num;
+        Ok(num)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reader skipping the zero bytes inserted by [`PaddedWriter`].
+pub struct PaddedReader<R> {
+    inner: R,
+    pos: usize,
+    alignment: usize,
+}
+
+impl<R> PaddedReader<R>
+where
+    R: io::Read,
+{
+    pub const fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pos: 0,
+            alignment: 1,
+        }
+    }
+
+    pub const fn set_alignment(&mut self, alignment: usize) {
+        self.alignment = alignment;
+    }
+
+    pub fn pad(&mut self) -> Result<()> {
+        let mut buf = [0; 64];
+        let num = self.pos.next_multiple_of(self.alignment) - self.pos;
+        self.read_exact(&mut buf[..num])?;
+        if buf[..num].iter().any(|&c| c != 0) {
+            return Err(anyhow!("broken padding"));
+        }
+        Ok(())
+    }
+}
+
+impl PaddedReader<&[u8]> {
+    /// Gets the number of bytes read so far.
+    pub const fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Skips `num` bytes without reading them.
+    pub fn skip(&mut self, num: usize) -> io::Result<()> {
+        if self.inner.len() < num {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.inner = &self.inner[num..];
+        self.pos += num;
+        Ok(())
+    }
+}
+
+impl<R> Read for PaddedReader<R>
+where
+    R: io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let num = self.inner.read(buf)?;
+        self.pos += num;
+        Ok(num)
+    }
+}
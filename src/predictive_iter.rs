@@ -1,3 +1,11 @@
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::iter::FusedIterator;
+
+use anyhow::{anyhow, Result};
+
+use crate::order::KeyOrder;
+use crate::stats::{self, Counter};
 use crate::utils;
 use crate::Set;
 
@@ -5,10 +13,7 @@ use crate::Set;
 #[derive(Clone)]
 pub struct PredictiveIter<'a> {
     set: &'a Set,
-    dec: Vec<u8>,
-    key: Vec<u8>,
-    pos: usize,
-    id: usize,
+    state: PredictiveState,
 }
 
 impl<'a> PredictiveIter<'a> {
@@ -23,11 +28,8 @@ impl<'a> PredictiveIter<'a> {
         P: AsRef<[u8]>,
     {
         Self {
-            key: key.as_ref().to_vec(),
             set,
-            dec: Vec::with_capacity(set.max_length()),
-            pos: 0,
-            id: 0,
+            state: PredictiveState::new(set, key.as_ref()),
         }
     }
 
@@ -40,87 +42,359 @@ impl<'a> PredictiveIter<'a> {
     where
         P: AsRef<[u8]>,
     {
-        self.key = key.as_ref().to_vec();
+        self.state.reset(self.set, key.as_ref());
+    }
+
+    /// Returns the next id and key like [`Iterator::next`], but reports broken data as an error.
+    ///
+    /// When the serialized area cannot be decoded, an error is returned once,
+    /// and the iterator is fused afterward.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let set = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+    /// let mut iter = set.predictive_iter(b"IC");
+    /// assert_eq!(iter.try_next().unwrap(), Some((0, b"ICDM".to_vec())));
+    /// assert_eq!(iter.try_next().unwrap(), Some((1, b"ICML".to_vec())));
+    /// assert_eq!(iter.try_next().unwrap(), None);
+    /// ```
+    pub fn try_next(&mut self) -> Result<Option<(usize, Vec<u8>)>> {
+        self.state.try_next(self.set)
+    }
+
+    /// Returns the next id and the part of the key after the prefix, borrowed from the iterator.
+    ///
+    /// Different from [`Iterator::next`], it does not copy the whole key,
+    /// which saves copies for long prefixes with many completions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.predictive_iter(b"SIG");
+    /// assert_eq!(iter.next_suffix(), Some((2, &b"IR"[..])));
+    /// assert_eq!(iter.next_suffix(), Some((3, &b"KDD"[..])));
+    /// assert_eq!(iter.next_suffix(), Some((4, &b"MOD"[..])));
+    /// assert_eq!(iter.next_suffix(), None);
+    /// ```
+    pub fn next_suffix(&mut self) -> Option<(usize, &[u8])> {
+        let id = self.state.try_advance(self.set).ok().flatten()?;
+        Some((id, self.state.suffix()))
+    }
+
+    /// Writes the next key into a caller buffer keeping the prefix, returning its id.
+    ///
+    /// The buffer is truncated to the length of the prefix, and the part of the key
+    /// after the prefix is appended, so the shared prefix is written only once
+    /// when the buffer starts with the prefix and is reused across calls.
+    ///
+    /// # Arguments
+    ///
+    ///  - `buf`: Buffer starting with the prefix.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use fcsd::Set;
+    ///
+    /// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    /// let set = Set::new(keys).unwrap();
+    ///
+    /// let mut iter = set.predictive_iter(b"SIG");
+    /// let mut buf = b"SIG".to_vec();
+    /// assert_eq!(iter.next_into(&mut buf), Some(2));
+    /// assert_eq!(buf, b"SIGIR");
+    /// assert_eq!(iter.next_into(&mut buf), Some(3));
+    /// assert_eq!(buf, b"SIGKDD");
+    /// ```
+    pub fn next_into(&mut self, buf: &mut Vec<u8>) -> Option<usize> {
+        let id = self.state.try_advance(self.set).ok().flatten()?;
+        let prefix_len = self.state.key.len();
+        debug_assert!(buf.starts_with(&self.state.key));
+        buf.truncate(prefix_len);
+        buf.extend_from_slice(self.state.suffix());
+        Some(id)
+    }
+
+    /// Decodes the next matched key without cloning it, returning its id.
+    /// The key can be borrowed with [`PredictiveIter::current_key`].
+    pub(crate) fn advance(&mut self) -> Option<usize> {
+        self.state.try_advance(self.set).ok().flatten()
+    }
+
+    /// Gets the key decoded last.
+    pub(crate) fn current_key(&self) -> &[u8] {
+        &self.state.dec
+    }
+}
+
+impl<'a> Iterator for PredictiveIter<'a> {
+    type Item = (usize, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Broken data ends the iteration.
+        self.try_next().ok().flatten()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.state.num_remaining(self.set);
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for PredictiveIter<'_> {}
+
+impl FusedIterator for PredictiveIter<'_> {}
+
+/// State of [`PredictiveIter`] independent of how the dictionary is held,
+/// whose methods take the dictionary.
+#[derive(Clone)]
+pub(crate) struct PredictiveState {
+    dec: Vec<u8>,
+    key: Vec<u8>,
+    pos: usize,
+    id: usize,
+    started: bool,
+    // Whether the keys starting with the prefix may not be contiguous.
+    scan: bool,
+    // The number of keys starting with the prefix, counted on demand for `size_hint`.
+    num_matches: Cell<Option<usize>>,
+    num_yielded: usize,
+}
+
+impl PredictiveState {
+    pub(crate) fn new(set: &Set, key: &[u8]) -> Self {
+        Self {
+            dec: Vec::with_capacity(set.max_length()),
+            key: key.to_vec(),
+            pos: 0,
+            id: 0,
+            started: false,
+            scan: !set.is_prefix_contiguous(key),
+            num_matches: Cell::new(None),
+            num_yielded: 0,
+        }
+    }
+
+    /// Gets the part of the key decoded last after the prefix.
+    fn suffix(&self) -> &[u8] {
+        &self.dec[self.key.len()..]
+    }
+
+    pub(crate) fn reset(&mut self, set: &Set, key: &[u8]) {
+        self.key.clear();
+        self.key.extend_from_slice(key);
+        self.scan = !set.is_prefix_contiguous(key);
         self.dec.clear();
         self.pos = 0;
         self.id = 0;
+        self.started = false;
+        self.num_matches.set(None);
+        self.num_yielded = 0;
     }
 
-    fn search_first(&mut self) -> bool {
-        let (set, dec) = (&self.set, &mut self.dec);
-
-        if self.key.is_empty() {
-            self.pos = set.decode_header(0, dec);
+    /// Positions the iterator at the first key no less than the prefix,
+    /// returning whether it starts with the prefix.
+    fn search_first(&mut self, set: &Set) -> bool {
+        if self.key.is_empty() || self.scan {
+            self.pos = set.decode_header(0, &mut self.dec);
             self.id = 0;
             return true;
         }
 
         let (bi, found) = set.search_bucket(&self.key);
-        self.pos = set.decode_header(bi, dec);
+        if found {
+            self.pos = set.decode_header(bi, &mut self.dec);
+            self.id = bi * set.bucket_size();
+            return true;
+        }
+
+        let seeked = if set.order == KeyOrder::Lexicographic && set.alphabet.is_none() {
+            self.seek_in_bucket(set, bi)
+        } else {
+            self.seek_in_bucket_by_order(set, bi)
+        };
+        if let Some(matched) = seeked {
+            return matched;
+        }
+
+        // All the keys in the bucket are less than the prefix,
+        // so the first key no less than it is the next header.
+        let bi = bi + 1;
+        if set.num_buckets() <= bi {
+            return false;
+        }
+        self.pos = set.decode_header(bi, &mut self.dec);
         self.id = bi * set.bucket_size();
+        utils::is_prefix(&self.key, &self.dec)
+    }
 
-        if found || utils::is_prefix(&self.key, dec) {
-            return true;
+    /// Seeks the first key no less than the prefix in the bucket with the LCP-pruned scan
+    /// of [`Locator`](crate::locator::Locator), comparing the keys in place.
+    ///
+    /// Returns whether the key starts with the prefix, or `None` if all the keys
+    /// in the bucket are less than the prefix.
+    /// Only the matched key is decoded, whose bytes before the suffix are shared with the prefix.
+    fn seek_in_bucket(&mut self, set: &Set, bi: usize) -> Option<bool> {
+        let header = set.get_header(bi);
+        let (mut lcp, cmp) = utils::get_lcp(&self.key, header);
+        if 0 <= cmp {
+            self.pos = set.decode_header(bi, &mut self.dec);
+            self.id = bi * set.bucket_size();
+            return Some(lcp == self.key.len());
         }
+        let mut pos = set.pointers.get(bi) as usize + header.len() + 1;
 
         for bj in 1..set.bucket_size() {
-            if self.pos == set.serialized.len() {
+            if pos == set.serialized.len() {
                 break;
             }
+            stats::record(Counter::PrefixKeysScanned, 1);
+            let (dec_lcp, next_pos) = set.decode_lcp(pos);
+            let suffix = &set.serialized[next_pos..];
+            let suffix_len = utils::get_strlen(suffix);
+            pos = next_pos + suffix_len + 1;
 
-            let (lcp, next_pos) = set.decode_lcp(self.pos);
-            self.pos = next_pos;
-            dec.resize(lcp, 0);
-            self.pos = set.decode_next(self.pos, dec);
-
-            if utils::is_prefix(&self.key, dec) {
-                self.id += bj;
-                return true;
+            match dec_lcp.cmp(&lcp) {
+                // The key shares the mismatching byte with the previous one.
+                Ordering::Greater => {}
+                // The key is greater than the prefix without starting with it.
+                Ordering::Less => return Some(false),
+                Ordering::Equal => {
+                    let suffix = &suffix[..suffix_len];
+                    let (next_lcp, cmp) = utils::get_lcp(&self.key[lcp..], suffix);
+                    if cmp < 0 {
+                        lcp += next_lcp;
+                        continue;
+                    }
+                    if lcp + next_lcp != self.key.len() {
+                        return Some(false);
+                    }
+                    self.dec.clear();
+                    self.dec.extend_from_slice(&self.key[..lcp]);
+                    self.dec.extend_from_slice(suffix);
+                    self.pos = pos;
+                    self.id = bi * set.bucket_size() + bj;
+                    return Some(true);
+                }
             }
         }
-
-        false
+        None
     }
-}
 
-impl<'a> Iterator for PredictiveIter<'a> {
-    type Item = (usize, Vec<u8>);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.pos == self.set.serialized.len() {
-            return None;
+    /// Seeks the first key no less than the prefix in the bucket by decoding the keys,
+    /// used when the keys cannot be compared in place.
+    fn seek_in_bucket_by_order(&mut self, set: &Set, bi: usize) -> Option<bool> {
+        self.pos = set.decode_header(bi, &mut self.dec);
+        self.id = bi * set.bucket_size();
+        for bj in 0..set.bucket_size() {
+            if bj != 0 {
+                if self.pos == set.serialized.len() {
+                    break;
+                }
+                stats::record(Counter::PrefixKeysScanned, 1);
+                let (lcp, next_pos) = set.decode_lcp(self.pos);
+                self.dec.truncate(lcp);
+                self.pos = set.decode_next(next_pos, &mut self.dec);
+                self.id += 1;
+            }
+            if set.order.compare(&self.dec, &self.key).is_ge() {
+                return Some(utils::is_prefix(&self.key, &self.dec));
+            }
         }
+        None
+    }
+
+    pub(crate) fn try_next(&mut self, set: &Set) -> Result<Option<(usize, Vec<u8>)>> {
+        Ok(self.try_advance(set)?.map(|id| (id, self.dec.clone())))
+    }
 
-        if self.dec.is_empty() {
-            if !self.search_first() {
-                self.dec.clear();
-                self.pos = self.set.serialized.len();
-                self.id = 0;
-                return None;
+    /// Decodes the next matched key into `self.dec` without cloning it, returning its id.
+    pub(crate) fn try_advance(&mut self, set: &Set) -> Result<Option<usize>> {
+        loop {
+            if self.pos == set.serialized.len() {
+                return Ok(None);
             }
-        } else {
-            self.id += 1;
-            if self.set.pos_in_bucket(self.id) == 0 {
-                self.dec.clear();
+
+            if !self.started {
+                self.started = true;
+                if !self.search_first(set) {
+                    self.fuse(set);
+                    return Ok(None);
+                }
             } else {
-                let (lcp, next_pos) = self.set.decode_lcp(self.pos);
-                self.pos = next_pos;
-                self.dec.resize(lcp, 0);
+                self.id += 1;
+                if set.len() <= self.id || self.decode_next(set).is_none() {
+                    self.fuse(set);
+                    return Err(anyhow!("broken serialized area"));
+                }
+            }
+
+            if utils::is_prefix(&self.key, &self.dec) {
+                self.num_yielded += 1;
+                return Ok(Some(self.id));
+            } else if !self.scan {
+                self.fuse(set);
+                return Ok(None);
             }
-            self.pos = self.set.decode_next(self.pos, &mut self.dec);
         }
+    }
 
-        if utils::is_prefix(&self.key, &self.dec) {
-            Some((self.id, self.dec.clone()))
-        } else {
-            self.dec.clear();
-            self.pos = self.set.serialized.len();
-            self.id = 0;
-            None
+    /// Returns the number of the remaining keys, counting the matches at the first call.
+    ///
+    /// The matches are found in logarithmic time with [`Set::prefix_range`],
+    /// unless the keys starting with the prefix are not contiguous.
+    pub(crate) fn num_remaining(&self, set: &Set) -> usize {
+        if self.started && self.pos == set.serialized.len() {
+            return 0;
         }
+        let num_matches = self.num_matches.get().unwrap_or_else(|| {
+            let n = self.count_matches(set);
+            self.num_matches.set(Some(n));
+            n
+        });
+        num_matches.saturating_sub(self.num_yielded)
     }
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, Some(self.set.len()))
+    fn count_matches(&self, set: &Set) -> usize {
+        if !self.scan {
+            return set.prefix_range(&self.key).len();
+        }
+        let mut iter = set.iter();
+        let mut n = 0;
+        while iter.advance().is_some() {
+            n += usize::from(iter.key().starts_with(&self.key));
+        }
+        n
+    }
+
+    fn fuse(&mut self, set: &Set) {
+        self.dec.clear();
+        self.pos = set.serialized.len();
+        self.id = 0;
+    }
+
+    fn decode_next(&mut self, set: &Set) -> Option<()> {
+        stats::record(Counter::PrefixKeysScanned, 1);
+        if set.pos_in_bucket(self.id) == 0 {
+            self.dec.clear();
+            if set.bucket_aligned {
+                // Skips the padding before the bucket.
+                self.pos = set.pointers.get(set.bucket_id(self.id)) as usize;
+            }
+        } else {
+            let (lcp, next_pos) = set.try_decode_lcp(self.pos, self.dec.len())?;
+            self.pos = next_pos;
+            self.dec.truncate(lcp);
+        }
+        self.pos = set.try_decode_next(self.pos, &mut self.dec)?;
+        Some(())
     }
 }
@@ -0,0 +1,78 @@
+//! Regular expressions compiled into DFAs with `regex-automata`.
+use anyhow::Result;
+use regex_automata::dfa::{dense, Automaton as _, StartKind};
+use regex_automata::util::primitives::StateID;
+use regex_automata::util::start;
+use regex_automata::{Anchored, MatchKind};
+
+use crate::automaton::Automaton;
+
+/// Automaton accepting the keys that match a regular expression as a whole.
+///
+/// The pattern follows the syntax of the `regex` crate and is matched against entire keys,
+/// as if it were surrounded by `^` and `$`.
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+///
+/// let keys = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+/// let set = Set::new(keys).unwrap();
+///
+/// let mut iter = set.regex_iter("SIG(IR|MOD)").unwrap();
+/// assert_eq!(iter.next(), Some((2, b"SIGIR".to_vec())));
+/// assert_eq!(iter.next(), Some((4, b"SIGMOD".to_vec())));
+/// assert_eq!(iter.next(), None);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Regex {
+    dfa: dense::DFA<Vec<u32>>,
+}
+
+impl Regex {
+    /// Compiles a pattern into an automaton [`Regex`].
+    ///
+    /// # Arguments
+    ///
+    ///  - `pattern`: Regular expression.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the pattern is invalid or
+    /// cannot be compiled into a DFA, such as one with Unicode word boundaries.
+    pub fn new(pattern: &str) -> Result<Self> {
+        let dfa = dense::Builder::new()
+            .configure(
+                dense::Config::new()
+                    .match_kind(MatchKind::All)
+                    .start_kind(StartKind::Anchored),
+            )
+            .build(&format!(r"(?:{})\z", pattern))?;
+        Ok(Self { dfa })
+    }
+}
+
+impl Automaton for Regex {
+    /// State of the DFA.
+    type State = StateID;
+
+    fn start(&self) -> Self::State {
+        let config = start::Config::new().anchored(Anchored::Yes);
+        // Anchored searches are always supported by the configuration above.
+        self.dfa.start_state(&config).unwrap()
+    }
+
+    fn accept(&self, state: &Self::State, byte: u8) -> Self::State {
+        self.dfa.next_state(*state, byte)
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        // The DFA reports matches one transition late.
+        self.dfa.is_match_state(self.dfa.next_eoi_state(*state))
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        !self.dfa.is_dead_state(*state) && !self.dfa.is_quit_state(*state)
+    }
+}
@@ -0,0 +1,116 @@
+//! Random sampling of keys, such as for generating evaluation sets.
+//!
+//! The keys are picked by arithmetic on id ranges without enumerating the dictionary,
+//! except for prefixes whose keys are not contiguous, which are scanned with reservoir sampling.
+use anyhow::{anyhow, Result};
+use rand::Rng;
+
+use crate::Set;
+
+/// Sampler picking keys with probabilities proportional to their payloads,
+/// made by [`Set::weighted_sampler`].
+///
+/// It keeps the cumulative sums of the payloads, so each key is picked in logarithmic time.
+#[derive(Clone)]
+pub struct WeightedSampler<'a> {
+    set: &'a Set,
+    // cumulative[i] is the sum of the payloads of the ids less than i.
+    cumulative: Vec<u64>,
+}
+
+impl<'a> WeightedSampler<'a> {
+    pub(crate) fn new(set: &'a Set) -> Result<Self> {
+        if set.payloads.is_none() {
+            return Err(anyhow!("The dictionary has no payloads as weights."));
+        }
+        let mut cumulative = Vec::with_capacity(set.len() + 1);
+        let mut sum = 0u64;
+        cumulative.push(sum);
+        for id in 0..set.len() {
+            sum = sum
+                .checked_add(set.payload(id).unwrap())
+                .ok_or_else(|| anyhow!("The sum of the weights overflows."))?;
+            cumulative.push(sum);
+        }
+        Ok(Self { set, cumulative })
+    }
+
+    /// Picks a key and its id, or returns `None` if all the weights are zero.
+    ///
+    /// # Arguments
+    ///
+    ///  - `rng`: Random number generator.
+    pub fn choose<R>(&self, rng: &mut R) -> Option<(usize, Vec<u8>)>
+    where
+        R: Rng + ?Sized,
+    {
+        self.choose_in_range(0..self.set.len(), rng)
+    }
+
+    /// Picks a key starting with a given prefix and its id,
+    /// or returns `None` if no such key has a positive weight.
+    ///
+    /// # Arguments
+    ///
+    ///  - `prefix`: Prefix of keys.
+    ///  - `rng`: Random number generator.
+    pub fn choose_in_prefix<P, R>(&self, prefix: P, rng: &mut R) -> Option<(usize, Vec<u8>)>
+    where
+        P: AsRef<[u8]>,
+        R: Rng + ?Sized,
+    {
+        let prefix = prefix.as_ref();
+        if self.set.is_prefix_contiguous(prefix) {
+            return self.choose_in_range(self.set.prefix_range(prefix), rng);
+        }
+        // Weighted reservoir sampling of a single key.
+        let mut total = 0;
+        let mut chosen = None;
+        for (id, key) in self.set.predictive_iter(prefix) {
+            let weight = self.cumulative[id + 1] - self.cumulative[id];
+            total += weight;
+            if weight != 0 && rng.gen_range(0..total) < weight {
+                chosen = Some((id, key));
+            }
+        }
+        chosen
+    }
+
+    fn choose_in_range<R>(
+        &self,
+        range: std::ops::Range<usize>,
+        rng: &mut R,
+    ) -> Option<(usize, Vec<u8>)>
+    where
+        R: Rng + ?Sized,
+    {
+        let (lo, hi) = (self.cumulative[range.start], self.cumulative[range.end]);
+        if lo == hi {
+            return None;
+        }
+        let target = rng.gen_range(lo..hi);
+        let id = self.cumulative.partition_point(|&sum| sum <= target) - 1;
+        Some((id, self.set.decoder().run(id)))
+    }
+}
+
+/// Picks `amount` items of an iterator uniformly without replacement, in the order of the iterator.
+pub(crate) fn reservoir<I, R>(iter: I, amount: usize, rng: &mut R) -> Vec<I::Item>
+where
+    I: Iterator,
+    R: Rng + ?Sized,
+{
+    let mut picked: Vec<(usize, I::Item)> = Vec::with_capacity(amount);
+    for (i, item) in iter.enumerate() {
+        if i < amount {
+            picked.push((i, item));
+        } else {
+            let j = rng.gen_range(0..=i);
+            if j < amount {
+                picked[j] = (i, item);
+            }
+        }
+    }
+    picked.sort_unstable_by_key(|&(i, _)| i);
+    picked.into_iter().map(|(_, item)| item).collect()
+}
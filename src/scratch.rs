@@ -0,0 +1,97 @@
+//! Thread-local pool of scratch buffers for queries taking `&self`.
+use std::cell::RefCell;
+
+/// The maximum number of buffers kept in the pool of each thread.
+const MAX_POOLED: usize = 8;
+
+/// The maximum capacity of a buffer returned to the pool.
+const MAX_POOLED_CAPACITY: usize = 1 << 16;
+
+/// Stack of buffers in a fixed array, so that pushing and popping never allocate.
+struct Pool {
+    bufs: [Vec<u8>; MAX_POOLED],
+    len: usize,
+}
+
+impl Pool {
+    const EMPTY: Vec<u8> = Vec::new();
+
+    const fn new() -> Self {
+        Self {
+            bufs: [Self::EMPTY; MAX_POOLED],
+            len: 0,
+        }
+    }
+
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(std::mem::take(&mut self.bufs[self.len]))
+    }
+
+    fn push(&mut self, buf: Vec<u8>) -> Result<(), Vec<u8>> {
+        if self.len == MAX_POOLED {
+            return Err(buf);
+        }
+        self.bufs[self.len] = buf;
+        self.len += 1;
+        Ok(())
+    }
+}
+
+thread_local! {
+    static POOL: RefCell<Pool> = const { RefCell::new(Pool::new()) };
+}
+
+/// Calls `f` with an empty buffer taken from the pool of the current thread.
+///
+/// Buffers are stacked, so `f` can call this function again. A buffer is not allocated
+/// once the pool is warmed up, unless it grows over the pooled capacity.
+pub fn with_buffer<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut Vec<u8>) -> R,
+{
+    let mut buf = POOL
+        .try_with(|pool| pool.borrow_mut().pop())
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    buf.clear();
+    let result = f(&mut buf);
+    if buf.capacity() <= MAX_POOLED_CAPACITY {
+        let _ = POOL.try_with(|pool| pool.borrow_mut().push(buf));
+    }
+    result
+}
+
+/// Calls `f` with the buffers lent to the pool of the current thread,
+/// taking back as many buffers as lent when it returns.
+pub fn lend<F, R>(bufs: &mut Vec<Vec<u8>>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let mut num_lent = 0;
+    let _ = POOL.try_with(|pool| {
+        let mut pool = pool.borrow_mut();
+        while let Some(buf) = bufs.pop() {
+            if let Err(buf) = pool.push(buf) {
+                bufs.push(buf);
+                break;
+            }
+            num_lent += 1;
+        }
+    });
+    let result = f();
+    let _ = POOL.try_with(|pool| {
+        let mut pool = pool.borrow_mut();
+        for _ in 0..num_lent {
+            match pool.pop() {
+                Some(buf) => bufs.push(buf),
+                None => break,
+            }
+        }
+    });
+    result
+}
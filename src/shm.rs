@@ -0,0 +1,135 @@
+//! POSIX shared-memory segments holding serialized dictionaries.
+//!
+//! A dictionary placed with [`Set::create_shm`](crate::Set::create_shm) is written
+//! in the aligned layout of [`Set::serialize_into_aligned`](crate::Set::serialize_into_aligned).
+//! [`Set::open_shm`](crate::Set::open_shm) maps the segment read-only and refers to
+//! its serialized area in place, so the processes opening the same segment share
+//! one physical copy of the keys.
+//! On little-endian targets, the chunks of the bucket pointers are also referred to in place,
+//! and the other areas, such as external ids, are copied into each process.
+use std::ffi::CString;
+use std::io;
+
+use anyhow::{anyhow, Result};
+
+/// Read-only or writable mapping of a shared-memory segment, unmapped on drop.
+pub(crate) struct Segment {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is never written after it is shared.
+unsafe impl Send for Segment {}
+unsafe impl Sync for Segment {}
+
+impl Segment {
+    /// Creates a new segment of `len` bytes mapped writable.
+    pub(crate) fn create(name: &str, len: usize) -> Result<Self> {
+        let name = segment_name(name)?;
+        let fd = unsafe {
+            libc::shm_open(
+                name.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+                0o644,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mapped = if unsafe { libc::ftruncate(fd, len as libc::off_t) } < 0 {
+            Err(io::Error::last_os_error().into())
+        } else {
+            Self::map(fd, len, libc::PROT_READ | libc::PROT_WRITE)
+        };
+        unsafe {
+            libc::close(fd);
+        }
+        if mapped.is_err() {
+            unsafe {
+                libc::shm_unlink(name.as_ptr());
+            }
+        }
+        mapped
+    }
+
+    /// Opens an existing segment mapped read-only.
+    pub(crate) fn open(name: &str) -> Result<Self> {
+        let name = segment_name(name)?;
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDONLY, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let mut stat = unsafe { std::mem::zeroed::<libc::stat>() };
+        let mapped = if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+            Err(io::Error::last_os_error().into())
+        } else {
+            Self::map(fd, stat.st_size as usize, libc::PROT_READ)
+        };
+        unsafe {
+            libc::close(fd);
+        }
+        mapped
+    }
+
+    fn map(fd: libc::c_int, len: usize, prot: libc::c_int) -> Result<Self> {
+        if len == 0 {
+            return Err(anyhow!("empty shared-memory segment"));
+        }
+        let ptr = unsafe { libc::mmap(std::ptr::null_mut(), len, prot, libc::MAP_SHARED, fd, 0) };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    pub(crate) const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) const fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    /// Gets the writable bytes, which must be called only for segments from [`Segment::create`].
+    pub(crate) const fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr as *mut u8, self.len) }
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// Removes the name of the shared-memory segment.
+///
+/// The processes that have opened the segment can still use it,
+/// and the memory is released when all of them drop the dictionaries.
+///
+/// # Arguments
+///
+///  - `name`: Name of the segment, such as `/vocab`.
+///
+/// # Errors
+///
+/// [`anyhow::Result`] will be returned when the segment does not exist.
+pub fn remove(name: &str) -> Result<()> {
+    let name = segment_name(name)?;
+    if unsafe { libc::shm_unlink(name.as_ptr()) } < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+fn segment_name(name: &str) -> Result<CString> {
+    if !name.starts_with('/') || name[1..].contains('/') {
+        return Err(anyhow!(
+            "The segment name must start with a slash and contain no other slashes, but it is {:?}.",
+            name
+        ));
+    }
+    Ok(CString::new(name)?)
+}
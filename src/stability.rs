@@ -0,0 +1,75 @@
+//! Stability of key ids between builds.
+//!
+//! [`Set::stability_report`](crate::Set::stability_report) compares a dictionary with
+//! the keyset of the next build and reports how the ids of the kept keys will shift,
+//! which helps decide between a delta layer and a full rebuild.
+use std::ops::Range;
+
+/// Run of consecutive old ids of kept keys shifted by the same amount.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Shift {
+    /// Old ids of the keys in the run.
+    pub old_ids: Range<usize>,
+    /// Difference of the new ids from the old ones.
+    pub delta: isize,
+}
+
+/// Result of [`Set::stability_report`](crate::Set::stability_report).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StabilityReport {
+    /// Number of keys stored in both keysets.
+    pub num_kept: usize,
+    /// Number of keys stored only in the old dictionary.
+    pub num_removed: usize,
+    /// Number of keys stored only in the new keyset.
+    pub num_added: usize,
+    /// Number of kept keys whose ids change.
+    pub num_moved: usize,
+    /// Maximum absolute difference between the old and new ids of a kept key.
+    pub max_shift: usize,
+    /// Runs of the kept keys in the order of old ids,
+    /// where removed keys are not covered by any run.
+    pub shifts: Vec<Shift>,
+}
+
+impl StabilityReport {
+    pub(crate) fn push(&mut self, old_id: usize, new_id: usize) {
+        let delta = new_id as isize - old_id as isize;
+        self.num_kept += 1;
+        if delta != 0 {
+            self.num_moved += 1;
+            self.max_shift = self.max_shift.max(delta.unsigned_abs());
+        }
+        match self.shifts.last_mut() {
+            Some(last) if last.old_ids.end == old_id && last.delta == delta => {
+                last.old_ids.end += 1;
+            }
+            _ => self.shifts.push(Shift {
+                old_ids: old_id..old_id + 1,
+                delta,
+            }),
+        }
+    }
+
+    /// Checks if every kept key keeps its id.
+    pub const fn is_stable(&self) -> bool {
+        self.num_moved == 0
+    }
+
+    /// Returns the number of the old ids that will be invalidated,
+    /// which are those of the removed and moved keys.
+    pub const fn num_invalidated(&self) -> usize {
+        self.num_removed + self.num_moved
+    }
+
+    /// Returns the new id of the key with `old_id`, or `None` if it is removed.
+    pub fn new_id(&self, old_id: usize) -> Option<usize> {
+        let i = self.shifts.partition_point(|s| s.old_ids.end <= old_id);
+        let shift = self.shifts.get(i)?;
+        if shift.old_ids.contains(&old_id) {
+            Some((old_id as isize + shift.delta) as usize)
+        } else {
+            None
+        }
+    }
+}
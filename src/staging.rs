@@ -0,0 +1,152 @@
+//! Staging buffer for the locate-or-insert workflow on a static dictionary.
+//!
+//! [`Staging`] locates keys in a dictionary and records those not found,
+//! which are merged into a new snapshot later. It is the usual loop
+//! assigning ids to a growing vocabulary.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::staging::Staged;
+//! use fcsd::Set;
+//!
+//! let set = Set::new(["ICDM", "SIGIR", "SIGMOD"]).unwrap();
+//! let mut staging = set.staging();
+//! assert_eq!(staging.locate_or_stage(b"SIGIR"), Staged::Found(1));
+//! assert_eq!(staging.locate_or_stage(b"SIGKDD"), Staged::Pending(0));
+//! assert_eq!(staging.locate_or_stage(b"ICML"), Staged::Pending(1));
+//! assert_eq!(staging.locate_or_stage(b"SIGKDD"), Staged::Pending(0));
+//!
+//! let merged = staging.merge().unwrap();
+//! assert_eq!(merged.pending_ids, vec![3, 1]);
+//! assert_eq!(merged.old_ids, vec![0, 2, 4]);
+//! assert_eq!(merged.set.locator().run(b"SIGKDD"), Some(3));
+//! ```
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::locator::Locator;
+use crate::Set;
+
+/// Result of [`Staging::locate_or_stage`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Staged {
+    /// The key is stored in the dictionary with the id.
+    Found(usize),
+    /// The key is staged with the ticket, which is the number of keys staged before it.
+    Pending(usize),
+}
+
+/// Result of [`Staging::merge`].
+#[derive(Clone)]
+pub struct Merged {
+    /// New dictionary storing the keys of the old one and the staged ones.
+    pub set: Set,
+    /// Ids of the staged keys in the new dictionary, indexed by their tickets.
+    pub pending_ids: Vec<usize>,
+    /// Ids of the old keys in the new dictionary, indexed by their old ids.
+    pub old_ids: Vec<usize>,
+}
+
+/// Staging buffer recording the keys not stored in a dictionary.
+#[derive(Clone)]
+pub struct Staging<'a> {
+    set: &'a Set,
+    locator: Locator<'a>,
+    tickets: HashMap<Vec<u8>, usize>,
+}
+
+impl<'a> Staging<'a> {
+    /// Makes an empty [`Staging`] for a dictionary.
+    ///
+    /// # Arguments
+    ///
+    ///  - `set`: Front-coding dictionay.
+    pub fn new(set: &'a Set) -> Self {
+        Self {
+            set,
+            locator: set.locator(),
+            tickets: HashMap::new(),
+        }
+    }
+
+    /// Returns the id of a key if it is stored, or stages it and returns its ticket otherwise.
+    /// A key staged twice gets the same ticket.
+    ///
+    /// # Arguments
+    ///
+    ///  - `key`: String key to be located.
+    pub fn locate_or_stage<P>(&mut self, key: P) -> Staged
+    where
+        P: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        if let Some(id) = self.locator.run(key) {
+            return Staged::Found(id);
+        }
+        if let Some(&ticket) = self.tickets.get(key) {
+            return Staged::Pending(ticket);
+        }
+        let ticket = self.tickets.len();
+        self.tickets.insert(key.to_vec(), ticket);
+        Staged::Pending(ticket)
+    }
+
+    /// Gets the number of staged keys.
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    /// Checks if no keys are staged.
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    /// Builds a new dictionary merging the staged keys into the old one,
+    /// with the same configuration as the old one.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when
+    ///
+    ///  - the dictionary has external ids, payloads, or values, which the staged keys lack, or
+    ///  - a staged key cannot be added, such as one containing [`END_MARKER`](crate::END_MARKER).
+    pub fn merge(self) -> Result<Merged> {
+        let set = self.set;
+        if set.has_external_ids() || set.payloads.is_some() || set.values.is_some() {
+            return Err(anyhow!(
+                "Dictionaries with external ids, payloads, or values cannot be merged with staged keys."
+            ));
+        }
+        let mut staged: Vec<_> = self.tickets.into_iter().collect();
+        staged.sort_unstable_by(|(a, _), (b, _)| set.order.compare(a, b));
+
+        let mut builder = set.new_builder(set.bucket_size())?;
+        let mut pending_ids = vec![0; staged.len()];
+        let mut old_ids = Vec::with_capacity(set.len());
+        let mut staged = staged.into_iter().peekable();
+        let mut iter = set.iter();
+        while let Some(id) = iter.advance() {
+            let key = iter.key();
+            while let Some((new_key, ticket)) =
+                staged.next_if(|(new_key, _)| set.order.compare(new_key, key).is_lt())
+            {
+                pending_ids[ticket] = builder.len();
+                builder.add(&new_key)?;
+            }
+            debug_assert_eq!(old_ids.len(), id);
+            old_ids.push(builder.len());
+            set.copy_key_into(&mut builder, id, key)?;
+        }
+        for (new_key, ticket) in staged {
+            pending_ids[ticket] = builder.len();
+            builder.add(&new_key)?;
+        }
+        Ok(Merged {
+            set: builder.finish(),
+            pending_ids,
+            old_ids,
+        })
+    }
+}
@@ -0,0 +1,96 @@
+//! Query instrumentation counters, enabled by the `metrics` feature.
+//!
+//! The counters are process-wide and updated with relaxed atomic operations,
+//! so they are cheap enough for high-QPS services. Without the feature,
+//! recording compiles to nothing.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "metrics")]
+//! # {
+//! use fcsd::Set;
+//! use fcsd::stats;
+//!
+//! let set = Set::new(["ICDM", "ICML", "SIGIR"]).unwrap();
+//! let before = stats::query_stats();
+//! set.locator().run(b"ICML");
+//! let after = stats::query_stats();
+//! assert!(after.locates > before.locates);
+//! # }
+//! ```
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Kinds of counted events.
+#[derive(Clone, Copy)]
+pub(crate) enum Counter {
+    Locates,
+    BucketProbes,
+    BytesDecoded,
+    CacheHits,
+    CacheMisses,
+    PrefixKeysScanned,
+}
+
+#[cfg(feature = "metrics")]
+const NUM_COUNTERS: usize = 6;
+
+#[cfg(feature = "metrics")]
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "metrics")]
+static COUNTERS: [AtomicU64; NUM_COUNTERS] = [ZERO; NUM_COUNTERS];
+
+/// Adds `n` to the counter.
+#[cfg(feature = "metrics")]
+#[inline(always)]
+pub(crate) fn record(counter: Counter, n: u64) {
+    COUNTERS[counter as usize].fetch_add(n, Ordering::Relaxed);
+}
+
+/// Does nothing without the `metrics` feature.
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) const fn record(_counter: Counter, _n: u64) {}
+
+/// Snapshot of the query counters.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// Number of keys searched by locators.
+    pub locates: u64,
+    /// Number of bucket headers compared in binary searches.
+    pub bucket_probes: u64,
+    /// Number of bytes of key suffixes decoded.
+    pub bytes_decoded: u64,
+    /// Number of bucket cache hits in decoders.
+    pub cache_hits: u64,
+    /// Number of bucket cache misses in decoders.
+    pub cache_misses: u64,
+    /// Number of keys scanned by predictive iterators.
+    pub prefix_keys_scanned: u64,
+}
+
+/// Returns a snapshot of the query counters.
+#[cfg(feature = "metrics")]
+pub fn query_stats() -> QueryStats {
+    let get = |counter: Counter| COUNTERS[counter as usize].load(Ordering::Relaxed);
+    QueryStats {
+        locates: get(Counter::Locates),
+        bucket_probes: get(Counter::BucketProbes),
+        bytes_decoded: get(Counter::BytesDecoded),
+        cache_hits: get(Counter::CacheHits),
+        cache_misses: get(Counter::CacheMisses),
+        prefix_keys_scanned: get(Counter::PrefixKeysScanned),
+    }
+}
+
+/// Resets all the query counters to zero.
+#[cfg(feature = "metrics")]
+pub fn reset_query_stats() {
+    for counter in COUNTERS.iter() {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
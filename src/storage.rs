@@ -0,0 +1,200 @@
+//! Byte and word areas owned by the dictionary, or borrowed from static data or other processes.
+use std::convert::TryInto;
+use std::ops::Deref;
+#[cfg(all(unix, feature = "shm"))]
+use std::ops::Range;
+#[cfg(all(unix, feature = "shm"))]
+use std::sync::Arc;
+
+#[cfg(all(unix, feature = "shm"))]
+use crate::shm::Segment;
+
+/// Immutable byte area, which is copied into an owned one to be modified.
+#[derive(Clone, Default)]
+pub struct Bytes {
+    inner: Inner,
+}
+
+#[derive(Clone)]
+enum Inner {
+    Owned(Vec<u8>),
+    Static(&'static [u8]),
+    #[cfg(all(unix, feature = "shm"))]
+    Shared(Arc<Segment>, Range<usize>),
+}
+
+impl Default for Inner {
+    fn default() -> Self {
+        Self::Owned(Vec::new())
+    }
+}
+
+impl Bytes {
+    pub const fn from_static(bytes: &'static [u8]) -> Self {
+        Self {
+            inner: Inner::Static(bytes),
+        }
+    }
+
+    #[cfg(all(unix, feature = "shm"))]
+    pub fn shared(segment: Arc<Segment>, range: Range<usize>) -> Self {
+        debug_assert!(range.end <= segment.len());
+        Self {
+            inner: Inner::Shared(segment, range),
+        }
+    }
+
+    pub const fn len(&self) -> usize {
+        match &self.inner {
+            Inner::Owned(bytes) => bytes.len(),
+            Inner::Static(bytes) => bytes.len(),
+            #[cfg(all(unix, feature = "shm"))]
+            Inner::Shared(_, range) => range.end - range.start,
+        }
+    }
+
+    /// Returns the bytes allocated on the heap, which are zero for borrowed areas.
+    pub const fn heap_bytes(&self) -> usize {
+        match &self.inner {
+            Inner::Owned(bytes) => bytes.capacity(),
+            Inner::Static(_) => 0,
+            #[cfg(all(unix, feature = "shm"))]
+            Inner::Shared(..) => 0,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<u8> {
+        match self.inner {
+            Inner::Owned(bytes) => bytes,
+            Inner::Static(bytes) => bytes.to_vec(),
+            #[cfg(all(unix, feature = "shm"))]
+            Inner::Shared(..) => self.to_vec(),
+        }
+    }
+}
+
+impl Bytes {
+    /// Checks if the bytes are borrowed from static data or a shared-memory segment.
+    const fn is_borrowed(&self) -> bool {
+        match &self.inner {
+            Inner::Owned(_) => false,
+            Inner::Static(_) => true,
+            #[cfg(all(unix, feature = "shm"))]
+            Inner::Shared(..) => true,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            inner: Inner::Owned(bytes),
+        }
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.inner {
+            Inner::Owned(bytes) => bytes,
+            Inner::Static(bytes) => bytes,
+            #[cfg(all(unix, feature = "shm"))]
+            Inner::Shared(segment, range) => &segment.as_slice()[range.clone()],
+        }
+    }
+}
+
+/// Area of 64-bit words, which is copied into an owned one to be modified.
+#[derive(Clone, Default)]
+pub struct Words {
+    inner: WordsInner,
+}
+
+#[derive(Clone)]
+enum WordsInner {
+    Owned(Vec<u64>),
+    // Borrowed bytes aligned to words, which are viewed in place only on little-endian targets.
+    Borrowed(Bytes),
+}
+
+impl Default for WordsInner {
+    fn default() -> Self {
+        Self::Owned(Vec::new())
+    }
+}
+
+impl Words {
+    pub const fn new() -> Self {
+        Self {
+            inner: WordsInner::Owned(Vec::new()),
+        }
+    }
+
+    /// Makes the words of little-endian bytes, referring to borrowed bytes in place when possible.
+    pub fn from_le_bytes(bytes: Bytes) -> Self {
+        debug_assert_eq!(bytes.len() % 8, 0);
+        if cfg!(target_endian = "little")
+            && bytes.is_borrowed()
+            && (bytes.as_ptr() as usize).is_multiple_of(std::mem::align_of::<u64>())
+        {
+            return Self {
+                inner: WordsInner::Borrowed(bytes),
+            };
+        }
+        bytes
+            .chunks_exact(8)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    pub const fn len(&self) -> usize {
+        match &self.inner {
+            WordsInner::Owned(words) => words.len(),
+            WordsInner::Borrowed(bytes) => bytes.len() / 8,
+        }
+    }
+
+    /// Returns the bytes allocated on the heap, which are zero for borrowed areas.
+    pub const fn heap_bytes(&self) -> usize {
+        match &self.inner {
+            WordsInner::Owned(words) => words.capacity() * std::mem::size_of::<u64>(),
+            WordsInner::Borrowed(_) => 0,
+        }
+    }
+
+    /// Gets the owned words, copying the borrowed ones first.
+    pub fn to_mut(&mut self) -> &mut Vec<u64> {
+        if let WordsInner::Borrowed(_) = &self.inner {
+            *self = self.to_vec().into();
+        }
+        match &mut self.inner {
+            WordsInner::Owned(words) => words,
+            WordsInner::Borrowed(_) => unreachable!(),
+        }
+    }
+}
+
+impl From<Vec<u64>> for Words {
+    fn from(words: Vec<u64>) -> Self {
+        Self {
+            inner: WordsInner::Owned(words),
+        }
+    }
+}
+
+impl Deref for Words {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        match &self.inner {
+            WordsInner::Owned(words) => words,
+            // The bytes are aligned to words and never modified while borrowed.
+            WordsInner::Borrowed(bytes) => unsafe {
+                std::slice::from_raw_parts(bytes.as_ptr() as *const u64, bytes.len() / 8)
+            },
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::utils;
+use crate::Set;
+
+/// Serial cookie value for serialization.
+const STORE_COOKIE: u32 = 114515;
+
+/// Container of named dictionaries serialized in one file.
+///
+/// The serialized form has a table of contents followed by the dictionaries,
+/// so that only the dictionaries of interest can be loaded with
+/// [`DictStore::deserialize_selected_from`].
+///
+/// # Example
+///
+/// ```
+/// use fcsd::Set;
+/// use fcsd::store::DictStore;
+///
+/// let mut store = DictStore::new();
+/// store.insert("venue", Set::new(["ICDM", "ICML", "SIGIR"]).unwrap());
+/// store.insert("author", Set::new(["Kanda", "Martínez-Prieto"]).unwrap());
+///
+/// let mut data = Vec::<u8>::new();
+/// store.serialize_into(&mut data).unwrap();
+/// assert_eq!(data.len(), store.size_in_bytes());
+///
+/// let other = DictStore::deserialize_selected_from(&data[..], &["author"]).unwrap();
+/// assert_eq!(other.len(), 1);
+/// assert_eq!(other.get("author").unwrap().len(), 2);
+/// assert!(other.get("venue").is_none());
+/// ```
+#[derive(Clone, Default)]
+pub struct DictStore {
+    sets: Vec<(String, Set)>,
+}
+
+impl DictStore {
+    /// Creates an empty [`DictStore`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a dictionary with a name, returning the old one with the same name if any.
+    ///
+    /// # Arguments
+    ///
+    ///  - `name`: Name of the dictionary.
+    ///  - `set`: Dictionary to be inserted.
+    pub fn insert<S>(&mut self, name: S, set: Set) -> Option<Set>
+    where
+        S: Into<String>,
+    {
+        let name = name.into();
+        match self.sets.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, old)) => Some(std::mem::replace(old, set)),
+            None => {
+                self.sets.push((name, set));
+                None
+            }
+        }
+    }
+
+    /// Gets the dictionary with the given name.
+    pub fn get(&self, name: &str) -> Option<&Set> {
+        self.sets
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, set)| set)
+    }
+
+    /// Removes and returns the dictionary with the given name.
+    pub fn remove(&mut self, name: &str) -> Option<Set> {
+        let i = self.sets.iter().position(|(n, _)| n == name)?;
+        Some(self.sets.remove(i).1)
+    }
+
+    /// Makes an iterator to enumerate the names of the dictionaries in the insertion order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sets.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Gets the number of dictionaries.
+    pub const fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Checks if the container is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// Returns the number of bytes needed to write the container.
+    pub fn size_in_bytes(&self) -> usize {
+        let mut bytes = 4 + 8; // STORE_COOKIE, number of dictionaries
+        for (name, set) in &self.sets {
+            bytes += 8 + name.len() + 8; // table of contents
+            bytes += set.size_in_bytes();
+        }
+        bytes
+    }
+
+    /// Serializes the container into a writer.
+    ///
+    /// # Arguments
+    ///
+    ///  - `writer`: Writable stream.
+    pub fn serialize_into<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: io::Write,
+    {
+        writer.write_u32::<LittleEndian>(STORE_COOKIE)?;
+        writer.write_u64::<LittleEndian>(self.sets.len() as u64)?;
+        for (name, set) in &self.sets {
+            writer.write_u64::<LittleEndian>(name.len() as u64)?;
+            writer.write_all(name.as_bytes())?;
+            writer.write_u64::<LittleEndian>(set.size_in_bytes() as u64)?;
+        }
+        for (_, set) in &self.sets {
+            set.serialize_into(&mut writer)?;
+        }
+        Ok(())
+    }
+
+    /// Deserializes the container from a reader.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream.
+    pub fn deserialize_from<R>(reader: R) -> Result<Self>
+    where
+        R: io::Read,
+    {
+        Self::deserialize_filtered_from(reader, |_| true)
+    }
+
+    /// Deserializes only the dictionaries with the given names from a reader.
+    ///
+    /// The other dictionaries are skipped without being decoded.
+    ///
+    /// # Arguments
+    ///
+    ///  - `reader`: Readable stream.
+    ///  - `names`: Names of the dictionaries to be loaded.
+    pub fn deserialize_selected_from<R>(reader: R, names: &[&str]) -> Result<Self>
+    where
+        R: io::Read,
+    {
+        Self::deserialize_filtered_from(reader, |name| names.contains(&name))
+    }
+
+    fn deserialize_filtered_from<R, F>(mut reader: R, f: F) -> Result<Self>
+    where
+        R: io::Read,
+        F: Fn(&str) -> bool,
+    {
+        let cookie = reader.read_u32::<LittleEndian>()?;
+        if cookie != STORE_COOKIE {
+            return Err(anyhow!("unknown cookie value"));
+        }
+        let num = utils::read_usize(&mut reader)?;
+        let mut toc = Vec::new();
+        for _ in 0..num {
+            let len = utils::read_usize(&mut reader)?;
+            let mut name = Vec::new();
+            (&mut reader).take(len as u64).read_to_end(&mut name)?;
+            if name.len() != len {
+                return Err(anyhow!("truncated table of contents"));
+            }
+            let name = String::from_utf8(name)?;
+            let size = reader.read_u64::<LittleEndian>()?;
+            toc.push((name, size));
+        }
+
+        let mut sets = Vec::new();
+        for (name, size) in toc {
+            let mut section = (&mut reader).take(size);
+            if f(&name) {
+                sets.push((name, Set::deserialize_from(&mut section)?));
+            }
+            io::copy(&mut section, &mut io::sink())?;
+        }
+        Ok(Self { sets })
+    }
+}
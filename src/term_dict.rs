@@ -0,0 +1,160 @@
+//! Term-dictionary interface for search engines.
+//!
+//! Inverted indexes such as [tantivy](https://github.com/quickwit-oss/tantivy) keep a
+//! term dictionary mapping each term to its ordinal, i.e., its rank in the lexicographical
+//! order, and back. [`TermDictionary`] and [`TermDictionaryBuilder`] describe the operations
+//! they need, so that [`Set`] can slot in as an alternative to FST-based term dictionaries.
+//!
+//! # Example
+//!
+//! ```
+//! use std::ops::Bound;
+//!
+//! use fcsd::builder::Builder;
+//! use fcsd::term_dict::{TermDictionary, TermDictionaryBuilder};
+//!
+//! let mut builder = Builder::new(4).unwrap();
+//! for term in ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"] {
+//!     builder.insert(term.as_bytes()).unwrap();
+//! }
+//! let dict = TermDictionaryBuilder::finish(builder).unwrap();
+//!
+//! assert_eq!(dict.num_terms(), 5);
+//! assert_eq!(dict.term_ord(b"SIGIR"), Some(2));
+//!
+//! let mut term = Vec::new();
+//! assert!(dict.ord_to_term(3, &mut term));
+//! assert_eq!(term, b"SIGKDD");
+//!
+//! assert_eq!(dict.ord_range_for_prefix(b"SIG"), 2..5);
+//! let ords = dict.ord_range(Bound::Excluded(b"ICML"), Bound::Included(b"SIGKDD"));
+//! assert_eq!(ords, 2..4);
+//!
+//! let terms: Vec<_> = dict.stream(ords).collect();
+//! assert_eq!(terms, vec![(2, b"SIGIR".to_vec()), (3, b"SIGKDD".to_vec())]);
+//! ```
+use std::convert::TryFrom;
+use std::ops::{Bound, Range};
+
+use anyhow::Result;
+
+use crate::builder::Builder;
+use crate::iter::Iter;
+use crate::Set;
+
+/// Read-only term dictionary assigning ordinals to terms in the lexicographical order.
+pub trait TermDictionary {
+    /// Iterator over terms with their ordinals.
+    type Stream<'a>: Iterator<Item = (u64, Vec<u8>)>
+    where
+        Self: 'a;
+
+    /// Returns the number of terms.
+    fn num_terms(&self) -> usize;
+
+    /// Returns the ordinal of a term if stored.
+    fn term_ord(&self, term: &[u8]) -> Option<u64>;
+
+    /// Writes the term of an ordinal into `term`, returning `false` if the ordinal is out of range.
+    fn ord_to_term(&self, ord: u64, term: &mut Vec<u8>) -> bool;
+
+    /// Returns the range of ordinals of the terms starting with a prefix.
+    fn ord_range_for_prefix(&self, prefix: &[u8]) -> Range<u64>;
+
+    /// Returns the range of ordinals of the terms within the bounds.
+    fn ord_range(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Range<u64>;
+
+    /// Makes an iterator to enumerate the terms in a range of ordinals.
+    fn stream(&self, ords: Range<u64>) -> Self::Stream<'_>;
+}
+
+/// Streaming builder of a term dictionary from terms in the lexicographical order.
+pub trait TermDictionaryBuilder {
+    /// Term dictionary to be built.
+    type Dictionary: TermDictionary;
+
+    /// Pushes a term back, which must be more than the last one.
+    fn insert(&mut self, term: &[u8]) -> Result<()>;
+
+    /// Builds and returns the term dictionary.
+    fn finish(self) -> Result<Self::Dictionary>;
+}
+
+/// Iterator to enumerate terms in a range of ordinals, returned by [`TermDictionary::stream`].
+pub struct TermStream<'a> {
+    iter: Iter<'a>,
+    end: usize,
+}
+
+impl Iterator for TermStream<'_> {
+    type Item = (u64, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.iter.advance()?;
+        if self.end <= id {
+            return None;
+        }
+        Some((id as u64, self.iter.key().to_vec()))
+    }
+}
+
+impl TermDictionary for Set {
+    type Stream<'a> = TermStream<'a>;
+
+    fn num_terms(&self) -> usize {
+        self.len()
+    }
+
+    fn term_ord(&self, term: &[u8]) -> Option<u64> {
+        self.locator().run(term).map(|id| id as u64)
+    }
+
+    fn ord_to_term(&self, ord: u64, term: &mut Vec<u8>) -> bool {
+        match usize::try_from(ord) {
+            Ok(id) if id < self.len() => {
+                *term = self.decoder().run(id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn ord_range_for_prefix(&self, prefix: &[u8]) -> Range<u64> {
+        let range = self.prefix_range(prefix);
+        range.start as u64..range.end as u64
+    }
+
+    fn ord_range(&self, lower: Bound<&[u8]>, upper: Bound<&[u8]>) -> Range<u64> {
+        let start = match lower {
+            Bound::Included(key) => self.partition_point(|k| self.order.compare(k, key).is_lt()),
+            Bound::Excluded(key) => self.partition_point(|k| self.order.compare(k, key).is_le()),
+            Bound::Unbounded => 0,
+        };
+        let end = match upper {
+            Bound::Included(key) => self.partition_point(|k| self.order.compare(k, key).is_le()),
+            Bound::Excluded(key) => self.partition_point(|k| self.order.compare(k, key).is_lt()),
+            Bound::Unbounded => self.len(),
+        };
+        start as u64..std::cmp::max(start, end) as u64
+    }
+
+    fn stream(&self, ords: Range<u64>) -> Self::Stream<'_> {
+        let start = usize::try_from(ords.start).unwrap_or(usize::MAX);
+        let end = usize::try_from(ords.end).unwrap_or(usize::MAX);
+        let mut iter = self.iter();
+        iter.seek_id(start);
+        TermStream { iter, end }
+    }
+}
+
+impl TermDictionaryBuilder for Builder {
+    type Dictionary = Set;
+
+    fn insert(&mut self, term: &[u8]) -> Result<()> {
+        self.add(term)
+    }
+
+    fn finish(self) -> Result<Self::Dictionary> {
+        Ok(Self::finish(self))
+    }
+}
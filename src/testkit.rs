@@ -0,0 +1,359 @@
+//! Conformance kit for the serialized format, enabled by the `testkit` feature.
+//!
+//! [`fixtures`] generates canonical dictionaries with known keysets, covering the key orders,
+//! alphabets, bucket sizes, and layouts. The fixtures are deterministic on every target,
+//! so other implementations of the format, such as language bindings, can be checked against
+//! them with [`check_bytes`], [`check_locate`], and [`check_decode`].
+//! [`write_fixtures`] writes them into files for implementations outside Rust.
+//!
+//! # Example
+//!
+//! ```
+//! use fcsd::testkit::{self, Layout};
+//! use fcsd::Set;
+//!
+//! for fixture in testkit::fixtures() {
+//!     let set = fixture.build().unwrap();
+//!     let mut data = vec![];
+//!     set.serialize_into_compact(&mut data).unwrap();
+//!     testkit::check_bytes(&fixture, Layout::Compact, &data).unwrap();
+//!
+//!     let set = Set::deserialize_from(&data[..]).unwrap();
+//!     testkit::check_locate(&fixture, |key| set.locate(key)).unwrap();
+//!     testkit::check_decode(&fixture, |id| set.decode(id)).unwrap();
+//! }
+//! ```
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+use crate::alphabet::Alphabet;
+use crate::builder::{BuildConfig, Builder};
+use crate::order::KeyOrder;
+use crate::Set;
+
+/// Layout of the serialized form.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Written by [`Set::serialize_into`].
+    Plain,
+    /// Written by [`Set::serialize_into_aligned`].
+    Aligned,
+    /// Written by [`Set::serialize_into_compact`].
+    Compact,
+}
+
+impl Layout {
+    /// All the layouts.
+    pub const ALL: [Self; 3] = [Self::Plain, Self::Aligned, Self::Compact];
+
+    /// Gets the file extension used by [`write_fixtures`].
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Plain => "fcsd",
+            Self::Aligned => "aligned.fcsd",
+            Self::Compact => "compact.fcsd",
+        }
+    }
+}
+
+/// Dictionary with a known keyset, whose ids are the positions of the keys.
+#[derive(Clone)]
+pub struct Fixture {
+    /// Name of the fixture, used as the stem of the file names.
+    pub name: &'static str,
+    /// Configuration of building.
+    pub config: BuildConfig,
+    /// Keys sorted in the order of the configuration.
+    pub keys: Vec<Vec<u8>>,
+}
+
+impl Fixture {
+    fn new<I>(name: &'static str, config: BuildConfig, keys: I) -> Self
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        let mut keys: Vec<_> = keys.into_iter().collect();
+        keys.sort_by(|a, b| config.key_order.compare(a, b));
+        keys.dedup_by(|a, b| config.key_order.compare(a, b).is_eq());
+        Self { name, config, keys }
+    }
+
+    /// Builds the dictionary of the fixture.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary cannot be built,
+    /// which never happens for the fixtures of [`fixtures`].
+    pub fn build(&self) -> Result<Set> {
+        let mut builder = Builder::with_config(self.config.clone())?;
+        builder.extend(&self.keys)?;
+        Ok(builder.finish())
+    }
+
+    /// Returns the canonical bytes of the fixture in a layout.
+    ///
+    /// # Arguments
+    ///
+    ///  - `layout`: Layout of the serialized form.
+    ///
+    /// # Errors
+    ///
+    /// [`anyhow::Result`] will be returned when the dictionary cannot be built.
+    pub fn serialize(&self, layout: Layout) -> Result<Vec<u8>> {
+        let set = self.build()?;
+        let mut data = Vec::with_capacity(set.size_in_bytes());
+        match layout {
+            Layout::Plain => set.serialize_into(&mut data)?,
+            Layout::Aligned => set.serialize_into_aligned(&mut data)?,
+            Layout::Compact => set.serialize_into_compact(&mut data)?,
+        }
+        Ok(data)
+    }
+
+    /// Returns keys not stored in the dictionary.
+    fn absent_keys(&self) -> Vec<Vec<u8>> {
+        let mut absent = vec![b"\xFF\xFF\xFF".to_vec()];
+        for key in self.keys.iter().step_by(7) {
+            absent.push([&key[..], b"\xFF"].concat());
+            absent.push(key[..key.len() - 1].to_vec());
+        }
+        absent.retain(|key| {
+            self.keys
+                .binary_search_by(|k| self.config.key_order.compare(k, key))
+                .is_err()
+        });
+        absent
+    }
+}
+
+/// Generates the canonical fixtures.
+pub fn fixtures() -> Vec<Fixture> {
+    let mut rng = XorShift(0x2545F4914F6CDD1D);
+    let config = |bucket_size| BuildConfig {
+        bucket_size,
+        ..BuildConfig::default()
+    };
+    let venues = ["ICDM", "ICML", "SIGIR", "SIGKDD", "SIGMOD"];
+    let bytes: Vec<u8> = (1..=255).collect();
+    let digits: Vec<u8> = (b'0'..=b'9').chain(b"ab".iter().copied()).collect();
+    let dna = b"ACGT";
+    let utf8: Vec<_> = ["a", "b", "é", "ß", "語", "辞", "書"]
+        .iter()
+        .map(|c| c.as_bytes())
+        .collect();
+
+    vec![
+        Fixture::new("empty", config(8), vec![]),
+        Fixture::new(
+            "venues",
+            config(4),
+            venues.iter().map(|k| k.as_bytes().to_vec()),
+        ),
+        Fixture::new(
+            "binary",
+            config(8),
+            (0..1000).map(|_| rng.gen_key(&bytes, 12)),
+        ),
+        Fixture::new(
+            "bucket1",
+            config(1),
+            (0..100).map(|_| rng.gen_key(b"abc", 8)),
+        ),
+        Fixture::new(
+            "natural",
+            BuildConfig {
+                key_order: KeyOrder::Natural,
+                ..config(4)
+            },
+            (0..300).map(|_| rng.gen_key(&digits, 8)),
+        ),
+        Fixture::new(
+            "dna",
+            BuildConfig {
+                alphabet: Some(Alphabet::dna()),
+                ..config(16)
+            },
+            (0..300).map(|_| rng.gen_key(dna, 20)),
+        ),
+        Fixture::new(
+            "aligned_buckets",
+            BuildConfig {
+                align_buckets: true,
+                ..config(8)
+            },
+            (0..300).map(|_| rng.gen_key(b"abcd", 10)),
+        ),
+        Fixture::new(
+            "utf8",
+            BuildConfig {
+                require_utf8: true,
+                max_key_len: Some(24),
+                ..config(4)
+            },
+            (0..300).map(|_| {
+                let len = 1 + rng.below(6);
+                (0..len)
+                    .flat_map(|_| utf8[rng.below(utf8.len())].to_vec())
+                    .collect()
+            }),
+        ),
+    ]
+}
+
+/// Writes the keys and the canonical bytes of all the fixtures into a directory.
+///
+/// For each fixture, `<name>.keys` has the keys in hex, one per line in the order of ids,
+/// and `<name>.<extension>` has the bytes in each [`Layout`].
+///
+/// # Arguments
+///
+///  - `dir`: Existing directory.
+///
+/// # Errors
+///
+/// [`anyhow::Result`] will be returned when an I/O error occurs.
+pub fn write_fixtures<P>(dir: P) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    for fixture in fixtures() {
+        let mut lines = String::new();
+        for key in &fixture.keys {
+            for &c in key {
+                lines.push_str(&format!("{:02x}", c));
+            }
+            lines.push('\n');
+        }
+        fs::write(dir.join(format!("{}.keys", fixture.name)), lines)?;
+        for layout in Layout::ALL {
+            let path = dir.join(format!("{}.{}", fixture.name, layout.extension()));
+            fs::write(path, fixture.serialize(layout)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that bytes are the canonical ones of a fixture in a layout.
+///
+/// # Arguments
+///
+///  - `fixture`: Expected fixture.
+///  - `layout`: Layout of `bytes`.
+///  - `bytes`: Bytes written by the implementation under test.
+///
+/// # Errors
+///
+/// [`anyhow::Result`] will be returned with the first differing offset when the bytes differ.
+pub fn check_bytes(fixture: &Fixture, layout: Layout, bytes: &[u8]) -> Result<()> {
+    let expected = fixture.serialize(layout)?;
+    if let Some(pos) = (0..expected.len().min(bytes.len())).find(|&i| expected[i] != bytes[i]) {
+        return Err(anyhow!(
+            "{}: the {:?} bytes differ at offset {}.",
+            fixture.name,
+            layout,
+            pos
+        ));
+    }
+    if expected.len() != bytes.len() {
+        return Err(anyhow!(
+            "{}: the {:?} bytes have {} bytes, but {} are expected.",
+            fixture.name,
+            layout,
+            bytes.len(),
+            expected.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that `locate` returns the id of every key of a fixture and `None` for absent keys.
+///
+/// # Arguments
+///
+///  - `fixture`: Expected fixture.
+///  - `locate`: Query of the implementation under test.
+///
+/// # Errors
+///
+/// [`anyhow::Result`] will be returned with the first wrong answer.
+pub fn check_locate<F>(fixture: &Fixture, mut locate: F) -> Result<()>
+where
+    F: FnMut(&[u8]) -> Option<usize>,
+{
+    let queries = fixture
+        .keys
+        .iter()
+        .enumerate()
+        .map(|(id, key)| (key.clone(), Some(id)));
+    let absent = fixture.absent_keys().into_iter().map(|key| (key, None));
+    for (key, expected) in queries.chain(absent) {
+        let actual = locate(&key);
+        if actual != expected {
+            return Err(anyhow!(
+                "{}: locating {:?} returns {:?}, but {:?} is expected.",
+                fixture.name,
+                key,
+                actual,
+                expected
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Checks that `decode` returns the key of every id of a fixture.
+///
+/// # Arguments
+///
+///  - `fixture`: Expected fixture.
+///  - `decode`: Query of the implementation under test.
+///
+/// # Errors
+///
+/// [`anyhow::Result`] will be returned with the first wrong answer.
+pub fn check_decode<F>(fixture: &Fixture, mut decode: F) -> Result<()>
+where
+    F: FnMut(usize) -> Vec<u8>,
+{
+    for (id, expected) in fixture.keys.iter().enumerate() {
+        let actual = decode(id);
+        if &actual != expected {
+            return Err(anyhow!(
+                "{}: decoding {} returns {:?}, but {:?} is expected.",
+                fixture.name,
+                id,
+                actual,
+                expected
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Xorshift generator, which is fixed so that the fixtures never change.
+struct XorShift(u64);
+
+impl XorShift {
+    const fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Generates an integer less than `n`, independently of the width of `usize`.
+    const fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Generates a key of 1 to `max_len` bytes in `symbols`.
+    fn gen_key(&mut self, symbols: &[u8], max_len: usize) -> Vec<u8> {
+        let len = 1 + self.below(max_len);
+        (0..len)
+            .map(|_| symbols[self.below(symbols.len())])
+            .collect()
+    }
+}
@@ -1,4 +1,8 @@
 use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt};
 
 use crate::END_MARKER;
 
@@ -20,9 +24,11 @@ pub fn get_lcp(a: &[u8], b: &[u8]) -> (usize, isize) {
     }
 }
 
+/// Returns the length of the string terminated by END_MARKER,
+/// or the whole length if broken data has no terminator.
 #[inline(always)]
 pub fn get_strlen(a: &[u8]) -> usize {
-    a.iter().position(|&c| c == END_MARKER).unwrap()
+    memchr::memchr(END_MARKER, a).unwrap_or(a.len())
 }
 
 /// Checks if a is a prefix of b.
@@ -42,7 +48,18 @@ pub fn is_prefix(a: &[u8], b: &[u8]) -> bool {
 /// Checks if END_MARKER is contained.
 #[inline(always)]
 pub fn contains_end_marker(a: &[u8]) -> bool {
-    a.iter().any(|&c| c == END_MARKER)
+    a.contains(&END_MARKER)
+}
+
+/// Hints the processor to fetch the cache line containing `p`.
+#[inline(always)]
+pub fn prefetch<T>(p: *const T) {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        std::arch::x86_64::_mm_prefetch::<{ std::arch::x86_64::_MM_HINT_T0 }>(p as *const i8);
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = p;
 }
 
 #[inline(always)]
@@ -64,6 +81,18 @@ pub const fn needed_bits(mut x: u64) -> usize {
     n
 }
 
+/// Reads a 64-bit little-endian integer that must fit in `usize` on the target,
+/// so that data written on 64-bit targets is rejected instead of truncated on 32-bit ones.
+pub fn read_usize<R: io::Read>(mut reader: R) -> io::Result<usize> {
+    let val = reader.read_u64::<LittleEndian>()?;
+    usize::try_from(val).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} does not fit in usize on this target", val),
+        )
+    })
+}
+
 pub mod vbyte {
     #[inline(always)]
     pub fn append(bytes: &mut Vec<u8>, mut val: usize) {
@@ -73,16 +102,42 @@ pub mod vbyte {
         }
         bytes.push((val & 127) as u8);
     }
+    pub fn write<W: std::io::Write>(mut writer: W, val: usize) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(10);
+        append(&mut bytes, val);
+        writer.write_all(&bytes)
+    }
+    pub fn read<R: std::io::Read>(mut reader: R) -> std::io::Result<usize> {
+        let mut val = 0;
+        for j in (0..usize::BITS).step_by(7) {
+            let mut b = [0];
+            reader.read_exact(&mut b)?;
+            if usize::BITS - j < 7 && (b[0] & 127) >> (usize::BITS - j) != 0 {
+                break;
+            }
+            val |= ((b[0] & 127) as usize) << j;
+            if (b[0] & 0x80) == 0 {
+                return Ok(val);
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "too long vbyte",
+        ))
+    }
     #[inline(always)]
-    pub const fn decode(bytes: &[u8]) -> (usize, usize) {
+    pub fn try_decode(bytes: &[u8]) -> Option<(usize, usize)> {
         let mut val = 0;
-        let (mut i, mut j) = (0, 0);
-        while (bytes[i] & 0x80) != 0 {
-            val |= ((bytes[i] & 127) as usize) << j;
-            i += 1;
-            j += 7;
+        for (i, &b) in bytes.iter().enumerate() {
+            let j = 7 * i;
+            if usize::BITS as usize <= j {
+                return None;
+            }
+            val |= ((b & 127) as usize) << j;
+            if (b & 0x80) == 0 {
+                return Some((val, i + 1));
+            }
         }
-        val |= ((bytes[i] & 127) as usize) << j;
-        (val, i + 1)
+        None
     }
 }
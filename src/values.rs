@@ -0,0 +1,241 @@
+//! Byte-string values associated with keys.
+use std::convert::TryFrom;
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Result};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::intvec::{IntVector, RANGE_BLOCK_LEN};
+use crate::utils;
+
+/// The number of values in each bucket of [`ValueCodec::FrontCoded`].
+const VALUE_BUCKET_SIZE: usize = 16;
+
+/// Codec of the value area, recorded in the serialized form.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValueCodec {
+    /// Values are stored as they are.
+    Raw,
+    /// Values are front-coded against the previous one in buckets of 16,
+    /// which is compact for values sharing prefixes such as URLs.
+    #[default]
+    FrontCoded,
+}
+
+impl ValueCodec {
+    const fn to_u8(self) -> u8 {
+        match self {
+            Self::Raw => 0,
+            Self::FrontCoded => 1,
+        }
+    }
+
+    fn from_u8(x: u8) -> Result<Self> {
+        match x {
+            0 => Ok(Self::Raw),
+            1 => Ok(Self::FrontCoded),
+            _ => Err(anyhow!("unknown value codec")),
+        }
+    }
+}
+
+/// Value area with random access.
+///
+/// With [`ValueCodec::Raw`], `pointers` has the offset of every value.
+/// With [`ValueCodec::FrontCoded`], `pointers` has the offset of every bucket,
+/// and each value is stored as the vbyte-coded LCP with the previous value
+/// (omitted for the first value in a bucket), the vbyte-coded length of the
+/// remaining suffix, and the suffix, so values may contain any bytes.
+#[derive(Clone)]
+pub(crate) struct Values {
+    codec: ValueCodec,
+    pointers: IntVector,
+    data: Vec<u8>,
+    len: usize,
+    last: Vec<u8>,
+}
+
+impl Values {
+    pub const fn new(codec: ValueCodec) -> Self {
+        Self {
+            codec,
+            pointers: IntVector::new(),
+            data: Vec::new(),
+            len: 0,
+            last: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, value: &[u8]) {
+        match self.codec {
+            ValueCodec::Raw => {
+                self.pointers.push(self.data.len() as u64);
+                self.data.extend_from_slice(value);
+            }
+            ValueCodec::FrontCoded => {
+                let lcp = if self.len.is_multiple_of(VALUE_BUCKET_SIZE) {
+                    self.pointers.push(self.data.len() as u64);
+                    0
+                } else {
+                    let lcp = utils::get_lcp(&self.last, value).0;
+                    utils::vbyte::append(&mut self.data, lcp);
+                    lcp
+                };
+                utils::vbyte::append(&mut self.data, value.len() - lcp);
+                self.data.extend_from_slice(&value[lcp..]);
+                self.last.clear();
+                self.last.extend_from_slice(value);
+            }
+        }
+        self.len += 1;
+    }
+
+    pub const fn codec(&self) -> ValueCodec {
+        self.codec
+    }
+
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Decodes the value of `id`, returning `None` if `id` is out of range or the data is broken.
+    pub fn get(&self, id: usize) -> Option<Vec<u8>> {
+        if self.len <= id {
+            return None;
+        }
+        let end_of = |i: usize| {
+            if i + 1 < self.pointers.len() {
+                self.pointers.get(i + 1) as usize
+            } else {
+                self.data.len()
+            }
+        };
+        match self.codec {
+            ValueCodec::Raw => {
+                let beg = self.pointers.get(id) as usize;
+                self.data.get(beg..end_of(id)).map(|v| v.to_vec())
+            }
+            ValueCodec::FrontCoded => {
+                let bi = id / VALUE_BUCKET_SIZE;
+                let bucket = self.data.get(self.pointers.get(bi) as usize..end_of(bi))?;
+                let mut value = Vec::new();
+                let mut pos = 0;
+                for bj in 0..=id % VALUE_BUCKET_SIZE {
+                    if bj != 0 {
+                        let (lcp, num) = utils::vbyte::try_decode(bucket.get(pos..)?)?;
+                        if value.len() < lcp {
+                            return None;
+                        }
+                        value.truncate(lcp);
+                        pos += num;
+                    }
+                    let (len, num) = utils::vbyte::try_decode(bucket.get(pos..)?)?;
+                    pos += num;
+                    value.extend_from_slice(bucket.get(pos..pos.checked_add(len)?)?);
+                    pos += len;
+                }
+                Some(value)
+            }
+        }
+    }
+
+    pub const fn heap_bytes(&self) -> usize {
+        self.pointers.heap_bytes() + self.data.capacity() + self.last.capacity()
+    }
+
+    pub const fn size_in_bytes(&self) -> usize {
+        1 + self.pointers.size_in_bytes() + 8 + self.data.len() + 8
+    }
+
+    pub fn serialize_into<W: io::Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_u8(self.codec.to_u8())?;
+        self.pointers.serialize_into(&mut writer)?;
+        writer.write_u64::<LittleEndian>(self.data.len() as u64)?;
+        writer.write_all(&self.data)?;
+        writer.write_u64::<LittleEndian>(self.len as u64)?;
+        Ok(())
+    }
+
+    pub fn deserialize_from<R: io::Read>(mut reader: R) -> Result<Self> {
+        let codec = ValueCodec::from_u8(reader.read_u8()?)?;
+        let pointers = IntVector::deserialize_from(&mut reader)?;
+        let data = {
+            let len = utils::read_usize(&mut reader)?;
+            let mut data = Vec::new();
+            (&mut reader).take(len as u64).read_to_end(&mut data)?;
+            if data.len() != len {
+                return Err(anyhow!("truncated values"));
+            }
+            data
+        };
+        let len = usize::try_from(reader.read_u64::<LittleEndian>()?)?;
+        if pointers.len() != Self::num_pointers(codec, len) {
+            return Err(anyhow!("broken values"));
+        }
+        Self::from_parts(codec, pointers, data, len)
+    }
+
+    /// Writes the values with the vbyte-encoded counts and differences of offsets,
+    /// used in the compact layout of dictionaries.
+    pub fn serialize_into_compact<W: io::Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_u8(self.codec.to_u8())?;
+        utils::vbyte::write(&mut writer, self.len)?;
+        utils::vbyte::write(&mut writer, self.data.len())?;
+        writer.write_all(&self.data)?;
+        self.pointers.serialize_deltas_into(&mut writer)?;
+        Ok(())
+    }
+
+    pub fn deserialize_compact_from<R: io::Read>(mut reader: R) -> Result<Self> {
+        let codec = ValueCodec::from_u8(reader.read_u8()?)?;
+        let len = utils::vbyte::read(&mut reader)?;
+        let data = {
+            let len = utils::vbyte::read(&mut reader)?;
+            let mut data = Vec::new();
+            (&mut reader).take(len as u64).read_to_end(&mut data)?;
+            if data.len() != len {
+                return Err(anyhow!("truncated values"));
+            }
+            data
+        };
+        let pointers =
+            IntVector::deserialize_deltas_from(&mut reader, Self::num_pointers(codec, len))?;
+        Self::from_parts(codec, pointers, data, len)
+    }
+
+    const fn num_pointers(codec: ValueCodec, len: usize) -> usize {
+        match codec {
+            ValueCodec::Raw => len,
+            ValueCodec::FrontCoded => len.div_ceil(VALUE_BUCKET_SIZE),
+        }
+    }
+
+    /// Checks that the offsets are non-decreasing within the data.
+    fn from_parts(
+        codec: ValueCodec,
+        pointers: IntVector,
+        data: Vec<u8>,
+        len: usize,
+    ) -> Result<Self> {
+        let mut prev = 0;
+        let mut buf = [0; RANGE_BLOCK_LEN];
+        for start in (0..pointers.len()).step_by(RANGE_BLOCK_LEN) {
+            let len = std::cmp::min(RANGE_BLOCK_LEN, pointers.len() - start);
+            pointers.get_range(start, len, &mut buf);
+            for &ptr in &buf[..len] {
+                let ptr = ptr as usize;
+                if ptr < prev || data.len() < ptr {
+                    return Err(anyhow!("broken values"));
+                }
+                prev = ptr;
+            }
+        }
+        Ok(Self {
+            codec,
+            pointers,
+            data,
+            len,
+            last: Vec::new(),
+        })
+    }
+}
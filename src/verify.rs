@@ -0,0 +1,12 @@
+//! Discrepancies between a dictionary and an expected keyset.
+
+/// First difference found by [`Set::verify_against`](crate::Set::verify_against).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Discrepancy {
+    /// Id at which the dictionary and the keyset differ.
+    pub id: usize,
+    /// Key with the id in the keyset, or `None` if the keyset is shorter.
+    pub expected: Option<Vec<u8>>,
+    /// Key with the id in the dictionary, or `None` if the dictionary is shorter.
+    pub actual: Option<Vec<u8>>,
+}
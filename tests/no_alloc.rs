@@ -0,0 +1,99 @@
+//! Checks the queries of the no-alloc profile with an allocator counting the allocations.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use fcsd::alphabet::Alphabet;
+use fcsd::bitmap::Bitmap;
+use fcsd::builder::Builder;
+use fcsd::locator::Lookup;
+use fcsd::no_alloc::Scratch;
+use fcsd::order::KeyOrder;
+use fcsd::Set;
+
+struct CountingAlloc;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static NUM_ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.with(Cell::get) {
+            NUM_ALLOCS.with(|n| n.set(n.get() + 1));
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if COUNTING.with(Cell::get) {
+            NUM_ALLOCS.with(|n| n.set(n.get() + 1));
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Returns the number of allocations in `f` on the current thread.
+fn count_allocs<F: FnOnce()>(f: F) -> usize {
+    NUM_ALLOCS.with(|n| n.set(0));
+    COUNTING.with(|c| c.set(true));
+    f();
+    COUNTING.with(|c| c.set(false));
+    NUM_ALLOCS.with(Cell::get)
+}
+
+fn check(set: &Set, keys: &[&str]) {
+    let mut scratch = Scratch::new(set);
+    let mut locator = set.locator();
+    let mut key = Vec::with_capacity(set.max_length());
+    let prefixes: Vec<_> = keys.iter().map(|key| &key[..2]).collect();
+    let mut bits = Bitmap::new();
+    set.prefix_membership_into(&prefixes, &mut bits);
+    let num_allocs = count_allocs(|| {
+        scratch.run(|| {
+            for (i, &expected) in keys.iter().enumerate() {
+                assert_eq!(locator.run(expected), Some(i));
+                assert_eq!(
+                    locator.run_parts(&[&expected[..1], &expected[1..]]),
+                    Some(i)
+                );
+                set.decode_into(i, &mut key);
+                assert_eq!(key, expected.as_bytes());
+                assert_eq!(set.key_len(i), expected.len());
+                assert!(set.cmp_key(i, expected).is_eq());
+                assert_eq!(set.rank(expected), i);
+                assert!(set.prefix_range(&expected[..1]).contains(&i));
+                assert!(set.contains_prefix(&expected[..2]));
+            }
+            set.prefix_membership_into(&prefixes, &mut bits);
+            assert_eq!(bits.count_ones(), keys.len());
+            assert_eq!(locator.run(b"ACGA"), None);
+            assert!(matches!(locator.lookup(b"ACGA"), Lookup::NotFound { .. }));
+        })
+    });
+    assert_eq!(num_allocs, 0);
+}
+
+#[test]
+fn test_no_alloc() {
+    let keys = ["AACGT", "ACGTTA", "CAT1", "CAT2", "CAT10", "GATTACA", "TTT"];
+
+    let sorted = ["AACGT", "ACGTTA", "CAT1", "CAT10", "CAT2"];
+    check(&Set::with_bucket_size(sorted, 2).unwrap(), &sorted);
+
+    let mut builder = Builder::new(2).unwrap().key_order(KeyOrder::Natural);
+    builder.extend(keys).unwrap();
+    check(&builder.finish(), &keys);
+
+    let keys = ["AACGT", "ACGTTA", "CAT", "GATTACA", "TTT"];
+    let mut builder = Builder::new(2).unwrap().alphabet(Alphabet::dna());
+    builder.extend(keys).unwrap();
+    check(&builder.finish(), &keys);
+}